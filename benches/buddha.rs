@@ -2,19 +2,33 @@ extern crate buddhabrot;
 
 use std::sync::{Arc, Mutex};
 
-use buddhabrot::{color::Rgb, images::Image, sample::sample};
+use buddhabrot::{
+    color::Rgb,
+    complex::Complex,
+    images::Image,
+    noise::{FrameNoise, NoiseCorrelation},
+    sample::{sample, SampleSettings},
+};
 use criterion::{criterion_group, criterion_main, Criterion};
 
-
 const IM_WIDTH: usize = 256;
 const IM_HEIGHT: usize = 256;
 const IM_SIZE: usize = IM_WIDTH * IM_HEIGHT;
-const PROGRESS_UPDATE: usize = IM_WIDTH;
 
 fn bench() {
     let im = Image::<Rgb>::new(IM_SIZE, IM_WIDTH);
-    sample(Arc::new(Mutex::new(im)), 10000, 20, PROGRESS_UPDATE);
-} 
+    sample(
+        Arc::new(Mutex::new(im)),
+        SampleSettings {
+            n: 10000,
+            m: 20,
+            scale: 1.0,
+            center: Complex::new(0.0, 0.0),
+            noise: FrameNoise::new(0, 0, NoiseCorrelation::Fixed),
+            ..Default::default()
+        },
+    );
+}
 
 fn criterion_bench(c: &mut Criterion) {
     c.bench_function("buddha sample 1", |b| b.iter(bench));
@@ -0,0 +1,80 @@
+extern crate buddhabrot;
+
+use buddhabrot::{
+    bench::{KernelSamples, Workload},
+    sample::Precision,
+    simd::{SimdFloat, SimdLevel},
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+/// The number of samples every kernel computes the escape times of.
+const SAMPLES: usize = 1 << 14;
+
+/// Times every SIMD level the CPU supports on the samples of a workload, so that every level
+/// iterates exactly the same orbits.
+fn bench_simd_levels<F: SimdFloat>(c: &mut Criterion, name: &str, workload: &str) {
+    let workload = Workload::find(workload).unwrap();
+    let samples = KernelSamples::<F>::new(&workload.settings(Precision::default()), SAMPLES);
+
+    let mut group = c.benchmark_group(format!("{} {}", name, workload.name));
+    group.throughput(Throughput::Elements(SAMPLES as u64));
+    for level in SimdLevel::supported() {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{:?}", level)),
+            &level,
+            |b, &level| b.iter(|| samples.escape_times(level)),
+        );
+    }
+    group.finish();
+}
+
+fn criterion_bench(c: &mut Criterion) {
+    for workload in ["buddhabrot", "deep-orbits", "zoom"] {
+        bench_simd_levels::<f32>(c, "escape times f32", workload);
+        bench_simd_levels::<f64>(c, "escape times f64", workload);
+    }
+
+    #[cfg(feature = "gpu")]
+    bench_accelerators(c);
+}
+
+/// Times the CPU and every GPU drawing the same samples of the buddhabrot workload.
+#[cfg(feature = "gpu")]
+fn bench_accelerators(c: &mut Criterion) {
+    use buddhabrot::gpu::{Accelerator, CpuSampler, GpuSampler};
+
+    let workload = Workload::find("buddhabrot").unwrap();
+    let settings = workload.settings(Precision::Single);
+    let mut devices: Vec<Box<dyn Accelerator>> = vec![Box::new(CpuSampler::new())];
+    devices.extend(
+        GpuSampler::all()
+            .into_iter()
+            .flatten()
+            .map(|gpu| Box::new(gpu) as Box<dyn Accelerator>),
+    );
+
+    let mut group = c.benchmark_group("accelerators buddhabrot");
+    group.throughput(Throughput::Elements(SAMPLES as u64));
+    for device in devices.iter() {
+        group.bench_function(device.name(), |b| {
+            b.iter(|| {
+                device
+                    .sample(
+                        &settings,
+                        (workload.width, workload.width),
+                        0..SAMPLES as u64,
+                        &mut |_, _| (),
+                    )
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = criterion_bench
+);
+criterion_main!(benches);
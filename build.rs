@@ -0,0 +1,36 @@
+use std::{fs, path::Path, process::Command};
+
+/// Records the commit the renderer is built from for the manifests written with `--manifest`,
+/// marking it dirty when the checkout has uncommitted changes. Builds outside of a git checkout
+/// leave it unset.
+fn main() {
+    let git = |args: &[&str]| {
+        let output = Command::new("git").args(args).output().ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+    };
+
+    if let Some(hash) = git(&["rev-parse", "HEAD"]) {
+        let dirty = git(&["status", "--porcelain", "--untracked-files=no"]).is_some_and(|status| !status.is_empty());
+        let suffix = if dirty { "-dirty" } else { "" };
+        println!("cargo:rustc-env=BUDDHABROT_GIT_HASH={}{}", hash, suffix);
+    }
+
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/index");
+    println!("cargo:rerun-if-changed=src");
+
+    // A commit moves the branch HEAD points to rather than HEAD itself, and that branch is either a
+    // file of its own or a line of the packed refs. Cargo reruns on every build when a path it
+    // watches is missing, so only those that exist are watched.
+    let branch = fs::read_to_string(".git/HEAD")
+        .ok()
+        .and_then(|head| head.strip_prefix("ref: ").map(|name| format!(".git/{}", name.trim())));
+    for path in branch.iter().map(String::as_str).chain([".git/packed-refs"]) {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+/// A file written under a temporary name next to where it belongs, and only renamed into place
+/// once every byte of it has reached the disk.
+///
+/// A crash, a full disk or a failed encoder midway through writing leaves whatever was at the path
+/// before untouched, so the only copy of a long render is never replaced by half of a new one. The
+/// temporary file is removed if the file is dropped without being [committed](Self::commit).
+pub struct AtomicFile {
+    out: BufWriter<File>,
+    path: PathBuf,
+    temp: PathBuf,
+    committed: bool,
+}
+
+impl AtomicFile {
+    /// Creates the temporary file that's renamed to `path` once it's committed.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<AtomicFile> {
+        let path = path.as_ref().to_owned();
+        let temp = temp_path(&path);
+        let out = BufWriter::new(File::create(&temp)?);
+
+        Ok(Self {
+            out,
+            path,
+            temp,
+            committed: false,
+        })
+    }
+
+    /// Flushes everything written to the disk and moves the file into place, replacing what was at
+    /// its path before.
+    pub fn commit(mut self) -> io::Result<()> {
+        self.out.flush()?;
+        self.out.get_ref().sync_all()?;
+        fs::rename(&self.temp, &self.path)?;
+        self.committed = true;
+        sync_parent(&self.path)
+    }
+}
+
+impl Write for AtomicFile {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.out.write(buf)
+    }
+
+    #[inline]
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.out.write_all(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+impl Seek for AtomicFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.out.seek(pos)
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.temp);
+        }
+    }
+}
+
+/// Writes `contents` to a file atomically, like [`fs::write`] but through an [`AtomicFile`].
+pub fn write(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let mut file = AtomicFile::create(path)?;
+    file.write_all(contents.as_ref())?;
+    file.commit()
+}
+
+/// Gets the hidden path next to `path` a file or directory is written to before it's moved into
+/// place.
+pub fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.tmp", name))
+}
+
+/// Moves a file written at the [temporary path](temp_path) of `path` by a library that opens files
+/// itself into place, syncing it first.
+pub fn commit_file(path: &Path) -> io::Result<()> {
+    let temp = temp_path(path);
+    File::open(&temp)?.sync_all()?;
+    fs::rename(&temp, path)?;
+    sync_parent(path)
+}
+
+/// Moves a directory written at the [temporary path](temp_path) of `path` into place, replacing
+/// the directory there before. Every file within it must already be synced.
+///
+/// Unlike a file, a directory can't replace another in one step, so the old one is moved aside
+/// first and only removed once the new one is in place.
+pub fn commit_dir(path: &Path) -> io::Result<()> {
+    let temp = temp_path(path);
+    sync_dir(&temp)?;
+
+    let old = path.with_file_name(format!(
+        ".{}.old",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    let replaced = path.exists();
+    if replaced {
+        if old.exists() {
+            fs::remove_dir_all(&old)?;
+        }
+        fs::rename(path, &old)?;
+    }
+    fs::rename(&temp, path)?;
+    sync_parent(path)?;
+
+    if replaced {
+        fs::remove_dir_all(&old)?;
+    }
+    Ok(())
+}
+
+/// Syncs the directory holding `path`, so that a file renamed into it survives a crash.
+fn sync_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => sync_dir(parent),
+        _ => sync_dir(Path::new(".")),
+    }
+}
+
+/// Syncs the entries of a directory to the disk. Directories can only be synced on Unix, so this
+/// does nothing elsewhere.
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    File::open(dir)?.sync_all()?;
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
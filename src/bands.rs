@@ -0,0 +1,255 @@
+use clap::ValueEnum;
+use std::{
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use crate::{
+    color::{Color, Rgb},
+    dynamics::Dynamics,
+    histogram::{self, HistogramHeader, HistogramWriter, Parameters, SampleType},
+    images::Image,
+    numa,
+    sample::{run_chunks, sample_point, SampleSettings},
+};
+
+/// The prefix of the names of the channels of a banded histogram file, followed by the index of
+/// the band.
+pub const CHANNEL_PREFIX: &str = "band";
+
+/// The parameter the edges of the bands of a banded histogram file are stored under.
+pub const EDGES_PARAMETER: &str = "band_edges";
+
+/// How the range of escape times is split into bands.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum BandSpacing {
+    /// Every band covers the same number of iterations.
+    Linear,
+    /// Every band covers the same ratio of iterations, giving the many short orbits as many bands
+    /// as the few long ones.
+    #[default]
+    Log,
+}
+
+/// The escape times splitting orbits into bands, which generalize the three iteration limits of a
+/// [`Nebulabrot`](crate::sample::Nebulabrot) to any number of channels.
+///
+/// Band `i` holds the orbits escaping after more than `edges[i]` iterations and at most
+/// `edges[i + 1]`, with the first band also holding those escaping after exactly `edges[0]`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EscapeBands {
+    pub edges: Vec<u32>,
+}
+
+impl EscapeBands {
+    /// Splits the escape times between `min` and `max` iterations into `count` bands.
+    pub fn new(count: usize, min: u32, max: u32, spacing: BandSpacing) -> EscapeBands {
+        let (low, high) = (min.max(1) as f64, max.max(min.max(1)) as f64);
+        let edges = (0..=count)
+            .map(|i| {
+                let t = i as f64 / count as f64;
+                let edge = match spacing {
+                    BandSpacing::Linear => low + (high - low) * t,
+                    BandSpacing::Log => low * (high / low).powf(t),
+                };
+                edge.round() as u32
+            })
+            .collect();
+
+        EscapeBands { edges }
+    }
+
+    /// Gets the number of bands.
+    pub fn len(&self) -> usize {
+        self.edges.len() - 1
+    }
+
+    /// Whether there are no bands.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets the band of the orbits escaping after `escape` iterations.
+    #[inline]
+    pub fn band(&self, escape: u32) -> usize {
+        self.edges[1..]
+            .partition_point(|&edge| edge < escape)
+            .min(self.len() - 1)
+    }
+
+    /// Parses edges stored as a comma separated list.
+    pub fn parse(s: &str) -> Option<EscapeBands> {
+        let edges = s.split(',').map(|edge| edge.parse().ok()).collect::<Option<Vec<_>>>()?;
+        (edges.len() >= 2).then_some(EscapeBands { edges })
+    }
+}
+
+impl std::fmt::Display for EscapeBands {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let edges = self.edges.iter().map(u32::to_string).collect::<Vec<_>>();
+        write!(f, "{}", edges.join(","))
+    }
+}
+
+/// An image whose pixels count the hits of orbits in every band of escape times separately, a 3D
+/// histogram that can be colored any way after it's rendered.
+#[derive(Clone, Debug)]
+pub struct BandedImage {
+    /// The hits of every band of a pixel, one pixel after another.
+    data: Vec<f32>,
+    pub width: usize,
+    pub height: usize,
+    pub bands: EscapeBands,
+}
+
+impl BandedImage {
+    /// Creates a new, blank image.
+    pub fn new(width: usize, height: usize, bands: EscapeBands) -> BandedImage {
+        Self {
+            data: vec![0.0; width * height * bands.len()],
+            width,
+            height,
+            bands,
+        }
+    }
+
+    /// Gets the hits of a band of a pixel at a given `(x, y)` pixel position.
+    #[inline]
+    pub fn get(&self, (x, y): (usize, usize), band: usize) -> f32 {
+        self.data[(y * self.width + x) * self.bands.len() + band]
+    }
+
+    /// Adds to the hits of a band of a pixel at a given `(x, y)` pixel position.
+    #[inline]
+    pub fn add(&mut self, (x, y): (usize, usize), band: usize, hits: f32) {
+        self.data[(y * self.width + x) * self.bands.len() + band] += hits;
+    }
+
+    /// Adds every hit of `other`, which must have the same dimensions and bands, to this image.
+    pub fn merge(&mut self, other: &BandedImage) {
+        for (hits, other) in self.data.iter_mut().zip(other.data.iter()) {
+            *hits += other;
+        }
+    }
+
+    /// Colors every band with a color of `palette`, which is blended across the bands from the
+    /// shortest orbits to the longest, and adds up the colored hits of every pixel.
+    pub fn colorize(&self, palette: &[Rgb]) -> Image<Rgb> {
+        let bands = self.bands.len();
+        let colors = (0..bands)
+            .map(|band| {
+                let t = if bands > 1 {
+                    band as f32 / (bands - 1) as f32 * (palette.len() - 1) as f32
+                } else {
+                    0.0
+                };
+                let (i, t) = ((t as usize).min(palette.len() - 1), t.fract());
+                let (a, b) = (palette[i], palette[(i + 1).min(palette.len() - 1)]);
+                Rgb::new(a.r + (b.r - a.r) * t, a.g + (b.g - a.g) * t, a.b + (b.b - a.b) * t)
+            })
+            .collect::<Vec<_>>();
+
+        let mut im = Image::<Rgb>::new(self.width * self.height, self.width);
+        for (x, y, px) in im.enumerate_pixels_mut() {
+            for (band, color) in colors.iter().enumerate() {
+                let hits = self.get((x, y), band);
+                px.add(color.map(|v| v * hits));
+            }
+        }
+        im
+    }
+
+    /// Writes this image to a histogram file with a channel per band, storing the edges of the
+    /// bands along with the parameters it was rendered with, compressed at the given level when
+    /// set.
+    pub fn save(&self, path: impl AsRef<Path>, parameters: &Parameters, compression: Option<u32>) -> io::Result<()> {
+        let mut parameters = parameters.clone();
+        parameters.insert(EDGES_PARAMETER.to_owned(), self.bands.to_string());
+
+        let mut out = HistogramWriter::create(path, compression)?;
+        histogram::write_header(
+            &mut out,
+            &HistogramHeader {
+                version: histogram::VERSION,
+                width: self.width,
+                height: self.height,
+                channels: (0..self.bands.len())
+                    .map(|band| format!("{}{}", CHANNEL_PREFIX, band))
+                    .collect(),
+                sample_type: SampleType::F32,
+                parameters,
+            },
+        )?;
+
+        for hits in self.data.iter() {
+            out.write_all(&hits.to_le_bytes())?;
+        }
+        out.commit()
+    }
+
+    /// Reads an image written with [`save`](Self::save), along with the parameters it was rendered
+    /// with.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<(BandedImage, Parameters)> {
+        let mut input = histogram::open_histogram(path)?;
+        let header = histogram::read_header(&mut input)?;
+        let bands = header
+            .parameters
+            .get(EDGES_PARAMETER)
+            .and_then(|edges| EscapeBands::parse(edges))
+            .filter(|bands| bands.len() == header.channels.len())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "histogram has no escape time bands"))?;
+
+        let mut im = BandedImage::new(header.width, header.height, bands);
+        let mut bytes = vec![0; header.sample_type.size()];
+        for hits in im.data.iter_mut() {
+            input.read_exact(&mut bytes)?;
+            *hits = match header.sample_type {
+                SampleType::F32 => f32::from_le_bytes(bytes[..].try_into().unwrap()),
+                SampleType::F64 => f64::from_le_bytes(bytes[..].try_into().unwrap()) as f32,
+            };
+        }
+
+        Ok((im, header.parameters))
+    }
+}
+
+/// Samples escaping orbits into a banded image, counting a hit in the band of the escape time of
+/// its orbit at every point.
+pub fn sample_banded(im: &mut BandedImage, settings: SampleSettings) {
+    let n = settings.n;
+    let (width, height) = (im.width, im.height);
+    let iters = width * height * settings.m as usize;
+    let plane_map = settings.plane_map();
+    let kernel = settings.kernel(settings.fractal);
+    let blank = BandedImage::new(width, height, im.bands.clone());
+
+    let images = run_chunks(
+        &settings,
+        iters,
+        || blank.clone(),
+        |image, stream, throttle, count| {
+            let mut trajectory = Vec::new();
+            for _ in 0..count {
+                let sample = sample_point::<f32>(&settings, stream.next_point());
+                let (z, c) = settings.orbit_start(&kernel, sample);
+
+                kernel.trajectory(z, c, n, &mut trajectory);
+                let escape = trajectory.len() as u32;
+                if escape >= settings.min_iterations {
+                    let band = image.bands.band(escape);
+                    for &z in trajectory.iter() {
+                        if let Some(px) = plane_map.to_pixel(settings.project(z, sample), width, height) {
+                            image.add(px, band, 1.0);
+                        }
+                    }
+                }
+
+                throttle.tick(1);
+            }
+        },
+    );
+
+    for other in numa::merge_by_node(images, |im, other| im.merge(&other)) {
+        im.merge(&other);
+    }
+}
@@ -0,0 +1,275 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+#[cfg(feature = "gpu")]
+use crate::gpu::{Accelerator, GpuError};
+use crate::{
+    canvas::Canvas,
+    complex::Complex,
+    fractal::Fractal,
+    images::Image,
+    memory::peak_memory,
+    noise::{FrameNoise, NoiseCorrelation},
+    sample::{sample_canvas, sample_point, Orbits, Precision, SampleSettings},
+    simd::{SimdFloat, SimdLevel},
+};
+
+/// A fixed render whose speed is measured, so that runs with different kernels, thread counts and
+/// machines can be compared with one another.
+#[derive(Clone, Copy, Debug)]
+pub struct Workload {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// The width and height of the image in pixels.
+    pub width: usize,
+    pub n: u32,
+    pub m: u32,
+    pub scale: f32,
+    pub center: Complex<f32>,
+    pub fractal: Fractal,
+    pub orbits: Orbits,
+    pub seed: u64,
+}
+
+/// Every built-in workload.
+pub const WORKLOADS: &[Workload] = &[
+    Workload {
+        name: "buddhabrot",
+        description: "The full buddhabrot, dominated by short orbits",
+        width: 256,
+        n: 1000,
+        m: 16,
+        scale: 1.0,
+        center: Complex { re: 0.0, im: 0.0 },
+        fractal: Fractal::Mandelbrot,
+        orbits: Orbits::Escaping,
+        seed: 1,
+    },
+    Workload {
+        name: "deep-orbits",
+        description: "The full buddhabrot at a high iteration count, dominated by iterating bounded samples",
+        width: 256,
+        n: 20000,
+        m: 2,
+        scale: 1.0,
+        center: Complex { re: 0.0, im: 0.0 },
+        fractal: Fractal::Mandelbrot,
+        orbits: Orbits::Escaping,
+        seed: 2,
+    },
+    Workload {
+        name: "anti-buddhabrot",
+        description: "The orbits of points inside the mandelbrot set, plotting every iteration",
+        width: 256,
+        n: 500,
+        m: 4,
+        scale: 1.0,
+        center: Complex { re: 0.0, im: 0.0 },
+        fractal: Fractal::Mandelbrot,
+        orbits: Orbits::Bounded,
+        seed: 3,
+    },
+    Workload {
+        name: "zoom",
+        description: "A zoom into seahorse valley, where most plotted points miss the image",
+        width: 256,
+        n: 2000,
+        m: 16,
+        scale: 0.05,
+        center: Complex { re: -0.75, im: 0.1 },
+        fractal: Fractal::Mandelbrot,
+        orbits: Orbits::Escaping,
+        seed: 4,
+    },
+    Workload {
+        name: "burning-ship",
+        description: "The full burning ship, whose orbits are iterated one at a time rather than in SIMD lanes",
+        width: 256,
+        n: 1000,
+        m: 16,
+        scale: 1.0,
+        center: Complex { re: 0.0, im: 0.0 },
+        fractal: Fractal::BurningShip,
+        orbits: Orbits::Escaping,
+        seed: 5,
+    },
+];
+
+/// The measured speed of a single run of a [`Workload`].
+#[derive(Clone, Copy, Debug)]
+pub struct BenchResult {
+    /// The number of samples drawn.
+    pub samples: usize,
+    /// The number of points plotted inside the image.
+    pub points: f64,
+    /// The wall clock time the render took, in seconds.
+    pub seconds: f64,
+    /// The largest amount of memory the process has had resident so far, in bytes, where the
+    /// platform reports it.
+    pub peak_memory: Option<usize>,
+}
+
+impl BenchResult {
+    pub fn samples_per_second(&self) -> f64 {
+        self.samples as f64 / self.seconds
+    }
+
+    pub fn points_per_second(&self) -> f64 {
+        self.points / self.seconds
+    }
+}
+
+impl Workload {
+    /// Finds the built-in workload with the given name.
+    pub fn find(name: &str) -> Option<&'static Workload> {
+        WORKLOADS.iter().find(|workload| workload.name == name)
+    }
+
+    /// Gets the settings this workload is rendered with, iterating orbits with the given precision.
+    pub fn settings(&self, precision: Precision) -> SampleSettings {
+        SampleSettings {
+            n: self.n,
+            m: self.m,
+            scale: self.scale.into(),
+            center: self.center.map(f64::from),
+            precision,
+            fractal: self.fractal,
+            orbits: self.orbits,
+            noise: FrameNoise::new(self.seed, 0, NoiseCorrelation::Fixed),
+            ..Default::default()
+        }
+    }
+
+    /// Renders this workload on the current thread pool and measures how long it took.
+    pub fn run(&self, precision: Precision) -> BenchResult {
+        let settings = self.settings(precision);
+        let canvas = Arc::new(Mutex::new(Canvas::new(Image::<f32>::new(
+            self.width * self.width,
+            self.width,
+        ))));
+
+        let start = Instant::now();
+        sample_canvas(canvas.clone(), settings);
+        let seconds = start.elapsed().as_secs_f64();
+
+        // Every point plotted inside the image adds exactly one hit
+        let canvas = Arc::try_unwrap(canvas).ok().unwrap().into_inner().unwrap();
+        let points = canvas.main.pixels().map(|&hits| hits as f64).sum();
+
+        BenchResult {
+            samples: self.width * self.width * self.m as usize,
+            points,
+            seconds,
+            peak_memory: peak_memory(),
+        }
+    }
+}
+
+/// The samples of a [`Workload`] whose mandelbrot escape times every kernel computes, so that
+/// kernels are compared on exactly the same orbits.
+#[derive(Clone, Debug)]
+pub struct KernelSamples<F = f32> {
+    pub z: Vec<Complex<F>>,
+    pub c: Vec<Complex<F>>,
+    pub n: u32,
+}
+
+impl<F: SimdFloat> KernelSamples<F> {
+    /// Draws `count` samples of the region sampled with `settings`, from its noise configuration.
+    pub fn new(settings: &SampleSettings, count: usize) -> KernelSamples<F> {
+        let mut stream = settings.noise.stream(0);
+        let z = (0..count)
+            .map(|_| sample_point::<F>(settings, stream.next_point()))
+            .collect::<Vec<_>>();
+
+        Self {
+            c: z.clone(),
+            z,
+            n: settings.n,
+        }
+    }
+
+    /// Computes the escape time of every sample with the given level on the current thread.
+    pub fn escape_times(&self, level: SimdLevel) -> Vec<Option<u32>> {
+        let mut times = vec![None; self.z.len()];
+        F::mandelbrot_escape_times_with(level, &self.z, &self.c, self.n, &mut times);
+        times
+    }
+}
+
+/// The measured speed of a kernel on a fixed set of samples.
+#[derive(Clone, Debug)]
+pub struct KernelResult {
+    /// The name of the kernel.
+    pub kernel: String,
+    /// The number of samples the kernel drew.
+    pub samples: usize,
+    /// The wall clock time the kernel took, in seconds.
+    pub seconds: f64,
+    /// Whether the kernel computed exactly what the first kernel compared did.
+    pub agrees: bool,
+}
+
+impl KernelResult {
+    pub fn samples_per_second(&self) -> f64 {
+        self.samples as f64 / self.seconds
+    }
+}
+
+/// Computes the escape times of `samples` with every [`SimdLevel`] the CPU supports, one thread at
+/// a time, checking that every level agrees with the scalar iteration.
+pub fn compare_simd_levels<F: SimdFloat>(samples: &KernelSamples<F>) -> Vec<KernelResult> {
+    let mut reference = None;
+    SimdLevel::supported()
+        .map(|level| {
+            let start = Instant::now();
+            let times = samples.escape_times(level);
+            let seconds = start.elapsed().as_secs_f64();
+
+            let reference = reference.get_or_insert_with(|| times.clone());
+            KernelResult {
+                kernel: format!("{:?}", level),
+                samples: times.len(),
+                seconds,
+                agrees: times == *reference,
+            }
+        })
+        .collect()
+}
+
+/// Renders the first `samples` samples of the image of a workload on every device, checking that
+/// every device plots the same histogram as the first one.
+#[cfg(feature = "gpu")]
+pub fn compare_accelerators(
+    workload: &Workload,
+    samples: u64,
+    devices: &[Arc<dyn Accelerator>],
+) -> Result<Vec<KernelResult>, GpuError> {
+    let settings = workload.settings(Precision::Single);
+    let size = (workload.width, workload.width);
+
+    let mut reference = None;
+    devices
+        .iter()
+        .map(|device| {
+            let mut histogram = vec![0u64; size.0 * size.1];
+            let start = Instant::now();
+            device.sample(&settings, size, 0..samples, &mut |hits, _| {
+                for (total, &hits) in histogram.iter_mut().zip(hits) {
+                    *total += hits as u64;
+                }
+            })?;
+            let seconds = start.elapsed().as_secs_f64();
+
+            let reference = reference.get_or_insert_with(|| histogram.clone());
+            Ok(KernelResult {
+                kernel: device.name().to_owned(),
+                samples: samples as usize,
+                seconds,
+                agrees: histogram == *reference,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,295 @@
+use num_traits::Float;
+
+use crate::{color::Color, complex::Complex, images::Image, sample::SampleCounts};
+
+/// A square region of the complex plane, `4 * scale` units wide and centered on `center`.
+///
+/// The region is stored in double precision so that deep zooms can be described exactly, and is
+/// converted to the precision of each point mapped through it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub center: Complex<f64>,
+    pub scale: f64,
+}
+
+impl Viewport {
+    /// Creates a new viewport.
+    #[inline]
+    pub fn new(center: Complex<f64>, scale: f64) -> Viewport {
+        Self { center, scale }
+    }
+
+    /// Converts a complex number to the coordinates of the pixel it lands on in a `width` by
+    /// `height` image of this viewport, if it is inside the image.
+    #[inline]
+    pub fn to_pixel<F: Float>(&self, z: Complex<F>, width: usize, height: usize) -> Option<(usize, usize)> {
+        let cast = |x: f64| F::from(x).unwrap();
+        let p = (z - self.center.map(cast)) / cast(self.scale) * cast(0.25) + cast(0.5);
+        let px = (p.re * cast(width as f64)).to_i32().unwrap_or(-1);
+        let py = (p.im * cast(height as f64)).to_i32().unwrap_or(-1);
+
+        if px < 0 || py < 0 || px >= width as i32 || py >= height as i32 {
+            return None;
+        }
+
+        Some((px as usize, py as usize))
+    }
+
+    /// Converts the coordinates of a pixel in a `width` by `height` image of this viewport to the
+    /// complex number at its corner.
+    #[inline]
+    pub fn to_complex(&self, (x, y): (usize, usize), width: usize, height: usize) -> Complex<f64> {
+        let p = Complex::new(x as f64 / width as f64, y as f64 / height as f64);
+        (p - 0.5) * 4.0 * self.scale + self.center
+    }
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::new(Complex::new(0.0, 0.0), 1.0)
+    }
+}
+
+/// A Möbius transformation `(az + b) / (cz + d)` of the complex plane, which maps circles to
+/// circles and can bring the point at infinity into view.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mobius {
+    pub a: Complex<f32>,
+    pub b: Complex<f32>,
+    pub c: Complex<f32>,
+    pub d: Complex<f32>,
+}
+
+impl Mobius {
+    /// Creates a new Möbius transformation, or `None` if `ad - bc` is zero, which would collapse
+    /// the plane onto a single point.
+    pub fn new(a: Complex<f32>, b: Complex<f32>, c: Complex<f32>, d: Complex<f32>) -> Option<Mobius> {
+        let det = a * d - b * c;
+        if det.re == 0.0 && det.im == 0.0 {
+            return None;
+        }
+
+        Some(Self { a, b, c, d })
+    }
+
+    /// Applies this transformation to `z`.
+    #[inline]
+    pub fn apply<F: Float>(&self, z: Complex<F>) -> Complex<F> {
+        let cast = |w: Complex<f32>| w.map(|x| F::from(x).unwrap());
+        (cast(self.a) * z + cast(self.b)) / (cast(self.c) * z + cast(self.d))
+    }
+}
+
+impl std::fmt::Display for Mobius {
+    /// Formats the real and imaginary parts of `a`, `b`, `c` and `d`, separated by commas.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let [a, b, c, d] = [self.a, self.b, self.c, self.d];
+        write!(
+            f,
+            "{},{},{},{},{},{},{},{}",
+            a.re, a.im, b.re, b.im, c.re, c.im, d.re, d.im
+        )
+    }
+}
+
+/// Maps points of the plotted plane onto the pixels of an image, optionally passing them through a
+/// Möbius transformation before the viewport.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlaneMap {
+    pub viewport: Viewport,
+    pub mobius: Option<Mobius>,
+}
+
+impl PlaneMap {
+    /// Converts a complex number to the coordinates of the pixel it lands on in a `width` by
+    /// `height` image, if it is inside the image.
+    #[inline]
+    pub fn to_pixel<F: Float>(&self, z: Complex<F>, width: usize, height: usize) -> Option<(usize, usize)> {
+        match self.mobius {
+            Some(mobius) => self.viewport.to_pixel(mobius.apply(z), width, height),
+            None => self.viewport.to_pixel(z, width, height),
+        }
+    }
+}
+
+/// A rectangle of the complex plane between the corners `min` and `max`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    pub min: Complex<f32>,
+    pub max: Complex<f32>,
+}
+
+impl Region {
+    /// Creates the rectangle with opposite corners `a` and `b`.
+    #[inline]
+    pub fn new(a: Complex<f32>, b: Complex<f32>) -> Region {
+        Self {
+            min: Complex::new(a.re.min(b.re), a.im.min(b.im)),
+            max: Complex::new(a.re.max(b.re), a.im.max(b.im)),
+        }
+    }
+
+    /// Checks whether `z` lies inside this rectangle.
+    #[inline]
+    pub fn contains(&self, z: Complex<f32>) -> bool {
+        (self.min.re..=self.max.re).contains(&z.re) && (self.min.im..=self.max.im).contains(&z.im)
+    }
+}
+
+/// An additional image accumulating the same orbits as the main image through its own viewport.
+#[derive(Debug, Clone)]
+pub struct Layer<T: Color> {
+    pub viewport: Viewport,
+    pub image: Image<T>,
+}
+
+/// The visits of an orbit that count towards the weight of its sampled point in an origins image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OriginsWeight {
+    /// Visits to a region of the plotted plane.
+    Target(Region),
+    /// Points plotted into the main image, weighting each sample by its contribution.
+    Contribution,
+}
+
+/// An additional image plotting where orbits started in the plane of sampled points, weighting each
+/// sampled point by the deposits of its orbit's visits.
+#[derive(Debug, Clone)]
+pub struct Origins<T: Color> {
+    pub weight: OriginsWeight,
+    /// The viewport the sampled points are plotted through.
+    pub viewport: Viewport,
+    pub image: Image<T>,
+}
+
+/// The set of images a render accumulates into.
+#[derive(Debug, Clone)]
+pub struct Canvas<T: Color> {
+    /// The image of the render's own viewport.
+    pub main: Image<T>,
+    pub layers: Vec<Layer<T>>,
+    pub origins: Option<Origins<T>>,
+    /// How many samples were drawn into the canvas and where their points landed.
+    pub counts: SampleCounts,
+}
+
+impl<T: Color + Clone + Copy> Canvas<T> {
+    /// Creates a new canvas with no additional layers.
+    #[inline]
+    pub fn new(main: Image<T>) -> Canvas<T> {
+        Self {
+            main,
+            layers: Vec::new(),
+            origins: None,
+            counts: SampleCounts::default(),
+        }
+    }
+
+    /// Adds a blank `width` by `width` layer looking through `viewport`.
+    #[inline]
+    pub fn with_layer(mut self, viewport: Viewport, width: usize) -> Canvas<T> {
+        self.layers.push(Layer {
+            viewport,
+            image: Image::new(width * width, width),
+        });
+        self
+    }
+
+    /// Adds a blank `width` by `width` image plotting the origins of orbits weighted by `weight`,
+    /// looking through `viewport`.
+    #[inline]
+    pub fn with_origins(mut self, weight: OriginsWeight, viewport: Viewport, width: usize) -> Canvas<T> {
+        self.origins = Some(Origins {
+            weight,
+            viewport,
+            image: Image::new(width * width, width),
+        });
+        self
+    }
+
+    /// Creates a blank canvas with the same dimensions and layers as this one, whose main image is
+    /// sparse if this one's still is.
+    pub fn blank(&self) -> Canvas<T> {
+        Self {
+            main: self.main.blank(),
+            layers: self
+                .layers
+                .iter()
+                .map(|layer| Layer {
+                    viewport: layer.viewport,
+                    image: Image::new(layer.image.size, layer.image.width),
+                })
+                .collect(),
+            origins: self.origins.as_ref().map(|origins| Origins {
+                weight: origins.weight,
+                viewport: origins.viewport,
+                image: Image::new(origins.image.size, origins.image.width),
+            }),
+            counts: SampleCounts::default(),
+        }
+    }
+
+    /// Converts every pixel of every image of this canvas with `f`.
+    pub fn convert<U: Color + Clone + Copy>(self, f: impl Fn(T) -> U) -> Canvas<U> {
+        Canvas {
+            main: self.main.convert(&f),
+            layers: self
+                .layers
+                .into_iter()
+                .map(|layer| Layer {
+                    viewport: layer.viewport,
+                    image: layer.image.convert(&f),
+                })
+                .collect(),
+            origins: self.origins.map(|origins| Origins {
+                weight: origins.weight,
+                viewport: origins.viewport,
+                image: origins.image.convert(&f),
+            }),
+            counts: self.counts,
+        }
+    }
+
+    /// Rearranges the pixels of every image of this canvas into rows, ready to be written.
+    pub fn linear(self) -> Canvas<T> {
+        Canvas {
+            main: self.main.linear(),
+            layers: self
+                .layers
+                .into_iter()
+                .map(|layer| Layer {
+                    viewport: layer.viewport,
+                    image: layer.image.linear(),
+                })
+                .collect(),
+            origins: self.origins.map(|origins| Origins {
+                weight: origins.weight,
+                viewport: origins.viewport,
+                image: origins.image.linear(),
+            }),
+            counts: self.counts,
+        }
+    }
+
+    /// Adds every pixel and count of `other`, which must have the same dimensions and layers, to
+    /// this canvas.
+    #[profiling::function]
+    pub fn merge(&mut self, other: Canvas<T>) {
+        self.main.merge(other.main);
+        self.counts = self.counts.merge(other.counts);
+
+        for (layer, other) in self.layers.iter_mut().zip(other.layers) {
+            layer.image.merge(other.image);
+        }
+
+        if let (Some(origins), Some(other)) = (self.origins.as_mut(), other.origins) {
+            origins.image.merge(other.image);
+        }
+    }
+}
+
+impl<T: Color + Clone + Copy> Default for Canvas<T> {
+    fn default() -> Self {
+        Self::new(Image::default())
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    color::Rgb,
+    histogram::{self, load_histogram, Parameters},
+    images::Image,
+    sample::SampleSlice,
+};
+
+/// The parameter the number of slices already sampled into a checkpoint is stored under.
+pub const SLICE_PARAMETER: &str = "checkpoint_slice";
+
+/// The parameter the number of slices the samples of a checkpointed render are split into is
+/// stored under.
+pub const SLICES_PARAMETER: &str = "checkpoint_slices";
+
+/// A render saved partway through, which can be continued after it's interrupted.
+///
+/// The samples of the render are split into [slices](SampleSlice) that are sampled one after
+/// another, and a checkpoint holds the hits of every slice sampled so far. Since every work item
+/// draws from its own noise stream keyed by its index, the next slice pins down exactly where every
+/// random stream picks up again, so a resumed render draws the same samples it would have drawn
+/// if it had never stopped.
+///
+/// Checkpoints are [histogram files](histogram::HistogramHeader), whose parameters are those of the
+/// render along with its progress, so they can be tonemapped like any other histogram to look at a
+/// render that's still going.
+#[derive(Clone, Debug)]
+pub struct Checkpoint {
+    /// The hits of every slice sampled so far.
+    pub image: Image<Rgb>,
+    /// The parameters of the render, without its progress.
+    pub parameters: Parameters,
+    /// The number of slices sampled so far.
+    pub slice: usize,
+    /// The number of slices the samples are split into.
+    pub slices: usize,
+}
+
+impl Checkpoint {
+    /// Creates the checkpoint of a render that hasn't sampled anything yet.
+    pub fn new(width: usize, height: usize, parameters: Parameters, slices: usize) -> Checkpoint {
+        Self {
+            image: Image::new(width * height, width),
+            parameters,
+            slice: 0,
+            slices,
+        }
+    }
+
+    /// Gets the next slice to sample, or `None` once every slice is sampled.
+    pub fn next_slice(&self) -> Option<SampleSlice> {
+        (self.slice < self.slices).then_some(SampleSlice {
+            index: self.slice,
+            count: self.slices,
+        })
+    }
+
+    /// Adds the hits of the next slice to the checkpoint.
+    pub fn add_slice(&mut self, im: Image<Rgb>) {
+        self.image.merge(im);
+        self.slice += 1;
+    }
+
+    /// Whether every slice is sampled.
+    pub fn is_complete(&self) -> bool {
+        self.slice >= self.slices
+    }
+
+    /// Gets the names of the parameters of the render that differ from `parameters`, which a
+    /// checkpoint can't be resumed with.
+    pub fn mismatches(&self, parameters: &Parameters) -> Vec<String> {
+        histogram::mismatched_parameters(&self.parameters, parameters)
+    }
+
+    /// Writes this checkpoint to a histogram file, compressed at the given level when set.
+    pub fn save(&self, path: impl AsRef<Path>, compression: Option<u32>) -> io::Result<()> {
+        let mut parameters = self.parameters.clone();
+        parameters.insert(SLICE_PARAMETER.to_owned(), self.slice.to_string());
+        parameters.insert(SLICES_PARAMETER.to_owned(), self.slices.to_string());
+        histogram::save_histogram(path, &self.image, &parameters, compression)
+    }
+
+    /// Writes this checkpoint to `path` like [`save`](Self::save), first moving the checkpoints
+    /// written there before back to make room, so that the last `keep` are kept at the
+    /// [paths](kept_path) of their age.
+    pub fn save_rotated(&self, path: &Path, keep: usize, compression: Option<u32>) -> io::Result<()> {
+        for age in (1..keep).rev() {
+            let newer = kept_path(path, age - 1);
+            if newer.exists() {
+                fs::rename(newer, kept_path(path, age))?;
+            }
+        }
+        self.save(path, compression)
+    }
+
+    /// Reads the newest of the last `keep` checkpoints kept at `path` that can be read, along with
+    /// the path it was read from, falling back to older checkpoints when newer ones are missing or
+    /// damaged.
+    pub fn load_newest(path: &Path, keep: usize) -> io::Result<(Checkpoint, PathBuf)> {
+        let mut error = None;
+        for age in 0..keep.max(1) {
+            let path = kept_path(path, age);
+            match Self::load(&path) {
+                Ok(checkpoint) => return Ok((checkpoint, path)),
+                Err(e) => {
+                    error.get_or_insert(e);
+                },
+            }
+        }
+        Err(error.unwrap())
+    }
+
+    /// Reads a checkpoint written with [`save`](Self::save).
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Checkpoint> {
+        let (image, header) = load_histogram::<Rgb>(path)?;
+        let mut parameters = header.parameters;
+        let mut progress = |key: &str| parameters.remove(key).and_then(|value| value.parse::<usize>().ok());
+        let (Some(slice), Some(slices)) = (progress(SLICE_PARAMETER), progress(SLICES_PARAMETER)) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "histogram is not a checkpoint",
+            ));
+        };
+
+        Ok(Self {
+            image,
+            parameters,
+            slice,
+            slices,
+        })
+    }
+}
+
+/// Gets the path of the checkpoint of the given age kept at `path`, which is `path` itself for the
+/// newest checkpoint and numbered before the extension for older ones.
+pub fn kept_path(path: &Path, age: usize) -> PathBuf {
+    if age == 0 {
+        return path.to_owned();
+    }
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    match path.extension() {
+        Some(extension) => path.with_file_name(format!("{}.{}.{}", stem, age, extension.to_string_lossy())),
+        None => path.with_file_name(format!("{}.{}", stem, age)),
+    }
+}
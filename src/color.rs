@@ -1,6 +1,6 @@
-pub type Float = f32;
+use num_traits::{Float, PrimInt};
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ColorChannel {
     Red,
     Green,
@@ -8,90 +8,122 @@ pub enum ColorChannel {
 }
 
 pub trait Color {
+    /// The scalar each channel is stored in.
+    type Channel: Float;
+
+    /// The names of the channels of the color, which they're written to EXR files under, in the
+    /// order [`channel`](Color::channel) indexes them.
+    const CHANNELS: &'static [&'static str];
+
     fn empty() -> Self;
     fn add(&mut self, rhs: Self);
     fn max(self, rhs: Self) -> Self;
-    fn map(self, f: impl Fn(Float) -> Float) -> Self;
+    fn map(self, f: impl Fn(Self::Channel) -> Self::Channel) -> Self;
     fn one(channel: ColorChannel) -> Self;
     fn cdiv_assign(&mut self, rhs: Self);
-    fn to_tuple_rgb(self) -> (Float, Float, Float);
+    fn to_tuple_rgb(self) -> (Self::Channel, Self::Channel, Self::Channel);
+
+    /// Gets the value of the channel named by `CHANNELS[index]`.
+    fn channel(self, index: usize) -> Self::Channel;
+
+    /// Creates a color from the values of its channels, in the order `CHANNELS` names them.
+    fn from_channels(channels: &[Self::Channel]) -> Self;
 }
 
-impl Color for Float {
+impl<F: Float> Color for F {
+    type Channel = F;
+
+    const CHANNELS: &'static [&'static str] = &["Y"];
+
     #[inline]
     fn empty() -> Self {
-        0.0
+        F::zero()
     }
 
     #[inline]
     fn add(&mut self, rhs: Self) {
-        *self += rhs
+        *self = *self + rhs
     }
 
     #[inline]
     fn max(self, rhs: Self) -> Self {
-        self.max(rhs)
+        Float::max(self, rhs)
     }
 
     #[inline]
-    fn map(self, f: impl Fn(Float) -> Float) -> Self {
+    fn map(self, f: impl Fn(F) -> F) -> Self {
         f(self)
     }
 
     #[inline]
     fn one(_channel: ColorChannel) -> Self {
-        1.0
+        F::one()
     }
 
     #[inline]
     fn cdiv_assign(&mut self, rhs: Self) {
-        *self /= rhs
+        *self = *self / rhs
     }
 
     #[inline]
-    fn to_tuple_rgb(self) -> (Float, Float, Float) {
+    fn to_tuple_rgb(self) -> (F, F, F) {
         (self, self, self)
     }
+
+    #[inline]
+    fn channel(self, _index: usize) -> F {
+        self
+    }
+
+    #[inline]
+    fn from_channels(channels: &[F]) -> Self {
+        channels[0]
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
-pub struct Rg {
-    pub r: Float,
-    pub g: Float,
+#[repr(C)]
+pub struct Rg<F = f32> {
+    pub r: F,
+    pub g: F,
 }
 
-impl Rg {
+impl<F> Rg<F> {
     /// Constructs a new RG color from red and green component values.
     #[inline]
-    pub fn new(r: Float, g: Float) -> Rg {
+    pub fn new(r: F, g: F) -> Rg<F> {
         Self { r, g }
     }
 }
 
-impl From<(Float, Float)> for Rg {
+impl<F> From<(F, F)> for Rg<F> {
     #[inline]
-    fn from(value: (Float, Float)) -> Rg {
+    fn from(value: (F, F)) -> Rg<F> {
         Self { r: value.0, g: value.1 }
     }
 }
 
-impl From<Rgb> for (Float, Float) {
+impl<F> From<Rg<F>> for (F, F) {
     #[inline]
-    fn from(value: Rgb) -> Self {
+    fn from(value: Rg<F>) -> Self {
         (value.r, value.g)
     }
 }
 
-impl Color for Rg {
+impl<F: Float> Color for Rg<F> {
+    type Channel = F;
+
+    const CHANNELS: &'static [&'static str] = &["R", "G"];
+
     #[inline]
     fn empty() -> Self {
-        Self::new(0.0, 0.0)
+        Self::new(F::zero(), F::zero())
     }
 
     #[inline]
     fn add(&mut self, rhs: Self) {
-        self.r += rhs.r;
-        self.g += rhs.g;
+        self.r = self.r + rhs.r;
+        self.g = self.g + rhs.g;
     }
 
     #[inline]
@@ -103,7 +135,7 @@ impl Color for Rg {
     }
 
     #[inline]
-    fn map(self, f: impl Fn(Float) -> Float) -> Self {
+    fn map(self, f: impl Fn(F) -> F) -> Self {
         Self {
             r: f(self.r),
             g: f(self.g),
@@ -113,42 +145,53 @@ impl Color for Rg {
     #[inline]
     fn one(channel: ColorChannel) -> Self {
         match channel {
-            ColorChannel::Red => Self::new(1.0, 0.0),
-            ColorChannel::Green => Self::new(0.0, 1.0),
+            ColorChannel::Red => Self::new(F::one(), F::zero()),
+            ColorChannel::Green => Self::new(F::zero(), F::one()),
             _ => panic!("color channel {:?} is not valid for Rg", channel),
         }
     }
 
     #[inline]
     fn cdiv_assign(&mut self, rhs: Self) {
-        self.r /= rhs.r;
-        self.g /= rhs.g;
+        self.r = self.r / rhs.r;
+        self.g = self.g / rhs.g;
+    }
+
+    #[inline]
+    fn to_tuple_rgb(self) -> (F, F, F) {
+        (self.r, self.g, F::zero())
     }
 
     #[inline]
-    fn to_tuple_rgb(self) -> (Float, Float, Float) {
-        (self.r, self.g, 0.0)
+    fn channel(self, index: usize) -> F {
+        [self.r, self.g][index]
+    }
+
+    #[inline]
+    fn from_channels(channels: &[F]) -> Self {
+        Self::new(channels[0], channels[1])
     }
 }
 
 #[derive(Clone, Copy, Debug)]
-pub struct Rgb {
-    pub r: Float,
-    pub g: Float,
-    pub b: Float,
+#[repr(C)]
+pub struct Rgb<F = f32> {
+    pub r: F,
+    pub g: F,
+    pub b: F,
 }
 
-impl Rgb {
+impl<F> Rgb<F> {
     /// Constructs a new RGB color from red, green, and blue component values.
     #[inline]
-    pub fn new(r: Float, g: Float, b: Float) -> Rgb {
+    pub fn new(r: F, g: F, b: F) -> Rgb<F> {
         Self { r, g, b }
     }
 }
 
-impl From<(Float, Float, Float)> for Rgb {
+impl<F> From<(F, F, F)> for Rgb<F> {
     #[inline]
-    fn from(value: (Float, Float, Float)) -> Rgb {
+    fn from(value: (F, F, F)) -> Rgb<F> {
         Self {
             r: value.0,
             g: value.1,
@@ -157,24 +200,28 @@ impl From<(Float, Float, Float)> for Rgb {
     }
 }
 
-impl From<Rgb> for (Float, Float, Float) {
+impl<F> From<Rgb<F>> for (F, F, F) {
     #[inline]
-    fn from(value: Rgb) -> Self {
+    fn from(value: Rgb<F>) -> Self {
         (value.r, value.g, value.b)
     }
 }
 
-impl Color for Rgb {
+impl<F: Float> Color for Rgb<F> {
+    type Channel = F;
+
+    const CHANNELS: &'static [&'static str] = &["R", "G", "B"];
+
     #[inline]
     fn empty() -> Self {
-        Self::new(0.0, 0.0, 0.0)
+        Self::new(F::zero(), F::zero(), F::zero())
     }
 
     #[inline]
     fn add(&mut self, rhs: Self) {
-        self.r += rhs.r;
-        self.g += rhs.g;
-        self.b += rhs.b;
+        self.r = self.r + rhs.r;
+        self.g = self.g + rhs.g;
+        self.b = self.b + rhs.b;
     }
 
     #[inline]
@@ -187,7 +234,7 @@ impl Color for Rgb {
     }
 
     #[inline]
-    fn map(self, f: impl Fn(Float) -> Float) -> Self {
+    fn map(self, f: impl Fn(F) -> F) -> Self {
         Self {
             r: f(self.r),
             g: f(self.g),
@@ -197,22 +244,119 @@ impl Color for Rgb {
 
     #[inline]
     fn one(channel: ColorChannel) -> Self {
+        let (zero, one) = (F::zero(), F::one());
         match channel {
-            ColorChannel::Red => Self::new(1.0, 0.0, 0.0),
-            ColorChannel::Green => Self::new(0.0, 1.0, 0.0),
-            ColorChannel::Blue => Self::new(0.0, 0.0, 1.0),
+            ColorChannel::Red => Self::new(one, zero, zero),
+            ColorChannel::Green => Self::new(zero, one, zero),
+            ColorChannel::Blue => Self::new(zero, zero, one),
         }
     }
 
     #[inline]
     fn cdiv_assign(&mut self, rhs: Self) {
-        self.r /= rhs.r;
-        self.g /= rhs.g;
-        self.b /= rhs.b;
+        self.r = self.r / rhs.r;
+        self.g = self.g / rhs.g;
+        self.b = self.b / rhs.b;
     }
 
     #[inline]
-    fn to_tuple_rgb(self) -> (Float, Float, Float) {
+    fn to_tuple_rgb(self) -> (F, F, F) {
         self.into()
     }
+
+    #[inline]
+    fn channel(self, index: usize) -> F {
+        [self.r, self.g, self.b][index]
+    }
+
+    #[inline]
+    fn from_channels(channels: &[F]) -> Self {
+        Self::new(channels[0], channels[1], channels[2])
+    }
+}
+
+/// An exact number of hits in a single channel, counted in the integer `N`.
+///
+/// A float stops being able to add one once it holds 2²⁴ for `f32`, silently dropping the hits of
+/// very long renders. Deposits are converted from their float [`Channel`](Color::Channel) and
+/// rounded to a whole number of hits, so only unweighted deposits are counted exactly.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(transparent)]
+pub struct Count<N = u64>(pub N);
+
+impl<N: PrimInt> Color for Count<N> {
+    type Channel = f64;
+
+    const CHANNELS: &'static [&'static str] = &["Y"];
+
+    #[inline]
+    fn empty() -> Self {
+        Self(N::zero())
+    }
+
+    #[inline]
+    fn add(&mut self, rhs: Self) {
+        self.0 = self.0.saturating_add(rhs.0);
+    }
+
+    #[inline]
+    fn max(self, rhs: Self) -> Self {
+        Self(self.0.max(rhs.0))
+    }
+
+    #[inline]
+    fn map(self, f: impl Fn(f64) -> f64) -> Self {
+        Self(N::from(f(self.0.to_f64().unwrap()).round().max(0.0)).unwrap_or_else(N::max_value))
+    }
+
+    #[inline]
+    fn one(_channel: ColorChannel) -> Self {
+        Self(N::one())
+    }
+
+    /// Divides the counts, rounding down.
+    #[inline]
+    fn cdiv_assign(&mut self, rhs: Self) {
+        self.0 = self.0 / rhs.0;
+    }
+
+    #[inline]
+    fn to_tuple_rgb(self) -> (f64, f64, f64) {
+        let count = self.0.to_f64().unwrap();
+        (count, count, count)
+    }
+
+    #[inline]
+    fn channel(self, _index: usize) -> f64 {
+        self.0.to_f64().unwrap()
+    }
+
+    /// Rounds the count to a whole number of hits.
+    #[inline]
+    fn from_channels(channels: &[f64]) -> Self {
+        Self::empty().map(|_| channels[0])
+    }
+}
+
+/// Encodes a linear value between 0-1 with the sRGB transfer function, spending more of the levels
+/// of an integer format on dark values, as displays expect of the images they show.
+#[inline]
+pub fn srgb_encode(linear: f32) -> f32 {
+    let linear = linear.clamp(0.0, 1.0);
+    if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Decodes a value between 0-1 encoded with the sRGB transfer function back into linear light.
+#[inline]
+pub fn srgb_decode(encoded: f32) -> f32 {
+    let encoded = encoded.clamp(0.0, 1.0);
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
 }
@@ -0,0 +1,171 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+};
+
+/// A closed outline of a region of an image, as the positions of its corners in pixels.
+pub type Outline = Vec<(f32, f32)>;
+
+/// The outlines of every region of an image at least as dense as `level`.
+#[derive(Clone, Debug)]
+pub struct Contour {
+    pub level: f32,
+    pub outlines: Vec<Outline>,
+}
+
+/// A scalar field surrounded by a border of zeros, so that every outline traced in it is closed,
+/// even those of regions touching the edge of the image.
+struct Field<'a> {
+    values: &'a [f32],
+    width: usize,
+    height: usize,
+}
+
+impl Field<'_> {
+    /// Gets the value at a point of the padded grid, whose first row and column are the border.
+    fn get(&self, x: usize, y: usize) -> f32 {
+        if x == 0 || y == 0 || x > self.width || y > self.height {
+            return 0.0;
+        }
+        self.values[(y - 1) * self.width + x - 1]
+    }
+
+    /// Gets the point of the edge between two neighbouring points of the padded grid where the
+    /// field crosses `level`, in pixels of the image, with pixel centers at half pixels.
+    fn crossing(&self, (x0, y0): (usize, usize), (x1, y1): (usize, usize), level: f32) -> (f32, f32) {
+        let (a, b) = (self.get(x0, y0), self.get(x1, y1));
+        let t = ((level - a) / (b - a)).clamp(0.0, 1.0);
+        (
+            x0 as f32 + (x1 as f32 - x0 as f32) * t - 0.5,
+            y0 as f32 + (y1 as f32 - y0 as f32) * t - 0.5,
+        )
+    }
+}
+
+/// Traces the outlines of every region of the `width` by `height` image of `values`, one row after
+/// another, that is at least as dense as `level` using marching squares, dropping outlines of fewer
+/// than `min_points` corners.
+///
+/// Saddle cells, where two diagonally opposite corners are inside a region, are split by the
+/// average of their corners.
+pub fn trace(values: &[f32], width: usize, height: usize, level: f32, min_points: usize) -> Contour {
+    let field = Field { values, width, height };
+    let points = width + 2;
+
+    // Every edge of the padded grid has a key, so that segments crossing the same edge are joined
+    let horizontal = |x: usize, y: usize| (y * points + x) * 2;
+    let vertical = |x: usize, y: usize| (y * points + x) * 2 + 1;
+
+    let mut segments = Vec::new();
+    for y in 0..height + 1 {
+        for x in 0..width + 1 {
+            let corners = [(x, y), (x + 1, y), (x + 1, y + 1), (x, y + 1)];
+            let inside = corners.map(|(x, y)| field.get(x, y) >= level);
+
+            // The top, right, bottom and left edges of the cell, from the corners they join
+            let edges = [
+                (horizontal(x, y), corners[0], corners[1]),
+                (vertical(x + 1, y), corners[1], corners[2]),
+                (horizontal(x, y + 1), corners[3], corners[2]),
+                (vertical(x, y), corners[0], corners[3]),
+            ];
+            let crossed = [
+                inside[0] != inside[1],
+                inside[1] != inside[2],
+                inside[2] != inside[3],
+                inside[3] != inside[0],
+            ];
+            let crossing = |edge: usize| {
+                let (key, a, b) = edges[edge];
+                (key, field.crossing(a, b, level))
+            };
+
+            match crossed.iter().filter(|&&crossed| crossed).count() {
+                2 => {
+                    let mut ends = (0..4).filter(|&edge| crossed[edge]);
+                    segments.push([crossing(ends.next().unwrap()), crossing(ends.next().unwrap())]);
+                },
+                4 => {
+                    let center = corners.iter().map(|&(x, y)| field.get(x, y)).sum::<f32>() / 4.0;
+                    if (center >= level) == inside[0] {
+                        // The top left and bottom right corners are joined through the center
+                        segments.push([crossing(0), crossing(1)]);
+                        segments.push([crossing(2), crossing(3)]);
+                    } else {
+                        segments.push([crossing(3), crossing(0)]);
+                        segments.push([crossing(1), crossing(2)]);
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+
+    // Every crossed edge is shared by exactly two segments, as the border keeps outlines closed
+    let mut ends = HashMap::<usize, [usize; 2]>::with_capacity(segments.len());
+    for (index, segment) in segments.iter().enumerate() {
+        for &(key, _) in segment {
+            ends.entry(key)
+                .and_modify(|ends| ends[1] = index)
+                .or_insert([index, index]);
+        }
+    }
+
+    let mut visited = vec![false; segments.len()];
+    let mut outlines = Vec::new();
+    for start in 0..segments.len() {
+        if visited[start] {
+            continue;
+        }
+
+        // Every segment is entered through the edge the last one left through, ending back at the
+        // edge the first one was entered through
+        let mut outline = Vec::new();
+        let (mut segment, mut key) = (start, segments[start][0].0);
+        loop {
+            visited[segment] = true;
+            let [(a, point_a), (b, point_b)] = segments[segment];
+            let (exit, point) = if a == key { (b, point_b) } else { (a, point_a) };
+            outline.push(point);
+
+            let [first, second] = ends[&exit];
+            let next = if first == segment { second } else { first };
+            if visited[next] {
+                break;
+            }
+            (segment, key) = (next, exit);
+        }
+
+        if outline.len() >= min_points {
+            outlines.push(outline);
+        }
+    }
+
+    Contour { level, outlines }
+}
+
+/// Writes the outlines of every contour of a `width` by `height` image as an SVG, with a path of
+/// unfilled outlines per contour so that plotters draw every level with its own pen.
+pub fn write_svg(mut writer: impl Write, width: usize, height: usize, contours: &[Contour]) -> io::Result<()> {
+    writeln!(
+        writer,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+    )?;
+
+    for contour in contours {
+        write!(
+            writer,
+            r#"  <path id="level-{}" fill="none" stroke="black" stroke-width="0.5" d=""#,
+            contour.level
+        )?;
+        for outline in contour.outlines.iter() {
+            for (i, &(x, y)) in outline.iter().enumerate() {
+                write!(writer, "{}{:.2} {:.2}", if i == 0 { "M" } else { "L" }, x, y)?;
+            }
+            write!(writer, "Z")?;
+        }
+        writeln!(writer, r#""/>"#)?;
+    }
+
+    writeln!(writer, "</svg>")
+}
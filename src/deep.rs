@@ -0,0 +1,486 @@
+use dashu_float::{round::mode::HalfAway, DBig, FBig};
+use std::{
+    f64::consts::SQRT_2,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    canvas::Canvas,
+    color::Color,
+    complex::Complex,
+    fractal::{Fractal, Kernel},
+    noise::NoiseStream,
+    numa,
+    sample::{plot_trajectory, run_chunks, Orbits, SampleSettings, SampleStats},
+};
+
+/// A decimal number that keeps every digit it was written with, so that the center of a deep zoom
+/// can be given exactly.
+pub type Decimal = DBig;
+
+/// A binary float of arbitrary precision.
+type Real = FBig<HalfAway>;
+
+/// The number of bits kept beyond those needed to resolve a view, absorbing the rounding error
+/// that builds up over an orbit.
+const GUARD_BITS: usize = 64;
+
+/// The squared escape radius of the mandelbrot iteration.
+const BAILOUT: f64 = 4.0;
+
+/// How much closer to zero than the reference orbit a perturbed orbit may come before its deltas
+/// are too imprecise to trust, following Pauldelbrot's glitch criterion.
+const GLITCH_TOLERANCE: f64 = 1e-3;
+
+/// How small the cubic term of a series approximation has to stay next to its linear term for the
+/// terms it truncates to be negligible.
+const SERIES_TOLERANCE: f64 = 1e-9;
+
+/// Rounds an exactly written point to double precision.
+pub fn approx(z: &Complex<Decimal>) -> Complex<f64> {
+    Complex::new(z.re.to_f64().value(), z.im.to_f64().value())
+}
+
+/// Chooses the number of bits orbits are iterated with to resolve a view of the given scale, which
+/// gives the points of the view as many significant bits relative to its size as an `f64` has.
+pub fn precision_bits(scale: f64) -> usize {
+    let depth = (-scale.log2()).max(0.0).ceil() as usize;
+    f64::MANTISSA_DIGITS as usize + depth + GUARD_BITS
+}
+
+/// A complex number of arbitrary precision.
+#[derive(Clone, Debug)]
+struct BigComplex {
+    re: Real,
+    im: Real,
+}
+
+impl BigComplex {
+    /// Converts a double precision point exactly, rounding later arithmetic to `bits` bits.
+    fn from_f64(z: Complex<f64>, bits: usize) -> BigComplex {
+        let real = |x: f64| Real::try_from(x).unwrap().with_precision(bits).value();
+        Self {
+            re: real(z.re),
+            im: real(z.im),
+        }
+    }
+
+    /// Converts a decimal point, rounding it to `bits` bits.
+    fn from_decimal(z: &Complex<Decimal>, bits: usize) -> BigComplex {
+        let real = |x: &Decimal| x.clone().with_base_and_precision::<2>(bits).value();
+        Self {
+            re: real(&z.re),
+            im: real(&z.im),
+        }
+    }
+
+    #[inline]
+    fn add(&self, rhs: &BigComplex) -> BigComplex {
+        Self {
+            re: &self.re + &rhs.re,
+            im: &self.im + &rhs.im,
+        }
+    }
+
+    #[inline]
+    fn sub(&self, rhs: &BigComplex) -> BigComplex {
+        Self {
+            re: &self.re - &rhs.re,
+            im: &self.im - &rhs.im,
+        }
+    }
+
+    /// Computes `self² + c`.
+    #[inline]
+    fn square_add(&self, c: &BigComplex) -> BigComplex {
+        Self {
+            re: self.re.sqr() - self.im.sqr() + &c.re,
+            im: ((&self.re * &self.im) << 1) + &c.im,
+        }
+    }
+
+    /// Rounds this number to double precision.
+    #[inline]
+    fn to_f64(&self) -> Complex<f64> {
+        Complex::new(self.re.to_f64().value(), self.im.to_f64().value())
+    }
+}
+
+/// The view of a deep zoom into the mandelbrot set, too deep for its orbits to be iterated in
+/// double precision.
+///
+/// Orbits are plotted relative to the center of the view, where double precision is enough to tell
+/// its pixels apart again. Iterating an orbit exactly in arbitrary precision is very slow, so most
+/// are iterated as perturbations of a [`ReferenceOrbit`] instead.
+#[derive(Clone, Debug)]
+pub struct DeepZoom {
+    center: BigComplex,
+    bits: usize,
+}
+
+impl DeepZoom {
+    /// Creates the view of `scale` around `center`, choosing the precision orbits are iterated in
+    /// from its scale.
+    pub fn new(center: &Complex<Decimal>, scale: f64) -> DeepZoom {
+        let bits = precision_bits(scale);
+        Self {
+            center: BigComplex::from_decimal(center, bits),
+            bits,
+        }
+    }
+
+    /// Creates the view of the sample settings, whose center is only exact to double precision.
+    pub fn from_settings(settings: &SampleSettings) -> DeepZoom {
+        let bits = precision_bits(settings.scale);
+        Self {
+            center: BigComplex::from_f64(settings.center, bits),
+            bits,
+        }
+    }
+
+    /// Gets the number of bits orbits are iterated with.
+    #[inline]
+    pub fn bits(&self) -> usize {
+        self.bits
+    }
+
+    /// Gets the initial value of `z` and the constant `c` of the orbit of the sample `delta` away
+    /// from the center.
+    fn orbit_start(&self, settings: &SampleSettings, delta: Complex<f64>) -> (BigComplex, BigComplex) {
+        let sample = self.center.add(&BigComplex::from_f64(delta, self.bits));
+        match settings.julia {
+            Some(c) => (sample, BigComplex::from_f64(c.map(f64::from), self.bits)),
+            None => (sample.clone(), sample),
+        }
+    }
+
+    /// Visits the first `n` points of the orbit of the sample `delta` away from the center, each
+    /// relative to the center, returning whether or not it escaped. The points of escaping orbits
+    /// are visited up to the one before they escape, like [`mandelbrot`](crate::sample::mandelbrot).
+    fn iterate(&self, settings: &SampleSettings, delta: Complex<f64>, mut visit: impl FnMut(Complex<f64>)) -> bool {
+        let (mut z, c) = self.orbit_start(settings, delta);
+
+        for _ in 0..settings.n {
+            visit(z.sub(&self.center).to_f64());
+
+            z = z.square_add(&c);
+            let w = z.to_f64();
+            if w.re * w.re + w.im * w.im > BAILOUT {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+/// The orbit of the center of a deep zoom, iterated once in arbitrary precision so that the orbits
+/// of samples can be iterated as small perturbations of it in double precision.
+///
+/// A sample's orbit `z` is tracked as its difference `δz` from the reference orbit `Z`, which
+/// follows `δz = 2·Z·δz + δz² + δc` and stays small enough for double precision to represent
+/// exactly where the two orbits stay close.
+#[derive(Clone, Debug)]
+pub struct ReferenceOrbit {
+    /// Every iterate, rounded to double precision.
+    z: Vec<Complex<f64>>,
+    /// Every iterate relative to the center, rounded after subtracting it.
+    relative: Vec<Complex<f64>>,
+    /// The number of iterations every perturbed orbit skips by starting from `series`.
+    skip: usize,
+    /// The approximation of the deltas after `skip` iterations.
+    series: Series,
+}
+
+impl ReferenceOrbit {
+    /// Iterates the orbit of the center of `zoom` for the iterations of `settings`, stopping after
+    /// the first point outside the escape radius.
+    pub fn new(zoom: &DeepZoom, settings: &SampleSettings) -> ReferenceOrbit {
+        let (mut z, c) = zoom.orbit_start(settings, Complex::new(0.0, 0.0));
+        let mut orbit = Self {
+            z: Vec::new(),
+            relative: Vec::new(),
+            skip: 0,
+            series: Series::IDENTITY,
+        };
+
+        for _ in 0..=settings.n {
+            let w = z.to_f64();
+            orbit.z.push(w);
+            orbit.relative.push(z.sub(&zoom.center).to_f64());
+
+            if w.re * w.re + w.im * w.im > BAILOUT {
+                break;
+            }
+            z = z.square_add(&c);
+        }
+
+        orbit.approximate(settings);
+        orbit
+    }
+
+    /// Chooses how many iterations every sample can skip, stepping a series approximation along
+    /// this orbit for as long as it bounds the deltas of every sample in the view closely enough.
+    ///
+    /// Skipped iterates are stood in for by those of this orbit, so the series is only used while
+    /// they provably land outside the view and can't escape or glitch, leaving the plotted image
+    /// unchanged and only approximating the arc length of the skipped part of each orbit.
+    fn approximate(&mut self, settings: &SampleSettings) {
+        // Every sample lies within the circle around the center touching the corners of the view
+        let radius = 2.0 * SQRT_2 * settings.scale;
+        let dc = match settings.julia {
+            Some(_) => 0.0,
+            None => 1.0,
+        };
+
+        let mut series = Series::IDENTITY;
+        for i in 1..self.len().min(settings.n as usize + 1) {
+            series = series.step(self.z[i - 1], dc);
+
+            let bound = series.bound(radius);
+            let z = self.z[i].abs();
+            let valid = series.c.abs() * radius * radius <= SERIES_TOLERANCE * series.a.abs()
+                && self.relative[i].abs() > radius + bound
+                && z + bound <= BAILOUT.sqrt()
+                && bound <= (1.0 - GLITCH_TOLERANCE) * z;
+
+            if !valid {
+                break;
+            }
+            self.skip = i;
+            self.series = series;
+        }
+    }
+
+    /// Gets the number of iterations every perturbed orbit skips through series approximation.
+    #[inline]
+    pub fn skip(&self) -> usize {
+        self.skip
+    }
+
+    /// Gets the number of iterates of this orbit, which is one more than the iterations of the
+    /// settings it was iterated with unless it escaped.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.z.len()
+    }
+
+    /// Whether or not this orbit has no iterates.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.z.is_empty()
+    }
+
+    /// Visits the orbit of the sample `delta` away from the center like [`DeepZoom::iterate`], by
+    /// perturbing this orbit. Returns `None` if the perturbation glitched or outlived this orbit,
+    /// in which case some points may have been visited already and the orbit has to be iterated
+    /// exactly instead.
+    fn perturb(
+        &self,
+        settings: &SampleSettings,
+        delta: Complex<f64>,
+        mut visit: impl FnMut(Complex<f64>),
+    ) -> Option<bool> {
+        let dc = match settings.julia {
+            Some(_) => Complex::new(0.0, 0.0),
+            None => delta,
+        };
+        let mut dz = delta;
+        let mut start = 0;
+
+        if self.skip > 0 {
+            visit(self.relative[0] + delta);
+            self.relative[1..self.skip].iter().for_each(|&w| visit(w));
+            dz = self.series.evaluate(delta);
+            start = self.skip;
+        }
+
+        for i in start..settings.n as usize {
+            let (&z, &next) = (self.z.get(i)?, self.z.get(i + 1)?);
+            visit(self.relative[i] + dz);
+
+            dz = z * dz * 2.0 + dz * dz + dc;
+            let w = next + dz;
+            let norm = w.re * w.re + w.im * w.im;
+            if norm > BAILOUT {
+                return Some(true);
+            }
+
+            // Close to zero, the deltas are larger than the orbit itself and have lost its precision
+            if norm < GLITCH_TOLERANCE * GLITCH_TOLERANCE * (next.re * next.re + next.im * next.im) {
+                return None;
+            }
+        }
+
+        Some(false)
+    }
+}
+
+/// The series `δz = A·δ + B·δ² + C·δ³` approximating the delta of a perturbed orbit after some
+/// number of iterations in terms of its sample's offset `δ` from the center.
+///
+/// Its coefficients only depend on the reference orbit, so stepping them once along it lets every
+/// sample jump straight to a later iteration.
+#[derive(Clone, Copy, Debug)]
+struct Series {
+    a: Complex<f64>,
+    b: Complex<f64>,
+    c: Complex<f64>,
+}
+
+impl Series {
+    /// The series of the starting deltas, which are the offsets themselves.
+    const IDENTITY: Series = Series {
+        a: Complex { re: 1.0, im: 0.0 },
+        b: Complex { re: 0.0, im: 0.0 },
+        c: Complex { re: 0.0, im: 0.0 },
+    };
+
+    /// Steps this series through one iteration of the reference iterate `z`, where `dc` is one if
+    /// the offsets perturb the constant and zero if they only perturb the starting point.
+    #[inline]
+    fn step(self, z: Complex<f64>, dc: f64) -> Series {
+        let z = z * 2.0;
+        Self {
+            a: z * self.a + dc,
+            b: z * self.b + self.a * self.a,
+            c: z * self.c + self.a * self.b * 2.0,
+        }
+    }
+
+    /// Evaluates this series at the offset `delta`.
+    #[inline]
+    fn evaluate(&self, delta: Complex<f64>) -> Complex<f64> {
+        ((self.c * delta + self.b) * delta + self.a) * delta
+    }
+
+    /// Bounds the deltas this series gives for offsets within `radius` of the center.
+    #[inline]
+    fn bound(&self, radius: f64) -> f64 {
+        ((self.c.abs() * radius + self.b.abs()) * radius + self.a.abs()) * radius
+    }
+}
+
+/// Statistics of a deep zoom render.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DeepStats {
+    /// The number of samples whose orbits were iterated.
+    pub samples: u64,
+    /// The number of samples whose perturbed orbits glitched, which were iterated exactly instead.
+    pub glitched: u64,
+    /// The number of iterations every perturbed orbit skipped through series approximation.
+    pub skipped: usize,
+}
+
+impl DeepStats {
+    /// Gets the fraction of samples whose perturbed orbits glitched.
+    #[inline]
+    pub fn glitch_rate(&self) -> f32 {
+        self.glitched as f32 / self.samples.max(1) as f32
+    }
+
+    /// Combines the statistics of two renders.
+    pub fn merge(self, rhs: DeepStats) -> DeepStats {
+        Self {
+            samples: self.samples + rhs.samples,
+            glitched: self.glitched + rhs.glitched,
+            skipped: self.skipped.max(rhs.skipped),
+        }
+    }
+}
+
+/// Samples orbits of a deep zoom into every image of a canvas, perturbing a reference orbit of its
+/// center in double precision and iterating glitched orbits in arbitrary precision. The settings
+/// are [validated](SampleSettings::validate) to only use features that work relative to the center
+/// of the view.
+pub fn sample_deep<T: Color + Clone + Copy + Send + Sync + 'static>(
+    canvas: Arc<Mutex<Canvas<T>>>,
+    settings: SampleSettings,
+    zoom: &DeepZoom,
+) -> SampleStats {
+    let blank = canvas.lock().unwrap().blank();
+    let iters = blank.main.size * settings.m as usize;
+    let reference = ReferenceOrbit::new(zoom, &settings);
+    let empty = DeepStats {
+        skipped: reference.skip(),
+        ..Default::default()
+    };
+
+    let subcanvases = run_chunks(
+        &settings,
+        iters,
+        || (blank.clone(), empty),
+        |(subcanvas, stats), stream, throttle, count| {
+            for _ in 0..count {
+                plot_deep_sample(subcanvas, &settings, zoom, &reference, stream, stats);
+                throttle.tick(1);
+            }
+        },
+    );
+
+    let threads = subcanvases.iter().map(|(_, (subcanvas, _))| subcanvas.counts).collect();
+    let mut stats = empty;
+    let merge = |(canvas, stats): &mut (Canvas<T>, DeepStats), (other, other_stats)| {
+        canvas.merge(other);
+        *stats = stats.merge(other_stats);
+    };
+    for (subcanvas, subcanvas_stats) in numa::merge_by_node(subcanvases, merge) {
+        canvas.lock().unwrap().merge(subcanvas);
+        stats = stats.merge(subcanvas_stats);
+    }
+
+    SampleStats {
+        deep: Some(stats),
+        threads,
+        ..Default::default()
+    }
+}
+
+/// Plots the orbit of a sample of a deep zoom drawn from `stream` into `canvas`, perturbing the
+/// reference orbit of its center if it can.
+pub(crate) fn plot_deep_sample<T: Color + Clone + Copy>(
+    canvas: &mut Canvas<T>,
+    settings: &SampleSettings,
+    zoom: &DeepZoom,
+    reference: &ReferenceOrbit,
+    stream: &mut NoiseStream,
+    stats: &mut DeepStats,
+) {
+    let (u, v) = stream.next_point();
+    let delta = Complex::new(u as f64 * 4.0 - 2.0, v as f64 * 4.0 - 2.0) * settings.scale;
+
+    let mut trajectory = Vec::new();
+    let escaped = match reference.perturb(settings, delta, |w| trajectory.push(w)) {
+        Some(escaped) => escaped,
+        None => {
+            stats.glitched += 1;
+            trajectory.clear();
+            zoom.iterate(settings, delta, |w| trajectory.push(w))
+        },
+    };
+    stats.samples += 1;
+    canvas.counts.samples += 1;
+    canvas.counts.escaped += escaped as u64;
+
+    let plotted = match settings.orbits {
+        Orbits::Escaping => escaped && trajectory.len() as u32 >= settings.min_iterations,
+        Orbits::Bounded => !escaped,
+    };
+
+    if plotted {
+        // Points are plotted relative to the center, so the view they land in is centered on zero
+        let relative = SampleSettings {
+            center: Complex::new(0.0, 0.0),
+            ..*settings
+        };
+        plot_trajectory(
+            canvas,
+            &relative,
+            Fractal::Mandelbrot,
+            &Kernel::default(),
+            delta,
+            trajectory.into_iter(),
+            1.0,
+        );
+    }
+}
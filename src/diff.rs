@@ -0,0 +1,65 @@
+use num_traits::{Float, ToPrimitive, Zero};
+
+use crate::{color::Color, images::Image};
+
+/// How far an image is from a reference image of the same dimensions, over every channel of every
+/// pixel.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Difference {
+    /// The number of pixels any channel of which differs.
+    pub pixels: usize,
+    /// The largest absolute difference of any channel.
+    pub max: f64,
+    /// The mean absolute difference.
+    pub mean: f64,
+    /// The root mean square difference.
+    pub rmse: f64,
+    /// The peak signal-to-noise ratio in decibels, relative to the brightest channel of the
+    /// reference, which is infinite for identical images.
+    pub psnr: f64,
+}
+
+impl Difference {
+    /// Compares `im` to `reference`, which must have the same dimensions, returning how far apart
+    /// they are along with the image of the absolute difference of every channel.
+    pub fn compute<T: Color + Clone + Copy>(reference: &Image<T>, im: &Image<T>) -> (Difference, Image<T>) {
+        let mut image = Image::<T>::new(reference.size, reference.width);
+        let (mut pixels, mut max, mut sum, mut squares, mut peak) = (0, 0.0f64, 0.0, 0.0, 0.0f64);
+        let mut channels = vec![T::Channel::zero(); T::CHANNELS.len()];
+
+        for ((a, b), px) in reference.pixels().zip(im.pixels()).zip(image.pixels_mut()) {
+            let mut differs = false;
+            for (i, channel) in channels.iter_mut().enumerate() {
+                let (a, b) = (a.channel(i), b.channel(i));
+                let delta = (a - b).abs();
+                *channel = delta;
+
+                let delta = delta.to_f64().unwrap();
+                differs |= delta != 0.0;
+                max = max.max(delta);
+                sum += delta;
+                squares += delta * delta;
+                peak = peak.max(a.to_f64().unwrap());
+            }
+
+            pixels += differs as usize;
+            *px = T::from_channels(&channels);
+        }
+
+        let count = (reference.size * T::CHANNELS.len()).max(1) as f64;
+        let rmse = (squares / count).sqrt();
+        let difference = Difference {
+            pixels,
+            max,
+            mean: sum / count,
+            rmse,
+            psnr: if rmse > 0.0 {
+                20.0 * (peak / rmse).log10()
+            } else {
+                f64::INFINITY
+            },
+        };
+
+        (difference, image)
+    }
+}
@@ -0,0 +1,206 @@
+use clap::ValueEnum;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::sync::OnceLock;
+
+/// The width of the tile of blue noise thresholds repeated over an image.
+const MASK_WIDTH: usize = 64;
+
+/// The spread of the gaussian the void-and-cluster method measures how crowded a point is with.
+const MASK_SIGMA: f32 = 1.5;
+
+/// How values are rounded to the levels of an 8-bit format.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Dither {
+    /// Round every value to the nearest level, which leaves visible bands in smooth gradients.
+    #[default]
+    None,
+    /// Offset every value by a threshold from a tile of blue noise before rounding it, breaking
+    /// bands up into fine grain without any low frequency pattern.
+    BlueNoise,
+    /// Spread the error of rounding every value over its neighbours to the right and below.
+    FloydSteinberg,
+}
+
+/// Rounds the rows of an image to 8-bit levels one after another from the top, carrying the error
+/// of every row over to the next when diffusing it.
+pub struct Quantizer {
+    dither: Dither,
+    channels: usize,
+    y: usize,
+    /// The error diffused into the current and the next row, per sample.
+    errors: [Vec<f32>; 2],
+}
+
+impl Quantizer {
+    /// Creates a quantizer of rows of `width` pixels of `channels` interleaved samples each.
+    pub fn new(dither: Dither, width: usize, channels: usize) -> Quantizer {
+        let errors = match dither {
+            Dither::FloydSteinberg => [vec![0.0; width * channels], vec![0.0; width * channels]],
+            _ => [Vec::new(), Vec::new()],
+        };
+
+        Quantizer {
+            dither,
+            channels,
+            y: 0,
+            errors,
+        }
+    }
+
+    /// Rounds the next row of samples between 0-1, already in the encoding they're stored in, to
+    /// levels between 0-255.
+    pub fn row(&mut self, values: &[f32], out: &mut [u8]) {
+        let channels = self.channels;
+        let width = values.len() / channels;
+        let level = |v: f32| v.clamp(0.0, 1.0) * u8::MAX as f32;
+
+        match self.dither {
+            Dither::None => {
+                for (out, &v) in out.iter_mut().zip(values) {
+                    *out = level(v).round() as u8;
+                }
+            },
+            Dither::BlueNoise => {
+                let mask = blue_noise();
+                let row = &mask[(self.y % MASK_WIDTH) * MASK_WIDTH..][..MASK_WIDTH];
+                for (i, (out, &v)) in out.iter_mut().zip(values).enumerate() {
+                    let threshold = row[(i / channels) % MASK_WIDTH];
+                    *out = (level(v) + threshold).floor().min(u8::MAX as f32) as u8;
+                }
+            },
+            Dither::FloydSteinberg => {
+                let [current, next] = &mut self.errors;
+                next.fill(0.0);
+                for x in 0..width {
+                    for c in 0..channels {
+                        let i = x * channels + c;
+                        let v = level(values[i]) + current[i];
+                        let rounded = v.round().clamp(0.0, u8::MAX as f32);
+                        out[i] = rounded as u8;
+
+                        let error = v - rounded;
+                        if x + 1 < width {
+                            current[i + channels] += error * 7.0 / 16.0;
+                            next[i + channels] += error / 16.0;
+                        }
+                        if x > 0 {
+                            next[i - channels] += error * 3.0 / 16.0;
+                        }
+                        next[i] += error * 5.0 / 16.0;
+                    }
+                }
+                self.errors.swap(0, 1);
+            },
+        }
+
+        self.y += 1;
+    }
+}
+
+/// Gets the thresholds of a tile of blue noise between 0-1, one row after another, made once with
+/// the void-and-cluster method.
+fn blue_noise() -> &'static [f32] {
+    static MASK: OnceLock<Vec<f32>> = OnceLock::new();
+    MASK.get_or_init(|| {
+        let size = MASK_WIDTH * MASK_WIDTH;
+        let mut field = Energy::new();
+
+        // Starts from a tenth of the points set at random, moving the point in the tightest cluster
+        // into the largest void until it's already the largest void
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut pattern = vec![false; size];
+        while pattern.iter().filter(|&&set| set).count() < size / 10 {
+            let i = rng.gen_range(0..size);
+            if !pattern[i] {
+                pattern[i] = true;
+                field.add(i, 1.0);
+            }
+        }
+        loop {
+            let cluster = field.tightest_cluster(&pattern);
+            pattern[cluster] = false;
+            field.add(cluster, -1.0);
+
+            let void = field.largest_void(&pattern);
+            pattern[void] = true;
+            field.add(void, 1.0);
+            if void == cluster {
+                break;
+            }
+        }
+
+        // Points of the initial pattern are ranked by removing them from the tightest cluster, and
+        // every other point by adding it to the largest void
+        let initial = pattern.iter().filter(|&&set| set).count();
+        let mut ranks = vec![0; size];
+        let (mut removed, mut removed_field) = (pattern.clone(), field.clone());
+        for rank in (0..initial).rev() {
+            let cluster = removed_field.tightest_cluster(&removed);
+            removed[cluster] = false;
+            removed_field.add(cluster, -1.0);
+            ranks[cluster] = rank;
+        }
+        for rank in initial..size {
+            let void = field.largest_void(&pattern);
+            pattern[void] = true;
+            field.add(void, 1.0);
+            ranks[void] = rank;
+        }
+
+        ranks
+            .into_iter()
+            .map(|rank| (rank as f32 + 0.5) / size as f32)
+            .collect()
+    })
+}
+
+/// How crowded every point of a tile is by the set points around it, wrapping around its edges.
+#[derive(Clone)]
+struct Energy {
+    values: Vec<f32>,
+    /// The weight of a point at every offset from another along one axis.
+    kernel: Vec<f32>,
+}
+
+impl Energy {
+    fn new() -> Energy {
+        let kernel = (0..MASK_WIDTH)
+            .map(|d| {
+                let d = d.min(MASK_WIDTH - d) as f32;
+                (-d * d / (2.0 * MASK_SIGMA * MASK_SIGMA)).exp()
+            })
+            .collect();
+
+        Energy {
+            values: vec![0.0; MASK_WIDTH * MASK_WIDTH],
+            kernel,
+        }
+    }
+
+    /// Adds the energy of setting the point `i`, or removes it when `sign` is negative.
+    fn add(&mut self, i: usize, sign: f32) {
+        let (px, py) = (i % MASK_WIDTH, i / MASK_WIDTH);
+        for y in 0..MASK_WIDTH {
+            let wy = self.kernel[(y + MASK_WIDTH - py) % MASK_WIDTH] * sign;
+            for x in 0..MASK_WIDTH {
+                self.values[y * MASK_WIDTH + x] += wy * self.kernel[(x + MASK_WIDTH - px) % MASK_WIDTH];
+            }
+        }
+    }
+
+    /// Gets the set point with the most energy.
+    fn tightest_cluster(&self, pattern: &[bool]) -> usize {
+        (0..pattern.len())
+            .filter(|&i| pattern[i])
+            .max_by(|&a, &b| self.values[a].total_cmp(&self.values[b]))
+            .unwrap()
+    }
+
+    /// Gets the unset point with the least energy.
+    fn largest_void(&self, pattern: &[bool]) -> usize {
+        (0..pattern.len())
+            .filter(|&i| !pattern[i])
+            .min_by(|&a, &b| self.values[a].total_cmp(&self.values[b]))
+            .unwrap()
+    }
+}
@@ -0,0 +1,167 @@
+use num_traits::Float;
+
+use crate::{complex::Complex, sample::Orbit};
+
+/// The distance within which an orbit is considered to have returned to a point of its cycle.
+const PERIOD_TOLERANCE: f32 = 1e-4;
+
+/// The state of an orbit carried from one iteration to the next.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct OrbitState<F = f32> {
+    /// The current iterate.
+    pub z: Complex<F>,
+    /// The previous iterate, which is zero before the first iteration.
+    pub prev: Complex<F>,
+}
+
+impl<F: Float> OrbitState<F> {
+    /// Creates the state of an orbit starting at `z`.
+    #[inline]
+    pub fn new(z: Complex<F>) -> OrbitState<F> {
+        Self {
+            z,
+            prev: Complex::new(F::zero(), F::zero()),
+        }
+    }
+}
+
+/// An iteration whose orbits are plotted, such as `z = z² + c`.
+///
+/// The sampler is generic over its dynamics, so a custom iteration passed to
+/// [`sample_canvas_with`](crate::sample::sample_canvas_with) is compiled into the sampling loop just
+/// like the built-in [`Kernel`](crate::fractal::Kernel).
+///
+/// Orbits are iterated in the scalar type `F`. Dynamics only need to implement the precisions they
+/// support, while the built-in kernels iterate in any of them.
+pub trait Dynamics<F: Float = f32>: Clone + Send + Sync + 'static {
+    /// Applies one iteration to `z` with constant `c`.
+    fn step(&self, z: Complex<F>, c: Complex<F>) -> Complex<F>;
+
+    /// Whether or not `z` has escaped, ending its orbit.
+    fn escaped(&self, z: Complex<F>) -> bool;
+
+    /// Applies one iteration to the orbit `state`. Iterations depending on earlier iterates than
+    /// the current one implement this in terms of the whole state.
+    #[inline]
+    fn advance(&self, state: OrbitState<F>, c: Complex<F>) -> OrbitState<F> {
+        OrbitState {
+            z: self.step(state.z, c),
+            prev: state.z,
+        }
+    }
+
+    /// Whether or not the orbit `state` has converged to a fixed point, after which it no longer
+    /// moves and can't escape.
+    #[inline]
+    fn converged(&self, _state: OrbitState<F>) -> bool {
+        false
+    }
+
+    /// Gets the first point of the orbit of `c`.
+    #[inline]
+    fn start(&self, c: Complex<F>) -> Complex<F> {
+        c
+    }
+
+    /// Gets the index of the attractor a converging orbit ending at `z` converged to.
+    #[inline]
+    fn root(&self, _z: Complex<F>) -> usize {
+        0
+    }
+
+    /// Detects the period of the cycle a bounded orbit ending at `z` with constant `c` has settled
+    /// into, or `None` if it doesn't return to `z` within `max` iterations.
+    #[inline]
+    fn period(&self, z: Complex<F>, c: Complex<F>, max: u32) -> Option<u32> {
+        let tolerance = F::from(PERIOD_TOLERANCE).unwrap();
+        let mut state = OrbitState::new(z);
+        for period in 1..=max {
+            state = self.advance(state, c);
+            let d = state.z - z;
+            if d.re * d.re + d.im * d.im < tolerance * tolerance {
+                return Some(period);
+            }
+        }
+
+        None
+    }
+
+    /// Calculates the trajectory of `z` with constant `c` into `sequence`, replacing its contents,
+    /// if it escapes within `n` iterations, or leaves `sequence` empty if it converges or doesn't
+    /// escape. Reusing the same `sequence` for every orbit saves allocating one per orbit.
+    #[inline]
+    fn trajectory(&self, z: Complex<F>, c: Complex<F>, n: u32, sequence: &mut Vec<Complex<F>>) {
+        iterate_trajectory(self, z, c, n, sequence)
+    }
+
+    /// Computes the number of iterations it takes for `z` with constant `c` to escape, or `None` if it
+    /// converges or doesn't escape within `n` iterations.
+    #[inline]
+    fn escape_time(&self, z: Complex<F>, c: Complex<F>, n: u32) -> Option<u32> {
+        iterate_escape_time(self, z, c, n)
+    }
+
+    /// Computes the escape time of every point of `z` with the matching constant of `c` into
+    /// `times`. Dynamics that can iterate several orbits at once override this to do so.
+    #[inline]
+    fn escape_times(&self, z: &[Complex<F>], c: &[Complex<F>], n: u32, times: &mut [Option<u32>]) {
+        for ((&z, &c), time) in z.iter().zip(c).zip(times) {
+            *time = self.escape_time(z, c, n);
+        }
+    }
+
+    /// Creates an iterator over the orbit of `z` with constant `c`.
+    #[inline]
+    fn orbit(&self, z: Complex<F>, c: Complex<F>, n: u32) -> Orbit<Self, F> {
+        Orbit::with_dynamics(self.clone(), z, c, n)
+    }
+}
+
+/// Iterates `dynamics` one step at a time to calculate the trajectory of `z` with constant `c`.
+pub(crate) fn iterate_trajectory<D: Dynamics<F>, F: Float>(
+    dynamics: &D,
+    z: Complex<F>,
+    c: Complex<F>,
+    n: u32,
+    sequence: &mut Vec<Complex<F>>,
+) {
+    sequence.clear();
+    let mut state = OrbitState::new(z);
+
+    for _ in 0..n {
+        sequence.push(state.z);
+
+        state = dynamics.advance(state, c);
+        if dynamics.escaped(state.z) {
+            return;
+        }
+
+        if dynamics.converged(state) {
+            break;
+        }
+    }
+
+    sequence.clear();
+}
+
+/// Iterates `dynamics` one step at a time to compute the escape time of `z` with constant `c`.
+pub(crate) fn iterate_escape_time<D: Dynamics<F>, F: Float>(
+    dynamics: &D,
+    z: Complex<F>,
+    c: Complex<F>,
+    n: u32,
+) -> Option<u32> {
+    let mut state = OrbitState::new(z);
+    for i in 0..n {
+        state = dynamics.advance(state, c);
+        if dynamics.escaped(state.z) {
+            return Some(i + 1);
+        }
+
+        if dynamics.converged(state) {
+            break;
+        }
+    }
+
+    None
+}
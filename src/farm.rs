@@ -0,0 +1,303 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    canvas::Canvas,
+    color::Color,
+    images::Image,
+    sample::{sample_chunk, ChunkState, SampleSettings},
+    throttle::Throttle,
+};
+
+/// The number of samples in a single unit of work. Tasks are interleaved at this granularity, so it
+/// bounds how long a newly submitted task waits for a worker.
+const CHUNK_SIZE: usize = 4096;
+
+/// The number of samples between cancellation checks within a chunk.
+const CANCEL_CHECK: usize = 256;
+
+/// A pool of worker threads shared between any number of independent render tasks.
+///
+/// Tasks are split into chunks which are scheduled round-robin, so a small preview render submitted
+/// while a large render is running finishes promptly instead of waiting for the large render.
+pub struct RenderFarm {
+    queue: Arc<Queue>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl RenderFarm {
+    /// Creates a new render farm with `threads` worker threads.
+    pub fn new(threads: usize) -> RenderFarm {
+        let queue = Arc::new(Queue {
+            state: Mutex::new(QueueState {
+                jobs: VecDeque::new(),
+                shutdown: false,
+            }),
+            available: Condvar::new(),
+        });
+
+        let workers = (0..threads.max(1))
+            .map(|_| {
+                let queue = queue.clone();
+                thread::spawn(move || queue.work())
+            })
+            .collect();
+
+        Self { queue, workers }
+    }
+
+    /// Submits a new render of a `width` by `height` image, returning a handle to it.
+    pub fn submit<T: Color + Clone + Copy + Send + Sync + 'static>(
+        &self,
+        width: usize,
+        height: usize,
+        settings: SampleSettings,
+    ) -> RenderTask<T> {
+        let samples = width * height * settings.m as usize;
+        let state = Arc::new(TaskState {
+            settings,
+            width,
+            size: width * height,
+            samples: samples as u64,
+            chunks: samples.div_ceil(CHUNK_SIZE),
+            schedule: Mutex::new(Schedule { next: 0, running: 0 }),
+            idle: Condvar::new(),
+            cancelled: AtomicBool::new(false),
+            progress: AtomicU64::new(0),
+            buffers: Mutex::new(Vec::new()),
+            chunk_state: ChunkState::default(),
+        });
+
+        let mut queue = self.queue.state.lock().unwrap();
+        queue.jobs.push_back(state.clone());
+        self.queue.available.notify_all();
+
+        RenderTask { state }
+    }
+
+    /// Gets the number of worker threads in this farm.
+    #[inline]
+    pub fn threads(&self) -> usize {
+        self.workers.len()
+    }
+}
+
+impl Default for RenderFarm {
+    fn default() -> Self {
+        Self::new(num_cpus::get())
+    }
+}
+
+impl Drop for RenderFarm {
+    fn drop(&mut self) {
+        self.queue.state.lock().unwrap().shutdown = true;
+        self.queue.available.notify_all();
+
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// A handle to a render running on a [`RenderFarm`].
+pub struct RenderTask<T: Color> {
+    state: Arc<TaskState<T>>,
+}
+
+impl<T: Color + Clone + Copy + Send + Sync + 'static> RenderTask<T> {
+    /// Gets the number of samples that have been plotted so far.
+    #[inline]
+    pub fn samples_done(&self) -> u64 {
+        self.state.progress.load(Ordering::Relaxed)
+    }
+
+    /// Gets the total number of samples this render will plot.
+    #[inline]
+    pub fn samples(&self) -> u64 {
+        self.state.samples
+    }
+
+    /// Gets the fraction of this render that has completed, between 0-1.
+    #[inline]
+    pub fn progress(&self) -> f32 {
+        self.samples_done() as f32 / self.samples().max(1) as f32
+    }
+
+    /// Stops this render as soon as possible. Samples plotted so far are kept.
+    pub fn cancel(&self) {
+        let schedule = self.state.schedule.lock().unwrap();
+        self.state.cancelled.store(true, Ordering::Relaxed);
+        if self.state.is_finished(&schedule) {
+            self.state.idle.notify_all();
+        }
+    }
+
+    /// Whether or not this render has been cancelled.
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Whether or not this render has stopped, either by completing or by being cancelled.
+    pub fn is_finished(&self) -> bool {
+        self.state.is_finished(&self.state.schedule.lock().unwrap())
+    }
+
+    /// Blocks until this render has stopped, returning the resulting image. If the render was
+    /// cancelled, the image only contains the samples plotted before it stopped.
+    pub fn wait(self) -> Image<T> {
+        let mut schedule = self.state.schedule.lock().unwrap();
+        while !self.state.is_finished(&schedule) {
+            schedule = self.state.idle.wait(schedule).unwrap();
+        }
+        drop(schedule);
+
+        let buffers = std::mem::take(&mut *self.state.buffers.lock().unwrap());
+        let mut buffers = buffers.into_iter().map(|(canvas, _)| canvas);
+        let mut canvas = buffers
+            .next()
+            .unwrap_or_else(|| Canvas::new(Image::new(self.state.size, self.state.width)));
+        for buffer in buffers {
+            canvas.merge(buffer);
+        }
+
+        canvas.main
+    }
+}
+
+struct Queue {
+    state: Mutex<QueueState>,
+    available: Condvar,
+}
+
+struct QueueState {
+    jobs: VecDeque<Arc<dyn Job>>,
+    shutdown: bool,
+}
+
+impl Queue {
+    /// Runs chunks from the queue until the farm shuts down.
+    fn work(&self) {
+        let mut throttle = Throttle::new(1.0);
+
+        loop {
+            let mut state = self.state.lock().unwrap();
+            let (job, chunk) = loop {
+                if state.shutdown {
+                    return;
+                }
+
+                if let Some(job) = state.jobs.pop_front() {
+                    if let Some(chunk) = job.claim() {
+                        // Move the job to the back of the queue so tasks take turns
+                        if job.has_more() {
+                            state.jobs.push_back(job.clone());
+                        }
+                        break (job, chunk);
+                    }
+                } else {
+                    state = self.available.wait(state).unwrap();
+                    throttle.restart();
+                }
+            };
+            drop(state);
+
+            job.run(chunk, &mut throttle);
+        }
+    }
+}
+
+/// A unit of schedulable work, erasing the color type of a task.
+trait Job: Send + Sync {
+    /// Claims the next chunk of this job, if there is one.
+    fn claim(&self) -> Option<usize>;
+
+    /// Whether or not this job has chunks left to claim.
+    fn has_more(&self) -> bool;
+
+    /// Runs a previously claimed chunk, pausing the worker as often as the job requires.
+    fn run(&self, chunk: usize, throttle: &mut Throttle);
+}
+
+struct Schedule {
+    /// The index of the next unclaimed chunk.
+    next: usize,
+    /// The number of chunks claimed but not yet finished.
+    running: usize,
+}
+
+struct TaskState<T: Color> {
+    settings: SampleSettings,
+    width: usize,
+    size: usize,
+    samples: u64,
+    chunks: usize,
+    schedule: Mutex<Schedule>,
+    idle: Condvar,
+    cancelled: AtomicBool,
+    progress: AtomicU64,
+    /// Accumulation buffers not currently in use by a worker, along with the state of the samplers
+    /// drawing into them. There are never more of these than there are workers in the farm.
+    buffers: Mutex<Vec<(Canvas<T>, ChunkState)>>,
+    /// The state every buffer's sampler state is forked from, sharing what's set up once per task.
+    chunk_state: ChunkState,
+}
+
+impl<T: Color> TaskState<T> {
+    #[inline]
+    fn is_finished(&self, schedule: &Schedule) -> bool {
+        schedule.running == 0 && (schedule.next >= self.chunks || self.cancelled.load(Ordering::Relaxed))
+    }
+}
+
+impl<T: Color + Clone + Copy + Send + Sync + 'static> Job for TaskState<T> {
+    fn claim(&self) -> Option<usize> {
+        let mut schedule = self.schedule.lock().unwrap();
+        if self.cancelled.load(Ordering::Relaxed) || schedule.next >= self.chunks {
+            return None;
+        }
+
+        schedule.next += 1;
+        schedule.running += 1;
+        Some(schedule.next - 1)
+    }
+
+    fn has_more(&self) -> bool {
+        !self.cancelled.load(Ordering::Relaxed) && self.schedule.lock().unwrap().next < self.chunks
+    }
+
+    fn run(&self, chunk: usize, throttle: &mut Throttle) {
+        let (mut canvas, mut state) = self
+            .buffers
+            .lock()
+            .unwrap()
+            .pop()
+            .unwrap_or_else(|| (Canvas::new(Image::new(self.size, self.width)), self.chunk_state.fork()));
+
+        // Each chunk draws from its own noise stream, so the result doesn't depend on which worker
+        // ran which chunk
+        let mut stream = self.settings.noise.stream(chunk as u64);
+        throttle.set_duty_cycle(self.settings.duty_cycle);
+        let mut remaining = (self.samples as usize - chunk * CHUNK_SIZE).min(CHUNK_SIZE);
+        while remaining > 0 && !self.cancelled.load(Ordering::Relaxed) {
+            let count = remaining.min(CANCEL_CHECK);
+            sample_chunk(&mut canvas, &self.settings, &mut state, &mut stream, throttle, count);
+            self.progress.fetch_add(count as u64, Ordering::Relaxed);
+            remaining -= count;
+        }
+
+        self.buffers.lock().unwrap().push((canvas, state));
+
+        let mut schedule = self.schedule.lock().unwrap();
+        schedule.running -= 1;
+        if self.is_finished(&schedule) {
+            self.idle.notify_all();
+        }
+    }
+}
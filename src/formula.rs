@@ -0,0 +1,598 @@
+use std::{fmt, sync::Arc};
+
+use crate::{complex::Complex, dynamics::Dynamics};
+
+#[cfg(feature = "jit")]
+mod jit;
+
+/// A function that can be called from a formula.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Function {
+    Sin,
+    Cos,
+    Tan,
+    Sinh,
+    Cosh,
+    Tanh,
+    Exp,
+    Ln,
+    Sqrt,
+    Abs,
+    Conj,
+    Re,
+    Im,
+}
+
+impl Function {
+    const ALL: [(&'static str, Function); 14] = [
+        ("sin", Function::Sin),
+        ("cos", Function::Cos),
+        ("tan", Function::Tan),
+        ("sinh", Function::Sinh),
+        ("cosh", Function::Cosh),
+        ("tanh", Function::Tanh),
+        ("exp", Function::Exp),
+        ("ln", Function::Ln),
+        ("log", Function::Ln),
+        ("sqrt", Function::Sqrt),
+        ("abs", Function::Abs),
+        ("conj", Function::Conj),
+        ("re", Function::Re),
+        ("im", Function::Im),
+    ];
+
+    fn from_name(name: &str) -> Option<Function> {
+        Self::ALL.iter().find(|(n, _)| *n == name).map(|&(_, f)| f)
+    }
+
+    #[inline]
+    fn apply(self, z: Complex<f32>) -> Complex<f32> {
+        match self {
+            Function::Sin => Complex::new(z.re.sin() * z.im.cosh(), z.re.cos() * z.im.sinh()),
+            Function::Cos => Complex::new(z.re.cos() * z.im.cosh(), -z.re.sin() * z.im.sinh()),
+            Function::Tan => Function::Sin.apply(z) / Function::Cos.apply(z),
+            Function::Sinh => Complex::new(z.re.sinh() * z.im.cos(), z.re.cosh() * z.im.sin()),
+            Function::Cosh => Complex::new(z.re.cosh() * z.im.cos(), z.re.sinh() * z.im.sin()),
+            Function::Tanh => Function::Sinh.apply(z) / Function::Cosh.apply(z),
+            Function::Exp => exp(z),
+            Function::Ln => ln(z),
+            Function::Sqrt => {
+                let r = z.abs().sqrt();
+                let theta = z.im.atan2(z.re) * 0.5;
+                Complex::new(r * theta.cos(), r * theta.sin())
+            },
+            Function::Abs => Complex::new(z.abs(), 0.0),
+            Function::Conj => Complex::new(z.re, -z.im),
+            Function::Re => Complex::new(z.re, 0.0),
+            Function::Im => Complex::new(z.im, 0.0),
+        }
+    }
+}
+
+#[inline]
+fn exp(z: Complex<f32>) -> Complex<f32> {
+    let r = z.re.exp();
+    Complex::new(r * z.im.cos(), r * z.im.sin())
+}
+
+#[inline]
+fn ln(z: Complex<f32>) -> Complex<f32> {
+    Complex::new(z.abs().ln(), z.im.atan2(z.re))
+}
+
+/// Raises `a` to the power `b`, taking zero to any power to be zero.
+#[inline]
+fn pow(a: Complex<f32>, b: Complex<f32>) -> Complex<f32> {
+    if a.re == 0.0 && a.im == 0.0 {
+        Complex::new(0.0, 0.0)
+    } else {
+        exp(b * ln(a))
+    }
+}
+
+/// Raises `z` to an integer power by repeated squaring.
+#[inline]
+fn powi(mut z: Complex<f32>, d: i32) -> Complex<f32> {
+    let mut result = Complex::new(1.0, 0.0);
+    let mut n = d.unsigned_abs();
+    while n > 0 {
+        if n & 1 == 1 {
+            result = result * z;
+        }
+        z = z * z;
+        n >>= 1;
+    }
+
+    if d < 0 {
+        Complex::new(1.0, 0.0) / result
+    } else {
+        result
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+/// A node of a parsed formula.
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Const(Complex<f32>),
+    Z,
+    C,
+    Neg(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    /// A power with a constant integer exponent, which is much faster than a general power.
+    Powi(Box<Expr>, i32),
+    Call(Function, Box<Expr>),
+}
+
+impl Expr {
+    /// Combines two nodes with a binary operator, folding constants.
+    fn binary(op: BinaryOp, lhs: Expr, rhs: Expr) -> Expr {
+        if let BinaryOp::Pow = op {
+            if let Expr::Const(d) = rhs {
+                if d.im == 0.0 && d.re.fract() == 0.0 && d.re.abs() <= 64.0 {
+                    return Expr::Powi(Box::new(lhs), d.re as i32).fold();
+                }
+            }
+        }
+
+        Expr::Binary(op, Box::new(lhs), Box::new(rhs)).fold()
+    }
+
+    /// Evaluates this node if it doesn't depend on `z` or `c`.
+    fn fold(self) -> Expr {
+        let constant = match &self {
+            Expr::Neg(x) | Expr::Powi(x, _) | Expr::Call(_, x) => x.is_const(),
+            Expr::Binary(_, lhs, rhs) => lhs.is_const() && rhs.is_const(),
+            _ => false,
+        };
+
+        if constant {
+            Expr::Const(self.eval(Complex::new(0.0, 0.0), Complex::new(0.0, 0.0)))
+        } else {
+            self
+        }
+    }
+
+    fn is_const(&self) -> bool {
+        matches!(self, Expr::Const(_))
+    }
+
+    fn depends_on_z(&self) -> bool {
+        match self {
+            Expr::Z => true,
+            Expr::Const(_) | Expr::C => false,
+            Expr::Neg(x) | Expr::Powi(x, _) | Expr::Call(_, x) => x.depends_on_z(),
+            Expr::Binary(_, lhs, rhs) => lhs.depends_on_z() || rhs.depends_on_z(),
+        }
+    }
+
+    fn eval(&self, z: Complex<f32>, c: Complex<f32>) -> Complex<f32> {
+        match self {
+            Expr::Const(x) => *x,
+            Expr::Z => z,
+            Expr::C => c,
+            Expr::Neg(x) => x.eval(z, c) * -1.0,
+            Expr::Binary(op, lhs, rhs) => {
+                let (a, b) = (lhs.eval(z, c), rhs.eval(z, c));
+                match op {
+                    BinaryOp::Add => a + b,
+                    BinaryOp::Sub => a - b,
+                    BinaryOp::Mul => a * b,
+                    BinaryOp::Div => a / b,
+                    BinaryOp::Pow => pow(a, b),
+                }
+            },
+            Expr::Powi(x, d) => powi(x.eval(z, c), *d),
+            Expr::Call(f, x) => f.apply(x.eval(z, c)),
+        }
+    }
+}
+
+/// An error encountered while parsing a formula.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: String,
+    /// The byte offset in the formula at which the error was found.
+    pub position: usize,
+}
+
+impl ParseError {
+    fn new(message: impl Into<String>, position: usize) -> ParseError {
+        Self {
+            message: message.into(),
+            position,
+        }
+    }
+
+    /// Formats this error below the `formula` it was found in, pointing at its position.
+    pub fn pointing_into(&self, formula: &str) -> String {
+        let column = formula[..self.position.min(formula.len())].chars().count();
+        format!("{}\n  {}\n  {}^", self.message, formula, " ".repeat(column))
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f32),
+    Ident(String),
+    Op(char),
+    LParen,
+    RParen,
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(x) => write!(f, "number {}", x),
+            Token::Ident(name) => write!(f, "`{}`", name),
+            Token::Op(op) => write!(f, "`{}`", op),
+            Token::LParen => write!(f, "`(`"),
+            Token::RParen => write!(f, "`)`"),
+            Token::End => write!(f, "the end of the formula"),
+        }
+    }
+}
+
+/// Splits a formula into tokens paired with their byte offsets.
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let bytes = source.as_bytes();
+    let digit_at = |i: usize| bytes.get(i).is_some_and(u8::is_ascii_digit);
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while let Some(ch) = source[i..].chars().next() {
+        let start = i;
+        if ch.is_whitespace() {
+            i += ch.len_utf8();
+        } else if ch.is_ascii_digit() || ch == '.' {
+            while digit_at(i) || bytes.get(i) == Some(&b'.') {
+                i += 1;
+            }
+
+            // Only treat an `e` as an exponent if digits follow, so `2exp(z)` is `2 * exp(z)`
+            if matches!(bytes.get(i), Some(b'e' | b'E')) {
+                let sign = matches!(bytes.get(i + 1), Some(b'+' | b'-')) as usize;
+                if digit_at(i + 1 + sign) {
+                    i += 1 + sign;
+                    while digit_at(i) {
+                        i += 1;
+                    }
+                }
+            }
+
+            let text = &source[start..i];
+            let value = text
+                .parse::<f32>()
+                .map_err(|_| ParseError::new(format!("invalid number `{}`", text), start))?;
+            tokens.push((Token::Number(value), start));
+        } else if ch.is_alphabetic() || ch == '_' {
+            while let Some(ch) = source[i..]
+                .chars()
+                .next()
+                .filter(|&ch| ch.is_alphanumeric() || ch == '_')
+            {
+                i += ch.len_utf8();
+            }
+            tokens.push((Token::Ident(source[start..i].to_lowercase()), start));
+        } else {
+            let token = match ch {
+                '+' | '-' | '*' | '/' | '^' => Token::Op(ch),
+                '(' => Token::LParen,
+                ')' => Token::RParen,
+                _ => return Err(ParseError::new(format!("unexpected character `{}`", ch), start)),
+            };
+            tokens.push((token, start));
+            i += 1;
+        }
+    }
+
+    tokens.push((Token::End, source.len()));
+    Ok(tokens)
+}
+
+/// A recursive descent parser over the tokens of a formula.
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.next].0
+    }
+
+    fn position(&self) -> usize {
+        self.tokens[self.next].1
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.next].0.clone();
+        if token != Token::End {
+            self.next += 1;
+        }
+        token
+    }
+
+    fn unexpected(&self, expected: &str) -> ParseError {
+        ParseError::new(
+            format!("expected {}, but found {}", expected, self.peek()),
+            self.position(),
+        )
+    }
+
+    /// `expr := term (('+' | '-') term)*`
+    fn expr(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.term()?;
+        loop {
+            let op = match self.peek() {
+                Token::Op('+') => BinaryOp::Add,
+                Token::Op('-') => BinaryOp::Sub,
+                _ => return Ok(lhs),
+            };
+            self.advance();
+            lhs = Expr::binary(op, lhs, self.term()?);
+        }
+    }
+
+    /// `term := unary (('*' | '/')? unary)*`, where a missing operator is a multiplication.
+    fn term(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Token::Op('*') => BinaryOp::Mul,
+                Token::Op('/') => BinaryOp::Div,
+                Token::Number(_) | Token::Ident(_) | Token::LParen => {
+                    lhs = Expr::binary(BinaryOp::Mul, lhs, self.power()?);
+                    continue;
+                },
+                _ => return Ok(lhs),
+            };
+            self.advance();
+            lhs = Expr::binary(op, lhs, self.unary()?);
+        }
+    }
+
+    /// `unary := '-' unary | power`
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        if *self.peek() == Token::Op('-') {
+            self.advance();
+            return Ok(Expr::Neg(Box::new(self.unary()?)).fold());
+        }
+
+        self.power()
+    }
+
+    /// `power := primary ('^' unary)?`
+    fn power(&mut self) -> Result<Expr, ParseError> {
+        let base = self.primary()?;
+        if *self.peek() == Token::Op('^') {
+            self.advance();
+            return Ok(Expr::binary(BinaryOp::Pow, base, self.unary()?));
+        }
+
+        Ok(base)
+    }
+
+    /// `primary := number | variable | function '(' expr ')' | '(' expr ')'`
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        let position = self.position();
+        match self.peek().clone() {
+            Token::Number(x) => {
+                self.advance();
+                Ok(Expr::Const(Complex::new(x, 0.0)))
+            },
+            Token::LParen => {
+                self.advance();
+                let inner = self.expr()?;
+                self.close()?;
+                Ok(inner)
+            },
+            Token::Ident(name) => {
+                self.advance();
+                match name.as_str() {
+                    "z" => Ok(Expr::Z),
+                    "c" => Ok(Expr::C),
+                    "i" => Ok(Expr::Const(Complex::new(0.0, 1.0))),
+                    _ => {
+                        let Some(function) = Function::from_name(&name) else {
+                            let names = Function::ALL.map(|(n, _)| n).join(", ");
+                            return Err(ParseError::new(
+                                format!(
+                                    "unknown name `{}`, expected z, c, i or one of the functions {}",
+                                    name, names
+                                ),
+                                position,
+                            ));
+                        };
+
+                        if *self.peek() != Token::LParen {
+                            return Err(self.unexpected(&format!("`(` after the function `{}`", name)));
+                        }
+                        self.advance();
+                        let argument = self.expr()?;
+                        self.close()?;
+                        Ok(Expr::Call(function, Box::new(argument)).fold())
+                    },
+                }
+            },
+            _ => Err(self.unexpected("a number, variable, function or `(`")),
+        }
+    }
+
+    fn close(&mut self) -> Result<(), ParseError> {
+        if *self.peek() != Token::RParen {
+            return Err(self.unexpected("`)`"));
+        }
+        self.advance();
+        Ok(())
+    }
+}
+
+/// An iteration `z = f(z, c)` parsed from a formula at runtime, such as `z^2 + c*sin(z)`.
+///
+/// Formulas may use the variables `z` and `c`, the imaginary unit `i`, real numbers, the operators
+/// `+`, `-`, `*`, `/` and `^`, and the functions `sin`, `cos`, `tan`, `sinh`, `cosh`, `tanh`,
+/// `exp`, `ln`, `sqrt`, `abs`, `conj`, `re` and `im`. Multiplication may be implied, as in `2z`.
+#[derive(Clone, Debug)]
+pub struct Formula {
+    /// The text the formula was parsed from, which it's displayed as.
+    source: Arc<str>,
+    expr: Arc<Expr>,
+    /// The formula compiled to native code, unless the host isn't supported.
+    #[cfg(feature = "jit")]
+    jit: Option<Arc<jit::JitFormula>>,
+    /// The squared escape radius.
+    bailout: f32,
+}
+
+impl Formula {
+    /// Parses a formula, checking that it is a valid iteration of `z`.
+    pub fn parse(source: &str) -> Result<Formula, ParseError> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            next: 0,
+        };
+
+        if *parser.peek() == Token::End {
+            return Err(ParseError::new("the formula is empty", 0));
+        }
+
+        let expr = parser.expr()?;
+        if *parser.peek() != Token::End {
+            return Err(parser.unexpected("an operator"));
+        }
+
+        if !expr.depends_on_z() {
+            return Err(ParseError::new(
+                "the formula doesn't depend on z, so every orbit would stand still",
+                0,
+            ));
+        }
+
+        Ok(Self {
+            #[cfg(feature = "jit")]
+            jit: jit::JitFormula::compile(&expr).map(Arc::new),
+            source: source.trim().into(),
+            expr: Arc::new(expr),
+            bailout: 4.0,
+        })
+    }
+
+    /// Sets the radius beyond which orbits are considered to have escaped, which is 2 by default.
+    pub fn with_escape_radius(mut self, radius: f32) -> Formula {
+        self.bailout = radius * radius;
+        self
+    }
+}
+
+impl fmt::Display for Formula {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl Dynamics for Formula {
+    #[inline]
+    fn step(&self, z: Complex<f32>, c: Complex<f32>) -> Complex<f32> {
+        #[cfg(feature = "jit")]
+        if let Some(jit) = &self.jit {
+            return jit.eval(z, c);
+        }
+
+        self.expr.eval(z, c)
+    }
+
+    /// Whether or not `z` is outside the escape radius or no longer a finite number.
+    #[inline]
+    fn escaped(&self, z: Complex<f32>) -> bool {
+        let norm = z.re * z.re + z.im * z.im;
+        norm > self.bailout || norm.is_nan()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fractal::{Fractal, Kernel};
+
+    /// Parses `source` and applies one iteration of it to `z`.
+    fn eval(source: &str, z: f32, c: f32) -> Complex<f32> {
+        Formula::parse(source)
+            .unwrap()
+            .step(Complex::new(z, 0.0), Complex::new(c, 0.0))
+    }
+
+    fn parse_error(source: &str) -> ParseError {
+        Formula::parse(source).unwrap_err()
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("z + 2*3", 1.0, 0.0), Complex::new(7.0, 0.0));
+        assert_eq!(eval("z*2 + 3", 1.0, 0.0), Complex::new(5.0, 0.0));
+        assert_eq!(eval("2*z^2", 3.0, 0.0), Complex::new(18.0, 0.0));
+        assert_eq!(eval("2z^2 + c", 3.0, 1.0), Complex::new(19.0, 0.0));
+        assert_eq!(eval("-z^2", 3.0, 0.0), Complex::new(-9.0, 0.0));
+        assert_eq!(eval("(z + 1)*2", 3.0, 0.0), Complex::new(8.0, 0.0));
+    }
+
+    #[test]
+    fn associativity() {
+        assert_eq!(eval("z - 4 - 2", 8.0, 0.0), Complex::new(2.0, 0.0));
+        assert_eq!(eval("z/4/2", 8.0, 0.0), Complex::new(1.0, 0.0));
+        assert_eq!(eval("z^3^2", 2.0, 0.0), Complex::new(512.0, 0.0));
+        assert_eq!(eval("z^-1", 4.0, 0.0), Complex::new(0.25, 0.0));
+    }
+
+    #[test]
+    fn matches_mandelbrot_kernel() {
+        let formula = Formula::parse("z^2+c").unwrap();
+        let kernel = Kernel::new(Fractal::Mandelbrot, 2.0);
+        for (c, steps) in [
+            (Complex::new(-0.75, 0.1), 100),
+            (Complex::new(0.3, 0.5), 50),
+            (Complex::new(-2.1, 0.0), 5),
+        ] {
+            let (mut a, mut b) = (c, c);
+            for _ in 0..steps {
+                a = formula.step(a, c);
+                b = Dynamics::<f32>::step(&kernel, b, c);
+                assert!((a - b).abs() <= 1e-5 * b.abs().max(1.0), "{:?} != {:?}", a, b);
+                assert_eq!(formula.escaped(a), Dynamics::<f32>::escaped(&kernel, b));
+                if formula.escaped(a) {
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_formulas() {
+        assert_eq!(parse_error("").message, "the formula is empty");
+        assert_eq!(parse_error("z +").position, 3);
+        assert_eq!(parse_error("(z + c").position, 6);
+        assert_eq!(parse_error("z + c)").position, 5);
+        assert_eq!(parse_error("z $ c").position, 2);
+        assert_eq!(parse_error("sin z").position, 4);
+        assert!(parse_error("foo(z)").message.starts_with("unknown name `foo`"));
+        assert!(parse_error("c^2 + 1").message.contains("doesn't depend on z"));
+        assert!(parse_error("z + 1..2").message.starts_with("invalid number"));
+    }
+}
@@ -0,0 +1,273 @@
+use std::fmt;
+
+use cranelift_codegen::{
+    ir::{
+        types, AbiParam, FuncRef, InstBuilder, MemFlags, StackSlot, StackSlotData, StackSlotKind, UserFuncName, Value,
+    },
+    settings::{self, Configurable},
+    Context,
+};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, Linkage, Module};
+
+use super::{pow, BinaryOp, Expr, Function};
+use crate::complex::Complex;
+
+/// The functions called through [`apply`], indexed by the first argument passed to it.
+const FUNCTIONS: [Function; 10] = [
+    Function::Sin,
+    Function::Cos,
+    Function::Tan,
+    Function::Sinh,
+    Function::Cosh,
+    Function::Tanh,
+    Function::Exp,
+    Function::Ln,
+    Function::Sqrt,
+    Function::Abs,
+];
+
+/// Applies the function at `index` of [`FUNCTIONS`] on behalf of compiled code, which has no
+/// transcendental functions of its own and must compute absolute values the same way as the
+/// interpreter.
+unsafe extern "C" fn apply(index: u32, re: f32, im: f32, out: *mut [f32; 2]) {
+    let w = FUNCTIONS[index as usize].apply(Complex::new(re, im));
+    *out = [w.re, w.im];
+}
+
+/// Computes a general power on behalf of compiled code.
+unsafe extern "C" fn power(a_re: f32, a_im: f32, b_re: f32, b_im: f32, out: *mut [f32; 2]) {
+    let w = pow(Complex::new(a_re, a_im), Complex::new(b_re, b_im));
+    *out = [w.re, w.im];
+}
+
+type Compiled = unsafe extern "C" fn(f32, f32, f32, f32, *mut [f32; 2]);
+
+/// A formula compiled to native code.
+pub(super) struct JitFormula {
+    /// The module owning the compiled code, which is only taken when it is freed.
+    module: Option<JITModule>,
+    function: Compiled,
+}
+
+// The compiled code is immutable once finalized, so it can be called from any thread.
+unsafe impl Send for JitFormula {}
+unsafe impl Sync for JitFormula {}
+
+impl JitFormula {
+    /// Compiles `expr`, or returns `None` if the host isn't supported by the code generator.
+    pub(super) fn compile(expr: &Expr) -> Option<JitFormula> {
+        let mut flags = settings::builder();
+        flags.set("opt_level", "speed").ok()?;
+        let isa = cranelift_native::builder()
+            .ok()?
+            .finish(settings::Flags::new(flags))
+            .ok()?;
+
+        let mut builder = JITBuilder::with_isa(isa, default_libcall_names());
+        builder.symbol("apply", apply as *const u8);
+        builder.symbol("power", power as *const u8);
+        let mut module = JITModule::new(builder);
+
+        let ptr = module.target_config().pointer_type();
+        let mut signature = module.make_signature();
+        signature.params.extend([AbiParam::new(types::F32); 4]);
+        signature.params.push(AbiParam::new(ptr));
+
+        let mut apply_signature = module.make_signature();
+        apply_signature.params.push(AbiParam::new(types::I32));
+        apply_signature.params.extend([AbiParam::new(types::F32); 2]);
+        apply_signature.params.push(AbiParam::new(ptr));
+
+        let apply = module
+            .declare_function("apply", Linkage::Import, &apply_signature)
+            .ok()?;
+        let power = module.declare_function("power", Linkage::Import, &signature).ok()?;
+        let step = module.declare_function("step", Linkage::Local, &signature).ok()?;
+
+        let mut ctx = Context::new();
+        ctx.func.signature = signature;
+        ctx.func.name = UserFuncName::user(0, step.as_u32());
+
+        let mut function_ctx = FunctionBuilderContext::new();
+        let mut b = FunctionBuilder::new(&mut ctx.func, &mut function_ctx);
+        let block = b.create_block();
+        b.append_block_params_for_function_params(block);
+        b.switch_to_block(block);
+
+        let params = b.block_params(block).to_vec();
+        let slot = b.create_sized_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, 8, 2));
+        let mut emitter = Emitter {
+            apply: module.declare_func_in_func(apply, b.func),
+            power: module.declare_func_in_func(power, b.func),
+            z: (params[0], params[1]),
+            c: (params[2], params[3]),
+            slot,
+            ptr,
+            b,
+        };
+
+        let (re, im) = emitter.emit(expr);
+        let out = params[4];
+        emitter.b.ins().store(MemFlags::trusted(), re, out, 0);
+        emitter.b.ins().store(MemFlags::trusted(), im, out, 4);
+        emitter.b.ins().return_(&[]);
+        emitter.b.seal_all_blocks();
+        emitter.b.finalize();
+
+        module.define_function(step, &mut ctx).ok()?;
+        module.finalize_definitions().ok()?;
+
+        let code = module.get_finalized_function(step);
+        // SAFETY: the function was just compiled with exactly this signature
+        let function = unsafe { std::mem::transmute::<*const u8, Compiled>(code) };
+
+        Some(Self {
+            module: Some(module),
+            function,
+        })
+    }
+
+    #[inline]
+    pub(super) fn eval(&self, z: Complex<f32>, c: Complex<f32>) -> Complex<f32> {
+        let mut out = [0.0; 2];
+        // SAFETY: the code is kept alive by the module and only writes to `out`
+        unsafe { (self.function)(z.re, z.im, c.re, c.im, &mut out) };
+        Complex::new(out[0], out[1])
+    }
+}
+
+impl Drop for JitFormula {
+    fn drop(&mut self) {
+        if let Some(module) = self.module.take() {
+            // SAFETY: the compiled function can no longer be called once its owner is dropped
+            unsafe { module.free_memory() };
+        }
+    }
+}
+
+impl fmt::Debug for JitFormula {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JitFormula").finish_non_exhaustive()
+    }
+}
+
+/// Emits the instructions evaluating a formula, keeping complex values as pairs of registers.
+struct Emitter<'a> {
+    b: FunctionBuilder<'a>,
+    apply: FuncRef,
+    power: FuncRef,
+    z: (Value, Value),
+    c: (Value, Value),
+    /// Scratch space the called functions write their results to.
+    slot: StackSlot,
+    ptr: types::Type,
+}
+
+impl Emitter<'_> {
+    fn emit(&mut self, expr: &Expr) -> (Value, Value) {
+        match expr {
+            Expr::Const(x) => (self.b.ins().f32const(x.re), self.b.ins().f32const(x.im)),
+            Expr::Z => self.z,
+            Expr::C => self.c,
+            Expr::Neg(x) => {
+                let (re, im) = self.emit(x);
+                (self.b.ins().fneg(re), self.b.ins().fneg(im))
+            },
+            Expr::Binary(op, lhs, rhs) => {
+                let a = self.emit(lhs);
+                let b = self.emit(rhs);
+                match op {
+                    BinaryOp::Add => (self.b.ins().fadd(a.0, b.0), self.b.ins().fadd(a.1, b.1)),
+                    BinaryOp::Sub => (self.b.ins().fsub(a.0, b.0), self.b.ins().fsub(a.1, b.1)),
+                    BinaryOp::Mul => self.mul(a, b),
+                    BinaryOp::Div => self.div(a, b),
+                    BinaryOp::Pow => {
+                        let out = self.b.ins().stack_addr(self.ptr, self.slot, 0);
+                        self.b.ins().call(self.power, &[a.0, a.1, b.0, b.1, out]);
+                        self.result()
+                    },
+                }
+            },
+            Expr::Powi(x, d) => {
+                let x = self.emit(x);
+                self.powi(x, *d)
+            },
+            Expr::Call(f, x) => {
+                let (re, im) = self.emit(x);
+                match f {
+                    Function::Conj => (re, self.b.ins().fneg(im)),
+                    Function::Re => (re, self.b.ins().f32const(0.0)),
+                    Function::Im => (im, self.b.ins().f32const(0.0)),
+                    f => {
+                        let index = FUNCTIONS.iter().position(|g| g == f).unwrap();
+                        let index = self.b.ins().iconst(types::I32, index as i64);
+                        let out = self.b.ins().stack_addr(self.ptr, self.slot, 0);
+                        self.b.ins().call(self.apply, &[index, re, im, out]);
+                        self.result()
+                    },
+                }
+            },
+        }
+    }
+
+    /// Loads the result a called function wrote to the scratch slot.
+    fn result(&mut self) -> (Value, Value) {
+        (
+            self.b.ins().stack_load(types::F32, self.slot, 0),
+            self.b.ins().stack_load(types::F32, self.slot, 4),
+        )
+    }
+
+    fn mul(&mut self, a: (Value, Value), b: (Value, Value)) -> (Value, Value) {
+        let rr = self.b.ins().fmul(a.0, b.0);
+        let ii = self.b.ins().fmul(a.1, b.1);
+        let ri = self.b.ins().fmul(a.0, b.1);
+        let ir = self.b.ins().fmul(a.1, b.0);
+        (self.b.ins().fsub(rr, ii), self.b.ins().fadd(ri, ir))
+    }
+
+    fn div(&mut self, a: (Value, Value), b: (Value, Value)) -> (Value, Value) {
+        let rr = self.b.ins().fmul(a.0, b.0);
+        let ii = self.b.ins().fmul(a.1, b.1);
+        let ir = self.b.ins().fmul(a.1, b.0);
+        let ri = self.b.ins().fmul(a.0, b.1);
+        let norm = self.norm(b);
+        let re = self.b.ins().fadd(rr, ii);
+        let im = self.b.ins().fsub(ir, ri);
+        (self.b.ins().fdiv(re, norm), self.b.ins().fdiv(im, norm))
+    }
+
+    fn norm(&mut self, z: (Value, Value)) -> Value {
+        let rr = self.b.ins().fmul(z.0, z.0);
+        let ii = self.b.ins().fmul(z.1, z.1);
+        self.b.ins().fadd(rr, ii)
+    }
+
+    /// Raises `z` to a constant integer power by repeated squaring, unrolled.
+    fn powi(&mut self, mut z: (Value, Value), d: i32) -> (Value, Value) {
+        let mut result = None;
+        let mut n = d.unsigned_abs();
+        while n > 0 {
+            if n & 1 == 1 {
+                result = Some(match result {
+                    Some(result) => self.mul(result, z),
+                    None => z,
+                });
+            }
+
+            n >>= 1;
+            if n > 0 {
+                z = self.mul(z, z);
+            }
+        }
+
+        let one = (self.b.ins().f32const(1.0), self.b.ins().f32const(0.0));
+        match result {
+            Some(result) if d < 0 => self.div(one, result),
+            Some(result) => result,
+            None => one,
+        }
+    }
+}
@@ -0,0 +1,403 @@
+use clap::ValueEnum;
+use num_traits::Float;
+
+use crate::{
+    canvas::Viewport,
+    color::{Color, ColorChannel},
+    complex::Complex,
+    dynamics::{iterate_escape_time, iterate_trajectory, Dynamics, OrbitState},
+    sample::{mandelbrot, mandelbrot_escape_time},
+    simd::SimdFloat,
+};
+
+/// The iteration whose orbits are plotted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Fractal {
+    /// `z = z² + c`
+    #[default]
+    Mandelbrot,
+    /// `z = (|Re(z)| + i|Im(z)|)² + c`
+    BurningShip,
+    /// `z = z̄² + c`, also known as the mandelbar.
+    #[value(alias = "mandelbar")]
+    Tricorn,
+    /// `z = |Re(z²)| + i·Im(z²) + c`
+    Celtic,
+    /// `z = (Re(z) - i|Im(z)|)² + c`
+    PerpendicularBurningShip,
+    /// `z = z² + c + p·z₋₁`, where `z₋₁` is the previous iterate and `p` a fixed coefficient.
+    Phoenix,
+    /// `z = ((z² + c - 1) / (2z + c - 2))²`, the type 1 magnet. Orbits converging to 1 are inside
+    /// the set, along with those that neither escape nor converge.
+    Magnet,
+    /// `z = c·z(1 - z)`, the logistic map with `c` as its parameter `λ`, conjugate to the mandelbrot
+    /// iteration with parameter `λ/2 - λ²/4`. Orbits start from the critical point `1/2`.
+    #[value(alias = "logistic")]
+    Lambda,
+    /// Newton's method for the roots of `zᵈ - 1`, `z = z - (zᵈ - 1) / (d·zᵈ⁻¹)`. Samples are
+    /// initial values of `z`, and orbits converge to a root rather than escaping.
+    Newton,
+    /// `z = z - (zᵈ - 1) / (d·zᵈ⁻¹) + c`, Newton's method perturbed by `c`. Orbits converge to a
+    /// fixed point rather than escaping.
+    Nova,
+}
+
+impl Fractal {
+    /// Gets the radius beyond which an orbit of this fractal with `z` raised to `power` is guaranteed
+    /// to escape, given that `c` lies within it.
+    #[inline]
+    pub fn escape_radius(self, power: f32) -> f32 {
+        match self {
+            // Taking absolute values of the components of `z` or `zᵈ` doesn't change its magnitude, so
+            // every variant escapes exactly when the multibrot of the same power does. The phoenix
+            // does too when its coefficient is zero, and its kernel widens the radius otherwise.
+            Fractal::Mandelbrot
+            | Fractal::BurningShip
+            | Fractal::Tricorn
+            | Fractal::Celtic
+            | Fractal::PerpendicularBurningShip
+            | Fractal::Phoenix => 2.0f32.powf(1.0 / (power - 1.0)).max(2.0),
+            // Beyond 100, `|z|` grows by a factor of at least 10 every iteration
+            Fractal::Magnet => 100.0,
+            // Every `λ` outside the set lies outside the unit disk, for which `|z|` grows by a factor
+            // of at least 3 every iteration beyond 4, while orbits of `λ` inside it stay near 0
+            Fractal::Lambda => 4.0,
+            // Far from the origin, Newton's method contracts `z` by a factor of `(d - 1) / d`, so only
+            // orbits thrown out by landing on the critical point at 0 get this far
+            Fractal::Newton | Fractal::Nova => 1e6,
+        }
+    }
+
+    /// Whether or not the power `z` is raised to can be changed from 2.
+    #[inline]
+    pub fn has_power(self) -> bool {
+        !matches!(self, Fractal::Magnet | Fractal::Lambda)
+    }
+
+    /// Whether or not orbits of this fractal can escape, rather than only converging.
+    #[inline]
+    pub fn escapes(self) -> bool {
+        !self.has_roots()
+    }
+
+    /// Whether or not orbits of this fractal converge to fixed points near the roots of `zᵈ - 1`, so
+    /// they can be told apart by the root they converge to. Newton orbits converge to the roots
+    /// themselves, while the fixed points of the nova iteration are shifted by `c`.
+    #[inline]
+    pub fn has_roots(self) -> bool {
+        matches!(self, Fractal::Newton | Fractal::Nova)
+    }
+
+    /// Gets the region in which this fractal's orbits are best sampled and viewed.
+    pub fn view(self) -> Viewport {
+        match self {
+            Fractal::Mandelbrot
+            | Fractal::BurningShip
+            | Fractal::Tricorn
+            | Fractal::PerpendicularBurningShip
+            | Fractal::Phoenix
+            | Fractal::Newton
+            | Fractal::Nova => Viewport::default(),
+            Fractal::Celtic => Viewport::new(Complex::new(-0.2, 0.0), 0.9),
+            Fractal::Magnet => Viewport::new(Complex::new(1.5, 0.0), 1.75),
+            Fractal::Lambda => Viewport::new(Complex::new(1.0, 0.0), 1.6),
+        }
+    }
+}
+
+/// The distance from a fixed point within which an orbit has converged to it.
+const CONVERGENCE_RADIUS: f32 = 1e-4;
+
+/// The power an iteration raises `z` to, specialized for faster iteration where possible.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Power {
+    Square,
+    Integer(u32),
+    Real(f32),
+}
+
+impl Power {
+    #[inline]
+    fn value(self) -> f32 {
+        match self {
+            Power::Square => 2.0,
+            Power::Integer(d) => d as f32,
+            Power::Real(d) => d,
+        }
+    }
+}
+
+/// A fractal iterating `z` raised to a given power, selecting the fastest way to iterate it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Kernel {
+    pub fractal: Fractal,
+    power: Power,
+    /// The coefficient of the previous iterate of a phoenix iteration.
+    phoenix: Complex<f32>,
+    /// The squared escape radius.
+    bailout: f32,
+}
+
+impl Kernel {
+    /// Creates a new kernel iterating `fractal` with `z` raised to `power`, which must be larger than 1.
+    pub fn new(fractal: Fractal, power: f32) -> Kernel {
+        let radius = fractal.escape_radius(power);
+
+        Self {
+            fractal,
+            power: if power == 2.0 {
+                Power::Square
+            } else if power.fract() == 0.0 && power <= u32::MAX as f32 {
+                Power::Integer(power as u32)
+            } else {
+                Power::Real(power)
+            },
+            phoenix: Complex::new(0.0, 0.0),
+            bailout: radius * radius,
+        }
+    }
+
+    /// Sets the coefficient `p` of the previous iterate of a phoenix iteration, widening the escape
+    /// radius by `|p|` so that the previous iterate can't pull an escaped orbit back.
+    pub fn with_phoenix(mut self, p: Complex<f32>) -> Kernel {
+        if self.fractal == Fractal::Phoenix {
+            let radius = self.fractal.escape_radius(self.power.value()) + p.abs();
+            self.phoenix = p;
+            self.bailout = radius * radius;
+        }
+        self
+    }
+
+    /// Gets the radius beyond which orbits of this kernel are considered to have escaped.
+    #[inline]
+    pub fn escape_radius(&self) -> f32 {
+        self.bailout.sqrt()
+    }
+
+    /// Raises `z` to the power of this kernel.
+    #[inline]
+    fn raise<F: Float>(&self, z: Complex<F>) -> Complex<F> {
+        match self.power {
+            Power::Square => Complex::new(z.re * z.re - z.im * z.im, cast::<F>(2.0) * z.re * z.im),
+            Power::Integer(d) => powi(z, d),
+            Power::Real(d) => {
+                let r = (z.re * z.re + z.im * z.im).powf(cast(d * 0.5));
+                let theta = z.im.atan2(z.re) * cast(d);
+                Complex::new(r * theta.cos(), r * theta.sin())
+            },
+        }
+    }
+}
+
+impl<F: SimdFloat> Dynamics<F> for Kernel {
+    #[inline]
+    fn step(&self, z: Complex<F>, c: Complex<F>) -> Complex<F> {
+        self.advance(OrbitState::new(z), c).z
+    }
+
+    /// Whether or not `z` is outside the escape radius.
+    #[inline]
+    fn escaped(&self, z: Complex<F>) -> bool {
+        z.re * z.re + z.im * z.im > cast(self.bailout)
+    }
+
+    /// Applies one iteration of this kernel to the orbit `state`.
+    #[inline]
+    fn advance(&self, state: OrbitState<F>, c: Complex<F>) -> OrbitState<F> {
+        let z = state.z;
+        match self.fractal {
+            Fractal::Magnet => {
+                let (one, two) = (F::one(), cast::<F>(2.0));
+                let w = (z * z + c - one) / (z * two + c - two);
+                return OrbitState { z: w * w, prev: z };
+            },
+            Fractal::Lambda => {
+                return OrbitState {
+                    z: c * (z - z * z),
+                    prev: z,
+                };
+            },
+            Fractal::Newton | Fractal::Nova => {
+                // z - (zᵈ - 1) / (d·zᵈ⁻¹) = z - z·(zᵈ - 1) / (d·zᵈ), which only needs one power
+                let zd = self.raise(z);
+                let z = z - z * (zd - F::one()) / (zd * cast::<F>(self.power.value()));
+                return OrbitState {
+                    z: if self.fractal == Fractal::Nova { z + c } else { z },
+                    prev: state.z,
+                };
+            },
+            _ => (),
+        }
+
+        let z = match self.fractal {
+            Fractal::Mandelbrot
+            | Fractal::Phoenix
+            | Fractal::Magnet
+            | Fractal::Lambda
+            | Fractal::Newton
+            | Fractal::Nova => z,
+            Fractal::BurningShip => Complex::new(z.re.abs(), z.im.abs()),
+            Fractal::Tricorn => Complex::new(z.re, -z.im),
+            Fractal::Celtic => z,
+            Fractal::PerpendicularBurningShip => Complex::new(z.re, -z.im.abs()),
+        };
+
+        let z = self.raise(z);
+
+        let z = match self.fractal {
+            Fractal::Celtic => Complex::new(z.re.abs(), z.im),
+            Fractal::Phoenix => z + self.phoenix.map(cast::<F>) * state.prev,
+            _ => z,
+        };
+
+        OrbitState {
+            z: z + c,
+            prev: state.z,
+        }
+    }
+
+    /// Whether or not the orbit `state` has converged to a fixed point of this kernel.
+    #[inline]
+    fn converged(&self, state: OrbitState<F>) -> bool {
+        let d = match self.fractal {
+            Fractal::Magnet => state.z - F::one(),
+            Fractal::Newton | Fractal::Nova => state.z - state.prev,
+            _ => return false,
+        };
+        let radius = cast::<F>(CONVERGENCE_RADIUS);
+        d.re * d.re + d.im * d.im < radius * radius
+    }
+
+    /// Gets the first point of the orbit of `c`, which is the first iterate after the critical point.
+    #[inline]
+    fn start(&self, c: Complex<F>) -> Complex<F> {
+        match self.fractal {
+            Fractal::Magnet => self.advance(OrbitState::new(Complex::new(F::zero(), F::zero())), c).z,
+            Fractal::Nova => self.advance(OrbitState::new(Complex::new(F::one(), F::zero())), c).z,
+            Fractal::Lambda => c * cast::<F>(0.25),
+            _ => c,
+        }
+    }
+
+    /// Gets the index of the root of `zᵈ - 1` nearest to `z`, counting counterclockwise from 1. Basin
+    /// coloring is only allowed for whole number powers, so there are exactly `d` roots.
+    #[inline]
+    fn root(&self, z: Complex<F>) -> usize {
+        let d = self.power.value();
+        let turns = z.im.atan2(z.re) / cast(std::f32::consts::TAU) * cast(d);
+        turns.round().to_i64().unwrap_or(0).rem_euclid(d as i64) as usize
+    }
+
+    fn trajectory(&self, z: Complex<F>, c: Complex<F>, n: u32, sequence: &mut Vec<Complex<F>>) {
+        if self.fractal == Fractal::Mandelbrot && self.power == Power::Square {
+            return mandelbrot(z, c, n, sequence);
+        }
+
+        iterate_trajectory(self, z, c, n, sequence)
+    }
+
+    fn escape_time(&self, z: Complex<F>, c: Complex<F>, n: u32) -> Option<u32> {
+        if self.fractal == Fractal::Mandelbrot && self.power == Power::Square {
+            return mandelbrot_escape_time(z, c, n);
+        }
+
+        iterate_escape_time(self, z, c, n)
+    }
+
+    /// Iterates plain mandelbrot orbits in SIMD lanes, and every other iteration one orbit at a time.
+    fn escape_times(&self, z: &[Complex<F>], c: &[Complex<F>], n: u32, times: &mut [Option<u32>]) {
+        if self.fractal == Fractal::Mandelbrot && self.power == Power::Square {
+            return F::mandelbrot_escape_times(z, c, n, times);
+        }
+
+        for ((&z, &c), time) in z.iter().zip(c).zip(times) {
+            *time = iterate_escape_time(self, z, c, n);
+        }
+    }
+}
+
+impl Default for Kernel {
+    fn default() -> Self {
+        Self::new(Fractal::default(), 2.0)
+    }
+}
+
+/// Converts a parameter of a kernel to the scalar its orbits are iterated in.
+#[inline]
+fn cast<F: Float>(x: f32) -> F {
+    F::from(x).unwrap()
+}
+
+/// Raises `z` to an integer power by repeated squaring.
+#[inline]
+fn powi<F: Float>(mut z: Complex<F>, mut d: u32) -> Complex<F> {
+    let mut result = Complex::new(F::one(), F::zero());
+    while d > 0 {
+        if d & 1 == 1 {
+            result = result * z;
+        }
+        z = z * z;
+        d >>= 1;
+    }
+    result
+}
+
+/// The fractal plotted into each of the three color channels, rendering a composite of several
+/// fractals in a single pass over the same samples.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChannelFractals {
+    pub red: Fractal,
+    pub green: Fractal,
+    pub blue: Fractal,
+}
+
+impl ChannelFractals {
+    /// Creates a new assignment of fractals to color channels.
+    #[inline]
+    pub fn new(red: Fractal, green: Fractal, blue: Fractal) -> ChannelFractals {
+        Self { red, green, blue }
+    }
+
+    /// Gets each channel paired with its fractal.
+    #[inline]
+    pub fn assignments(&self) -> [(ColorChannel, Fractal); 3] {
+        [
+            (ColorChannel::Red, self.red),
+            (ColorChannel::Green, self.green),
+            (ColorChannel::Blue, self.blue),
+        ]
+    }
+
+    /// Gets every fractal assigned to at least one channel, each exactly once.
+    pub fn fractals(&self) -> Vec<Fractal> {
+        let mut fractals = Vec::with_capacity(3);
+        for (_, fractal) in self.assignments() {
+            if !fractals.contains(&fractal) {
+                fractals.push(fractal);
+            }
+        }
+        fractals
+    }
+
+    /// Gets the deposit of an orbit of `fractal`, with a one in every channel it is assigned to.
+    #[inline]
+    pub fn route<T: Color>(&self, fractal: Fractal) -> T {
+        let mut deposit = T::empty();
+        for (channel, assigned) in self.assignments() {
+            if assigned == fractal {
+                deposit.add(T::one(channel));
+            }
+        }
+        deposit
+    }
+}
+
+impl std::fmt::Display for ChannelFractals {
+    /// Formats the names of the fractals of the red, green and blue channels, separated by commas.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names = self
+            .assignments()
+            .map(|(_, fractal)| fractal.to_possible_value().unwrap().get_name().to_owned());
+        write!(f, "{}", names.join(","))
+    }
+}
@@ -0,0 +1,785 @@
+use bytemuck::{Pod, Zeroable};
+use indicatif::{ProgressBar, ProgressStyle};
+use rand::Rng;
+use rayon::prelude::*;
+use std::{
+    fmt,
+    ops::Range,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    canvas::Canvas,
+    color::{Color, ColorChannel},
+    complex::Complex,
+    fractal::Fractal,
+    images::Image,
+    interrupt,
+    sample::{
+        progress_bar, OrbitWeight, Orbits, Precision, Projection, SampleCounts, SampleSettings, SampleStats, Sampler,
+        CHUNK_SIZE,
+    },
+    simd::SimdFloat,
+};
+
+#[cfg(feature = "cuda")]
+pub mod cuda;
+#[cfg(feature = "opencl")]
+pub mod opencl;
+
+/// The number of invocations in a workgroup of the shader.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// The largest number of samples drawn by one dispatch, which fills the most workgroups a dispatch
+/// may have along one dimension.
+const MAX_BATCH: u32 = 65535 * WORKGROUP_SIZE;
+
+/// The number of chunks per device the samples of an image would be split into if every chunk was
+/// as small as those claimed before the rates of all devices are known.
+const CHUNKS_PER_DEVICE: u64 = 16;
+
+/// The fewest samples a device claims at a time, below which the overhead of a claim outweighs the
+/// balance gained.
+const MIN_CHUNK: u64 = 1 << 16;
+
+/// The stream of the noise configuration the GPU draws its key and shift from.
+const GPU_STREAM: u64 = u64::MAX;
+
+/// The number of counters following the pixels of a histogram: the escaping samples, and the low
+/// and high words of the points of plotted orbits outside the image.
+const COUNTERS: usize = 3;
+
+/// The parameters of a batch, laid out like the `Params` struct of the shader.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct Params {
+    center: [f32; 2],
+    julia: [f32; 2],
+    shift: [f32; 2],
+    scale: f32,
+    n: u32,
+    min_iterations: u32,
+    width: u32,
+    height: u32,
+    key: u32,
+    first: u32,
+    count: u32,
+    fractal: u32,
+    flags: u32,
+    bailout: f32,
+    _padding: u32,
+}
+
+/// An error setting up the GPU.
+#[derive(Debug)]
+pub enum GpuError {
+    /// No GPU is available to the graphics APIs wgpu supports.
+    NoAdapter,
+    /// The GPU refused to create a device.
+    Device(wgpu::RequestDeviceError),
+    /// The histogram of the image is larger than the GPU can bind at once.
+    ImageTooLarge { size: u64, max: u64 },
+    /// The CUDA driver or compiler failed.
+    #[cfg(feature = "cuda")]
+    Cuda(String),
+    /// The OpenCL runtime failed, or the kernel didn't build, with its build log.
+    #[cfg(feature = "opencl")]
+    OpenCl(String),
+}
+
+impl fmt::Display for GpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GpuError::NoAdapter => write!(f, "no GPU was found"),
+            GpuError::Device(err) => write!(f, "the GPU couldn't be set up: {}", err),
+            GpuError::ImageTooLarge { size, max } => write!(
+                f,
+                "the image needs a {} byte histogram, but the GPU can only bind {} bytes at once",
+                size, max
+            ),
+            #[cfg(feature = "cuda")]
+            GpuError::Cuda(err) => write!(f, "CUDA failed: {}", err),
+            #[cfg(feature = "opencl")]
+            GpuError::OpenCl(err) => write!(f, "OpenCL failed: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for GpuError {}
+
+/// A device sampling orbits into histograms of an image, such as a GPU.
+///
+/// Every accelerator runs the same kernel, counting the visits of plain orbits to every pixel, and
+/// hands its histograms to the CPU to be merged into a canvas by [`sample_accelerated`].
+pub trait Accelerator: Send + Sync {
+    /// Gets the name of the device, for reports.
+    fn name(&self) -> &str;
+
+    /// Checks that the histogram of an image `width` by `height` pixels fits on the device.
+    fn fits(&self, width: usize, height: usize) -> Result<(), GpuError>;
+
+    /// Draws the samples with indices in `samples` of an image `width` by `height` pixels in
+    /// batches, passing the histogram of visits of each batch to `merge` along with its counts
+    /// once it is done. Every index stands for the same sample on every device.
+    fn sample(
+        &self,
+        settings: &SampleSettings,
+        size: (usize, usize),
+        samples: Range<u64>,
+        merge: &mut dyn FnMut(&[u32], SampleCounts),
+    ) -> Result<(), GpuError>;
+}
+
+/// Sets up every GPU, through CUDA or else OpenCL when they find any and through the graphics APIs
+/// wgpu supports otherwise.
+pub fn devices() -> Result<Vec<Arc<dyn Accelerator>>, GpuError> {
+    #[cfg(feature = "cuda")]
+    match cuda::CudaSampler::all() {
+        Ok(gpus) => {
+            return Ok(gpus
+                .into_iter()
+                .map(|gpu| Arc::new(gpu) as Arc<dyn Accelerator>)
+                .collect())
+        },
+        Err(GpuError::NoAdapter) => {},
+        Err(e) => println!("Falling back from CUDA: {}.", e),
+    }
+    #[cfg(feature = "opencl")]
+    match opencl::OpenClSampler::all() {
+        Ok(gpus) => {
+            return Ok(gpus
+                .into_iter()
+                .map(|gpu| Arc::new(gpu) as Arc<dyn Accelerator>)
+                .collect())
+        },
+        Err(GpuError::NoAdapter) => {},
+        Err(e) => println!("Falling back from OpenCL: {}.", e),
+    }
+
+    let gpus = GpuSampler::all()?;
+    Ok(gpus
+        .into_iter()
+        .map(|gpu| Arc::new(gpu) as Arc<dyn Accelerator>)
+        .collect())
+}
+
+/// A GPU running the compute shader that samples orbits into a histogram of the image.
+pub struct GpuSampler {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    /// The name of the GPU, for reports.
+    name: String,
+}
+
+impl GpuSampler {
+    /// Sets up the fastest GPU available.
+    pub fn new() -> Result<GpuSampler, GpuError> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            ..Default::default()
+        }))
+        .ok_or(GpuError::NoAdapter)?;
+
+        Self::with_adapter(adapter)
+    }
+
+    /// Sets up every GPU available, or the fastest adapter if there are none, which may be one
+    /// emulating a GPU on the CPU.
+    pub fn all() -> Result<Vec<GpuSampler>, GpuError> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let mut adapters = instance.enumerate_adapters(wgpu::Backends::all());
+
+        // A GPU is listed once for every graphics API it supports, of which the first is kept
+        let mut seen = Vec::new();
+        adapters.retain(|adapter| {
+            let info = adapter.get_info();
+            let id = (info.vendor, info.device, info.name);
+            if info.device_type == wgpu::DeviceType::Cpu || seen.contains(&id) {
+                return false;
+            }
+            seen.push(id);
+            true
+        });
+
+        if adapters.is_empty() {
+            return Self::new().map(|gpu| vec![gpu]);
+        }
+        adapters.into_iter().map(Self::with_adapter).collect()
+    }
+
+    /// Sets up the GPU of `adapter`.
+    pub fn with_adapter(adapter: wgpu::Adapter) -> Result<GpuSampler, GpuError> {
+        // Ask for the adapter's own limits, so that histograms can be as large as it can bind
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("buddhabrot"),
+                required_features: wgpu::Features::empty(),
+                required_limits: adapter.limits(),
+                memory_hints: wgpu::MemoryHints::Performance,
+            },
+            None,
+        ))
+        .map_err(GpuError::Device)?;
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("buddhabrot"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("gpu/buddhabrot.wgsl").into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("buddhabrot"),
+            layout: None,
+            module: &module,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        Ok(Self {
+            device,
+            queue,
+            pipeline,
+            name: adapter.get_info().name,
+        })
+    }
+
+    /// Waits for the histogram of a batch to arrive in `staging` and passes it to `merge`.
+    fn finish(&self, staging: &wgpu::Buffer, transfer: Transfer, merge: &mut dyn FnMut(&[u32], SampleCounts)) {
+        self.device
+            .poll(wgpu::Maintain::WaitForSubmissionIndex(transfer.submission));
+        if transfer.receiver.recv().is_ok_and(|result| result.is_ok()) {
+            let data = staging.slice(..).get_mapped_range();
+            let (histogram, counts) = batch_counts(bytemuck::cast_slice(&data), transfer.samples as u64);
+            merge(histogram, counts);
+        }
+        staging.unmap();
+    }
+}
+
+impl Accelerator for GpuSampler {
+    #[inline]
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn fits(&self, width: usize, height: usize) -> Result<(), GpuError> {
+        let size = ((width * height + COUNTERS) * std::mem::size_of::<u32>()) as u64;
+        let max = self.device.limits().max_storage_buffer_binding_size as u64;
+        if size > max {
+            return Err(GpuError::ImageTooLarge { size, max });
+        }
+        Ok(())
+    }
+
+    fn sample(
+        &self,
+        settings: &SampleSettings,
+        (width, height): (usize, usize),
+        samples: Range<u64>,
+        merge: &mut dyn FnMut(&[u32], SampleCounts),
+    ) -> Result<(), GpuError> {
+        self.fits(width, height)?;
+        let size = ((width * height + COUNTERS) * std::mem::size_of::<u32>()) as u64;
+
+        let params_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("params"),
+            size: std::mem::size_of::<Params>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let histogram = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("histogram"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // Two staging buffers let the histogram of one batch be read back while the next one runs
+        let staging = [0, 1].map(|_| {
+            self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("staging"),
+                size,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        });
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("buddhabrot"),
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: histogram.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut pending: Option<Transfer> = None;
+        let mut dispatches = dispatches(settings, (width, height), samples);
+        for slot in (0..2).cycle() {
+            let submitted = dispatches.next().map(|params| {
+                self.queue.write_buffer(&params_buffer, 0, bytemuck::bytes_of(&params));
+
+                let staging = &staging[slot];
+                let mut encoder = self
+                    .device
+                    .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("batch") });
+                encoder.clear_buffer(&histogram, 0, None);
+                {
+                    let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                        label: Some("batch"),
+                        timestamp_writes: None,
+                    });
+                    pass.set_pipeline(&self.pipeline);
+                    pass.set_bind_group(0, &bind_group, &[]);
+                    pass.dispatch_workgroups(params.count.div_ceil(WORKGROUP_SIZE), 1, 1);
+                }
+                encoder.copy_buffer_to_buffer(&histogram, 0, staging, 0, size);
+                let submission = self.queue.submit(Some(encoder.finish()));
+
+                let (sender, receiver) = mpsc::channel();
+                staging.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+                    let _ = sender.send(result);
+                });
+
+                Transfer {
+                    staging: slot,
+                    submission,
+                    receiver,
+                    samples: params.count,
+                }
+            });
+
+            // Read back the previous batch while this one runs
+            if let Some(transfer) = pending.take() {
+                self.finish(&staging[transfer.staging], transfer, merge);
+            }
+
+            match submitted {
+                Some(transfer) => pending = Some(transfer),
+                None => break,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The transfer of the histogram of a submitted batch into a staging buffer.
+struct Transfer {
+    /// The index of the staging buffer the histogram is copied into.
+    staging: usize,
+    submission: wgpu::SubmissionIndex,
+    /// Receives the result of mapping the staging buffer once the batch is done.
+    receiver: mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>,
+    samples: u32,
+}
+
+/// The cores of the CPU, running the compute shader's sampling so that they can share an image with
+/// GPUs.
+pub struct CpuSampler {
+    /// The name of the CPU, for reports.
+    name: String,
+}
+
+impl CpuSampler {
+    /// Sets up every thread of the rayon thread pool.
+    pub fn new() -> CpuSampler {
+        Self {
+            name: format!("CPU ({} threads)", rayon::current_num_threads()),
+        }
+    }
+}
+
+impl Default for CpuSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Accelerator for CpuSampler {
+    #[inline]
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn fits(&self, _width: usize, _height: usize) -> Result<(), GpuError> {
+        Ok(())
+    }
+
+    fn sample(
+        &self,
+        settings: &SampleSettings,
+        (width, height): (usize, usize),
+        samples: Range<u64>,
+        merge: &mut dyn FnMut(&[u32], SampleCounts),
+    ) -> Result<(), GpuError> {
+        let histogram: Vec<_> = (0..width * height + COUNTERS).map(|_| AtomicU32::new(0)).collect();
+        let mut data = vec![0; width * height + COUNTERS];
+
+        for params in dispatches(settings, (width, height), samples) {
+            let end = params.first + params.count;
+            (params.first..end)
+                .into_par_iter()
+                .step_by(CHUNK_SIZE)
+                .for_each(|start| draw(&params, &histogram, start..(start + CHUNK_SIZE as u32).min(end)));
+
+            for (value, hits) in data.iter_mut().zip(&histogram) {
+                *value = hits.swap(0, Ordering::Relaxed);
+            }
+            let (histogram, counts) = batch_counts(&data, params.count as u64);
+            merge(histogram, counts);
+        }
+
+        Ok(())
+    }
+}
+
+/// Splits the data of a batch of `samples` samples into its histogram and the counts of the batch.
+fn batch_counts(data: &[u32], samples: u64) -> (&[u32], SampleCounts) {
+    let (histogram, counters) = data.split_at(data.len() - COUNTERS);
+    let counts = SampleCounts {
+        samples,
+        escaped: counters[0] as u64,
+        plotted: histogram.iter().map(|&hits| hits as u64).sum(),
+        culled: counters[1] as u64 | (counters[2] as u64) << 32,
+    };
+    (histogram, counts)
+}
+
+/// The number of samples whose escape times are computed at once on the CPU.
+const BLOCK_SIZE: usize = 256;
+
+/// Draws the samples of a dispatch with offsets in `offsets` on the CPU, doing exactly what the
+/// invocations of the compute shader drawing them do.
+fn draw(params: &Params, histogram: &[AtomicU32], offsets: Range<u32>) {
+    // The PCG hash, mixing every bit of `x` into every bit of the result
+    let hash = |x: u32| {
+        let state = x.wrapping_mul(747796405).wrapping_add(2891336453);
+        let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+        (word >> 22) ^ word
+    };
+    let unit = |bits: u32| (bits >> 8) as f32 / 16777216.0;
+    let fract = |x: f32| x - x.floor();
+
+    let center = Complex::new(params.center[0], params.center[1]);
+    let julia = (params.flags & 1 != 0).then(|| Complex::new(params.julia[0], params.julia[1]));
+    let bounded = params.flags & 2 != 0;
+    let (width, height) = (params.width as i32, params.height as i32);
+    let key = hash(params.key);
+
+    let advance = |z: Complex<f32>, c: Complex<f32>| {
+        let w = match params.fractal {
+            1 => Complex::new(z.re.abs(), z.im.abs()),
+            2 => Complex::new(z.re, -z.im),
+            4 => Complex::new(z.re, -z.im.abs()),
+            _ => z,
+        };
+        let mut square = Complex::new(w.re * w.re - w.im * w.im, 2.0 * w.re * w.im);
+        if params.fractal == 3 {
+            square.re = square.re.abs();
+        }
+        Complex::new(square.re + c.re, square.im + c.im)
+    };
+    let plot = |z: Complex<f32>| {
+        let x = ((z.re - center.re) / params.scale * 0.25 + 0.5) * params.width as f32;
+        let y = ((z.im - center.im) / params.scale * 0.25 + 0.5) * params.height as f32;
+        let (x, y) = (x as i32, y as i32);
+        let inside = x >= 0 && y >= 0 && x < width && y < height;
+        if inside {
+            histogram[(y * width + x) as usize].fetch_add(1, Ordering::Relaxed);
+        }
+        inside
+    };
+    let counters = &histogram[histogram.len() - COUNTERS..];
+
+    let mut z = Vec::with_capacity(BLOCK_SIZE);
+    let mut c = Vec::with_capacity(BLOCK_SIZE);
+    let mut times = [None; BLOCK_SIZE];
+    for start in offsets.clone().step_by(BLOCK_SIZE) {
+        z.clear();
+        c.clear();
+        for offset in start..(start + BLOCK_SIZE as u32).min(offsets.end) {
+            let bits = hash(offset ^ key);
+            let u = fract(unit(bits) + params.shift[0]);
+            let v = fract(unit(hash(bits)) + params.shift[1]);
+            let sample = Complex::new(
+                (u * 4.0 - 2.0) * params.scale + center.re,
+                (v * 4.0 - 2.0) * params.scale + center.im,
+            );
+            z.push(sample);
+            c.push(julia.unwrap_or(sample));
+        }
+
+        // Find out whether the orbits escape first, several at once where the iteration is the
+        // plain mandelbrot one
+        let times = &mut times[..z.len()];
+        if params.fractal == 0 && params.bailout == 4.0 {
+            f32::mandelbrot_escape_times(&z, &c, params.n, times);
+        } else {
+            for ((&z, &c), time) in z.iter().zip(&c).zip(times.iter_mut()) {
+                let mut w = z;
+                *time = (0..params.n).find_map(|i| {
+                    w = advance(w, c);
+                    (w.re * w.re + w.im * w.im > params.bailout).then_some(i + 1)
+                });
+            }
+        }
+
+        for ((&sample, &c), &time) in z.iter().zip(&c).zip(times.iter()) {
+            if time.is_some() {
+                counters[0].fetch_add(1, Ordering::Relaxed);
+            }
+
+            let length = time.unwrap_or(params.n);
+            if time.is_some() == bounded || time.is_some_and(|length| length < params.min_iterations) {
+                continue;
+            }
+
+            let mut z = sample;
+            let mut culled = 0;
+            for _ in 0..length {
+                culled += !plot(z) as u32;
+                z = advance(z, c);
+            }
+
+            // Carry into the high word when adding to the low one wraps around
+            if culled > 0 && counters[1].fetch_add(culled, Ordering::Relaxed) > u32::MAX - culled {
+                counters[2].fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Gets the first feature enabled by `settings` that accelerators can't render, if any.
+pub fn unsupported_feature(settings: &SampleSettings) -> Option<&'static str> {
+    if fractal_index(settings.fractal).is_none() || settings.power != 2.0 || settings.channel_fractals.is_some() {
+        Some("fractals other than the mandelbrot set, burning ship, tricorn and celtic")
+    } else if settings.precision != Precision::Single {
+        Some("--precision")
+    } else if matches!(settings.sampler, Sampler::Metropolis(_)) {
+        Some("Metropolis sampling")
+    } else if settings.weight != OrbitWeight::Hits || settings.arc_length_channel.is_some() {
+        Some("orbit weights and arc length")
+    } else if settings.nebulabrot.is_some() || settings.basins || settings.periods {
+        Some("nebulabrot, basins and periods modes")
+    } else if settings.projection != Projection::ZrZi
+        || settings.rotation.is_some()
+        || !settings.transform.is_identity()
+        || settings.mobius.is_some()
+    {
+        Some("projections, rotations, transforms and Möbius transformations")
+    } else {
+        None
+    }
+}
+
+/// Gets the index of `fractal` in the shader, if it can iterate it.
+fn fractal_index(fractal: Fractal) -> Option<u32> {
+    match fractal {
+        Fractal::Mandelbrot => Some(0),
+        Fractal::BurningShip => Some(1),
+        Fractal::Tricorn => Some(2),
+        Fractal::Celtic => Some(3),
+        Fractal::PerpendicularBurningShip => Some(4),
+        _ => None,
+    }
+}
+
+/// Gets the parameters of every dispatch drawing the samples with indices in `samples` of an image
+/// `width` by `height` pixels.
+///
+/// Samples are numbered in batches sharing a key, so a range starting partway through a batch picks
+/// up at its offset into it.
+fn dispatches(
+    settings: &SampleSettings,
+    (width, height): (usize, usize),
+    samples: Range<u64>,
+) -> impl Iterator<Item = Params> {
+    // Draw the key and shift from the noise configuration, so that the samples of animation
+    // frames are correlated just like those drawn on the CPU
+    let mut stream = settings.noise.stream(GPU_STREAM);
+    let key = stream.rng().gen::<u32>();
+    let shift = stream.next_point();
+
+    // Every pixel counts at most `n` visits from every sample, so batches are kept small
+    // enough that no count can overflow before it is merged
+    let batch_size = (u32::MAX / settings.n.max(1)).clamp(WORKGROUP_SIZE, MAX_BATCH) as u64;
+    let center = settings.center.map(|x| x as f32);
+    let julia = settings.julia.unwrap_or_default();
+
+    let params = Params {
+        center: [center.re, center.im],
+        julia: [julia.re, julia.im],
+        shift: [shift.0, shift.1],
+        scale: settings.scale as f32,
+        n: settings.n,
+        min_iterations: settings.min_iterations,
+        width: width as u32,
+        height: height as u32,
+        key,
+        first: 0,
+        count: 0,
+        fractal: fractal_index(settings.fractal).unwrap_or(0),
+        flags: settings.julia.is_some() as u32 | ((settings.orbits == Orbits::Bounded) as u32) << 1,
+        bailout: settings.escape_radius() * settings.escape_radius(),
+        _padding: 0,
+    };
+
+    let mut next = samples.start;
+    std::iter::from_fn(move || {
+        (next < samples.end).then(|| {
+            let batch = next / batch_size;
+            let first = next % batch_size;
+            let count = (samples.end - next).min(batch_size - first);
+            next += count;
+
+            Params {
+                key: key ^ (batch as u32).wrapping_mul(0x9e37_79b9),
+                first: first as u32,
+                count: count as u32,
+                ..params
+            }
+        })
+    })
+}
+
+/// Hands out the samples of an image to devices in chunks sized by how fast each device draws them.
+struct Schedule {
+    next: AtomicU64,
+    start: u64,
+    end: u64,
+    /// The samples per second every device drew its last chunk at, or 0 before it has drawn one.
+    rates: Vec<AtomicU64>,
+}
+
+impl Schedule {
+    fn new(samples: Range<u64>, devices: usize) -> Schedule {
+        Self {
+            next: AtomicU64::new(samples.start),
+            start: samples.start,
+            end: samples.end,
+            rates: (0..devices).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    /// Claims the next chunk of samples for `device`, or `None` once every sample is claimed.
+    ///
+    /// Once the rates of all devices are known, every device claims its share of half of the
+    /// remaining samples by rate, so that all of them finish their chunks at about the same time
+    /// and the chunks shrink as the samples run out.
+    fn claim(&self, device: usize) -> Option<Range<u64>> {
+        let rates: Vec<_> = self.rates.iter().map(|rate| rate.load(Ordering::Relaxed)).collect();
+        let size = if rates.contains(&0) {
+            (self.end - self.start).div_ceil(rates.len() as u64 * CHUNKS_PER_DEVICE)
+        } else {
+            let remaining = self.end.saturating_sub(self.next.load(Ordering::Relaxed));
+            let total = rates.iter().sum::<u64>();
+            (remaining as u128 * rates[device] as u128 / total as u128 / 2) as u64
+        }
+        .max(MIN_CHUNK);
+
+        let start = self.next.fetch_add(size, Ordering::Relaxed);
+        (start < self.end).then(|| start..(start + size).min(self.end))
+    }
+
+    /// Records that `device` drew `samples` samples in `elapsed`.
+    fn record(&self, device: usize, samples: u64, elapsed: Duration) {
+        let rate = samples as f64 / elapsed.as_secs_f64().max(1e-9);
+        self.rates[device].store((rate as u64).max(1), Ordering::Relaxed);
+    }
+}
+
+/// Adds a histogram of visits to every pixel of `image` into its first channel.
+#[profiling::function]
+pub fn add_histogram<T: Color + Clone + Copy>(image: &mut Image<T>, histogram: &[u32]) {
+    let one = T::one(ColorChannel::Red);
+    let width = image.width;
+    for (i, &hits) in histogram.iter().enumerate() {
+        if hits > 0 {
+            let hits = <T::Channel as num_traits::NumCast>::from(hits).unwrap();
+            image.add((i % width, i / width), one.map(|x| x * hits));
+        }
+    }
+}
+
+/// Samples orbits into the main image of a canvas on every device of `devices` at once. The
+/// settings must not use any [unsupported feature](unsupported_feature), and the duty cycle is
+/// ignored.
+///
+/// The devices claim chunks of the samples sized by how fast each of them draws them until there
+/// are none left, and show their own progress below the total when there are several.
+pub fn sample_accelerated<T: Color + Clone + Copy + Send + Sync + 'static>(
+    canvas: Arc<Mutex<Canvas<T>>>,
+    settings: SampleSettings,
+    devices: &[Arc<dyn Accelerator>],
+) -> Result<SampleStats, GpuError> {
+    let mut blank = canvas.lock().unwrap().blank();
+    let (width, height) = (blank.main.width, blank.main.size / blank.main.width);
+    let iters = (blank.main.size * settings.m as usize) as u64;
+
+    let samples = settings.slice.map_or(0..iters, |slice| slice.samples(iters));
+    let count = samples.end - samples.start;
+
+    let (multiprogress, bar) = progress_bar(count as usize);
+    let style = ProgressStyle::with_template("  {prefix}: {pos} samples ({per_sec})").unwrap();
+    let schedule = Schedule::new(samples, devices.len());
+    let image = Mutex::new(&mut blank.main);
+
+    let threads = thread::scope(|scope| {
+        let threads: Vec<_> = devices
+            .iter()
+            .enumerate()
+            .map(|(i, device)| {
+                let device_bar = (devices.len() > 1).then(|| {
+                    multiprogress.add(
+                        ProgressBar::new(count)
+                            .with_style(style.clone())
+                            .with_prefix(device.name().to_owned()),
+                    )
+                });
+                let (settings, schedule, image, bar) = (&settings, &schedule, &image, &bar);
+
+                scope.spawn(move || {
+                    let mut device_counts = SampleCounts::default();
+                    while let Some(chunk) = schedule.claim(i).filter(|_| !interrupt::interrupted()) {
+                        let start = Instant::now();
+                        let count = chunk.end - chunk.start;
+                        device.sample(settings, (width, height), chunk, &mut |histogram, counts| {
+                            add_histogram(&mut image.lock().unwrap(), histogram);
+                            device_counts = device_counts.merge(counts);
+                            bar.inc(counts.samples);
+                            if let Some(device_bar) = &device_bar {
+                                device_bar.inc(counts.samples);
+                            }
+                        })?;
+                        schedule.record(i, count, start.elapsed());
+                    }
+                    Ok(device_counts)
+                })
+            })
+            .collect();
+
+        threads
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .collect::<Result<Vec<_>, GpuError>>()
+    })?;
+
+    multiprogress.clear().unwrap();
+    canvas.lock().unwrap().merge(blank);
+
+    Ok(SampleStats {
+        threads,
+        ..Default::default()
+    })
+}
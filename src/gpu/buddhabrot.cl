@@ -0,0 +1,150 @@
+// The OpenCL port of `buddhabrot.wgsl`, which samples orbits of the escape time fractals iterating
+// `z²` and counts their visits to every pixel of the image. Every work item draws one sample,
+// exactly like an invocation of the shader does.
+
+// Round products and sums separately, as the CPU does, rather than fusing them
+#pragma OPENCL FP_CONTRACT OFF
+
+typedef struct {
+    float center[2];
+    float julia[2];
+    // The toroidal shift of every sample, which correlates the noise of animation frames
+    float shift[2];
+    float scale;
+    unsigned int n;
+    unsigned int min_iterations;
+    unsigned int width;
+    unsigned int height;
+    // Seeds the samples of a batch, which differs from batch to batch
+    unsigned int key;
+    // The index of the first sample of the launch within its batch
+    unsigned int first;
+    // The number of samples of the launch, which the last work group may overshoot
+    unsigned int count;
+    // The index of the fractal in `Fractal`, limited to those raising `z` to the power of 2
+    unsigned int fractal;
+    // Bit 0 is set for Julia sets and bit 1 for plotting bounded orbits instead of escaping ones
+    unsigned int flags;
+    float bailout;
+    unsigned int padding;
+} Params;
+
+#define MANDELBROT 0u
+#define BURNING_SHIP 1u
+#define TRICORN 2u
+#define CELTIC 3u
+#define PERPENDICULAR_BURNING_SHIP 4u
+
+// The PCG hash, mixing every bit of `x` into every bit of the result
+unsigned int hash(unsigned int x) {
+    unsigned int state = x * 747796405u + 2891336453u;
+    unsigned int word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// Maps random bits to a float in `[0, 1)`
+float unit(unsigned int bits) {
+    return (float)(bits >> 8u) / 16777216.0f;
+}
+
+// The fractional part of `x`, which the built-in `fract` also returns alongside its floor
+float wrap(float x) {
+    return x - floor(x);
+}
+
+float2 advance(const Params *params, float2 z, float2 c) {
+    float2 w = z;
+    switch (params->fractal) {
+        case BURNING_SHIP:
+            w = (float2)(fabs(z.x), fabs(z.y));
+            break;
+        case TRICORN:
+            w = (float2)(z.x, -z.y);
+            break;
+        case PERPENDICULAR_BURNING_SHIP:
+            w = (float2)(z.x, -fabs(z.y));
+            break;
+        default:
+            break;
+    }
+
+    float2 square = (float2)(w.x * w.x - w.y * w.y, 2.0f * w.x * w.y);
+    if (params->fractal == CELTIC) {
+        square.x = fabs(square.x);
+    }
+    return (float2)(square.x + c.x, square.y + c.y);
+}
+
+// Counts a visit of `z` to its pixel, returning whether it landed inside the image
+bool plot(const Params *params, volatile __global unsigned int *histogram, float2 z) {
+    float x = ((z.x - params->center[0]) / params->scale * 0.25f + 0.5f) * (float)params->width;
+    float y = ((z.y - params->center[1]) / params->scale * 0.25f + 0.5f) * (float)params->height;
+    // Saturate like the shader, since casting a float out of the range of `int` is undefined
+    int px = convert_int_sat(x);
+    int py = convert_int_sat(y);
+    if (px < 0 || py < 0 || px >= (int)params->width || py >= (int)params->height) {
+        return false;
+    }
+
+    atomic_add(&histogram[(unsigned int)py * params->width + (unsigned int)px], 1u);
+    return true;
+}
+
+// The visits to every pixel are followed by the number of escaping samples and the number of points
+// of plotted orbits outside the image, whose low and high words are kept apart so it can't overflow
+__kernel void sample(const Params params, volatile __global unsigned int *histogram) {
+    unsigned int id = get_global_id(0);
+    if (id >= params.count) {
+        return;
+    }
+
+    unsigned int bits = hash((params.first + id) ^ hash(params.key));
+    float u = wrap(unit(bits) + params.shift[0]);
+    float v = wrap(unit(hash(bits)) + params.shift[1]);
+    float2 sample = (float2)(
+        (u * 4.0f - 2.0f) * params.scale + params.center[0],
+        (v * 4.0f - 2.0f) * params.scale + params.center[1]
+    );
+
+    float2 c = sample;
+    if ((params.flags & 1u) != 0u) {
+        c = (float2)(params.julia[0], params.julia[1]);
+    }
+
+    // Find out whether the orbit escapes first, then iterate it again while plotting
+    float2 z = sample;
+    unsigned int length = params.n;
+    bool escaped = false;
+    for (unsigned int i = 0u; i < params.n; i++) {
+        z = advance(&params, z, c);
+        if (z.x * z.x + z.y * z.y > params.bailout) {
+            length = i + 1u;
+            escaped = true;
+            break;
+        }
+    }
+
+    unsigned int counters = params.width * params.height;
+    if (escaped) {
+        atomic_add(&histogram[counters], 1u);
+    }
+
+    bool bounded = (params.flags & 2u) != 0u;
+    if (escaped == bounded || (escaped && length < params.min_iterations)) {
+        return;
+    }
+
+    z = sample;
+    unsigned int culled = 0u;
+    for (unsigned int i = 0u; i < length; i++) {
+        if (!plot(&params, histogram, z)) {
+            culled++;
+        }
+        z = advance(&params, z, c);
+    }
+
+    // Carry into the high word when adding to the low one wraps around
+    if (culled > 0u && atomic_add(&histogram[counters + 1u], culled) > 0xffffffffu - culled) {
+        atomic_add(&histogram[counters + 2u], 1u);
+    }
+}
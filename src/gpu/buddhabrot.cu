@@ -0,0 +1,145 @@
+// The CUDA port of `buddhabrot.wgsl`, which samples orbits of the escape time fractals iterating
+// `z²` and counts their visits to every pixel of the image. Every thread draws one sample, exactly
+// like an invocation of the shader does.
+
+struct Params {
+    float center[2];
+    float julia[2];
+    // The toroidal shift of every sample, which correlates the noise of animation frames
+    float shift[2];
+    float scale;
+    unsigned int n;
+    unsigned int min_iterations;
+    unsigned int width;
+    unsigned int height;
+    // Seeds the samples of a batch, which differs from batch to batch
+    unsigned int key;
+    // The index of the first sample of the launch within its batch
+    unsigned int first;
+    // The number of samples of the launch, which the last block may overshoot
+    unsigned int count;
+    // The index of the fractal in `Fractal`, limited to those raising `z` to the power of 2
+    unsigned int fractal;
+    // Bit 0 is set for Julia sets and bit 1 for plotting bounded orbits instead of escaping ones
+    unsigned int flags;
+    float bailout;
+    unsigned int padding;
+};
+
+#define MANDELBROT 0u
+#define BURNING_SHIP 1u
+#define TRICORN 2u
+#define CELTIC 3u
+#define PERPENDICULAR_BURNING_SHIP 4u
+
+// The PCG hash, mixing every bit of `x` into every bit of the result
+__device__ unsigned int hash(unsigned int x) {
+    unsigned int state = x * 747796405u + 2891336453u;
+    unsigned int word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// Maps random bits to a float in `[0, 1)`
+__device__ float unit(unsigned int bits) {
+    return (float)(bits >> 8u) / 16777216.0f;
+}
+
+__device__ float fract(float x) {
+    return x - floorf(x);
+}
+
+__device__ float2 advance(const Params &params, float2 z, float2 c) {
+    float2 w = z;
+    switch (params.fractal) {
+        case BURNING_SHIP:
+            w = make_float2(fabsf(z.x), fabsf(z.y));
+            break;
+        case TRICORN:
+            w = make_float2(z.x, -z.y);
+            break;
+        case PERPENDICULAR_BURNING_SHIP:
+            w = make_float2(z.x, -fabsf(z.y));
+            break;
+        default:
+            break;
+    }
+
+    float2 square = make_float2(w.x * w.x - w.y * w.y, 2.0f * w.x * w.y);
+    if (params.fractal == CELTIC) {
+        square.x = fabsf(square.x);
+    }
+    return make_float2(square.x + c.x, square.y + c.y);
+}
+
+// Counts a visit of `z` to its pixel, returning whether it landed inside the image
+__device__ bool plot(const Params &params, unsigned int *histogram, float2 z) {
+    float x = ((z.x - params.center[0]) / params.scale * 0.25f + 0.5f) * (float)params.width;
+    float y = ((z.y - params.center[1]) / params.scale * 0.25f + 0.5f) * (float)params.height;
+    int px = (int)x;
+    int py = (int)y;
+    if (px < 0 || py < 0 || px >= (int)params.width || py >= (int)params.height) {
+        return false;
+    }
+
+    atomicAdd(&histogram[(unsigned int)py * params.width + (unsigned int)px], 1u);
+    return true;
+}
+
+// The visits to every pixel are followed by the number of escaping samples and the number of points
+// of plotted orbits outside the image, whose low and high words are kept apart so it can't overflow
+extern "C" __global__ void sample(const Params params, unsigned int *histogram) {
+    unsigned int id = blockIdx.x * blockDim.x + threadIdx.x;
+    if (id >= params.count) {
+        return;
+    }
+
+    unsigned int bits = hash((params.first + id) ^ hash(params.key));
+    float u = fract(unit(bits) + params.shift[0]);
+    float v = fract(unit(hash(bits)) + params.shift[1]);
+    float2 sample = make_float2(
+        (u * 4.0f - 2.0f) * params.scale + params.center[0],
+        (v * 4.0f - 2.0f) * params.scale + params.center[1]
+    );
+
+    float2 c = sample;
+    if ((params.flags & 1u) != 0u) {
+        c = make_float2(params.julia[0], params.julia[1]);
+    }
+
+    // Find out whether the orbit escapes first, then iterate it again while plotting
+    float2 z = sample;
+    unsigned int length = params.n;
+    bool escaped = false;
+    for (unsigned int i = 0u; i < params.n; i++) {
+        z = advance(params, z, c);
+        if (z.x * z.x + z.y * z.y > params.bailout) {
+            length = i + 1u;
+            escaped = true;
+            break;
+        }
+    }
+
+    unsigned int counters = params.width * params.height;
+    if (escaped) {
+        atomicAdd(&histogram[counters], 1u);
+    }
+
+    bool bounded = (params.flags & 2u) != 0u;
+    if (escaped == bounded || (escaped && length < params.min_iterations)) {
+        return;
+    }
+
+    z = sample;
+    unsigned int culled = 0u;
+    for (unsigned int i = 0u; i < length; i++) {
+        if (!plot(params, histogram, z)) {
+            culled++;
+        }
+        z = advance(params, z, c);
+    }
+
+    // Carry into the high word when adding to the low one wraps around
+    if (culled > 0u && atomicAdd(&histogram[counters + 1u], culled) > 0xffffffffu - culled) {
+        atomicAdd(&histogram[counters + 2u], 1u);
+    }
+}
@@ -0,0 +1,136 @@
+// Samples orbits of the escape time fractals iterating `z²` and counts their visits to every pixel
+// of the image. Every invocation draws one sample.
+
+struct Params {
+    center: vec2<f32>,
+    julia: vec2<f32>,
+    // The toroidal shift of every sample, which correlates the noise of animation frames
+    shift: vec2<f32>,
+    scale: f32,
+    n: u32,
+    min_iterations: u32,
+    width: u32,
+    height: u32,
+    // Seeds the samples of a batch, which differs from batch to batch
+    key: u32,
+    // The index of the first sample of the dispatch within its batch
+    first: u32,
+    // The number of samples of the dispatch, which the last workgroup may overshoot
+    count: u32,
+    // The index of the fractal in `Fractal`, limited to those raising `z` to the power of 2
+    fractal: u32,
+    // Bit 0 is set for Julia sets and bit 1 for plotting bounded orbits instead of escaping ones
+    flags: u32,
+    bailout: f32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+// The visits to every pixel, followed by the number of escaping samples and the number of points
+// of plotted orbits outside the image, whose low and high words are kept apart so it can't overflow
+@group(0) @binding(1) var<storage, read_write> histogram: array<atomic<u32>>;
+
+const MANDELBROT: u32 = 0u;
+const BURNING_SHIP: u32 = 1u;
+const TRICORN: u32 = 2u;
+const CELTIC: u32 = 3u;
+const PERPENDICULAR_BURNING_SHIP: u32 = 4u;
+
+// The PCG hash, mixing every bit of `x` into every bit of the result
+fn hash(x: u32) -> u32 {
+    let state = x * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// Maps random bits to a float in `[0, 1)`
+fn unit(bits: u32) -> f32 {
+    return f32(bits >> 8u) / 16777216.0;
+}
+
+fn advance(z: vec2<f32>, c: vec2<f32>) -> vec2<f32> {
+    var w = z;
+    switch params.fractal {
+        case BURNING_SHIP: {
+            w = abs(z);
+        }
+        case TRICORN: {
+            w = vec2(z.x, -z.y);
+        }
+        case PERPENDICULAR_BURNING_SHIP: {
+            w = vec2(z.x, -abs(z.y));
+        }
+        default: {}
+    }
+
+    var square = vec2(w.x * w.x - w.y * w.y, 2.0 * w.x * w.y);
+    if params.fractal == CELTIC {
+        square.x = abs(square.x);
+    }
+    return square + c;
+}
+
+// Counts a visit of `z` to its pixel, returning whether it landed inside the image
+fn plot(z: vec2<f32>) -> bool {
+    let p = (z - params.center) / params.scale * 0.25 + 0.5;
+    let px = vec2<i32>(p * vec2(f32(params.width), f32(params.height)));
+    if px.x < 0 || px.y < 0 || px.x >= i32(params.width) || px.y >= i32(params.height) {
+        return false;
+    }
+
+    atomicAdd(&histogram[u32(px.y) * params.width + u32(px.x)], 1u);
+    return true;
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.count {
+        return;
+    }
+
+    let bits = hash((params.first + id.x) ^ hash(params.key));
+    let u = fract(unit(bits) + params.shift.x);
+    let v = fract(unit(hash(bits)) + params.shift.y);
+    let sample = (vec2(u, v) * 4.0 - 2.0) * params.scale + params.center;
+
+    var c = sample;
+    if (params.flags & 1u) != 0u {
+        c = params.julia;
+    }
+
+    // Find out whether the orbit escapes first, then iterate it again while plotting
+    var z = sample;
+    var length = params.n;
+    var escaped = false;
+    for (var i = 0u; i < params.n; i++) {
+        z = advance(z, c);
+        if dot(z, z) > params.bailout {
+            length = i + 1u;
+            escaped = true;
+            break;
+        }
+    }
+
+    let counters = params.width * params.height;
+    if escaped {
+        atomicAdd(&histogram[counters], 1u);
+    }
+
+    let bounded = (params.flags & 2u) != 0u;
+    if escaped == bounded || (escaped && length < params.min_iterations) {
+        return;
+    }
+
+    z = sample;
+    var culled = 0u;
+    for (var i = 0u; i < length; i++) {
+        if !plot(z) {
+            culled++;
+        }
+        z = advance(z, c);
+    }
+
+    // Carry into the high word when adding to the low one wraps around
+    if culled > 0u && atomicAdd(&histogram[counters + 1u], culled) > 0xffffffffu - culled {
+        atomicAdd(&histogram[counters + 2u], 1u);
+    }
+}
@@ -0,0 +1,163 @@
+use cudarc::{
+    driver::{CudaContext, CudaFunction, CudaStream, DeviceRepr, DriverError, LaunchConfig, PushKernelArg},
+    nvrtc::{self, CompileOptions, Ptx},
+};
+use std::{fmt, ops::Range, sync::Arc};
+
+use super::{batch_counts, dispatches, Accelerator, GpuError, Params, COUNTERS, WORKGROUP_SIZE};
+use crate::sample::{SampleCounts, SampleSettings};
+
+// SAFETY: `Params` is plain old data laid out like the `Params` struct of the kernel.
+unsafe impl DeviceRepr for Params {}
+
+/// An NVIDIA GPU running the CUDA port of the compute shader that samples orbits into a histogram
+/// of the image.
+pub struct CudaSampler {
+    context: Arc<CudaContext>,
+    /// Two streams, so that the histogram of one batch is read back while the next one runs.
+    streams: [Arc<CudaStream>; 2],
+    function: CudaFunction,
+    /// The name of the GPU, for reports.
+    name: String,
+}
+
+impl CudaSampler {
+    /// Sets up every CUDA device, compiling the kernel for them when the program runs.
+    ///
+    /// The driver is loaded rather than linked, so a missing driver or a machine without NVIDIA
+    /// GPUs fails with [`GpuError::NoAdapter`], leaving other APIs to be tried instead.
+    pub fn all() -> Result<Vec<CudaSampler>, GpuError> {
+        if !unsafe { cudarc::driver::sys::is_culib_present() } {
+            return Err(GpuError::NoAdapter);
+        }
+        let count = CudaContext::device_count().map_err(cuda_error)?;
+        if count <= 0 {
+            return Err(GpuError::NoAdapter);
+        }
+
+        if !unsafe { nvrtc::sys::is_culib_present() } {
+            return Err(GpuError::Cuda(
+                "NVRTC isn't installed, so the kernel can't be compiled".to_owned(),
+            ));
+        }
+        // Contracting multiplications and additions would round differently from the other devices
+        let ptx = nvrtc::compile_ptx_with_opts(
+            include_str!("buddhabrot.cu"),
+            CompileOptions {
+                fmad: Some(false),
+                ..Default::default()
+            },
+        )
+        .map_err(cuda_error)?;
+
+        (0..count as usize)
+            .map(|ordinal| Self::with_device(ordinal, ptx.clone()))
+            .collect()
+    }
+
+    /// Sets up the CUDA device numbered `ordinal`, loading the kernel compiled into `ptx`.
+    pub fn with_device(ordinal: usize, ptx: Ptx) -> Result<CudaSampler, GpuError> {
+        let context = CudaContext::new(ordinal).map_err(cuda_error)?;
+        let function = context
+            .load_module(ptx)
+            .and_then(|module| module.load_function("sample"))
+            .map_err(cuda_error)?;
+        let streams = [
+            context.new_stream().map_err(cuda_error)?,
+            context.new_stream().map_err(cuda_error)?,
+        ];
+        let name = context.name().map_err(cuda_error)?;
+
+        Ok(Self {
+            context,
+            streams,
+            function,
+            name,
+        })
+    }
+}
+
+/// Wraps an error of the CUDA driver or compiler.
+fn cuda_error(err: impl fmt::Display) -> GpuError {
+    GpuError::Cuda(err.to_string())
+}
+
+impl Accelerator for CudaSampler {
+    #[inline]
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn fits(&self, width: usize, height: usize) -> Result<(), GpuError> {
+        // The kernel indexes pixels with 32 bits, and each stream holds a histogram of its own
+        let size = ((width * height + COUNTERS) * std::mem::size_of::<u32>()) as u64;
+        let memory =
+            unsafe { cudarc::driver::result::device::total_mem(self.context.cu_device()) }.map_err(cuda_error)?;
+        let max = (memory as u64 / 2).min(u32::MAX as u64);
+        if size > max {
+            return Err(GpuError::ImageTooLarge { size, max });
+        }
+        Ok(())
+    }
+
+    fn sample(
+        &self,
+        settings: &SampleSettings,
+        (width, height): (usize, usize),
+        samples: Range<u64>,
+        merge: &mut dyn FnMut(&[u32], SampleCounts),
+    ) -> Result<(), GpuError> {
+        self.fits(width, height)?;
+        let len = width * height + COUNTERS;
+
+        let mut histograms = Vec::with_capacity(self.streams.len());
+        for stream in &self.streams {
+            histograms.push(stream.alloc_zeros::<u32>(len).map_err(cuda_error)?);
+        }
+        let mut data = vec![0; len];
+
+        // The stream and sample count of the batch running before the current one
+        let mut pending: Option<(usize, u32)> = None;
+        let mut dispatches = dispatches(settings, (width, height), samples);
+        for slot in (0..2).cycle() {
+            let submitted = dispatches
+                .next()
+                .map(|params| {
+                    let stream = &self.streams[slot];
+                    let histogram = &mut histograms[slot];
+                    stream.memset_zeros(histogram)?;
+
+                    let config = LaunchConfig {
+                        grid_dim: (params.count.div_ceil(WORKGROUP_SIZE), 1, 1),
+                        block_dim: (WORKGROUP_SIZE, 1, 1),
+                        shared_mem_bytes: 0,
+                    };
+                    let mut launch = stream.launch_builder(&self.function);
+                    launch.arg(&params).arg(histogram);
+                    // SAFETY: the arguments match the parameters of the kernel, and every thread
+                    // stays within the histogram, which is sized for the image of `params`
+                    unsafe { launch.launch(config) }?;
+                    Ok::<_, DriverError>((slot, params.count))
+                })
+                .transpose()
+                .map_err(cuda_error)?;
+
+            // Read back the previous batch while this one runs, copying into pageable memory so
+            // that the copy waits for its stream alone
+            if let Some((previous, count)) = pending.take() {
+                self.streams[previous]
+                    .memcpy_dtoh(&histograms[previous], &mut data)
+                    .map_err(cuda_error)?;
+                let (histogram, counts) = batch_counts(&data, count as u64);
+                merge(histogram, counts);
+            }
+
+            match submitted {
+                Some(batch) => pending = Some(batch),
+                None => break,
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+use opencl3::{
+    command_queue::CommandQueue,
+    context::Context,
+    device::{Device, CL_DEVICE_TYPE_GPU},
+    error_codes::ClError,
+    kernel::Kernel,
+    memory::{Buffer, ClMem, CL_MEM_READ_WRITE},
+    platform,
+    program::Program,
+    types::{cl_device_id, CL_BLOCKING},
+};
+use std::{ops::Range, ptr, sync::Mutex};
+
+use super::{batch_counts, dispatches, Accelerator, GpuError, COUNTERS, WORKGROUP_SIZE};
+use crate::sample::{SampleCounts, SampleSettings};
+
+/// A GPU running the OpenCL port of the compute shader that samples orbits into a histogram of the
+/// image, for GPUs and platforms that neither wgpu nor CUDA reach.
+pub struct OpenClSampler {
+    /// The objects of the device, which OpenCL doesn't let several threads use at once.
+    state: Mutex<State>,
+    /// The largest buffer the device can allocate, in bytes.
+    max_alloc: u64,
+    /// The name of the GPU, for reports.
+    name: String,
+}
+
+/// The OpenCL objects of a device.
+struct State {
+    context: Context,
+    /// Two queues, so that the histogram of one batch is read back while the next one runs.
+    queues: [CommandQueue; 2],
+    kernel: Kernel,
+}
+
+impl OpenClSampler {
+    /// Sets up every GPU of every OpenCL platform, compiling the kernel for each of them.
+    ///
+    /// Fails with [`GpuError::NoAdapter`] when no platform is installed or none of them has a GPU,
+    /// leaving other APIs to be tried instead.
+    pub fn all() -> Result<Vec<OpenClSampler>, GpuError> {
+        let mut devices = Vec::new();
+        for platform in platform::get_platforms().unwrap_or_default() {
+            // A platform without GPUs reports an error rather than an empty list
+            devices.extend(platform.get_devices(CL_DEVICE_TYPE_GPU).unwrap_or_default());
+        }
+
+        if devices.is_empty() {
+            return Err(GpuError::NoAdapter);
+        }
+        devices.into_iter().map(Self::with_device).collect()
+    }
+
+    /// Sets up the OpenCL device `id`.
+    pub fn with_device(id: cl_device_id) -> Result<OpenClSampler, GpuError> {
+        let device = Device::new(id);
+        let context = Context::from_device(&device).map_err(opencl_error)?;
+        let queues = [
+            CommandQueue::create(&context, id, 0).map_err(opencl_error)?,
+            CommandQueue::create(&context, id, 0).map_err(opencl_error)?,
+        ];
+        // Divisions are rounded correctly on the other devices, but only within 2.5 ulps by default
+        let program = Program::create_and_build_from_source(
+            &context,
+            include_str!("buddhabrot.cl"),
+            "-cl-fp32-correctly-rounded-divide-sqrt",
+        )
+        .map_err(GpuError::OpenCl)?;
+        let kernel = Kernel::create(&program, "sample").map_err(opencl_error)?;
+
+        Ok(Self {
+            state: Mutex::new(State {
+                context,
+                queues,
+                kernel,
+            }),
+            max_alloc: device.max_mem_alloc_size().map_err(opencl_error)?,
+            name: device.name().map_err(opencl_error)?,
+        })
+    }
+}
+
+/// Wraps an error of the OpenCL runtime.
+fn opencl_error(err: ClError) -> GpuError {
+    GpuError::OpenCl(err.to_string())
+}
+
+impl Accelerator for OpenClSampler {
+    #[inline]
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn fits(&self, width: usize, height: usize) -> Result<(), GpuError> {
+        // The kernel indexes pixels with 32 bits
+        let size = ((width * height + COUNTERS) * std::mem::size_of::<u32>()) as u64;
+        let max = self.max_alloc.min(u32::MAX as u64);
+        if size > max {
+            return Err(GpuError::ImageTooLarge { size, max });
+        }
+        Ok(())
+    }
+
+    fn sample(
+        &self,
+        settings: &SampleSettings,
+        (width, height): (usize, usize),
+        samples: Range<u64>,
+        merge: &mut dyn FnMut(&[u32], SampleCounts),
+    ) -> Result<(), GpuError> {
+        self.fits(width, height)?;
+        let len = width * height + COUNTERS;
+        let state = self.state.lock().unwrap();
+
+        let mut histograms = Vec::with_capacity(state.queues.len());
+        for _ in &state.queues {
+            let buffer = Buffer::<u32>::create(&state.context, CL_MEM_READ_WRITE, len, ptr::null_mut());
+            histograms.push(buffer.map_err(opencl_error)?);
+        }
+        let mut data = vec![0; len];
+
+        // The queue and sample count of the batch running before the current one
+        let mut pending: Option<(usize, u32)> = None;
+        let mut dispatches = dispatches(settings, (width, height), samples);
+        for slot in (0..2).cycle() {
+            let submitted = dispatches
+                .next()
+                .map(|params| {
+                    let queue = &state.queues[slot];
+                    let histogram = &mut histograms[slot];
+                    queue.enqueue_fill_buffer(histogram, &[0u32], 0, len * std::mem::size_of::<u32>(), &[])?;
+
+                    // The arguments are copied when the kernel is enqueued, so they can be set
+                    // again for the next batch while this one runs
+                    state.kernel.set_arg(0, &params)?;
+                    state.kernel.set_arg(1, &histogram.get())?;
+                    let global = (params.count.div_ceil(WORKGROUP_SIZE) * WORKGROUP_SIZE) as usize;
+                    let local = WORKGROUP_SIZE as usize;
+                    queue.enqueue_nd_range_kernel(state.kernel.get(), 1, ptr::null(), &global, &local, &[])?;
+                    queue.flush()?;
+                    Ok::<_, ClError>((slot, params.count))
+                })
+                .transpose()
+                .map_err(opencl_error)?;
+
+            // Read back the previous batch while this one runs
+            if let Some((previous, count)) = pending.take() {
+                state.queues[previous]
+                    .enqueue_read_buffer(&histograms[previous], CL_BLOCKING, 0, &mut data, &[])
+                    .map_err(opencl_error)?;
+                let (histogram, counts) = batch_counts(&data, count as u64);
+                merge(histogram, counts);
+            }
+
+            match submitted {
+                Some(batch) => pending = Some(batch),
+                None => break,
+            }
+        }
+
+        Ok(())
+    }
+}
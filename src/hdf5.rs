@@ -0,0 +1,220 @@
+use hdf5_metno_sys::{
+    h5::{hsize_t, H5open},
+    h5a::{H5Aclose, H5Acreate2, H5Awrite},
+    h5d::{H5Dclose, H5Dcreate2, H5Dwrite},
+    h5f::{H5Fclose, H5Fcreate, H5F_ACC_TRUNC},
+    h5i::hid_t,
+    h5p::H5P_DEFAULT,
+    h5s::{H5Sclose, H5Screate, H5Screate_simple, H5Sselect_hyperslab, H5S_SCALAR, H5S_SELECT_SET},
+    h5t::{
+        H5Tclose, H5Tcopy, H5Tset_cset, H5Tset_size, H5T_CSET_UTF8, H5T_C_S1, H5T_IEEE_F32LE, H5T_IEEE_F64LE,
+        H5T_NATIVE_DOUBLE, H5T_NATIVE_FLOAT,
+    },
+};
+use std::{
+    ffi::{c_int, CString},
+    fs,
+    io::{self, ErrorKind},
+    mem,
+    path::Path,
+    ptr,
+};
+
+use crate::{
+    atomic,
+    color::Color,
+    histogram::{Parameters, SampleType},
+};
+
+/// The name of the dataset holding the histogram within the file.
+pub const DATASET: &str = "histogram";
+
+/// An open HDF5 object, closed once it's dropped.
+struct Handle {
+    id: hid_t,
+    close: unsafe extern "C" fn(hid_t) -> c_int,
+}
+
+impl Handle {
+    /// Takes ownership of the object `id` returned by an HDF5 call, which is negative when the call
+    /// failed. The library prints why to stderr.
+    fn new(id: hid_t, close: unsafe extern "C" fn(hid_t) -> c_int, action: &str) -> io::Result<Handle> {
+        if id < 0 {
+            return Err(io::Error::other(format!("HDF5 failed to {}", action)));
+        }
+        Ok(Self { id, close })
+    }
+
+    /// Closes the object, failing if the library does, such as when a file can't be flushed.
+    fn close(self, action: &str) -> io::Result<()> {
+        let status = unsafe { (self.close)(self.id) };
+        mem::forget(self);
+        check(status, action)
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        unsafe { (self.close)(self.id) };
+    }
+}
+
+/// Checks the status returned by an HDF5 call, which is negative when the call failed.
+fn check(status: c_int, action: &str) -> io::Result<()> {
+    if status < 0 {
+        return Err(io::Error::other(format!("HDF5 failed to {}", action)));
+    }
+    Ok(())
+}
+
+/// Converts `s` to a string HDF5 takes, which mustn't contain NUL bytes.
+fn c_string(s: &str) -> io::Result<CString> {
+    CString::new(s).map_err(|_| io::Error::new(ErrorKind::InvalidInput, format!("{:?} contains a NUL byte", s)))
+}
+
+/// Writes an HDF5 file holding the dataset [`DATASET`] of shape `(height, width, channels)` of the
+/// color `pixel` gives every pixel, which is called one row after another, replacing any file
+/// already at `path` once the new one is complete.
+///
+/// Every parameter of the render is attached to the dataset as a UTF-8 string attribute, so that
+/// archives keep the settings an image was made with alongside the samples themselves.
+pub fn write_hdf5<T: Color + Copy>(
+    path: impl AsRef<Path>,
+    width: usize,
+    height: usize,
+    parameters: &Parameters,
+    pixel: impl FnMut(usize, usize) -> T,
+) -> io::Result<()> {
+    // HDF5 opens the file itself, so it's written next to `path` and moved into place once closed
+    let path = path.as_ref();
+    let temp = atomic::temp_path(path);
+    let result = write_file(&temp, width, height, parameters, pixel);
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result.and_then(|_| atomic::commit_file(path))
+}
+
+/// Writes the file of [`write_hdf5`] directly at `path`.
+fn write_file<T: Color + Copy>(
+    path: &Path,
+    width: usize,
+    height: usize,
+    parameters: &Parameters,
+    mut pixel: impl FnMut(usize, usize) -> T,
+) -> io::Result<()> {
+    let name = path
+        .to_str()
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "HDF5 file names must be valid UTF-8"))?;
+    let name = c_string(name)?;
+    let channels = T::CHANNELS.len();
+
+    // SAFETY: every identifier passed to the library was returned by it and is still open, and every
+    // buffer holds exactly the values of the selection it's written to, in the type it's written as
+    unsafe {
+        check(H5open(), "initialize")?;
+        let (file_type, memory_type) = match SampleType::of::<T>() {
+            SampleType::F32 => (*H5T_IEEE_F32LE, *H5T_NATIVE_FLOAT),
+            SampleType::F64 => (*H5T_IEEE_F64LE, *H5T_NATIVE_DOUBLE),
+        };
+
+        let file = Handle::new(
+            H5Fcreate(name.as_ptr(), H5F_ACC_TRUNC, H5P_DEFAULT, H5P_DEFAULT),
+            H5Fclose,
+            "create the file",
+        )?;
+        let dims = [height, width, channels].map(|x| x as hsize_t);
+        let space = Handle::new(
+            H5Screate_simple(3, dims.as_ptr(), ptr::null()),
+            H5Sclose,
+            "create the dataspace",
+        )?;
+        let dataset_name = c_string(DATASET)?;
+        let dataset = Handle::new(
+            H5Dcreate2(
+                file.id,
+                dataset_name.as_ptr(),
+                file_type,
+                space.id,
+                H5P_DEFAULT,
+                H5P_DEFAULT,
+                H5P_DEFAULT,
+            ),
+            H5Dclose,
+            "create the dataset",
+        )?;
+
+        // Only one row is held in memory at a time, written into its slab of the dataset
+        let row_dims = [(width * channels) as hsize_t];
+        let row_space = Handle::new(
+            H5Screate_simple(1, row_dims.as_ptr(), ptr::null()),
+            H5Sclose,
+            "create the dataspace",
+        )?;
+        let mut row = Vec::with_capacity(width * channels);
+        for y in 0..height {
+            row.clear();
+            for x in 0..width {
+                let px = pixel(x, y);
+                row.extend((0..channels).map(|channel| px.channel(channel)));
+            }
+
+            let start = [y as hsize_t, 0, 0];
+            let count = [1, width as hsize_t, channels as hsize_t];
+            check(
+                H5Sselect_hyperslab(
+                    space.id,
+                    H5S_SELECT_SET,
+                    start.as_ptr(),
+                    ptr::null(),
+                    count.as_ptr(),
+                    ptr::null(),
+                ),
+                "select a row",
+            )?;
+            check(
+                H5Dwrite(
+                    dataset.id,
+                    memory_type,
+                    row_space.id,
+                    space.id,
+                    H5P_DEFAULT,
+                    row.as_ptr().cast(),
+                ),
+                "write a row",
+            )?;
+        }
+
+        let scalar = Handle::new(H5Screate(H5S_SCALAR), H5Sclose, "create the dataspace")?;
+        for (key, value) in parameters {
+            // Fixed length strings can't be empty, so an empty value is stored as a lone NUL
+            let string_type = Handle::new(H5Tcopy(*H5T_C_S1), H5Tclose, "create the string type")?;
+            check(H5Tset_size(string_type.id, value.len().max(1)), "size the string type")?;
+            check(H5Tset_cset(string_type.id, H5T_CSET_UTF8), "set the character set")?;
+
+            let key = c_string(key)?;
+            let mut bytes = value.as_bytes().to_vec();
+            bytes.resize(value.len().max(1), 0);
+            let attribute = Handle::new(
+                H5Acreate2(
+                    dataset.id,
+                    key.as_ptr(),
+                    string_type.id,
+                    scalar.id,
+                    H5P_DEFAULT,
+                    H5P_DEFAULT,
+                ),
+                H5Aclose,
+                "create an attribute",
+            )?;
+            check(
+                H5Awrite(attribute.id, string_type.id, bytes.as_ptr().cast()),
+                "write an attribute",
+            )?;
+        }
+
+        // The file is only flushed once every object within it is closed
+        drop((scalar, row_space, dataset, space));
+        file.close("close the file")
+    }
+}
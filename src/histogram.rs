@@ -0,0 +1,997 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Read, Write},
+    mem,
+    path::Path,
+};
+
+use clap::ValueEnum;
+use flate2::{bufread::GzDecoder, write::ZlibEncoder, Compression};
+use num_traits::{NumCast, ToPrimitive, Zero};
+use serde_json::json;
+
+use crate::{
+    atomic::{self, AtomicFile},
+    color::Color,
+    images::Image,
+    sample::{SampleSettings, Sampler},
+};
+
+/// The bytes every histogram file starts with.
+pub const MAGIC: [u8; 8] = *b"BUDDHIST";
+
+/// The longest string a histogram file may hold in bytes, far longer than any channel name or
+/// parameter, so that a damaged length can't make a reader allocate without bound.
+const MAX_STRING_LEN: u32 = 1 << 16;
+
+/// The version of the format written by [`save_histogram`]. Files of later versions aren't read.
+pub const VERSION: u32 = 1;
+
+/// The file extension histograms are written with.
+pub const EXTENSION: &str = "bhist";
+
+/// The parameters an image was rendered with, by name.
+pub type Parameters = BTreeMap<String, String>;
+
+/// The type every channel value of a histogram is stored as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SampleType {
+    F32,
+    F64,
+}
+
+impl SampleType {
+    /// Gets the type of the channel values of the color `T`.
+    pub fn of<T: Color>() -> SampleType {
+        if mem::size_of::<T::Channel>() == mem::size_of::<f64>() {
+            SampleType::F64
+        } else {
+            SampleType::F32
+        }
+    }
+
+    /// Gets the number of bytes every value of this type is stored in.
+    pub fn size(self) -> usize {
+        match self {
+            SampleType::F32 => 4,
+            SampleType::F64 => 8,
+        }
+    }
+}
+
+/// A format the raw values of every pixel of an image are written in, before they're tonemapped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RawFormat {
+    /// A histogram file, which keeps the parameters of the render along with it, compressed with
+    /// zstd at the given level between 1-22 when set.
+    Histogram(Option<u32>),
+    /// A NumPy array of shape `(height, width, channels)`.
+    Npy,
+    /// A Zarr store of the same array split into compressed square chunks of the given width, which
+    /// tools read regions of without loading the whole image.
+    Zarr(usize),
+    /// A text table with a line of the position and channels of every pixel, or only of the pixels
+    /// with any hits when `nonzero` is set.
+    Table { format: TableFormat, nonzero: bool },
+    /// An HDF5 file holding the same array as a dataset with the parameters of the render as its
+    /// attributes, which takes the HDF5 C library.
+    #[cfg(feature = "hdf5")]
+    Hdf5,
+}
+
+/// The separator between the columns of a text table.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum TableFormat {
+    /// Comma separated values.
+    #[default]
+    Csv,
+    /// Tab separated values.
+    Tsv,
+}
+
+impl TableFormat {
+    fn separator(self) -> char {
+        match self {
+            TableFormat::Csv => ',',
+            TableFormat::Tsv => '\t',
+        }
+    }
+}
+
+impl RawFormat {
+    /// Gets the file extension of this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            RawFormat::Histogram(_) => EXTENSION,
+            RawFormat::Npy => "npy",
+            RawFormat::Zarr(_) => "zarr",
+            RawFormat::Table {
+                format: TableFormat::Csv,
+                ..
+            } => "csv",
+            RawFormat::Table {
+                format: TableFormat::Tsv,
+                ..
+            } => "tsv",
+            #[cfg(feature = "hdf5")]
+            RawFormat::Hdf5 => "h5",
+        }
+    }
+
+    /// Writes a file of the color `pixel` gives every pixel in this format, calling it one row after
+    /// another.
+    pub fn write<T: Color + Copy>(
+        self,
+        path: impl AsRef<Path>,
+        width: usize,
+        height: usize,
+        parameters: &Parameters,
+        pixel: impl FnMut(usize, usize) -> T,
+    ) -> io::Result<()> {
+        match self {
+            RawFormat::Histogram(compression) => write_histogram(path, width, height, parameters, compression, pixel),
+            RawFormat::Npy => write_npy(path, width, height, pixel),
+            RawFormat::Zarr(chunk_size) => write_zarr(path, width, height, chunk_size, parameters, pixel),
+            RawFormat::Table { format, nonzero } => write_table(path, width, height, format, nonzero, pixel),
+            #[cfg(feature = "hdf5")]
+            RawFormat::Hdf5 => crate::hdf5::write_hdf5(path, width, height, parameters, pixel),
+        }
+    }
+}
+
+/// Everything stored in a histogram file besides its samples.
+///
+/// Histogram files keep every hit of a render exactly as it was accumulated, so that it can be
+/// tonemapped again, added to other renders of the same image or continued later. They're laid out
+/// as follows, with every number little-endian:
+///
+/// | Field       | Encoding                                                                    |
+/// |-------------|-----------------------------------------------------------------------------|
+/// | Magic       | The 8 bytes [`MAGIC`]                                                       |
+/// | Version     | `u32`, currently [`VERSION`]                                                |
+/// | Width       | `u64`, in pixels                                                            |
+/// | Height      | `u64`, in pixels                                                            |
+/// | Channels    | `u32` count, then the name of every channel as a string                     |
+/// | Sample type | `u8`, 0 for `f32` and 1 for `f64`                                           |
+/// | Parameters  | `u32` count, then the key and value of every parameter as strings           |
+/// | Samples     | Every channel of every pixel, one row after another from the top of the image |
+///
+/// Strings are a `u32` length in bytes followed by that many bytes of UTF-8. The channels of a pixel
+/// are stored next to one another in the order they're named in.
+///
+/// A histogram file may also be compressed as a whole with zstd, which is detected when it's
+/// [opened](open_histogram), as is the gzip earlier versions compressed with. Raw floats compress
+/// poorly, but the many empty pixels around the set and the low bits of sparse counts still often
+/// halve the size of a file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HistogramHeader {
+    pub version: u32,
+    pub width: usize,
+    pub height: usize,
+    /// The names of the channels of every pixel, in the order they're stored in.
+    pub channels: Vec<String>,
+    pub sample_type: SampleType,
+    pub parameters: Parameters,
+}
+
+/// Gets the parameters of `settings` that determine the image it renders, to be stored along with
+/// its histogram.
+///
+/// Every parameter is named after the flag of `generate` that sets it and holds a value that flag
+/// takes, so that a render can be repeated from the parameters it stored. The rotation is left to
+/// the caller, since `settings` only holds it raised to the power of the frame.
+pub fn parameters(settings: &SampleSettings) -> Parameters {
+    let mut parameters = Parameters::new();
+    let mut set = |key: &str, value: String| {
+        parameters.insert(key.to_owned(), value);
+    };
+
+    set("n", settings.n.to_string());
+    set("min_iterations", settings.min_iterations.to_string());
+    set("m", settings.m.to_string());
+    set("scale", settings.scale.to_string());
+    set("center", format!("{},{}", settings.center.re, settings.center.im));
+    set("precision", value_name(&settings.precision));
+    set("fractal", value_name(&settings.fractal));
+    set("power", settings.power.to_string());
+    set("phoenix", format!("{},{}", settings.phoenix.re, settings.phoenix.im));
+    set("orbits", value_name(&settings.orbits));
+    set("weight", value_name(&settings.weight));
+    set("arc_length", settings.arc_length_channel.is_some().to_string());
+    set("trap", settings.trap.trap.to_string());
+    set("trap_falloff", settings.trap.falloff.to_string());
+    set("projection", value_name(&settings.projection));
+    set("transform", settings.transform.to_string());
+    set("seed", settings.noise.seed.to_string());
+    set("frame", settings.noise.frame.to_string());
+    set("noise", value_name(&settings.noise.correlation));
+    set(
+        "metropolis",
+        matches!(settings.sampler, Sampler::Metropolis(_)).to_string(),
+    );
+    if let Sampler::Metropolis(metropolis) = settings.sampler {
+        set("chains", metropolis.chains.to_string());
+        set("burn_in", metropolis.burn_in.to_string());
+        set("chain_swap", metropolis.swap_interval.to_string());
+    }
+    if let Some(julia) = settings.julia {
+        set("julia", format!("{},{}", julia.re, julia.im));
+    }
+    if let Some(mobius) = settings.mobius {
+        set("mobius", mobius.to_string());
+    }
+    if let Some(bands) = settings.nebulabrot {
+        set("bands", bands.to_string());
+    }
+    if let Some(fractals) = settings.channel_fractals {
+        set("fractals", fractals.to_string());
+    }
+    if let Some(streams) = settings.streams {
+        set("streams", streams.to_string());
+    }
+
+    parameters
+}
+
+/// Gets the name `value` is given by on the command line.
+pub fn value_name(value: &impl ValueEnum) -> String {
+    value.to_possible_value().unwrap().get_name().to_owned()
+}
+
+/// Writes the raw values of every pixel of `im` to a histogram file, along with the parameters it
+/// was rendered with, compressing it at the given level when set.
+pub fn save_histogram<T: Color + Clone + Copy>(
+    path: impl AsRef<Path>,
+    im: &Image<T>,
+    parameters: &Parameters,
+    compression: Option<u32>,
+) -> io::Result<()> {
+    write_histogram(path, im.width, im.size / im.width, parameters, compression, |x, y| {
+        im.get((x, y))
+    })
+}
+
+/// Writes a histogram file of the color `pixel` gives every pixel, which is called one row after
+/// another, so that images too large to be held in memory at once can be written. The file is
+/// compressed as it's written at the given level when set.
+pub fn write_histogram<T: Color + Copy>(
+    path: impl AsRef<Path>,
+    width: usize,
+    height: usize,
+    parameters: &Parameters,
+    compression: Option<u32>,
+    mut pixel: impl FnMut(usize, usize) -> T,
+) -> io::Result<()> {
+    let mut out = HistogramWriter::create(path, compression)?;
+    let sample_type = SampleType::of::<T>();
+    write_header(
+        &mut out,
+        &HistogramHeader {
+            version: VERSION,
+            width,
+            height,
+            channels: T::CHANNELS.iter().map(|&name| name.to_owned()).collect(),
+            sample_type,
+            parameters: parameters.clone(),
+        },
+    )?;
+
+    for y in 0..height {
+        for x in 0..width {
+            let px = pixel(x, y);
+            for channel in 0..T::CHANNELS.len() {
+                let value = px.channel(channel);
+                match sample_type {
+                    SampleType::F32 => out.write_all(&value.to_f32().unwrap().to_le_bytes())?,
+                    SampleType::F64 => out.write_all(&value.to_f64().unwrap().to_le_bytes())?,
+                }
+            }
+        }
+    }
+
+    out.commit()
+}
+
+/// The bytes every zstd frame starts with, which tell a compressed histogram file apart from one
+/// starting with [`MAGIC`].
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// The bytes every gzip stream starts with. Histogram files were compressed with gzip before zstd,
+/// and are still read so that they don't have to be rendered again.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// A histogram file being written [atomically](AtomicFile), compressed with zstd as it's written
+/// when a level is given, so that a compressed file never has to be held in memory.
+pub enum HistogramWriter {
+    Raw(AtomicFile),
+    Compressed(zstd::stream::Encoder<'static, AtomicFile>),
+}
+
+impl HistogramWriter {
+    /// Creates a histogram file at `path`, compressed at the given level between 1-22 when set.
+    pub fn create(path: impl AsRef<Path>, compression: Option<u32>) -> io::Result<HistogramWriter> {
+        let file = AtomicFile::create(path)?;
+        Ok(match compression {
+            Some(level) => HistogramWriter::Compressed(zstd::stream::Encoder::new(file, level.clamp(1, 22) as i32)?),
+            None => HistogramWriter::Raw(file),
+        })
+    }
+
+    /// Finishes compressing the file and moves it into place.
+    pub fn commit(self) -> io::Result<()> {
+        match self {
+            HistogramWriter::Raw(file) => file.commit(),
+            HistogramWriter::Compressed(encoder) => encoder.finish()?.commit(),
+        }
+    }
+}
+
+impl Write for HistogramWriter {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            HistogramWriter::Raw(file) => file.write(buf),
+            HistogramWriter::Compressed(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            HistogramWriter::Raw(file) => file.flush(),
+            HistogramWriter::Compressed(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Writes a NumPy `.npy` file holding an array of shape `(height, width, channels)` of the color
+/// `pixel` gives every pixel, which is called one row after another.
+pub fn write_npy<T: Color + Copy>(
+    path: impl AsRef<Path>,
+    width: usize,
+    height: usize,
+    mut pixel: impl FnMut(usize, usize) -> T,
+) -> io::Result<()> {
+    let mut out = AtomicFile::create(path)?;
+    let sample_type = SampleType::of::<T>();
+    let descr = match sample_type {
+        SampleType::F32 => "<f4",
+        SampleType::F64 => "<f8",
+    };
+
+    // The header is padded with spaces so that the array starts on a multiple of 64 bytes, counting
+    // the 10 bytes of magic, version and header length before it
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': ({}, {}, {}), }}",
+        descr,
+        height,
+        width,
+        T::CHANNELS.len()
+    );
+    let len = (10 + header.len() + 1).next_multiple_of(64) - 10;
+    header.extend(std::iter::repeat_n(' ', len - header.len() - 1));
+    header.push('\n');
+
+    out.write_all(b"\x93NUMPY\x01\x00")?;
+    out.write_all(&(header.len() as u16).to_le_bytes())?;
+    out.write_all(header.as_bytes())?;
+
+    for y in 0..height {
+        for x in 0..width {
+            let px = pixel(x, y);
+            for channel in 0..T::CHANNELS.len() {
+                let value = px.channel(channel);
+                match sample_type {
+                    SampleType::F32 => out.write_all(&value.to_f32().unwrap().to_le_bytes())?,
+                    SampleType::F64 => out.write_all(&value.to_f64().unwrap().to_le_bytes())?,
+                }
+            }
+        }
+    }
+
+    out.commit()
+}
+
+/// Writes a Zarr (version 2) store holding an array of shape `(height, width, channels)` of the
+/// color `pixel` gives every pixel, which is called one row after another, replacing any store
+/// already at `path` once the new one is complete.
+///
+/// The array is split into zlib compressed chunks of `chunk_size` by `chunk_size` pixels, only one
+/// row of which is held in memory at a time. Chunks without any hits aren't written, and are read
+/// as zero. The parameters of the render are stored as the attributes of the array.
+pub fn write_zarr<T: Color + Copy>(
+    path: impl AsRef<Path>,
+    width: usize,
+    height: usize,
+    chunk_size: usize,
+    parameters: &Parameters,
+    mut pixel: impl FnMut(usize, usize) -> T,
+) -> io::Result<()> {
+    // The store is written next to `path` and moved into place once it's complete
+    let path = path.as_ref();
+    let temp = atomic::temp_path(path);
+    if temp.exists() {
+        fs::remove_dir_all(&temp)?;
+    }
+    fs::create_dir_all(&temp)?;
+
+    let channels = T::CHANNELS.len();
+    let sample_type = SampleType::of::<T>();
+    let metadata = json!({
+        "zarr_format": 2,
+        "shape": [height, width, channels],
+        "chunks": [chunk_size, chunk_size, channels],
+        "dtype": match sample_type {
+            SampleType::F32 => "<f4",
+            SampleType::F64 => "<f8",
+        },
+        "compressor": { "id": "zlib", "level": 1 },
+        "fill_value": 0.0,
+        "order": "C",
+        "filters": null,
+    });
+    atomic::write(temp.join(".zarray"), serde_json::to_string_pretty(&metadata)?)?;
+    atomic::write(temp.join(".zattrs"), serde_json::to_string_pretty(parameters)?)?;
+
+    // Every chunk has the full size, with the chunks past the edges of the image padded with zeros
+    let columns = width.div_ceil(chunk_size);
+    let mut band = vec![T::Channel::zero(); columns * chunk_size * chunk_size * channels];
+    for row in 0..height.div_ceil(chunk_size) {
+        band.fill(T::Channel::zero());
+        for y in row * chunk_size..((row + 1) * chunk_size).min(height) {
+            for x in 0..width {
+                let px = pixel(x, y);
+                let offset = ((x / chunk_size * chunk_size + y % chunk_size) * chunk_size + x % chunk_size) * channels;
+                for channel in 0..channels {
+                    band[offset + channel] = px.channel(channel);
+                }
+            }
+        }
+
+        for (column, chunk) in band.chunks_exact(chunk_size * chunk_size * channels).enumerate() {
+            if chunk.iter().all(|v| v.is_zero()) {
+                continue;
+            }
+
+            let file = AtomicFile::create(temp.join(format!("{}.{}.0", row, column)))?;
+            let mut out = ZlibEncoder::new(file, Compression::fast());
+            for value in chunk {
+                match sample_type {
+                    SampleType::F32 => out.write_all(&value.to_f32().unwrap().to_le_bytes())?,
+                    SampleType::F64 => out.write_all(&value.to_f64().unwrap().to_le_bytes())?,
+                }
+            }
+            out.finish()?.commit()?;
+        }
+    }
+
+    atomic::commit_dir(path)
+}
+
+/// Writes a text table with a header line naming its columns, followed by a line of the `x` and `y`
+/// position and the channels of the color `pixel` gives every pixel, which is called one row after
+/// another. Pixels without any hits are left out when `nonzero` is set.
+pub fn write_table<T: Color + Copy>(
+    path: impl AsRef<Path>,
+    width: usize,
+    height: usize,
+    format: TableFormat,
+    nonzero: bool,
+    mut pixel: impl FnMut(usize, usize) -> T,
+) -> io::Result<()> {
+    let mut out = AtomicFile::create(path)?;
+    let separator = format.separator();
+    let sample_type = SampleType::of::<T>();
+
+    write!(out, "x{}y", separator)?;
+    for name in T::CHANNELS {
+        write!(out, "{}{}", separator, name)?;
+    }
+    writeln!(out)?;
+
+    for y in 0..height {
+        for x in 0..width {
+            let px = pixel(x, y);
+            if nonzero && (0..T::CHANNELS.len()).all(|channel| px.channel(channel).is_zero()) {
+                continue;
+            }
+
+            write!(out, "{}{}{}", x, separator, y)?;
+            for channel in 0..T::CHANNELS.len() {
+                let value = px.channel(channel);
+                match sample_type {
+                    SampleType::F32 => write!(out, "{}{}", separator, value.to_f32().unwrap())?,
+                    SampleType::F64 => write!(out, "{}{}", separator, value.to_f64().unwrap())?,
+                }
+            }
+            writeln!(out)?;
+        }
+    }
+
+    out.commit()
+}
+
+/// Writes the header of a histogram file, which the samples of every pixel follow.
+pub fn write_header(out: &mut impl Write, header: &HistogramHeader) -> io::Result<()> {
+    out.write_all(&MAGIC)?;
+    out.write_all(&header.version.to_le_bytes())?;
+    out.write_all(&(header.width as u64).to_le_bytes())?;
+    out.write_all(&(header.height as u64).to_le_bytes())?;
+    out.write_all(&(header.channels.len() as u32).to_le_bytes())?;
+    for name in header.channels.iter() {
+        write_string(out, name)?;
+    }
+    out.write_all(&[header.sample_type as u8])?;
+    out.write_all(&(header.parameters.len() as u32).to_le_bytes())?;
+    for (key, value) in header.parameters.iter() {
+        write_string(out, key)?;
+        write_string(out, value)?;
+    }
+    Ok(())
+}
+
+/// Reads the header of a histogram file, leaving `input` at the start of its samples.
+pub fn read_header(input: &mut impl Read) -> io::Result<HistogramHeader> {
+    let mut magic = [0; 8];
+    input.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(invalid("not a histogram file"));
+    }
+
+    let version = read_u32(input)?;
+    if version > VERSION {
+        return Err(invalid(format!(
+            "histogram file is of version {}, but only versions up to {} can be read",
+            version, VERSION
+        )));
+    }
+
+    let width = read_u64(input)?;
+    let height = read_u64(input)?;
+    let channels = (0..read_u32(input)?)
+        .map(|_| read_string(input))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let mut sample_type = [0];
+    input.read_exact(&mut sample_type)?;
+    let sample_type = match sample_type[0] {
+        0 => SampleType::F32,
+        1 => SampleType::F64,
+        other => return Err(invalid(format!("unknown sample type {}", other))),
+    };
+
+    let parameters = (0..read_u32(input)?)
+        .map(|_| Ok((read_string(input)?, read_string(input)?)))
+        .collect::<io::Result<Parameters>>()?;
+
+    if width == 0 || height == 0 || channels.is_empty() {
+        return Err(invalid(format!(
+            "histogram file is {}x{} pixels of {} channels, so it holds no samples",
+            width,
+            height,
+            channels.len()
+        )));
+    }
+
+    // A damaged header could otherwise make readers allocate more memory than can be addressed
+    let bytes = [height, channels.len() as u64, sample_type.size() as u64]
+        .into_iter()
+        .try_fold(width, u64::checked_mul)
+        .filter(|&bytes| bytes <= isize::MAX as u64);
+    if bytes.is_none() {
+        return Err(invalid(format!(
+            "histogram file of {}x{} pixels of {} channels is too large to be read",
+            width,
+            height,
+            channels.len()
+        )));
+    }
+
+    Ok(HistogramHeader {
+        version,
+        width: width as usize,
+        height: height as usize,
+        channels,
+        sample_type,
+        parameters,
+    })
+}
+
+/// Opens a histogram file to [read its header](read_header) and samples from, decompressing it as
+/// it's read if it was written compressed.
+pub fn open_histogram(path: impl AsRef<Path>) -> io::Result<Box<dyn Read>> {
+    let mut input = BufReader::new(File::open(path)?);
+    let start = input.fill_buf()?;
+    if start.starts_with(&ZSTD_MAGIC) {
+        Ok(Box::new(BufReader::new(zstd::stream::Decoder::with_buffer(input)?)))
+    } else if start.starts_with(&GZIP_MAGIC) {
+        Ok(Box::new(BufReader::new(GzDecoder::new(input))))
+    } else {
+        Ok(Box::new(input))
+    }
+}
+
+/// Reads a histogram file into an image of the color `T`, which must have the same channels as
+/// the histogram, returning it along with the header of the file.
+pub fn load_histogram<T: Color + Clone + Copy>(path: impl AsRef<Path>) -> io::Result<(Image<T>, HistogramHeader)> {
+    let mut input = open_histogram(path)?;
+    let header = read_header(&mut input)?;
+
+    if header.channels != T::CHANNELS {
+        return Err(invalid(format!(
+            "histogram has channels {:?}, but channels {:?} were expected",
+            header.channels,
+            T::CHANNELS
+        )));
+    }
+
+    let mut im = Image::<T>::new(header.width * header.height, header.width);
+    let mut bytes = vec![0; header.sample_type.size() * header.channels.len()];
+    let mut channels = vec![T::Channel::zero(); header.channels.len()];
+    for y in 0..header.height {
+        for x in 0..header.width {
+            input.read_exact(&mut bytes)?;
+            for (channel, bytes) in channels.iter_mut().zip(bytes.chunks_exact(header.sample_type.size())) {
+                let value = match header.sample_type {
+                    SampleType::F32 => <T::Channel as NumCast>::from(f32::from_le_bytes(bytes.try_into().unwrap())),
+                    SampleType::F64 => <T::Channel as NumCast>::from(f64::from_le_bytes(bytes.try_into().unwrap())),
+                };
+                *channel = value.unwrap();
+            }
+            im.set((x, y), T::from_channels(&channels));
+        }
+    }
+
+    Ok((im, header))
+}
+
+/// The parameters histograms of the same image rendered separately may differ in when they're
+/// [merged](merge_histograms), which are the number of samples drawn per pixel and the seed they
+/// were drawn with.
+pub const MERGED_PARAMETERS: [&str; 2] = ["m", "seed"];
+
+/// Sums the histogram files at `inputs` into one at `path`, compressed at the given level when set,
+/// returning the header of the merged file.
+///
+/// Renders of the same image with different seeds draw independent samples, so a render can be
+/// split across machines and the histograms they write added up afterwards, as if one render had
+/// drawn every sample of them. Every file must have the same dimensions and channels and, unless
+/// `force` is set, the same parameters besides [`MERGED_PARAMETERS`] and a seed of its own. The
+/// merged file records the samples per pixel of every file added up and their seeds, and its
+/// samples are stored as `f64` if those of any file are.
+///
+/// The files are read alongside one another and summed a sample at a time, so histograms far
+/// larger than memory can be merged.
+pub fn merge_histograms(
+    inputs: &[impl AsRef<Path>],
+    path: impl AsRef<Path>,
+    force: bool,
+    compression: Option<u32>,
+) -> io::Result<HistogramHeader> {
+    let mut readers = Vec::with_capacity(inputs.len());
+    let mut headers = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let mut reader = open_histogram(input)?;
+        headers.push(read_header(&mut reader)?);
+        readers.push(reader);
+    }
+
+    let Some(first) = headers.first() else {
+        return Err(invalid("there are no histograms to merge"));
+    };
+    let name = |i: usize| inputs[i].as_ref();
+    for (i, header) in headers.iter().enumerate().skip(1) {
+        if (header.width, header.height) != (first.width, first.height) {
+            return Err(invalid(format!(
+                "{:?} is {}x{}, but {:?} is {}x{}",
+                name(i),
+                header.width,
+                header.height,
+                name(0),
+                first.width,
+                first.height
+            )));
+        }
+        if header.channels != first.channels {
+            return Err(invalid(format!(
+                "{:?} has channels {:?}, but {:?} has channels {:?}",
+                name(i),
+                header.channels,
+                name(0),
+                first.channels
+            )));
+        }
+
+        let mismatches = mismatched_parameters(&first.parameters, &header.parameters)
+            .into_iter()
+            .filter(|key| !MERGED_PARAMETERS.contains(&key.as_str()))
+            .collect::<Vec<_>>();
+        if !force && !mismatches.is_empty() {
+            return Err(invalid(format!(
+                "{:?} was rendered with different {} than {:?}",
+                name(i),
+                mismatches.join(", "),
+                name(0)
+            )));
+        }
+    }
+
+    let seeds = headers
+        .iter()
+        .map(|header| header.parameters.get("seed").map(String::as_str))
+        .collect::<Vec<_>>();
+    for (i, &seed) in seeds.iter().enumerate().filter(|_| !force) {
+        let Some(seed) = seed else { continue };
+        if let Some(j) = seeds[..i].iter().position(|&other| other == Some(seed)) {
+            return Err(invalid(format!(
+                "{:?} and {:?} were both rendered with seed {}, so they drew the same samples",
+                name(j),
+                name(i),
+                seed
+            )));
+        }
+    }
+
+    let mut parameters = first.parameters.clone();
+    let samples = headers
+        .iter()
+        .map(|header| header.parameters.get("m")?.parse::<u64>().ok())
+        .sum::<Option<u64>>();
+    if let Some(samples) = samples {
+        parameters.insert("m".to_owned(), samples.to_string());
+    }
+    if let Some(seeds) = seeds.into_iter().collect::<Option<Vec<_>>>() {
+        parameters.insert("seed".to_owned(), seeds.join(","));
+    }
+
+    let sample_type = if headers.iter().any(|header| header.sample_type == SampleType::F64) {
+        SampleType::F64
+    } else {
+        SampleType::F32
+    };
+    let header = HistogramHeader {
+        version: VERSION,
+        width: first.width,
+        height: first.height,
+        channels: first.channels.clone(),
+        sample_type,
+        parameters,
+    };
+
+    let mut out = HistogramWriter::create(path, compression)?;
+    write_header(&mut out, &header)?;
+    for _ in 0..header.width * header.height * header.channels.len() {
+        let mut sum = 0.0;
+        for (input, header) in readers.iter_mut().zip(headers.iter()) {
+            sum += read_sample(input, header.sample_type)?;
+        }
+        match sample_type {
+            SampleType::F32 => out.write_all(&(sum as f32).to_le_bytes())?,
+            SampleType::F64 => out.write_all(&sum.to_le_bytes())?,
+        }
+    }
+    out.commit()?;
+
+    Ok(header)
+}
+
+/// Gets the names of the parameters that are set differently in `a` and `b`, or only in one of
+/// them, in order.
+pub fn mismatched_parameters(a: &Parameters, b: &Parameters) -> Vec<String> {
+    let mut keys = a.keys().chain(b.keys()).collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .filter(|&key| a.get(key) != b.get(key))
+        .cloned()
+        .collect()
+}
+
+/// Reads a single sample of the given type.
+fn read_sample(input: &mut impl Read, sample_type: SampleType) -> io::Result<f64> {
+    Ok(match sample_type {
+        SampleType::F32 => f32::from_bits(read_u32(input)?) as f64,
+        SampleType::F64 => f64::from_bits(read_u64(input)?),
+    })
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn write_string(out: &mut impl Write, string: &str) -> io::Result<()> {
+    if string.len() > MAX_STRING_LEN as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} bytes is too long for a string of a histogram file", string.len()),
+        ));
+    }
+
+    out.write_all(&(string.len() as u32).to_le_bytes())?;
+    out.write_all(string.as_bytes())
+}
+
+fn read_string(input: &mut impl Read) -> io::Result<String> {
+    let len = read_u32(input)?;
+    if len > MAX_STRING_LEN {
+        return Err(invalid(format!("string of {} bytes is too long", len)));
+    }
+
+    // The bytes are read as they come rather than into a buffer of the stated length, which a
+    // truncated file may never fill
+    let mut bytes = Vec::new();
+    input.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "histogram file ends within a string",
+        ));
+    }
+    String::from_utf8(bytes).map_err(|_| invalid("string is not UTF-8"))
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Rgb;
+
+    /// Gets a path in the temporary directory that no other test writes to.
+    fn temp_file(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("buddhabrot-{}-{}.{}", name, std::process::id(), EXTENSION))
+    }
+
+    fn test_image() -> Image<Rgb> {
+        let mut im = Image::<Rgb>::new(7 * 5, 7);
+        for (x, y, px) in im.enumerate_pixels_mut() {
+            *px = Rgb::new(x as f32, y as f32 * 0.5, (x * y) as f32 + 0.25);
+        }
+        im
+    }
+
+    fn test_parameters() -> Parameters {
+        Parameters::from([
+            ("n".to_owned(), "200".to_owned()),
+            ("center".to_owned(), "-0.5,0".to_owned()),
+        ])
+    }
+
+    /// Writes the test image to a histogram file, reads it back and checks nothing was lost.
+    fn round_trip(name: &str, compression: Option<u32>) -> Vec<u8> {
+        let (path, im, parameters) = (temp_file(name), test_image(), test_parameters());
+        save_histogram(&path, &im, &parameters, compression).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        let (loaded, header) = load_histogram::<Rgb>(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(header.version, VERSION);
+        assert_eq!((header.width, header.height), (7, 5));
+        assert_eq!(header.channels, ["R", "G", "B"]);
+        assert_eq!(header.sample_type, SampleType::F32);
+        assert_eq!(header.parameters, parameters);
+        for (x, y, px) in im.enumerate_pixels() {
+            assert_eq!(loaded.get((x, y)).to_tuple_rgb(), px.to_tuple_rgb());
+        }
+        bytes
+    }
+
+    #[test]
+    fn round_trip_plain() {
+        let bytes = round_trip("plain", None);
+        assert!(bytes.starts_with(&MAGIC));
+    }
+
+    #[test]
+    fn round_trip_compressed() {
+        let bytes = round_trip("compressed", Some(6));
+        assert!(bytes.starts_with(&ZSTD_MAGIC));
+    }
+
+    #[test]
+    fn reads_gzip_files() {
+        let (path, im, parameters) = (temp_file("gzip"), test_image(), test_parameters());
+        save_histogram(&path, &im, &parameters, None).unwrap();
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&fs::read(&path).unwrap()).unwrap();
+        fs::write(&path, encoder.finish().unwrap()).unwrap();
+
+        let (loaded, header) = load_histogram::<Rgb>(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(header.parameters, parameters);
+        for (x, y, px) in im.enumerate_pixels() {
+            assert_eq!(loaded.get((x, y)).to_tuple_rgb(), px.to_tuple_rgb());
+        }
+    }
+
+    #[test]
+    fn rejects_future_versions() {
+        let mut bytes = Vec::new();
+        write_header(
+            &mut bytes,
+            &HistogramHeader {
+                version: VERSION + 1,
+                width: 1,
+                height: 1,
+                channels: vec!["Y".to_owned()],
+                sample_type: SampleType::F32,
+                parameters: Parameters::new(),
+            },
+        )
+        .unwrap();
+
+        let err = read_header(&mut bytes.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_truncated_headers() {
+        let mut bytes = Vec::new();
+        write_header(
+            &mut bytes,
+            &HistogramHeader {
+                version: VERSION,
+                width: 7,
+                height: 5,
+                channels: vec!["Y".to_owned()],
+                sample_type: SampleType::F32,
+                parameters: test_parameters(),
+            },
+        )
+        .unwrap();
+
+        for len in [4, 12, 30, bytes.len() - 1] {
+            let err = read_header(&mut &bytes[..len]).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof, "{} bytes", len);
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_headers() {
+        let header = |width, height| {
+            let mut bytes = Vec::new();
+            write_header(
+                &mut bytes,
+                &HistogramHeader {
+                    version: VERSION,
+                    width,
+                    height,
+                    channels: vec!["R".to_owned(), "G".to_owned(), "B".to_owned()],
+                    sample_type: SampleType::F64,
+                    parameters: Parameters::new(),
+                },
+            )
+            .unwrap();
+            bytes
+        };
+
+        for (width, height) in [(usize::MAX, 2), (1 << 32, 1 << 32), (0, 5), (7, 0)] {
+            let err = read_header(&mut header(width, height).as_slice()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}x{}", width, height);
+        }
+
+        // A string claiming to be far longer than the file is refused before anything is allocated
+        let mut bytes = header(7, 5);
+        let channels = MAGIC.len() + 4 + 8 + 8;
+        bytes[channels + 4..channels + 8].copy_from_slice(&u32::MAX.to_le_bytes());
+        let err = read_header(&mut bytes.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let err = read_header(&mut b"not a histogram".as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
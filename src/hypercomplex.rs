@@ -0,0 +1,185 @@
+use std::sync::{Arc, Mutex};
+
+use clap::ValueEnum;
+
+use crate::{
+    canvas::Canvas,
+    color::{Color, ColorChannel},
+    complex::Complex,
+    numa,
+    quaternion::Quaternion,
+    sample::{run_chunks, sample_point, Orbits, SampleSettings, SampleStats},
+};
+
+/// The squared escape radius of the quaternion iteration.
+const BAILOUT: f32 = 4.0;
+
+/// Determines which two parts of each quaternion of an orbit are plotted onto the image.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum QuaternionPlane {
+    #[default]
+    #[value(name = "re-i")]
+    ReI,
+    #[value(name = "re-j")]
+    ReJ,
+    #[value(name = "re-k")]
+    ReK,
+    #[value(name = "i-j")]
+    IJ,
+    #[value(name = "i-k")]
+    IK,
+    #[value(name = "j-k")]
+    JK,
+}
+
+impl QuaternionPlane {
+    /// Projects `q` onto the image plane.
+    #[inline]
+    pub fn project(self, q: Quaternion<f32>) -> Complex<f32> {
+        match self {
+            QuaternionPlane::ReI => Complex::new(q.re, q.i),
+            QuaternionPlane::ReJ => Complex::new(q.re, q.j),
+            QuaternionPlane::ReK => Complex::new(q.re, q.k),
+            QuaternionPlane::IJ => Complex::new(q.i, q.j),
+            QuaternionPlane::IK => Complex::new(q.i, q.k),
+            QuaternionPlane::JK => Complex::new(q.j, q.k),
+        }
+    }
+}
+
+/// The quaternion iteration `q = q² + c`, an experimental 4D analog of the mandelbrot set.
+///
+/// Samples are drawn from the complex plane and lifted into the quaternions by giving them
+/// constant `j` and `k` parts. Note that when `q` starts at zero, every orbit stays within the
+/// plane spanned by `1` and the imaginary part of `c`, so the interesting structure lies in Julia
+/// sets, whose starting points fill all four dimensions.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct QuaternionSlice {
+    /// The `j` part of every sample.
+    pub j: f32,
+    /// The `k` part of every sample.
+    pub k: f32,
+    /// The plane orbits are projected onto.
+    pub plane: QuaternionPlane,
+}
+
+impl QuaternionSlice {
+    /// Gets the starting point and constant of the orbit of `sample`.
+    #[inline]
+    fn orbit_start(&self, settings: &SampleSettings, sample: Complex<f32>) -> (Quaternion<f32>, Quaternion<f32>) {
+        let lifted = Quaternion::new(sample.re, sample.im, self.j, self.k);
+        match settings.julia {
+            Some(c) => (lifted, Quaternion::from_complex(c)),
+            None => (lifted, lifted),
+        }
+    }
+
+    /// Visits the first `n` points of the orbit of `q` with constant `c`, returning whether or not
+    /// it escaped.
+    #[inline]
+    fn iterate(
+        &self,
+        mut q: Quaternion<f32>,
+        c: Quaternion<f32>,
+        n: u32,
+        mut visit: impl FnMut(Quaternion<f32>),
+    ) -> bool {
+        for _ in 0..n {
+            visit(q);
+
+            q = q.square() + c;
+            if q.norm_sqr() > BAILOUT {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+/// Samples orbits of the quaternion iteration into every image of a canvas.
+pub fn sample_quaternion<T: Color + Clone + Copy + Send + Sync + 'static>(
+    canvas: Arc<Mutex<Canvas<T>>>,
+    settings: SampleSettings,
+    slice: QuaternionSlice,
+) -> SampleStats {
+    let n = settings.n;
+    let blank = canvas.lock().unwrap().blank();
+    let iters = blank.main.size * settings.m as usize;
+
+    let subcanvases = run_chunks(
+        &settings,
+        iters,
+        || (blank.clone(), Vec::new()),
+        |(subcanvas, trajectory), stream, throttle, count| {
+            for _ in 0..count {
+                let sample = sample_point(&settings, stream.next_point());
+                let (q, c) = slice.orbit_start(&settings, sample);
+
+                trajectory.clear();
+                subcanvas.counts.samples += 1;
+                match settings.orbits {
+                    Orbits::Escaping => {
+                        let escaped = slice.iterate(q, c, n, |q| trajectory.push(slice.plane.project(q)));
+                        subcanvas.counts.escaped += escaped as u64;
+                        if escaped && trajectory.len() as u32 >= settings.min_iterations {
+                            plot(subcanvas, &settings, trajectory);
+                        }
+                    },
+                    Orbits::Bounded => {
+                        // Bounded orbits are as long as they can be, so iterate them again while
+                        // plotting rather than storing them
+                        if slice.iterate(q, c, n, |_| ()) {
+                            subcanvas.counts.escaped += 1;
+                        } else {
+                            slice.iterate(q, c, n, |q| plot(subcanvas, &settings, &[slice.plane.project(q)]));
+                        }
+                    },
+                }
+
+                throttle.tick(1);
+            }
+        },
+    );
+
+    let threads = subcanvases.iter().map(|(_, (subcanvas, _))| subcanvas.counts).collect();
+    let merge = |(canvas, _): &mut (Canvas<T>, _), (other, _)| canvas.merge(other);
+    for (subcanvas, _) in numa::merge_by_node(subcanvases, merge) {
+        canvas.lock().unwrap().merge(subcanvas);
+    }
+
+    SampleStats {
+        threads,
+        ..Default::default()
+    }
+}
+
+/// Deposits every point of a projected trajectory into every image of `canvas`.
+#[inline]
+fn plot<T: Color + Clone + Copy>(canvas: &mut Canvas<T>, settings: &SampleSettings, trajectory: &[Complex<f32>]) {
+    let deposit = match settings.nebulabrot {
+        Some(nebulabrot) => nebulabrot.route(trajectory.len() as u32),
+        None => T::one(ColorChannel::Red),
+    };
+
+    let plane_map = settings.plane_map();
+    let width = canvas.main.width;
+    let height = canvas.main.size / canvas.main.width;
+
+    for &z in trajectory {
+        match plane_map.to_pixel(z, width, height) {
+            Some(px) => {
+                canvas.main.add(px, deposit);
+                canvas.counts.plotted += 1;
+            },
+            None => canvas.counts.culled += 1,
+        }
+
+        for layer in canvas.layers.iter_mut() {
+            let width = layer.image.width;
+            if let Some(px) = layer.viewport.to_pixel(z, width, layer.image.size / width) {
+                layer.image.add(px, deposit);
+            }
+        }
+    }
+}
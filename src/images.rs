@@ -1,15 +1,58 @@
 use std::{
-    slice::{Iter, IterMut},
+    fmt,
+    fs::{File, OpenOptions},
+    io,
+    marker::PhantomData,
+    mem,
+    ops::{Deref, DerefMut},
+    path::Path,
+    ptr::NonNull,
+    slice::IterMut,
+    sync::atomic::AtomicU32,
     vec::IntoIter,
 };
 
-use crate::color::Color;
+use exr::{
+    meta::attribute::Text,
+    prelude::{AnyChannel, AnyChannels, FlatSamples, SmallVec, WritableImage},
+};
+use num_traits::ToPrimitive;
+
+use crate::{
+    atomic::AtomicFile,
+    color::{Color, Count, Rg, Rgb},
+    histogram::Parameters,
+    metadata,
+};
+
+/// The number of pixels in a page of a sparse image.
+const PAGE_SIZE: usize = 4096;
+
+/// The width and height in pixels of the blocks a [tiled](Layout::Tiled) image is stored in, which
+/// makes every block exactly one page of a sparse image.
+const TILE_SIZE: usize = 64;
 
 #[derive(Debug, Clone)]
 pub struct Image<T: Color> {
-    data: Vec<T>,
+    data: Storage<T>,
     pub size: usize,
     pub width: usize,
+    layout: Layout,
+}
+
+/// The order the pixels of an image are stored in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Layout {
+    /// One row after another.
+    #[default]
+    Linear,
+    /// In square blocks of [`TILE_SIZE`] pixels, one row of blocks after another, with the pixels
+    /// of every block stored one row after another.
+    ///
+    /// Trajectories scatter their points all over an image, but consecutive points often land
+    /// close to one another, which a block keeps in the same few cache lines where rows would
+    /// spread them over many. Images are padded to a whole number of blocks.
+    Tiled,
 }
 
 impl<T: Color + Clone + Copy> Image<T> {
@@ -17,9 +60,170 @@ impl<T: Color + Clone + Copy> Image<T> {
     #[inline]
     pub fn new(size: usize, width: usize) -> Image<T> {
         Self {
-            data: vec![T::empty(); size],
+            data: Storage::Dense(Buffer::Heap(vec![T::empty(); size])),
             size,
             width,
+            layout: Layout::Linear,
+        }
+    }
+
+    /// Creates a new, blank image whose pixels are stored in pages allocated the first time one of
+    /// their pixels is written to.
+    ///
+    /// Most of the image of a deep zoom stays empty for a long time, which this saves the memory
+    /// of. Once more than half of the pages are allocated, the image switches to storing every
+    /// pixel like a regular image does.
+    pub fn sparse(size: usize, width: usize) -> Image<T> {
+        Self {
+            data: Storage::Paged(Pages {
+                pages: vec![None; size.div_ceil(PAGE_SIZE)],
+                allocated: 0,
+                len: size,
+                empty: T::empty(),
+            }),
+            size,
+            width,
+            layout: Layout::Linear,
+        }
+    }
+
+    /// Creates a new, blank image with the same dimensions and layout as this one, which is sparse
+    /// if this one still is.
+    pub fn blank(&self) -> Image<T> {
+        let blank = match self.data {
+            Storage::Paged(_) => Self::sparse(self.size, self.width),
+            Storage::Dense(_) => Self::new(self.size, self.width),
+        };
+
+        match self.layout {
+            Layout::Linear => blank,
+            Layout::Tiled => blank.tiled(),
+        }
+    }
+
+    /// Gets the order the pixels of this image are stored in.
+    #[inline]
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Rearranges the pixels of this image into [tiled](Layout::Tiled) blocks, keeping it sparse
+    /// if it is, which suits images plotted into one scattered point at a time.
+    pub fn tiled(self) -> Image<T> {
+        if self.layout == Layout::Tiled {
+            return self;
+        }
+
+        let height = self.size / self.width;
+        let len = self.width.div_ceil(TILE_SIZE) * height.div_ceil(TILE_SIZE) * TILE_SIZE * TILE_SIZE;
+        let mut tiled = Image {
+            data: match self.data {
+                Storage::Dense(_) => Storage::Dense(Buffer::Heap(vec![T::empty(); len])),
+                Storage::Paged(_) => Storage::Paged(Pages {
+                    pages: vec![None; len.div_ceil(PAGE_SIZE)],
+                    allocated: 0,
+                    len,
+                    empty: T::empty(),
+                }),
+            },
+            size: self.size,
+            width: self.width,
+            layout: Layout::Tiled,
+        };
+
+        // Only the pages of a sparse image that were written to need to be moved
+        let width = self.width;
+        match self.data {
+            Storage::Dense(data) => {
+                for (i, &px) in data.iter().enumerate() {
+                    tiled.set((i % width, i / width), px);
+                }
+            },
+            Storage::Paged(data) => {
+                for (page, pixels) in data.pages.into_iter().enumerate() {
+                    for (i, &px) in pixels.iter().flat_map(|pixels| pixels.iter()).enumerate() {
+                        let i = page * PAGE_SIZE + i;
+                        tiled.set((i % width, i / width), px);
+                    }
+                }
+            },
+        }
+
+        tiled
+    }
+
+    /// Rearranges the pixels of this image into rows, which is the order every pixel is enumerated,
+    /// converted and written in.
+    pub fn linear(self) -> Image<T> {
+        if self.layout == Layout::Linear {
+            return self;
+        }
+
+        let mut linear = Image::new(self.size, self.width);
+        for y in 0..self.size / self.width {
+            for x in 0..self.width {
+                linear.set((x, y), self.get((x, y)));
+            }
+        }
+        linear
+    }
+
+    /// Gets the index of the pixel at a given `(x, y)` pixel position in the storage of the image.
+    #[inline]
+    fn index(&self, (x, y): (usize, usize)) -> usize {
+        match self.layout {
+            Layout::Linear => y * self.width + x,
+            Layout::Tiled => {
+                let block = y / TILE_SIZE * self.width.div_ceil(TILE_SIZE) + x / TILE_SIZE;
+                (block * TILE_SIZE + y % TILE_SIZE) * TILE_SIZE + x % TILE_SIZE
+            },
+        }
+    }
+
+    /// Creates a new, blank image whose pixels are stored in the file at `path` and mapped into
+    /// memory, replacing the file if it exists.
+    ///
+    /// The operating system pages pixels in and out of the file as they are used, so the image can
+    /// be larger than the memory of the machine, and its pixels are left in the file if the process
+    /// dies before the image is dropped. Nothing else may change the file while the image is alive.
+    pub fn mapped(path: impl AsRef<Path>, size: usize, width: usize) -> io::Result<Image<T>>
+    where
+        T: Zeroed,
+    {
+        // SAFETY: the file is created for this image alone, and nothing else touches it while the
+        // image is alive
+        let data = unsafe { MappedBuffer::create(path, size)? };
+        Ok(Self {
+            data: Storage::Dense(Buffer::Mapped(data)),
+            size,
+            width,
+            layout: Layout::Linear,
+        })
+    }
+
+    /// Maps the pixels left in the file at `path` by an image created with
+    /// [`mapped`](Self::mapped) back into an image `width` pixels wide.
+    pub fn open_mapped(path: impl AsRef<Path>, width: usize) -> io::Result<Image<T>>
+    where
+        T: Zeroed,
+    {
+        // SAFETY: the file was left by an image that is gone, and nothing else touches it while this
+        // image is alive
+        let data = unsafe { MappedBuffer::open(path)? };
+        Ok(Self {
+            size: data.len(),
+            data: Storage::Dense(Buffer::Mapped(data)),
+            width,
+            layout: Layout::Linear,
+        })
+    }
+
+    /// Writes every changed pixel of an image created with [`mapped`](Self::mapped) to its file,
+    /// waiting until they reach the disk. Does nothing for images in memory.
+    pub fn flush(&self) -> io::Result<()> {
+        match &self.data {
+            Storage::Dense(Buffer::Mapped(data)) => data.flush(),
+            _ => Ok(()),
         }
     }
 
@@ -27,52 +231,117 @@ impl<T: Color + Clone + Copy> Image<T> {
     #[inline]
     pub fn new_fill(size: usize, width: usize, col: T) -> Image<T> {
         Self {
-            data: vec![col; size],
+            data: Storage::Dense(Buffer::Heap(vec![col; size])),
             size,
             width,
+            layout: Layout::Linear,
         }
     }
 
     /// Gets the value of a pixel at a given `(x, y)` pixel position.
     #[inline]
     pub fn get(&self, px: (usize, usize)) -> T {
-        self.data[px.1 * self.width + px.0]
+        self.data.get(self.index(px))
     }
 
     /// Sets the value of a pixel at a given `(x, y)` pixel position.
     #[inline]
     pub fn set(&mut self, px: (usize, usize), col: T) {
-        self.data[px.1 * self.width + px.0] = col;
+        let i = self.index(px);
+        *self.data.get_mut(i) = col;
     }
 
     /// Swaps two pixels
     #[inline]
     pub fn swap(&mut self, p1: (usize, usize), p2: (usize, usize)) {
-        self.data.swap(p1.1 * self.width + p1.0, p2.1 * self.width + p2.0);
+        let (i, j) = (self.index(p1), self.index(p2));
+        self.data.dense().swap(i, j);
     }
 
     /// Adds to the value of a pixel at a given `(x, y)` pixel position.
     #[inline]
     pub fn add(&mut self, px: (usize, usize), col: T) {
-        self.data[px.1 * self.width + px.0].add(col);
+        let i = self.index(px);
+        self.data.get_mut(i).add(col);
+    }
+
+    /// Converts every pixel of this image with `f` into a new image of the same dimensions and
+    /// layout.
+    pub fn convert<U: Color + Clone + Copy>(self, f: impl Fn(T) -> U) -> Image<U> {
+        Image {
+            data: Storage::Dense(Buffer::Heap(self.data.into_vec().into_iter().map(f).collect())),
+            size: self.size,
+            width: self.width,
+            layout: self.layout,
+        }
+    }
+
+    /// Adds every pixel of `other`, which must have the same dimensions and layout, to this image,
+    /// skipping the pages of a sparse image that were never written to.
+    pub fn merge(&mut self, other: Image<T>) {
+        debug_assert_eq!(self.layout, other.layout);
+        match other.data {
+            Storage::Dense(data) => {
+                for (i, &px) in data.iter().enumerate() {
+                    self.data.get_mut(i).add(px);
+                }
+            },
+            Storage::Paged(data) => {
+                for (page, pixels) in data.pages.into_iter().enumerate() {
+                    for (i, &px) in pixels.iter().flat_map(|pixels| pixels.iter()).enumerate() {
+                        self.data.get_mut(page * PAGE_SIZE + i).add(px);
+                    }
+                }
+            },
+        }
+    }
+
+    /// Writes every channel of this image to an OpenEXR file as 32-bit floats, each under the name
+    /// its [`Color`] gives it, keeping the full range of values rather than tonemapping them. The
+    /// `parameters` it was rendered with are stored as attributes.
+    pub fn write_exr(&self, path: impl AsRef<Path>, parameters: &Parameters) -> exr::error::UnitResult {
+        let height = self.size / self.width;
+        let channels = T::CHANNELS
+            .iter()
+            .enumerate()
+            .map(|(index, &name)| {
+                let samples = (0..height)
+                    .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+                    .map(|px| self.get(px).channel(index).to_f32().unwrap_or(f32::MAX))
+                    .collect();
+                AnyChannel::new(name, FlatSamples::F32(samples))
+            })
+            .collect::<SmallVec<_>>();
+
+        let mut image = exr::image::Image::from_channels((self.width, height), AnyChannels::sort(channels));
+        image.layer_data.attributes.software_name = Some(Text::from(metadata::SOFTWARE));
+        image.layer_data.attributes.other = metadata::exr_attributes(parameters);
+
+        let mut out = AtomicFile::create(path)?;
+        image.write().to_buffered(&mut out)?;
+        Ok(out.commit()?)
     }
 
-    /// Get an iterator over every pixel in the image.
+    /// Get an iterator over every pixel in the image, in the order they're stored in, which includes
+    /// the empty padding of a tiled image.
     #[inline]
-    pub fn pixels(&self) -> Pixels<T> {
+    pub fn pixels(&self) -> Pixels<'_, T> {
         Pixels { iter: self.data.iter() }
     }
 
-    /// Get a mutable iterator over every pixel in the image.
+    /// Get a mutable iterator over every pixel in the image, in the order they're stored in.
     #[inline]
-    pub fn pixels_mut(&mut self) -> PixelsMut<T> {
+    pub fn pixels_mut(&mut self) -> PixelsMut<'_, T> {
         PixelsMut {
-            iter: self.data.iter_mut(),
+            iter: self.data.dense().iter_mut(),
         }
     }
 
+    /// Get an iterator over every pixel in the image along with its position, one row after
+    /// another. The image must be [linear](Layout::Linear).
     #[inline]
-    pub fn enumerate_pixels(&self) -> EnumeratePixels<T> {
+    pub fn enumerate_pixels(&self) -> EnumeratePixels<'_, T> {
+        debug_assert_eq!(self.layout, Layout::Linear);
         EnumeratePixels {
             iter: self.data.iter(),
             index: 0,
@@ -81,20 +350,24 @@ impl<T: Color + Clone + Copy> Image<T> {
         }
     }
 
+    /// Like [`enumerate_pixels`](Self::enumerate_pixels), but consuming the image.
     #[inline]
     pub fn into_enumerate_pixels(self) -> IntoEnumeratePixels<T> {
+        debug_assert_eq!(self.layout, Layout::Linear);
         IntoEnumeratePixels {
-            iter: self.data.into_iter(),
+            iter: self.data.into_vec().into_iter(),
             index: 0,
             size: self.size,
             width: self.width,
         }
     }
 
+    /// Like [`enumerate_pixels`](Self::enumerate_pixels), but mutably.
     #[inline]
-    pub fn enumerate_pixels_mut(&mut self) -> EnumeratePixelsMut<T> {
+    pub fn enumerate_pixels_mut(&mut self) -> EnumeratePixelsMut<'_, T> {
+        debug_assert_eq!(self.layout, Layout::Linear);
         EnumeratePixelsMut {
-            iter: self.data.iter_mut(),
+            iter: self.data.dense().iter_mut(),
             index: 0,
             size: self.size,
             width: self.width,
@@ -108,8 +381,318 @@ impl<T: Color + Clone + Copy> Default for Image<T> {
     }
 }
 
+/// Types whose values can be stored as raw bytes in a file and read back, and which are valid when
+/// every byte is zero.
+///
+/// # Safety
+///
+/// Every bit pattern of the size of the type must be a valid value, and the type must not hold any
+/// pointers or references.
+pub unsafe trait Zeroed {}
+
+unsafe impl Zeroed for f32 {}
+unsafe impl Zeroed for f64 {}
+unsafe impl Zeroed for u32 {}
+unsafe impl Zeroed for u64 {}
+unsafe impl Zeroed for AtomicU32 {}
+unsafe impl<N: Zeroed> Zeroed for Count<N> {}
+unsafe impl<F: Zeroed> Zeroed for Rg<F> {}
+unsafe impl<F: Zeroed> Zeroed for Rgb<F> {}
+
+/// The storage of the pixels of an image, either all at once or in pages.
+#[derive(Debug, Clone)]
+enum Storage<T> {
+    Dense(Buffer<T>),
+    Paged(Pages<T>),
+}
+
+impl<T: Copy> Storage<T> {
+    #[inline]
+    fn get(&self, i: usize) -> T {
+        match self {
+            Storage::Dense(data) => data[i],
+            Storage::Paged(data) => data.pages[i / PAGE_SIZE]
+                .as_ref()
+                .map_or(data.empty, |page| page[i % PAGE_SIZE]),
+        }
+    }
+
+    /// Gets a pixel to write to, allocating its page first if needed.
+    #[inline]
+    fn get_mut(&mut self, i: usize) -> &mut T {
+        if let Storage::Paged(data) = self {
+            if data.pages[i / PAGE_SIZE].is_none() && (data.allocated + 1) * 2 > data.pages.len() {
+                self.dense();
+            }
+        }
+
+        match self {
+            Storage::Dense(data) => &mut data[i],
+            Storage::Paged(data) => {
+                let page = i / PAGE_SIZE;
+                if data.pages[page].is_none() {
+                    data.pages[page] = Some(vec![data.empty; data.page_len(page)].into_boxed_slice());
+                    data.allocated += 1;
+                }
+                &mut data.pages[page].as_mut().unwrap()[i % PAGE_SIZE]
+            },
+        }
+    }
+
+    /// Switches to storing every pixel, returning them all.
+    fn dense(&mut self) -> &mut [T] {
+        if let Storage::Paged(data) = self {
+            let pixels = data
+                .pages
+                .iter()
+                .enumerate()
+                .flat_map(|(page, pixels)| match pixels {
+                    Some(pixels) => pixels.to_vec(),
+                    None => vec![data.empty; data.page_len(page)],
+                })
+                .collect();
+            *self = Storage::Dense(Buffer::Heap(pixels));
+        }
+
+        match self {
+            Storage::Dense(data) => data,
+            Storage::Paged(_) => unreachable!(),
+        }
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = &T> + '_> {
+        match self {
+            Storage::Dense(data) => Box::new(data.iter()),
+            Storage::Paged(data) => Box::new(data.pages.iter().enumerate().flat_map(move |(page, pixels)| {
+                (0..data.page_len(page)).map(move |i| pixels.as_ref().map_or(&data.empty, |pixels| &pixels[i]))
+            })),
+        }
+    }
+
+    fn into_vec(mut self) -> Vec<T> {
+        self.dense();
+        match self {
+            Storage::Dense(data) => data.into_vec(),
+            Storage::Paged(_) => unreachable!(),
+        }
+    }
+}
+
+/// The pages of a sparse image, of which only those that were written to are allocated.
+#[derive(Debug, Clone)]
+struct Pages<T> {
+    pages: Vec<Option<Box<[T]>>>,
+    /// The number of allocated pages.
+    allocated: usize,
+    /// The number of pixels of every page together.
+    len: usize,
+    empty: T,
+}
+
+impl<T> Pages<T> {
+    /// Gets the number of pixels of a page, which is less than a whole page for the last one.
+    #[inline]
+    fn page_len(&self, page: usize) -> usize {
+        PAGE_SIZE.min(self.len - page * PAGE_SIZE)
+    }
+}
+
+/// The storage of the pixels of an image, either on the heap or in a memory-mapped file.
+pub enum Buffer<T> {
+    Heap(Vec<T>),
+    Mapped(MappedBuffer<T>),
+}
+
+impl<T: Clone> Buffer<T> {
+    /// Moves every value out of the buffer, copying them out of the file of a mapped buffer.
+    pub fn into_vec(self) -> Vec<T> {
+        match self {
+            Buffer::Heap(data) => data,
+            Buffer::Mapped(data) => data.to_vec(),
+        }
+    }
+}
+
+impl<T> Deref for Buffer<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &[T] {
+        match self {
+            Buffer::Heap(data) => data,
+            Buffer::Mapped(data) => data,
+        }
+    }
+}
+
+impl<T> DerefMut for Buffer<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [T] {
+        match self {
+            Buffer::Heap(data) => data,
+            Buffer::Mapped(data) => data,
+        }
+    }
+}
+
+/// Cloning a buffer always clones it onto the heap, leaving the file of a mapped buffer to it alone.
+impl<T: Clone> Clone for Buffer<T> {
+    fn clone(&self) -> Self {
+        Buffer::Heap(self.to_vec())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Buffer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// A fixed number of values stored in a file that is mapped into memory and shared with it, so
+/// every write lands in the file.
+pub struct MappedBuffer<T> {
+    ptr: NonNull<T>,
+    len: usize,
+    _file: File,
+    _marker: PhantomData<T>,
+}
+
+// The buffer owns its values just like a `Vec` does
+unsafe impl<T: Send> Send for MappedBuffer<T> {}
+unsafe impl<T: Sync> Sync for MappedBuffer<T> {}
+
+impl<T: Zeroed> MappedBuffer<T> {
+    /// Creates a buffer of `len` zeroed values in the file at `path`, replacing the file if it
+    /// exists.
+    ///
+    /// # Safety
+    ///
+    /// The mapping is shared with the file, so nothing else, in this process or any other, may
+    /// write to, truncate or map the file at `path` while the buffer is alive.
+    pub unsafe fn create(path: impl AsRef<Path>, len: usize) -> io::Result<MappedBuffer<T>> {
+        let bytes = Self::bytes(len)?;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(bytes as u64)?;
+        Self::map(file, len)
+    }
+
+    /// Maps the values stored in the file at `path` by a buffer created with
+    /// [`create`](Self::create).
+    ///
+    /// # Safety
+    ///
+    /// The same as for [`create`](Self::create): nothing else may write to, truncate or map the
+    /// file at `path` while the buffer is alive.
+    pub unsafe fn open(path: impl AsRef<Path>) -> io::Result<MappedBuffer<T>> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let bytes = file.metadata()?.len() as usize;
+        if !bytes.is_multiple_of(mem::size_of::<T>()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the file doesn't hold a whole number of values",
+            ));
+        }
+
+        Self::map(file, bytes / mem::size_of::<T>())
+    }
+
+    /// The number of bytes `len` values take up, if it fits in the address space.
+    fn bytes(len: usize) -> io::Result<usize> {
+        len.checked_mul(mem::size_of::<T>())
+            .filter(|&bytes| bytes <= isize::MAX as usize)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "the buffer is too large to map"))
+    }
+
+    #[cfg(unix)]
+    fn map(file: File, len: usize) -> io::Result<MappedBuffer<T>> {
+        use std::os::fd::AsRawFd;
+
+        let bytes = Self::bytes(len)?;
+        let ptr = if bytes == 0 {
+            NonNull::dangling()
+        } else {
+            // The mapping is page aligned, which is aligned enough for any `T`
+            let ptr = unsafe {
+                libc::mmap(
+                    std::ptr::null_mut(),
+                    bytes,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_SHARED,
+                    file.as_raw_fd(),
+                    0,
+                )
+            };
+            if ptr == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+            NonNull::new(ptr as *mut T).unwrap()
+        };
+
+        Ok(Self {
+            ptr,
+            len,
+            _file: file,
+            _marker: PhantomData,
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn map(_file: File, _len: usize) -> io::Result<MappedBuffer<T>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "memory-mapped images are only supported on unix",
+        ))
+    }
+}
+
+impl<T> MappedBuffer<T> {
+    /// Writes every changed value to the file, waiting until they reach the disk.
+    pub fn flush(&self) -> io::Result<()> {
+        #[cfg(unix)]
+        if self.len != 0 {
+            let result =
+                unsafe { libc::msync(self.ptr.as_ptr().cast(), self.len * mem::size_of::<T>(), libc::MS_SYNC) };
+            if result != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> Deref for MappedBuffer<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &[T] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T> DerefMut for MappedBuffer<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T> Drop for MappedBuffer<T> {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if self.len != 0 {
+            unsafe { libc::munmap(self.ptr.as_ptr().cast(), self.len * mem::size_of::<T>()) };
+        }
+    }
+}
+
 pub struct Pixels<'a, T: Color> {
-    iter: Iter<'a, T>,
+    iter: Box<dyn Iterator<Item = &'a T> + 'a>,
 }
 
 impl<'a, T: Color> Iterator for Pixels<'a, T> {
@@ -160,7 +743,7 @@ pub struct EnumeratePixels<'a, T: Color> {
     size: usize,
     width: usize,
     index: usize,
-    iter: Iter<'a, T>,
+    iter: Box<dyn Iterator<Item = &'a T> + 'a>,
 }
 
 impl<'a, T: Color> Iterator for EnumeratePixels<'a, T> {
@@ -0,0 +1,35 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether the process was asked to stop.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// The exit code of a process stopped by `SIGINT`, which shells report as being interrupted.
+pub const EXIT_CODE: i32 = 130;
+
+/// Catches `SIGINT` and `SIGTERM`, so that the first of them stops sampling at the end of the work
+/// items being drawn rather than killing the process and losing every hit drawn so far. A second
+/// signal exits right away, for when writing what was sampled is taking too long.
+///
+/// Signals can only be caught on Unix, so this does nothing elsewhere.
+pub fn install() {
+    #[cfg(unix)]
+    unsafe {
+        let handler = handle as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+/// Whether a signal asked the process to stop, after which no more samples should be drawn.
+#[inline]
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+#[cfg(unix)]
+extern "C" fn handle(_: libc::c_int) {
+    // Only async-signal-safe calls may be made here, which rules out printing
+    if INTERRUPTED.swap(true, Ordering::Relaxed) {
+        unsafe { libc::_exit(EXIT_CODE) };
+    }
+}
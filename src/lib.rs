@@ -1,4 +1,46 @@
+pub mod atomic;
+pub mod bands;
+pub mod bench;
+pub mod canvas;
+pub mod checkpoint;
 pub mod color;
 pub mod complex;
+pub mod contour;
+pub mod deep;
+pub mod diff;
+pub mod dither;
+pub mod dynamics;
+pub mod farm;
+pub mod formula;
+pub mod fractal;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+#[cfg(feature = "hdf5")]
+pub mod hdf5;
+pub mod histogram;
+pub mod hypercomplex;
 pub mod images;
+pub mod interrupt;
+pub mod manifest;
+pub mod memory;
+pub mod metadata;
+pub mod metropolis;
+pub mod noise;
+pub mod numa;
+pub mod output;
+pub mod points;
+pub mod profile;
+pub mod pyramid;
+pub mod quaternion;
+pub mod rotation;
 pub mod sample;
+pub mod scene;
+pub mod shared;
+pub mod simd;
+pub mod throttle;
+pub mod tiles;
+pub mod tonemap;
+pub mod transform;
+pub mod trap;
+pub mod tune;
+pub mod volume;
@@ -1,18 +1,92 @@
 use clap::{error::ErrorKind, CommandFactory, Parser, Subcommand, ValueEnum};
-use image::GenericImageView;
+use exr::prelude::{SpecificChannels, Text, Vec2, WritableImage};
+use image::{
+    codecs::{
+        avif::AvifEncoder,
+        gif::{GifEncoder, Repeat},
+        hdr::HdrEncoder,
+        webp::WebPEncoder,
+    },
+    Delay, DynamicImage, Frame, GenericImageView,
+};
 use std::{
-    path::PathBuf,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, IsTerminal, Read, Seek, Write},
+    mem,
+    path::{Path, PathBuf},
     str::FromStr,
-    sync::{Arc, Mutex},
+    sync::{atomic::AtomicU32, Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tiff::{
+    decoder::{Decoder, DecodingResult, Limits},
+    encoder::{colortype, TiffEncoder, TiffValue},
+    TiffError, TiffResult, TiffUnsupportedError,
 };
 
 use buddhabrot::{
-    color::{Color, Float, Rgb},
+    atomic::AtomicFile,
+    bands::{self, sample_banded, BandSpacing, BandedImage, EscapeBands},
+    bench::{compare_simd_levels, KernelSamples, Workload, WORKLOADS},
+    canvas::{Canvas, Mobius, OriginsWeight, Region, Viewport},
+    checkpoint::Checkpoint,
+    color::{srgb_decode, srgb_encode, Color, ColorChannel, Count, Rg, Rgb},
     complex::Complex,
+    contour,
+    deep::{self, sample_deep, Decimal, DeepZoom},
+    diff::Difference,
+    dither::{Dither, Quantizer},
+    formula::Formula,
+    fractal::{ChannelFractals, Fractal},
+    histogram::{self, load_histogram, Parameters, RawFormat, TableFormat},
+    hypercomplex::{sample_quaternion, QuaternionPlane, QuaternionSlice},
     images::Image,
-    sample::sample,
+    interrupt,
+    manifest::Manifest,
+    memory::{available_memory, format_bytes, parse_bytes, peak_memory, MemoryEstimate},
+    metadata,
+    metropolis::MetropolisSettings,
+    noise::{FrameNoise, NoiseCorrelation},
+    numa::{self, Topology},
+    output::OutputQueue,
+    points::{sample_points, PointFormat},
+    profile::Profiler,
+    pyramid::Pyramid,
+    rotation::{Axis, Rotation},
+    sample::{
+        sample_canvas, sample_canvas_with, ConfigError, Nebulabrot, OrbitWeight, Orbits, Precision, Projection,
+        SampleCounts, SampleSettings, SampleSlice, SampleStats, Sampler,
+    },
+    scene::{Scene, SCENES},
+    shared::{self, countable, sample_shared, SharedImage},
+    tiles::TileGrid,
+    tonemap::Tonemap,
+    transform::{Transform, Transforms},
+    trap::{OrbitTrap, Trap},
+    tune::{tune_iterations, TuneReport, TuneSettings},
+    volume::{sample_volume, Volume, VolumeFormat, VolumeView},
 };
+#[cfg(feature = "gpu")]
+use buddhabrot::{
+    bench::compare_accelerators,
+    gpu::{self, sample_accelerated, Accelerator, CpuSampler},
+};
+
+/// The largest number of samples per pixel taken by the low-memory profile.
+const LOW_MEMORY_SAMPLES: u32 = 16;
+
+/// The number of slices the samples of a checkpointed render are split into by default.
+const CHECKPOINT_SLICES: u32 = 16;
 
+/// The number of slices the samples of a render saved with `--autosave` are split into by default,
+/// enough for slices of even a multi-day render to end every few minutes.
+const AUTOSAVE_SLICES: u32 = 256;
+
+/// The smallest tiles `--max-memory` splits a render into, below which the cost of sampling every
+/// orbit once per tile outweighs the memory saved.
+const MIN_TILE_SIZE: u32 = 64;
+
+#[profiling::function]
 fn normalize_im<T: Color + Clone + Copy + Send + Sync + 'static>(im: &mut Image<T>) {
     let mut max = T::empty();
     for pixel in im.pixels() {
@@ -24,6 +98,7 @@ fn normalize_im<T: Color + Clone + Copy + Send + Sync + 'static>(im: &mut Image<
     }
 }
 
+#[profiling::function]
 fn reflect_im<T: Color + Clone + Copy>(im: &mut Image<T>) {
     for i in 0..im.size / 2 {
         let x = i % im.width;
@@ -35,6 +110,7 @@ fn reflect_im<T: Color + Clone + Copy>(im: &mut Image<T>) {
     }
 }
 
+#[profiling::function]
 fn rotate_im<T: Color + Clone + Copy>(im: &mut Image<T>) {
     for i in 0..im.size {
         let x = i % im.width;
@@ -55,6 +131,314 @@ fn fuse(im1: Image<f32>, im2: Image<f32>, im3: Image<f32>) -> Image<Rgb> {
     im
 }
 
+/// An iteration rendered instead of the fractal of the sample settings.
+enum CustomIteration {
+    Formula(Formula),
+    Quaternion(QuaternionSlice),
+    Deep(DeepZoom),
+    #[cfg(feature = "gpu")]
+    Gpu(Vec<Arc<dyn Accelerator>>),
+}
+
+impl CustomIteration {
+    /// Gets the option that selected this iteration.
+    fn flag(&self) -> &'static str {
+        match self {
+            CustomIteration::Formula(_) => "--formula",
+            CustomIteration::Quaternion(_) => "--quaternion",
+            CustomIteration::Deep(_) => "--precision arbitrary",
+            #[cfg(feature = "gpu")]
+            CustomIteration::Gpu(_) => "--gpu",
+        }
+    }
+}
+
+/// Renders a canvas, iterating `custom` instead of the settings' fractal when one is given.
+fn render_canvas<T: Color + Clone + Copy + Send + Sync + 'static>(
+    canvas: Canvas<T>,
+    settings: SampleSettings,
+    custom: Option<&CustomIteration>,
+) -> Canvas<T> {
+    let canvas = Arc::new(Mutex::new(canvas));
+    let stats = match custom {
+        Some(CustomIteration::Formula(formula)) => sample_canvas_with(canvas.clone(), settings, formula.clone()),
+        Some(CustomIteration::Quaternion(slice)) => sample_quaternion(canvas.clone(), settings, *slice),
+        Some(CustomIteration::Deep(zoom)) => sample_deep(canvas.clone(), settings, zoom),
+        #[cfg(feature = "gpu")]
+        Some(CustomIteration::Gpu(gpu)) => {
+            sample_accelerated(canvas.clone(), settings, gpu).expect("the image was checked to fit on the GPU")
+        },
+        None => sample_canvas(canvas.clone(), settings),
+    };
+    print_sample_stats(&stats);
+    Arc::try_unwrap(canvas).ok().unwrap().into_inner().unwrap().linear()
+}
+
+/// How the images of a render accumulate deposits.
+#[derive(Clone, Copy, Debug)]
+struct Accumulation {
+    /// Whether images are [`sparse`](Image::sparse).
+    sparse: bool,
+    /// Whether hits are counted exactly in integers rather than summed in floats.
+    exact: bool,
+    /// Whether images are stored in [tiled](buddhabrot::images::Layout::Tiled) blocks while they're rendered.
+    tiled: bool,
+}
+
+/// Creates the blank `width` by `width` image a render accumulates into.
+fn blank_image<T: Color + Clone + Copy>(width: usize, accumulation: Accumulation) -> Image<T> {
+    let image = if accumulation.sparse {
+        Image::sparse(width * width, width)
+    } else {
+        Image::new(width * width, width)
+    };
+
+    if accumulation.tiled {
+        image.tiled()
+    } else {
+        image
+    }
+}
+
+/// Creates the blank canvas a render accumulates into, along with a `locator` pixel wide view of
+/// the full set and the origins of orbits weighted by `origins` when requested.
+fn blank_canvas<T: Color + Clone + Copy>(
+    width: usize,
+    accumulation: Accumulation,
+    locator: Option<usize>,
+    origins: Option<OriginsWeight>,
+) -> Canvas<T> {
+    let mut canvas = Canvas::new(blank_image(width, accumulation));
+    if let Some(size) = locator {
+        canvas = canvas.with_layer(Viewport::default(), size);
+    }
+    if let Some(weight) = origins {
+        canvas = canvas.with_origins(weight, Viewport::default(), width);
+    }
+    canvas
+}
+
+/// Converts an exact count of hits into a float, which may round it but never drops hits.
+fn counted(count: Count) -> f32 {
+    count.0 as f32
+}
+
+fn render_channel(
+    settings: SampleSettings,
+    custom: Option<&CustomIteration>,
+    im_width: usize,
+    accumulation: Accumulation,
+) -> Image<f32> {
+    if accumulation.exact {
+        let canvas = Canvas::new(blank_image::<Count>(im_width, accumulation));
+        return render_canvas(canvas, settings, custom).main.convert(counted);
+    }
+
+    render_canvas(Canvas::new(blank_image(im_width, accumulation)), settings, custom).main
+}
+
+/// The first channel of a render along with the additional images accumulated from its orbits.
+struct Primary {
+    image: Image<f32>,
+    /// The per-pixel total arc length of every orbit passing through each pixel.
+    arc: Option<Image<f32>>,
+    /// The view of the full set.
+    locator: Option<Image<Rgb>>,
+    /// The origins of orbits, weighted by their visits to the target region or to the image.
+    origins: Option<Image<Rgb>>,
+}
+
+/// Renders the first channel, additionally accumulating the arc length of orbits, a `locator` pixel
+/// wide view of the full set and the origins of orbits weighted by `origins` when requested.
+fn render_primary(
+    settings: SampleSettings,
+    custom: Option<&CustomIteration>,
+    im_width: usize,
+    accumulation: Accumulation,
+    arc_length: bool,
+    locator: Option<usize>,
+    origins: Option<OriginsWeight>,
+) -> Primary {
+    if arc_length {
+        let canvas = render_canvas(
+            blank_canvas::<Rg>(im_width, accumulation, locator, origins),
+            SampleSettings {
+                arc_length_channel: Some(ColorChannel::Green),
+                ..settings
+            },
+            custom,
+        );
+
+        let mut hits = Image::<f32>::new(im_width * im_width, im_width);
+        let mut arc = Image::<f32>::new(im_width * im_width, im_width);
+        for (x, y, px) in canvas.main.into_enumerate_pixels() {
+            hits.set((x, y), px.r);
+            arc.set((x, y), px.g);
+        }
+
+        let gray = |image: Image<Rg>| {
+            let mut im = Image::<Rgb>::new(image.size, image.width);
+            for (x, y, px) in image.into_enumerate_pixels() {
+                im.set((x, y), Rgb::new(px.r, px.r, px.r));
+            }
+            im
+        };
+        let locator = canvas.layers.into_iter().next().map(|layer| gray(layer.image));
+        let origins = canvas.origins.map(|origins| gray(origins.image));
+
+        Primary {
+            image: hits,
+            arc: Some(arc),
+            locator,
+            origins,
+        }
+    } else {
+        let canvas = if accumulation.exact {
+            let canvas = blank_canvas::<Count>(im_width, accumulation, locator, origins);
+            render_canvas(canvas, settings, custom).convert(counted)
+        } else {
+            render_canvas(
+                blank_canvas::<f32>(im_width, accumulation, locator, origins),
+                settings,
+                custom,
+            )
+        };
+        let gray = |image: Image<f32>| fuse(image.clone(), image.clone(), image);
+        let locator = canvas.layers.into_iter().next().map(|layer| gray(layer.image));
+        let origins = canvas.origins.map(|origins| gray(origins.image));
+
+        Primary {
+            image: canvas.main,
+            arc: None,
+            locator,
+            origins,
+        }
+    }
+}
+
+/// Marks the outline of `frame` on an image of the `view` viewport.
+fn mark_frame(im: &mut Image<Rgb>, view: Viewport, frame: Viewport, col: Rgb) {
+    let width = im.width;
+    let height = im.size / im.width;
+    let clamp = |p: Complex<f64>| {
+        let p = (p - view.center) / view.scale * 0.25 + 0.5;
+        (
+            ((p.re * width as f64) as i64).clamp(0, width as i64 - 1) as usize,
+            ((p.im * height as f64) as i64).clamp(0, height as i64 - 1) as usize,
+        )
+    };
+
+    let (x0, y0) = clamp(frame.center - Complex::new(2.0, 2.0) * frame.scale);
+    let (x1, y1) = clamp(frame.center + Complex::new(2.0, 2.0) * frame.scale);
+
+    for x in x0..=x1 {
+        im.set((x, y0), col);
+        im.set((x, y1), col);
+    }
+
+    for y in y0..=y1 {
+        im.set((x0, y), col);
+        im.set((x1, y), col);
+    }
+}
+
+/// Pastes a normalized `inset` into the bottom right corner of `im` with a border, scaling it to the
+/// brightness of `im`.
+fn paste_inset(im: &mut Image<Rgb>, inset: Image<Rgb>) {
+    let mut max = Rgb::empty();
+    for pixel in im.pixels() {
+        max = max.max(*pixel);
+    }
+
+    let width = im.width;
+    let height = im.size / im.width;
+    let inset_height = inset.size / inset.width;
+    if inset.width + 2 > width || inset_height + 2 > height {
+        return;
+    }
+
+    let (x0, y0) = (width - inset.width - 1, height - inset_height - 1);
+    for x in x0 - 1..width {
+        im.set((x, y0 - 1), max);
+    }
+    for y in y0 - 1..height {
+        im.set((x0 - 1, y), max);
+    }
+
+    for (x, y, px) in inset.into_enumerate_pixels() {
+        im.set((x0 + x, y0 + y), Rgb::new(px.r * max.r, px.g * max.g, px.b * max.b));
+    }
+}
+
+fn print_sample_stats(stats: &SampleStats) {
+    if !stats.threads.is_empty() {
+        println!(
+            "{:>8} {:>14} {:>14} {:>9} {:>16} {:>16} {:>9}",
+            "thread", "samples", "escaped", "", "plotted", "culled", "in frame"
+        );
+        let row = |name: &str, counts: SampleCounts| {
+            let percent = |part: u64, whole: u64| part as f64 / whole.max(1) as f64 * 100.0;
+            println!(
+                "{:>8} {:>14} {:>14} {:>8.2}% {:>16} {:>16} {:>8.2}%",
+                name,
+                counts.samples,
+                counts.escaped,
+                percent(counts.escaped, counts.samples),
+                counts.plotted,
+                counts.culled,
+                percent(counts.plotted, counts.plotted + counts.culled),
+            );
+        };
+        for (thread, &counts) in stats.threads.iter().enumerate() {
+            row(&thread.to_string(), counts);
+        }
+        row("total", stats.counts());
+    }
+
+    if let Some(metropolis) = stats.metropolis {
+        println!(
+            "Metropolis acceptance rate: {:.2}% over {} chains (min {:.2}%, max {:.2}%).",
+            metropolis.acceptance_rate() * 100.0,
+            metropolis.chains,
+            metropolis.min_acceptance * 100.0,
+            metropolis.max_acceptance * 100.0,
+        );
+    }
+
+    if let Some(deep) = stats.deep {
+        println!(
+            "Perturbation glitched on {:.2}% of {} samples, which were iterated exactly instead.",
+            deep.glitch_rate() * 100.0,
+            deep.samples,
+        );
+
+        if deep.skipped > 0 {
+            println!(
+                "Series approximation skipped the first {} iterations of every perturbed orbit.",
+                deep.skipped
+            );
+        }
+    }
+}
+
+fn print_tune_report(report: &TuneReport) {
+    println!("{:>10} {:>10} {:>10} {:>14}", "n", "escaped", "detail", "productivity");
+    for s in report.samples.iter() {
+        println!(
+            "{:>10} {:>9.2}% {:>9.2}% {:>14.4}{}",
+            s.n,
+            s.escaped * 100.0,
+            s.detail * 100.0,
+            s.productivity,
+            if s.n == report.recommended {
+                "  <- recommended"
+            } else {
+                ""
+            }
+        );
+    }
+}
+
 fn parse_color(s: &str) -> Result<(f32, f32, f32), String> {
     let e = format!("{} is not a valid rgb color", s);
     if s.starts_with('#') {
@@ -71,7 +455,7 @@ fn parse_color(s: &str) -> Result<(f32, f32, f32), String> {
 
         let mut v = v.split(' ').map(|s| {
             let bytes = u8::from_str_radix(s, 16).unwrap();
-            bytes as f32 / 255.0
+            from_srgb8(bytes)
         });
 
         Ok((
@@ -89,22 +473,613 @@ fn parse_color(s: &str) -> Result<(f32, f32, f32), String> {
     }
 }
 
-fn write_rgb(im: Image<Rgb>, mut file: PathBuf, png: bool) {
-    if png {
-        file.set_extension("png");
-        let mut imgbuf = image::ImageBuffer::new(im.width as u32, im.width as u32);
+/// Writes one to three single-channel images as the red, green, and blue channels of a file, one row
+/// at a time. A single image is written in grayscale.
+fn write_channels(ims: &[Image<f32>], file: PathBuf, format: OutputFormat, dither: Dither, parameters: &Parameters) {
+    let width = ims[0].width;
+    write_pixels(
+        width,
+        ims[0].size / width,
+        channel_colors(ims),
+        file,
+        format,
+        dither,
+        parameters,
+    );
+}
+
+/// Gets the color of every pixel of one to three single-channel images, as the red, green and blue
+/// channels of a color. A single image is gray.
+fn channel_colors(ims: &[Image<f32>]) -> impl Fn(usize, usize) -> (f32, f32, f32) + Sync + '_ {
+    move |x, y| match ims {
+        [r] => (r.get((x, y)), r.get((x, y)), r.get((x, y))),
+        [r, g] => (r.get((x, y)), g.get((x, y)), 0.0),
+        [r, g, b, ..] => (r.get((x, y)), g.get((x, y)), b.get((x, y))),
+        [] => unreachable!(),
+    }
+}
+
+/// The number of pixels every tile of a Deep Zoom pyramid shares with its neighbours.
+const DZI_OVERLAP: usize = 1;
+
+/// Writes the pyramid of the `width` by `height` image of the colors `pixel` gives every pixel as a
+/// Deep Zoom image next to `file`, of PNG tiles `tile_size` pixels wide, replacing the tiles of any
+/// pyramid already there.
+#[profiling::function]
+fn write_pyramid(
+    width: usize,
+    height: usize,
+    pixel: impl Fn(usize, usize) -> (f32, f32, f32),
+    file: &Path,
+    tile_size: usize,
+    dither: Dither,
+) {
+    let mut im = Image::<Rgb>::new(width * height, width);
+    for (x, y, px) in im.enumerate_pixels_mut() {
+        *px = pixel(x, y).into();
+    }
+
+    let tiles_dir = Pyramid::tiles_dir(file);
+    if tiles_dir.exists() {
+        fs::remove_dir_all(tiles_dir).unwrap();
+    }
+
+    let pyramid = Pyramid {
+        tile_size,
+        overlap: DZI_OVERLAP,
+    };
+    let format = OutputFormat::Png;
+    pyramid
+        .write(file, im, format.extension(), |tile, path| {
+            write_rgb(tile, path, format, dither, &Parameters::new())
+        })
+        .unwrap();
+}
+
+/// Writes the raw values of the channels of a low-memory render, which are named like the channels
+/// of a color with as many of them.
+fn write_raw_channels(ims: &[Image<f32>], file: PathBuf, format: RawFormat, parameters: &Parameters) -> io::Result<()> {
+    let (width, height) = (ims[0].width, ims[0].size / ims[0].width);
+    match ims {
+        [y] => format.write(file, width, height, parameters, |px, py| y.get((px, py))),
+        [r, g] => format.write(file, width, height, parameters, |x, y| {
+            Rg::new(r.get((x, y)), g.get((x, y)))
+        }),
+        [r, g, b, ..] => format.write(file, width, height, parameters, |x, y| {
+            Rgb::new(r.get((x, y)), g.get((x, y)), b.get((x, y)))
+        }),
+        [] => unreachable!(),
+    }
+}
+
+/// Reads a histogram file of one, two or three channels into an RGB image.
+fn read_histogram(file: &PathBuf) -> io::Result<Image<Rgb>> {
+    let header = histogram::read_header(&mut histogram::open_histogram(file)?)?;
+    if header.parameters.contains_key(bands::EDGES_PARAMETER) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "histogram holds {} bands of escape times, which need coloring with `recolor` first",
+                header.channels.len()
+            ),
+        ));
+    }
+
+    Ok(match header.channels.len() {
+        1 => load_histogram::<f32>(file)?.0.convert(|px| px.to_tuple_rgb().into()),
+        2 => load_histogram::<Rg>(file)?.0.convert(|px| px.to_tuple_rgb().into()),
+        _ => load_histogram::<Rgb>(file)?.0,
+    })
+}
+
+/// Creates an encoder of an 8-bit RGB PNG tagged as sRGB, which stores the `parameters` it was
+/// rendered with in text chunks.
+fn png_encoder<W: Write>(out: W, width: usize, height: usize, parameters: &Parameters) -> png::Encoder<'static, W> {
+    let mut encoder = png::Encoder::new(out, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+
+    // The gamma and chromaticities of sRGB, for viewers that don't read the sRGB chunk
+    encoder.set_source_gamma(png::ScaledFloat::from_scaled(45455));
+    let point = |x, y| (png::ScaledFloat::from_scaled(x), png::ScaledFloat::from_scaled(y));
+    encoder.set_source_chromaticities(png::SourceChromaticities {
+        white: point(31270, 32900),
+        red: point(64000, 33000),
+        green: point(30000, 60000),
+        blue: point(15000, 6000),
+    });
+    encoder
+        .add_text_chunk("Software".to_owned(), metadata::SOFTWARE.to_owned())
+        .unwrap();
+    for (name, value) in metadata::entries(parameters) {
+        encoder.add_text_chunk(name, value.to_owned()).unwrap();
+    }
+    encoder
+}
+
+/// Writes the image of the colors `pixel` gives every pixel, one row after another, storing the
+/// `parameters` it was rendered with in formats that have room for them and rounding values to the
+/// levels of 8-bit formats with `dither`.
+#[profiling::function]
+fn write_pixels(
+    width: usize,
+    height: usize,
+    pixel: impl Fn(usize, usize) -> (f32, f32, f32) + Sync,
+    mut file: PathBuf,
+    format: OutputFormat,
+    dither: Dither,
+    parameters: &Parameters,
+) {
+    file.set_extension(format.extension());
+    if format == OutputFormat::Png {
+        let mut out = AtomicFile::create(file).unwrap();
+        let mut writer = png_encoder(&mut out, width, height, parameters).write_header().unwrap();
+        let mut stream = writer.stream_writer().unwrap();
+
+        let mut quantizer = Quantizer::new(dither, width, 3);
+        let mut row = vec![0; width * 3];
+        for y in 0..height {
+            quantizer.row(&srgb_row(width, y, &pixel), &mut row);
+            stream.write_all(&row).unwrap();
+        }
+
+        stream.finish().unwrap();
+        writer.finish().unwrap();
+        out.commit().unwrap();
+    } else if format == OutputFormat::Hdr {
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let (r, g, b) = pixel(x, y);
+                image::Rgb([r, g, b])
+            })
+            .collect::<Vec<_>>();
+        let mut out = AtomicFile::create(file).unwrap();
+        HdrEncoder::new(&mut out).encode(&pixels, width, height).unwrap();
+        out.commit().unwrap();
+    } else if let OutputFormat::Tiff(depth) = format {
+        write_tiff(width, height, pixel, depth, file).unwrap();
+    } else if format == OutputFormat::Pfm {
+        write_pfm(width, height, pixel, file).unwrap();
+    } else if format.clamps() {
+        write_quantized(quantize(width, height, pixel, dither), file, format, parameters);
+    } else {
+        let channels = SpecificChannels::rgb(|Vec2(x, y)| pixel(x, y));
+        let mut image = exr::image::Image::from_channels((width, height), channels);
+        image.layer_data.attributes.software_name = Some(Text::from(metadata::SOFTWARE));
+        image.layer_data.attributes.other = metadata::exr_attributes(parameters);
+
+        let mut out = AtomicFile::create(file).unwrap();
+        image.write().to_buffered(&mut out).unwrap();
+        out.commit().unwrap();
+    }
+}
+
+/// Writes the image of the colors `pixel` gives every pixel to a TIFF file, or to a BigTIFF file
+/// if its samples don't fit in the 4 GiB a TIFF file can address.
+fn write_tiff(
+    width: usize,
+    height: usize,
+    pixel: impl Fn(usize, usize) -> (f32, f32, f32),
+    depth: TiffDepth,
+    file: PathBuf,
+) -> TiffResult<()> {
+    let samples = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .flat_map(|(x, y)| {
+            let (r, g, b) = pixel(x, y);
+            [r, g, b]
+        });
+    let mut out = AtomicFile::create(file)?;
+    let (width, height) = (u32::try_from(width)?, u32::try_from(height)?);
+
+    match depth {
+        TiffDepth::Integer => {
+            let samples = samples
+                .map(|v| (srgb_encode(v) * u16::MAX as f32).round() as u16)
+                .collect::<Vec<_>>();
+            encode_tiff::<colortype::RGB16>(&mut out, width, height, &samples)?;
+        },
+        TiffDepth::Float => {
+            let samples = samples.collect::<Vec<_>>();
+            encode_tiff::<colortype::RGB32Float>(&mut out, width, height, &samples)?;
+        },
+    }
+
+    Ok(out.commit()?)
+}
+
+/// Reads an RGB or RGBA TIFF or BigTIFF file, with integer samples decoded from sRGB into linear
+/// values between 0-1 and float samples kept as they are.
+fn read_tiff(file: &PathBuf) -> io::Result<Image<Rgb>> {
+    decode_tiff(BufReader::new(File::open(file)?)).map_err(|e| match e {
+        TiffError::IoError(e) => e,
+        e => io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+    })
+}
+
+fn decode_tiff(input: impl Read + Seek) -> TiffResult<Image<Rgb>> {
+    let mut decoder = Decoder::new(input)?.with_limits(Limits::unlimited());
+    let (width, height) = decoder.dimensions()?;
+    let channels = match decoder.colortype()? {
+        tiff::ColorType::RGB(_) => 3,
+        tiff::ColorType::RGBA(_) => 4,
+        colortype => return Err(TiffUnsupportedError::UnsupportedColorType(colortype).into()),
+    };
+
+    let samples = match decoder.read_image()? {
+        DecodingResult::U8(samples) => samples.into_iter().map(from_srgb8).collect(),
+        DecodingResult::U16(samples) => samples
+            .into_iter()
+            .map(|v| srgb_decode(v as f32 / u16::MAX as f32))
+            .collect(),
+        DecodingResult::F32(samples) => samples,
+        _ => return Err(TiffUnsupportedError::UnsupportedDataType.into()),
+    };
+
+    let mut im = Image::<Rgb>::new(width as usize * height as usize, width as usize);
+    for ((_, _, px), c) in im.enumerate_pixels_mut().zip(samples.chunks_exact(channels)) {
+        *px = Rgb::new(c[0], c[1], c[2]);
+    }
+
+    Ok(im)
+}
+
+/// Writes the image of the colors `pixel` gives every pixel to a portable float map, a header
+/// followed by the little-endian floats of every row from the bottom of the image up.
+fn write_pfm(
+    width: usize,
+    height: usize,
+    pixel: impl Fn(usize, usize) -> (f32, f32, f32),
+    file: PathBuf,
+) -> io::Result<()> {
+    let mut file = AtomicFile::create(file)?;
+    // A negative scale marks the floats as little-endian
+    write!(file, "PF\n{} {}\n-1.0\n", width, height)?;
 
-        for (x, y, pixel) in imgbuf.enumerate_pixels_mut() {
-            let c = im.get((x as usize, y as usize)).map(|x| x * 255.0);
-            let v = c.to_tuple_rgb();
-            *pixel = image::Rgb([v.0 as u8, v.1 as u8, v.2 as u8])
+    for y in (0..height).rev() {
+        for x in 0..width {
+            let (r, g, b) = pixel(x, y);
+            for v in [r, g, b] {
+                file.write_all(&v.to_le_bytes())?;
+            }
         }
+    }
+
+    file.commit()
+}
+
+/// Reads a color or grayscale portable float map of either endianness.
+fn read_pfm(file: &PathBuf) -> io::Result<Image<Rgb>> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_owned());
+    let data = fs::read(file)?;
+
+    // The header is three lines of text: the kind of map, its dimensions and its scale
+    let mut header = data.splitn(4, |&b| b == b'\n');
+    let mut line = || {
+        let line = header.next().ok_or_else(|| invalid("truncated header"))?;
+        String::from_utf8(line.to_vec()).map_err(|_| invalid("header is not text"))
+    };
+    let channels = match line()?.trim() {
+        "PF" => 3,
+        "Pf" => 1,
+        _ => return Err(invalid("not a portable float map")),
+    };
+    let dimensions = line()?;
+    let (width, height) = dimensions
+        .split_whitespace()
+        .map(|v| v.parse::<usize>())
+        .collect::<Result<Vec<_>, _>>()
+        .ok()
+        .and_then(|v| Some((*v.first()?, *v.get(1)?)))
+        .filter(|&(width, height)| width > 0 && height > 0)
+        .ok_or_else(|| invalid("invalid dimensions"))?;
+    let scale = line()?.trim().parse::<f32>().map_err(|_| invalid("invalid scale"))?;
+    let body = header.next().unwrap_or_default();
+
+    let size = width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(channels * 4))
+        .ok_or_else(|| invalid("dimensions are too large"))?;
+    if body.len() < size {
+        return Err(invalid("truncated pixel data"));
+    }
+    let samples = body
+        .chunks_exact(4)
+        .map(|b| {
+            let bytes = [b[0], b[1], b[2], b[3]];
+            if scale < 0.0 {
+                f32::from_le_bytes(bytes)
+            } else {
+                f32::from_be_bytes(bytes)
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut im = Image::<Rgb>::new(width * height, width);
+    for (i, c) in samples.chunks_exact(channels).take(width * height).enumerate() {
+        let px = match *c {
+            [v] => Rgb::new(v, v, v),
+            [r, g, b] => Rgb::new(r, g, b),
+            _ => unreachable!(),
+        };
+        im.set((i % width, height - 1 - i / width), px);
+    }
+
+    Ok(im)
+}
+
+/// Writes `samples` as a TIFF image of color type `C`, switching to BigTIFF when they're too large.
+fn encode_tiff<C: colortype::ColorType>(
+    file: &mut AtomicFile,
+    width: u32,
+    height: u32,
+    samples: &[C::Inner],
+) -> TiffResult<()>
+where
+    [C::Inner]: TiffValue,
+{
+    // Leaves room for the tags and strip offsets written besides the samples
+    let big = mem::size_of_val(samples) as u64 > u32::MAX as u64 - (1 << 20);
+    if big {
+        TiffEncoder::new_big(file)?.write_image::<C>(width, height, samples)
+    } else {
+        TiffEncoder::new(file)?.write_image::<C>(width, height, samples)
+    }
+}
+
+/// Encodes a linear value as an 8-bit sRGB level, clamping it between 0-1.
+fn to_srgb8(v: f32) -> u8 {
+    (srgb_encode(v) * u8::MAX as f32).round() as u8
+}
+
+/// Decodes an 8-bit sRGB level into a linear value between 0-1.
+fn from_srgb8(v: u8) -> f32 {
+    srgb_decode(v as f32 / u8::MAX as f32)
+}
+
+/// Encodes the colors `pixel` gives every pixel of row `y` in sRGB, as interleaved samples.
+fn srgb_row(width: usize, y: usize, pixel: impl Fn(usize, usize) -> (f32, f32, f32)) -> Vec<f32> {
+    (0..width)
+        .flat_map(|x| {
+            let (r, g, b) = pixel(x, y);
+            [r, g, b]
+        })
+        .map(srgb_encode)
+        .collect()
+}
+
+/// Quantizes the colors `pixel` gives every pixel to 8 bits per channel in sRGB, clamping them
+/// between 0-1 and rounding them with `dither`.
+fn quantize(
+    width: usize,
+    height: usize,
+    pixel: impl Fn(usize, usize) -> (f32, f32, f32),
+    dither: Dither,
+) -> image::RgbImage {
+    let mut quantizer = Quantizer::new(dither, width, 3);
+    let mut im = image::RgbImage::new(width as u32, height as u32);
+    for (y, row) in im.chunks_exact_mut(width * 3).enumerate() {
+        quantizer.row(&srgb_row(width, y, &pixel), row);
+    }
+    im
+}
+
+/// Encodes a [quantized](quantize) image in one of the 8-bit formats, storing the `parameters` it
+/// was rendered with in a PNG.
+fn write_quantized(im: image::RgbImage, file: PathBuf, format: OutputFormat, parameters: &Parameters) {
+    let mut out = AtomicFile::create(file).unwrap();
+    match format {
+        OutputFormat::Webp => im.write_with_encoder(WebPEncoder::new_lossless(&mut out)).unwrap(),
+        OutputFormat::Avif(quality) => im
+            .write_with_encoder(AvifEncoder::new_with_speed_quality(&mut out, 4, quality))
+            .unwrap(),
+        _ => {
+            let (width, height) = (im.width() as usize, im.height() as usize);
+            let mut writer = png_encoder(&mut out, width, height, parameters).write_header().unwrap();
+            writer.write_image_data(&im).unwrap();
+            writer.finish().unwrap();
+        },
+    }
+    out.commit().unwrap();
+}
+
+/// Writes the red channel of an image as an indexed PNG, whose palette of 256 colors blends from
+/// `min` to `max` like `colorize-r` does. Indices are spaced evenly in sRGB rather than in linear
+/// light, so that dark values get as many colors as they do in a full color PNG.
+#[profiling::function]
+fn write_indexed(
+    im: Image<Rgb>,
+    min: (f32, f32, f32),
+    max: (f32, f32, f32),
+    mut file: PathBuf,
+    dither: Dither,
+    parameters: &Parameters,
+) {
+    file.set_extension(OutputFormat::Png.extension());
+    let lerp = |a: f32, b: f32, t: f32| to_srgb8(a + (b - a) * t);
+    let palette = (0..=u8::MAX)
+        .flat_map(|i| {
+            let t = from_srgb8(i);
+            [lerp(min.0, max.0, t), lerp(min.1, max.1, t), lerp(min.2, max.2, t)]
+        })
+        .collect::<Vec<_>>();
+
+    let height = im.size / im.width;
+    let mut out = AtomicFile::create(file).unwrap();
+    let mut encoder = png_encoder(&mut out, im.width, height, parameters);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_palette(palette);
+    let mut writer = encoder.write_header().unwrap();
+
+    let mut quantizer = Quantizer::new(dither, im.width, 1);
+    let mut indices = vec![0; im.width * height];
+    for (y, row) in indices.chunks_exact_mut(im.width).enumerate() {
+        let values = (0..im.width).map(|x| srgb_encode(im.get((x, y)).r)).collect::<Vec<_>>();
+        quantizer.row(&values, row);
+    }
+    writer.write_image_data(&indices).unwrap();
+    writer.finish().unwrap();
+    out.commit().unwrap();
+}
+
+/// The time every snapshot of a timelapse is shown for, in milliseconds.
+const TIMELAPSE_DELAY: u16 = 100;
+
+/// Encodes the [quantized](quantize) snapshots of a timelapse as an animation looping forever.
+#[profiling::function]
+fn write_timelapse(frames: Vec<image::RgbImage>, file: PathBuf, format: TimelapseFormat, parameters: &Parameters) {
+    let mut out = AtomicFile::create(file).unwrap();
+    match format {
+        TimelapseFormat::Apng => {
+            let (width, height) = frames[0].dimensions();
+            let mut encoder = png_encoder(&mut out, width as usize, height as usize, parameters);
+            encoder.set_animated(frames.len() as u32, 0).unwrap();
+            encoder.set_frame_delay(TIMELAPSE_DELAY, 1000).unwrap();
+
+            let mut writer = encoder.write_header().unwrap();
+            for frame in frames {
+                writer.write_image_data(&frame).unwrap();
+            }
+            writer.finish().unwrap();
+        },
+        TimelapseFormat::Gif => {
+            let mut encoder = GifEncoder::new(&mut out);
+            encoder.set_repeat(Repeat::Infinite).unwrap();
+            let delay = Delay::from_numer_denom_ms(TIMELAPSE_DELAY as u32, 1);
+            let frames = frames
+                .into_iter()
+                .map(|frame| Frame::from_parts(DynamicImage::ImageRgb8(frame).into_rgba8(), 0, 0, delay));
+            encoder.encode_frames(frames).unwrap();
+        },
+    }
+    out.commit().unwrap();
+}
 
-        imgbuf.save(file).unwrap();
+#[profiling::function]
+fn write_rgb(im: Image<Rgb>, mut file: PathBuf, format: OutputFormat, dither: Dither, parameters: &Parameters) {
+    file.set_extension(format.extension());
+    if format == OutputFormat::Hdr {
+        let pixels = im
+            .pixels()
+            .map(|&px| image::Rgb(px.to_tuple_rgb().into()))
+            .collect::<Vec<_>>();
+        let mut out = AtomicFile::create(file).unwrap();
+        HdrEncoder::new(&mut out)
+            .encode(&pixels, im.width, im.size / im.width)
+            .unwrap();
+        out.commit().unwrap();
+    } else if let OutputFormat::Tiff(depth) = format {
+        let pixel = |x, y| im.get((x, y)).to_tuple_rgb();
+        write_tiff(im.width, im.size / im.width, pixel, depth, file).unwrap();
+    } else if format == OutputFormat::Pfm {
+        let pixel = |x, y| im.get((x, y)).to_tuple_rgb();
+        write_pfm(im.width, im.size / im.width, pixel, file).unwrap();
+    } else if format.clamps() {
+        let pixel = |x, y| im.get((x, y)).to_tuple_rgb();
+        write_quantized(
+            quantize(im.width, im.size / im.width, pixel, dither),
+            file,
+            format,
+            parameters,
+        );
     } else {
-        file.set_extension("exr");
-        exr::image::write::write_rgb_file(file, im.width, im.width, |x, y| im.get((x, y)).to_tuple_rgb()).unwrap();
+        im.write_exr(file, parameters).unwrap();
+    }
+}
+
+/// Gets the path of a file written along with `file`, named after it with a suffix. Unlike setting
+/// the file name and then the extension, this keeps any dots within the name of `file`.
+fn suffixed(file: &Path, suffix: &str, extension: &str) -> PathBuf {
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+    file.with_file_name(format!("{}-{}.{}", stem, suffix, extension))
+}
+
+/// Asks a yes or no question on the terminal, taking an empty answer as `default`.
+fn confirm(question: &str, default: bool) -> io::Result<bool> {
+    print!("{} [{}] ", question, if default { "Y/n" } else { "y/N" });
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(match answer.trim().to_lowercase().as_str() {
+        "" => default,
+        answer => answer.starts_with('y'),
+    })
+}
+
+/// Fills in a file name template such as `bb_{center}_{scale}_{fractal}_{date}` with the values of
+/// the parameters named between braces, replacing the characters of values that can't be part of a
+/// file name, such as the slashes of a formula, with underscores.
+fn expand_template(template: &str, parameters: &Parameters) -> Result<String, String> {
+    let mut name = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("the file name {:?} has an unclosed {{", template))?;
+        let key = &rest[start + 1..start + end];
+        let value = parameters.get(key).ok_or_else(|| {
+            let keys = parameters.keys().map(String::as_str).collect::<Vec<_>>();
+            format!(
+                "unknown parameter {{{}}} in the file name. available parameters are: {}",
+                key,
+                keys.join(", ")
+            )
+        })?;
+
+        name.push_str(&rest[..start]);
+        name.extend(value.chars().map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_whitespace() || c.is_control() => '_',
+            c => c,
+        }));
+        rest = &rest[start + end + 1..];
     }
+
+    name.push_str(rest);
+    Ok(name)
+}
+
+/// Gets the current date and time in UTC, formatted as `YYYY-MM-DD` and `HHMMSS` for file names.
+fn utc_now() -> (String, String) {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, seconds) = ((seconds / 86400) as i64, seconds % 86400);
+
+    // Converts days since 1970-01-01 to a date of the proleptic gregorian calendar, in eras of 400
+    // years starting in March so that leap days come last
+    let days = days + 719468;
+    let (era, day_of_era) = (days.div_euclid(146097), days.rem_euclid(146097));
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    (
+        format!("{:04}-{:02}-{:02}", year, month, day),
+        format!("{:02}{:02}{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60),
+    )
+}
+
+/// Reads the parameters an image was rendered with from its metadata or from the header of a
+/// histogram file, which are empty for formats that don't store them.
+fn read_parameters(input_file: &PathBuf) -> Parameters {
+    let parameters = match input_file.extension().and_then(|extension| extension.to_str()) {
+        Some("exr") => metadata::read_exr(input_file).ok(),
+        Some("png") => metadata::read_png(input_file).ok(),
+        Some(histogram::EXTENSION) => histogram::open_histogram(input_file)
+            .and_then(|mut input| histogram::read_header(&mut input))
+            .map(|header| header.parameters)
+            .ok(),
+        _ => None,
+    };
+    parameters.unwrap_or_default()
 }
 
 fn load_image(input_file: &PathBuf) -> clap::error::Result<Image<Rgb>, clap::Error> {
@@ -117,24 +1092,45 @@ fn load_image(input_file: &PathBuf) -> clap::error::Result<Image<Rgb>, clap::Err
                     image.set((pos.x(), pos.y()), Rgb::new(r, g, b))
                 },
             )
-            .unwrap()
+            .unwrap_or_else(|e| unreadable(input_file, e))
             .layer_data
             .channel_data
             .pixels
-        } else if extension == "png" {
-            let png = image::open(input_file).unwrap();
+        } else if extension == "hdr" {
+            let hdr = image::open(input_file)
+                .unwrap_or_else(|e| unreadable(input_file, e))
+                .into_rgb32f();
+            let mut im = Image::<Rgb>::new((hdr.width() * hdr.height()) as usize, hdr.width() as usize);
+
+            for (x, y, px) in im.enumerate_pixels_mut() {
+                let c = hdr.get_pixel(x as u32, y as u32);
+                *px = Rgb::new(c.0[0], c.0[1], c.0[2]);
+            }
+
+            im
+        } else if extension == "tiff" || extension == "tif" {
+            read_tiff(input_file).unwrap_or_else(|e| unreadable(input_file, e))
+        } else if extension == "pfm" {
+            read_pfm(input_file).unwrap_or_else(|e| unreadable(input_file, e))
+        } else if extension == histogram::EXTENSION {
+            read_histogram(input_file).unwrap_or_else(|e| unreadable(input_file, e))
+        } else if extension == "png" || extension == "webp" {
+            let png = image::open(input_file).unwrap_or_else(|e| unreadable(input_file, e));
             let mut im = Image::<Rgb>::new((png.width() * png.height()) as usize, png.width() as usize);
 
             for (x, y, px) in im.enumerate_pixels_mut() {
                 let c = png.get_pixel(x as u32, y as u32);
-                *px = Rgb::new(c.0[0] as f32 / 255.0, c.0[1] as f32 / 255.0, c.0[2] as f32 / 255.0);
+                *px = Rgb::new(from_srgb8(c.0[0]), from_srgb8(c.0[1]), from_srgb8(c.0[2]));
             }
 
             im
         } else {
             let err = Cli::command().error(
                 ErrorKind::Io,
-                format!("file {:?} is invalid; expected either exr or png file", input_file),
+                format!(
+                    "file {:?} is invalid; expected an exr, hdr, tiff, pfm, png, webp or bhist file",
+                    input_file
+                ),
             );
             err.print()?;
             return Err(err);
@@ -142,18 +1138,50 @@ fn load_image(input_file: &PathBuf) -> clap::error::Result<Image<Rgb>, clap::Err
     } else {
         let err = Cli::command().error(
             ErrorKind::Io,
-            format!("file {:?} is invalid; expected either exr or png file", input_file),
+            format!(
+                "file {:?} is invalid; expected an exr, hdr, tiff, pfm, png, webp or bhist file",
+                input_file
+            ),
         );
         err.print()?;
         return Err(err);
     })
 }
 
+/// Exits with an error saying why `input_file` couldn't be read.
+fn unreadable(input_file: &Path, e: impl std::fmt::Display) -> ! {
+    Cli::command()
+        .error(ErrorKind::Io, format!("couldn't read {:?}: {}", input_file, e))
+        .exit()
+}
+
+/// Creates the error for settings that fail validation, saying which part of the render would be
+/// empty when that's why.
+fn invalid_settings(name: &str, e: ConfigError) -> clap::Error {
+    let message = if e.empties_image() {
+        format!("the {} would be empty: {}", name, e)
+    } else {
+        e.to_string()
+    };
+    Cli::command().error(ErrorKind::ValueValidation, message)
+}
+
 #[derive(Parser)]
 #[command(version, author, about)]
 pub struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Whether or not to pin one rendering thread to every CPU, keeping the image each thread
+    /// renders into in the memory of its NUMA node and merging the images of every node on that
+    /// node. Speeds up renders on machines with several sockets.
+    #[arg(long, global = true)]
+    numa: bool,
+
+    /// The number of rendering threads. Defaults to one per CPU the renderer may use, which
+    /// respects the CPU quota of a container.
+    #[arg(long, global = true)]
+    threads: Option<usize>,
 }
 
 #[derive(Subcommand)]
@@ -174,10 +1202,10 @@ enum Commands {
         #[arg(value_enum)]
         mode: ColorChannelMode,
 
-        #[arg(short, long, value_name = "PROGRESS_UPDATE")]
-        progress_update: Option<u32>,
-
-        /// The file to write the image to, excluding the extension.
+        /// The file to write the image to, excluding the extension. Parameters of the render named
+        /// between braces are filled in, such as `bb_{center}_{scale}_{fractal}_{date}`, along with
+        /// the `{date}` and `{time}` it started at and the `{width}` of the image. Characters that
+        /// can't be part of a file name are replaced with underscores.
         #[arg(short, long, value_name = "FILENAME", default_value = "buddhabrot")]
         file: PathBuf,
 
@@ -185,50 +1213,709 @@ enum Commands {
         #[arg(short, long)]
         overwrite: bool,
 
-        #[arg(short, long, default_value = "1")]
-        scale: f32,
+        /// The scale of the region sampled and viewed. Defaults to the recommended view of the fractal.
+        #[arg(short, long)]
+        scale: Option<f64>,
+
+        /// The center of the region sampled and viewed. Defaults to the recommended view of the
+        /// fractal. Every digit is kept for arbitrary precision renders.
+        #[arg(short, long, value_parser = parse_complex::<Decimal>)]
+        center: Option<Complex<Decimal>>,
 
-        #[arg(short, long, value_parser = parse_complex::<f32>, default_value = "0,0")]
-        center: Complex<f32>,
+        /// The constant of a Julia set whose orbits to plot. When set, samples are drawn as initial
+        /// values of z instead of values of c.
+        #[arg(long, value_name = "C", value_parser = parse_complex::<f32>)]
+        julia: Option<Complex<f32>>,
+
+        /// The power z is raised to every iteration, rendering a multibrot when it isn't 2. Powers that
+        /// aren't whole numbers are much slower to iterate.
+        #[arg(long, value_name = "D", default_value = "2")]
+        power: f32,
+
+        /// The coefficient of the previous iterate of the phoenix fractal.
+        #[arg(long, value_name = "P", value_parser = parse_complex::<f32>, default_value = "-0.5,0")]
+        phoenix: Complex<f32>,
 
         /// Whether to output the image in PNG format. If false, uses EXR. Note that this
         /// automatically normalizes the image beforehand.
         #[arg(long)]
         png: bool,
 
-        /// Whether or not to normalize all pixel values between 0-1 before writing the image.
+        /// Whether to output the image in Radiance HDR format rather than EXR, which keeps the
+        /// range of values like EXR does in smaller files.
         #[arg(long)]
-        normalize: bool,
+        hdr: bool,
 
-        /// Whether or not to rotate the resulting image. Useful only when rendering the full
-        /// buddhabrot.
+        /// Output the image in TIFF format with samples of the given bit depth rather than EXR,
+        /// writing BigTIFF for images over 4 GiB. 16-bit samples hold values between 0-1, so should
+        /// be used with `--normalize`.
+        #[arg(long, value_name = "BITS", num_args = 0..=1, default_missing_value = "32")]
+        tiff: Option<TiffDepth>,
+
+        /// Whether to output the image as a portable float map rather than EXR, an uncompressed
+        /// format of raw floats that scientific tooling can read without any image library.
         #[arg(long)]
-        rotate: bool,
+        pfm: bool,
 
-        /// Whether or not to reflect the resulting image and add it back to the original. This
-        /// effectively doubles the number of samples but only works when rendering a symmetrical
-        /// region of the fractal.
+        /// Whether to output the image in lossless WebP format rather than EXR. Like PNG, values
+        /// are clamped between 0-1.
         #[arg(long)]
-        reflect: bool,
-    },
-    Process {
-        /// The full input file path to process, including the extension.
-        input_file: PathBuf,
+        webp: bool,
 
-        #[command(subcommand)]
-        colorize: Option<ColorizeCommand>,
+        /// Output the image in lossy AVIF format with the given quality between 1-100 rather than
+        /// EXR, which makes much smaller files than PNG. Like PNG, values are clamped between 0-1.
+        #[arg(long, value_name = "QUALITY", num_args = 0..=1, default_missing_value = "80", value_parser = clap::value_parser!(u8).range(1..=100))]
+        avif: Option<u8>,
 
-        /// The output file path, excluding the extension. When unspecified, overwrites the original file.
-        #[arg(short, long, value_name = "OUTFILE")]
-        file: Option<PathBuf>,
+        /// How values are rounded to the levels of PNG, WebP and AVIF images, breaking up the
+        /// bands smooth gradients of low density leave at 8 bits with fine grain.
+        #[arg(long, value_name = "DITHER", default_value = "none")]
+        dither: Dither,
 
-        /// The exposure of the image.
-        ///
-        /// Recommended value: 2.5
+        /// Whether or not to normalize all pixel values between 0-1 before writing the image.
+        #[arg(long)]
+        normalize: bool,
+
+        /// The operators to tonemap the image with in place of normalizing it, applied one after
+        /// another: `normalize`, `exposure:FACTOR`, `gamma:GAMMA`, `power:P`, `log1p`, `sqrt`,
+        /// `black-point:THRESHOLD` and `clamp`, separated by commas. Previews in formats that clamp
+        /// values take the tonemapped image as it is, so the operators should bring values between
+        /// 0-1.
+        ///
+        /// Recommended value: log1p,normalize,gamma:2.2
+        #[arg(long, value_name = "OPERATORS", value_parser = Tonemap::parse, conflicts_with_all = ["normalize", "tiles"])]
+        tonemap: Option<Tonemap>,
+
+        /// Whether to also write the raw histogram of the image, before it's normalized or
+        /// transformed, to a `.bhist` file that `process` can tonemap again.
+        #[arg(long)]
+        histogram: bool,
+
+        /// Compress histogram files and checkpoints with zstd at the given level between 1-22, which
+        /// is detected when they're read again. Large renders with many empty pixels often shrink
+        /// by half.
+        #[arg(long, value_name = "LEVEL", num_args = 0..=1, default_missing_value = "3", value_parser = clap::value_parser!(u32).range(1..=22))]
+        compress: Option<u32>,
+
+        /// Whether to also write the raw histogram of the image to a NumPy `.npy` file, as an
+        /// array of shape `(height, width, channels)`.
+        #[arg(long)]
+        npy: bool,
+
+        /// Whether to also write the raw histogram of the image to an HDF5 `.h5` file, as a
+        /// `histogram` dataset of shape `(height, width, channels)` with every parameter of the
+        /// render attached as an attribute.
+        #[cfg(feature = "hdf5")]
+        #[arg(long)]
+        hdf5: bool,
+
+        /// Whether to also write the raw histogram of the image to a Zarr store, split into
+        /// compressed chunks of the given width in pixels so that huge renders can be read one
+        /// region at a time.
+        #[arg(long, value_name = "CHUNK", num_args = 0..=1, default_missing_value = "256")]
+        zarr: Option<u32>,
+
+        /// Also write the raw histogram of the image to a text table, with a line of the position
+        /// and channels of every pixel.
+        #[arg(long, value_name = "FORMAT", num_args = 0..=1, default_missing_value = "csv")]
+        table: Option<TableFormat>,
+
+        /// Whether to leave the pixels without any hits out of the table written with `--table`.
+        #[arg(long, requires = "table")]
+        nonzero: bool,
+
+        /// A comma separated list of files to also write in the same run, each either the image in
+        /// another format (`exr`, `png`, `hdr`, `tiff[:16|32]`, `pfm`, `webp` or `avif[:QUALITY]`)
+        /// or the raw histogram (`bhist[:LEVEL]`, `npy`, `zarr[:CHUNK]`, `csv`, `tsv` or `h5` with the
+        /// `hdf5` feature). Images in formats that clamp values are normalized first, so that a
+        /// render kept losslessly in EXR or a `.bhist` file comes with previews ready to look at.
+        #[arg(long, value_name = "OUTPUTS", value_delimiter = ',', value_parser = parse_output)]
+        outputs: Vec<Output>,
+
+        /// Also write a Deep Zoom pyramid of the image for viewers like OpenSeadragon, a `.dzi`
+        /// descriptor next to the image and a directory of PNG tiles of the given width for every
+        /// level down to a single pixel. Tiles are normalized like PNG images written by
+        /// `--outputs` are.
+        #[arg(long, value_name = "TILE", num_args = 0..=1, default_missing_value = "254")]
+        dzi: Option<u32>,
+
+        /// Also write an animation of the image developing out of noise as it's sampled, made of
+        /// the given number of snapshots each adding an equal share of the samples to the last.
+        /// Every snapshot is normalized, or tonemapped with `--tonemap`, so the image brightens evenly
+        /// rather than fading in.
+        #[arg(long, value_name = "SNAPSHOTS")]
+        timelapse: Option<u32>,
+
+        /// The format the animation written with `--timelapse` is encoded in.
+        #[arg(long, value_name = "FORMAT", default_value = "apng", requires = "timelapse")]
+        timelapse_format: TimelapseFormat,
+
+        /// Whether to also write a JSON manifest next to every image, recording the version and
+        /// commit of the renderer, the command it was run with and the effective value of every
+        /// parameter, so that the image can be rendered again exactly.
+        #[arg(long)]
+        manifest: bool,
+
+        /// Sample the image a slice at a time, writing the hits of every slice sampled so far to
+        /// this histogram file after each, so that an interrupted render can be continued with
+        /// `--resume` rather than started over. Named with a `.bhist` extension, it can be
+        /// tonemapped with `process` to look at a render that's still going.
+        #[arg(long, value_name = "FILE")]
+        checkpoint: Option<PathBuf>,
+
+        /// The number of slices the samples are split into when checkpointing, each of which is
+        /// followed by writing a checkpoint. Defaults to 16, or to 256 with `--autosave` so that
+        /// slices end often enough to save on time.
+        #[arg(long, value_name = "SLICES")]
+        checkpoint_slices: Option<u32>,
+
+        /// Write checkpoints at most this often, such as `10m`, rather than after every slice. When
+        /// no `--checkpoint` is given, they're written next to the image with a `-checkpoint`
+        /// suffix.
+        #[arg(long, value_name = "INTERVAL", value_parser = humantime::parse_duration)]
+        autosave: Option<std::time::Duration>,
+
+        /// The number of the last checkpoints to keep, older ones numbered before their extension,
+        /// so that a checkpoint damaged while it's written falls back to the one before it.
+        #[arg(long, value_name = "COUNT", default_value = "1")]
+        keep_checkpoints: u32,
+
+        /// Continue an incomplete checkpoint found where checkpoints are written, left behind by a
+        /// render that was interrupted, without asking first.
+        #[arg(long)]
+        recover: bool,
+
+        /// Continue the render saved in a checkpoint, which must have been made with the same
+        /// parameters, taking its seed when none is given. Checkpoints keep being written to the
+        /// same file unless `--checkpoint` names another.
+        #[arg(long, value_name = "FILE")]
+        resume: Option<PathBuf>,
+
+        /// Whether or not to rotate the resulting image. Useful only when rendering the full
+        /// buddhabrot.
+        #[arg(long)]
+        rotate: bool,
+
+        /// Whether or not to reflect the resulting image and add it back to the original. This
+        /// effectively doubles the number of samples but only works when rendering a symmetrical
+        /// region of the fractal.
+        #[arg(long)]
+        reflect: bool,
+
+        /// The seed used to generate samples. When unspecified, a random seed is chosen and printed
+        /// so that the render can be reproduced.
+        #[arg(long, value_name = "SEED")]
+        seed: Option<u64>,
+
+        /// The index of this frame within an animation.
+        #[arg(long, value_name = "FRAME", default_value = "0")]
+        frame: u32,
+
+        /// The number of frames of an animation to render, starting at FRAME. Each frame is written to
+        /// its own file, numbered by its index.
+        #[arg(long, value_name = "COUNT", default_value = "1")]
+        frames: u32,
+
+        /// The 4D rotation applied between consecutive frames, as comma separated rotations within
+        /// planes of `(cr, ci, zr, zi)` in degrees, such as `zr-cr:1,zi-ci:1`. Each frame is
+        /// rotated by it as many times as its index, smoothly turning the projected slice.
+        #[arg(long, value_name = "PLANE:DEGREES,...", value_parser = parse_rotation)]
+        rotation: Option<Given<Rotation>>,
+
+        /// Comma separated transforms applied in order to every point of an orbit before it is
+        /// plotted, such as `square,reciprocal`, warping the image without changing the iteration.
+        #[arg(long, value_enum, value_name = "TRANSFORM,...", value_delimiter = ',')]
+        transform: Vec<Transform>,
+
+        /// The coefficients of a Möbius transformation `(az + b) / (cz + d)` applied to the plotted
+        /// plane before it is mapped onto the image, either as 4 real numbers `A,B,C,D` or as 8
+        /// numbers giving the real and imaginary part of each. `0,1,1,0` views the plane around the
+        /// point at infinity.
+        #[arg(long, value_name = "A,B,C,D", value_parser = parse_mobius)]
+        mobius: Option<Mobius>,
+
+        /// How sample noise is correlated across the frames of an animation.
+        #[arg(long, value_enum, value_name = "CORRELATION", default_value_t = NoiseCorrelation::default())]
+        noise: NoiseCorrelation,
+
+        /// The iteration limits of the red, green, and blue channels in nebulabrot mode. Defaults to
+        /// N_ITERATIONS, a tenth of it, and a hundredth of it.
+        #[arg(long, value_name = "R,G,B", value_parser = parse_bands)]
+        bands: Option<Nebulabrot>,
+
+        /// The fractals plotted into the red, green, and blue channels in composite mode. Defaults to
+        /// mandelbrot, burning-ship, and tricorn.
+        #[arg(long, value_name = "R,G,B", value_parser = parse_fractals)]
+        fractals: Option<ChannelFractals>,
+
+        /// The minimum number of iterations an orbit must survive before escaping for it to be
+        /// plotted.
+        #[arg(long, value_name = "MIN_ITERATIONS", default_value = "0")]
+        min_iterations: u32,
+
+        /// Which orbits to plot. Plotting bounded orbits renders the anti-buddhabrot.
+        #[arg(long, value_enum, value_name = "ORBITS", default_value_t = Orbits::default())]
+        orbits: Orbits,
+
+        /// The fractal whose orbits to plot. Ignored in composite mode, where each channel has its own
+        /// fractal.
+        #[arg(long, value_enum, value_name = "FRACTAL", default_value_t = Fractal::default())]
+        fractal: Fractal,
+
+        /// A custom iteration `z = f(z, c)` to plot instead of the fractal, such as `z^2 + c*sin(z)`.
+        /// May use `z`, `c`, `i`, real numbers, `+ - * / ^` and common functions like `sin` and `exp`.
+        #[arg(long, value_name = "EXPR", value_parser = parse_formula)]
+        formula: Option<Formula>,
+
+        /// The radius beyond which orbits of a custom formula escape.
+        #[arg(long, value_name = "RADIUS", default_value = "2")]
+        escape_radius: f32,
+
+        /// Plot the experimental quaternion iteration `q = q² + c` instead of the fractal, lifting
+        /// every sample into the quaternions with these `j` and `k` parts.
+        #[arg(long, value_name = "J,K", value_parser = parse_complex::<f32>)]
+        quaternion: Option<Complex<f32>>,
+
+        /// The two parts of each quaternion plotted onto the image.
+        #[arg(long, value_enum, value_name = "PLANE", default_value_t = QuaternionPlane::default())]
+        quaternion_plane: QuaternionPlane,
+
+        /// The amount each point of an orbit deposits into the image.
+        #[arg(long, value_enum, value_name = "WEIGHT", default_value_t = OrbitWeight::default())]
+        weight: OrbitWeight,
+
+        /// The trap shape points are weighted by when weighting by trap: `point:X,Y`, `line:X,Y,DEGREES`
+        /// through a point at an angle from the real axis, or `circle:X,Y,RADIUS`.
+        #[arg(long, value_name = "SHAPE", value_parser = parse_trap, default_value = "point:0,0")]
+        trap: Trap,
+
+        /// The distance from the trap at which a point's weight falls to 1/e.
+        #[arg(long, value_name = "DISTANCE", default_value = "0.1")]
+        trap_falloff: f32,
+
+        /// Which two of the coordinates `(cr, ci, zr, zi)` of each orbit point to plot, accumulating over
+        /// the other two. Plotting any but `zr-zi` renders a different slice of the 4D buddhabrot.
+        #[arg(long, value_enum, value_name = "PROJECTION", default_value_t = Projection::default())]
+        projection: Projection,
+
+        /// The floating point precision orbits are iterated in. Double precision is slower, but keeps
+        /// deep zooms sharp past scales of about 1e-5, where single precision breaks down. Arbitrary
+        /// precision reaches any depth, but is far slower still. Chosen from the size of a pixel at
+        /// the scale of the view if not given.
+        #[arg(long, value_enum, value_name = "PRECISION")]
+        precision: Option<Precision>,
+
+        /// Sample orbits on the GPU, which only renders plain orbits of the mandelbrot set, burning
+        /// ship, tricorn and celtic fractals into single channels.
+        #[cfg(feature = "gpu")]
+        #[arg(long)]
+        gpu: bool,
+
+        /// Sample orbits on the CPU alongside the GPU, splitting the samples between them by how
+        /// fast each draws them.
+        #[cfg(feature = "gpu")]
+        #[arg(long, requires = "gpu")]
+        hybrid: bool,
+
+        /// Whether or not to also write the per-pixel total arc length of every orbit passing through
+        /// each pixel, to a separate file with an `-arc-length` suffix.
+        #[arg(long)]
+        arc_length: bool,
+
+        /// Whether or not to use Metropolis-Hastings importance sampling, which concentrates samples
+        /// on those whose orbits land inside the image. Greatly speeds up zoomed renders.
+        #[arg(long)]
+        metropolis: bool,
+
+        /// The number of independent Metropolis chains each thread advances in turn.
+        #[arg(long, value_name = "CHAINS", default_value = "4")]
+        chains: u32,
+
+        /// The number of mutations each Metropolis chain undergoes before it starts plotting.
+        #[arg(long, value_name = "BURN_IN", default_value = "1000")]
+        burn_in: u32,
+
+        /// The number of plotted mutations after which a Metropolis chain is replaced by a freshly
+        /// seeded one, or 0 to never replace chains.
+        #[arg(long, value_name = "CHAIN_SWAP", default_value = "100000")]
+        chain_swap: u32,
+
+        /// Whether or not to automatically choose the number of iterations before rendering. When
+        /// set, N_ITERATIONS is used as an upper bound.
+        #[arg(long)]
+        auto_tune: bool,
+
+        /// The fraction of the best measured productivity the automatically chosen number of
+        /// iterations must retain, between 0-1. Lower values favor detail, higher values favor speed.
+        #[arg(long, value_name = "TARGET", default_value = "0.5")]
+        tune_target: f32,
+
+        /// The width in pixels of a view of the full set, accumulated from the same orbits as the
+        /// image, with the image's frame marked on it.
+        #[arg(long, value_name = "SIZE", value_parser = clap::value_parser!(u32).range(1..))]
+        locator: Option<u32>,
+
+        /// How to output the full set view.
+        #[arg(long, value_enum, value_name = "LOCATOR_OUTPUT", default_value_t = LocatorOutput::Inset)]
+        locator_output: LocatorOutput,
+
+        /// A rectangle of the plotted plane, given by two opposite corners. When set, the sampled
+        /// point of every orbit passing through it is plotted onto a view of the full set, weighted
+        /// by the number of visits, and written to a separate file with an `-origins` suffix.
+        #[arg(long, value_name = "X0,Y0,X1,Y1", value_parser = parse_region)]
+        target: Option<Region>,
+
+        /// Whether or not to also render the density of sampled points from the same orbits as the
+        /// image, each weighted by the number of points its orbit plotted into the image, and write
+        /// it to a separate file with a `-c-plane` suffix.
+        #[arg(long)]
+        c_plane: bool,
+
+        /// Whether or not to render with the low-memory profile: every channel is counted into a single
+        /// image of atomic counters shared by all threads, at most 16 samples are taken per pixel, and
+        /// the image is streamed to disk. Only hit counts can be rendered this way, though they can be
+        /// routed to channels like nebulabrot, composite, basins and periods modes do.
+        #[arg(long)]
+        low_memory: bool,
+
+        /// Whether or not to plot escaping orbits while iterating them a second time, instead of
+        /// storing their trajectories, so memory use doesn't grow with the number of iterations.
+        #[arg(long)]
+        stream_orbits: bool,
+
+        /// The number of logical streams to split samples between, each accumulating its own
+        /// image, so that the image is bit for bit the same with any number of threads. Uses an
+        /// image per stream instead of an image per thread. A stream draws its samples in order on a
+        /// single thread, so several times more streams than threads keep every thread busy until
+        /// the end of the render.
+        #[arg(long, value_name = "STREAMS")]
+        streams: Option<u32>,
+
+        /// Renders the low-memory profile in square tiles of SIZE pixels, each written to disk as soon
+        /// as it is done, and then stitches them into the image. Only a single tile is kept in memory,
+        /// so the size of the image isn't bounded by RAM, at the cost of sampling every orbit once per
+        /// tile.
+        #[arg(long, value_name = "SIZE", requires = "low_memory")]
+        tiles: Option<u32>,
+
+        /// Stores the counters of the low-memory profile in FILE, mapped into memory, so images larger
+        /// than RAM spill to disk. Should the render die, the file is left holding the counts of every
+        /// channel of each pixel so far, one pixel after another, as native-endian 32-bit integers.
+        /// It is removed once the image is written.
+        #[arg(long, value_name = "FILE", requires = "low_memory")]
+        backing: Option<PathBuf>,
+
+        /// Whether or not to store images in pages allocated only once an orbit lands in them, until
+        /// most of the image is in use. Every thread keeps its own copy of the image, so this saves
+        /// a lot of memory on zoomed renders, where most pixels stay empty for a long time.
+        #[arg(long)]
+        sparse: bool,
+
+        /// Whether or not to count hits in 64-bit integers while rendering, which never drop hits,
+        /// rather than summing them in floats, which stop counting past about 16.7 million hits in a
+        /// pixel. Only unweighted hits can be counted, in the red, rg and rgb modes.
+        #[arg(long)]
+        exact_counts: bool,
+
+        /// Whether or not to store the images a render accumulates into in blocks of 64 by 64 pixels
+        /// rather than in rows, so that the points of an orbit landing near one another touch fewer
+        /// cache lines. Can speed up renders of images much larger than the CPU's caches, and the
+        /// image is rearranged into rows before it's written.
+        #[arg(long)]
+        tiled_layout: bool,
+
+        /// The fraction of time each thread spends rendering, between 0-1. Threads sleep for the rest
+        /// of the time, keeping the machine responsive without needing to change thread priorities.
+        #[arg(long, value_name = "FRACTION", default_value = "1")]
+        duty_cycle: f32,
+
+        /// Whether or not to render even when the render is expected to need more memory than is
+        /// available.
+        #[arg(long)]
+        ignore_memory_limit: bool,
+
+        /// The most memory the render may use, such as `8G`. Renders expected to need more fall back
+        /// to counters shared by every thread, and then to ever smaller tiles, when they can be
+        /// rendered that way. Unlike --low-memory, the samples per pixel are left as they are.
+        #[arg(long, value_name = "SIZE", value_parser = parse_bytes)]
+        max_memory: Option<usize>,
+    },
+    /// Measure escape statistics at several iteration counts and recommend one.
+    Tune {
+        /// The largest number of iterations to consider.
+        #[arg(short, long, value_name = "MAX_ITERATIONS", default_value = "50000")]
+        max_iterations: u32,
+
+        /// The number of samples to measure.
+        #[arg(long, value_name = "BATCH", default_value = "20000")]
+        batch: usize,
+
+        /// The minimum number of iterations an orbit must survive before escaping for it to count
+        /// as detail.
+        #[arg(long, value_name = "MIN_ESCAPE", default_value = "20")]
+        min_escape: u32,
+
+        /// The fraction of the best measured productivity the recommended number of iterations
+        /// must retain. Lower values favor detail, higher values favor speed.
+        #[arg(short, long, value_name = "TARGET", default_value = "0.5")]
+        target: f32,
+
+        /// The scale of the region sampled and viewed. Defaults to the recommended view of the fractal.
+        #[arg(short, long)]
+        scale: Option<f64>,
+
+        /// The center of the region sampled and viewed. Defaults to the recommended view of the
+        /// fractal.
+        #[arg(short, long, value_parser = parse_complex::<f64>)]
+        center: Option<Complex<f64>>,
+
+        /// The seed used to generate samples.
+        #[arg(long, value_name = "SEED")]
+        seed: Option<u64>,
+
+        /// The fractal whose orbits to measure.
+        #[arg(long, value_enum, value_name = "FRACTAL", default_value_t = Fractal::default())]
+        fractal: Fractal,
+
+        /// The power z is raised to every iteration.
+        #[arg(long, value_name = "D", default_value = "2")]
+        power: f32,
+
+        /// The coefficient of the previous iterate of the phoenix fractal.
+        #[arg(long, value_name = "P", value_parser = parse_complex::<f32>, default_value = "-0.5,0")]
+        phoenix: Complex<f32>,
+    },
+    /// Accumulate orbits into a 3D voxel grid over three of the coordinates `(cr, ci, zr, zi)`, for
+    /// viewing in external volume renderers.
+    Volume {
+        /// The number of mandelbrot iterations each complex number undegoes.
+        n_iterations: u32,
+
+        /// The number of times to sample each column of voxels. (num_samples = resolution *
+        /// resolution * samples).
+        samples: u32,
+
+        /// The number of voxels along each side of the volume.
+        resolution: u32,
+
+        /// The file to write the volume to, excluding the extension.
+        #[arg(short, long, value_name = "FILENAME", default_value = "buddhabrot")]
+        file: PathBuf,
+
+        /// Whether or not to overwrite the file if it already exists.
+        #[arg(short, long)]
+        overwrite: bool,
+
+        /// The format to write the volume in.
+        #[arg(long, value_enum, value_name = "FORMAT", default_value_t = VolumeFormat::default())]
+        format: VolumeFormat,
+
+        /// The coordinates along the x, y and z axes of the volume.
+        #[arg(long, value_name = "X,Y,Z", value_parser = parse_axes, default_value = "zr,zi,cr")]
+        axes: [Axis; 3],
+
+        /// The scale of the region covered by the volume.
+        #[arg(short, long, default_value = "1")]
+        scale: f32,
+
+        /// The center of the region covered by the volume.
+        #[arg(short, long, value_name = "X,Y,Z", value_parser = parse_point, default_value = "0,0,0")]
+        center: [f32; 3],
+
+        /// Whether or not to normalize all voxel values between 0-1 before writing the volume.
+        #[arg(short, long)]
+        normalize: bool,
+
+        /// Also write the points of a subset of the orbits that land in the volume as a point cloud
+        /// in the given format, to a file with a `-points` suffix.
+        #[arg(long, value_name = "FORMAT", num_args = 0..=1, default_missing_value = "ply")]
+        points: Option<PointFormat>,
+
+        /// The number of orbits whose points make up the point cloud.
+        #[arg(long, value_name = "ORBITS", default_value_t = 10000, requires = "points")]
+        point_orbits: u32,
+
+        /// Only keep every nth point of each orbit in the point cloud.
+        #[arg(long, value_name = "N", default_value_t = 1, requires = "points")]
+        point_every: u32,
+
+        /// Whether to store the iteration every point of the point cloud was reached at along with
+        /// its coordinates.
+        #[arg(long, requires = "points")]
+        point_iterations: bool,
+
+        /// The constant of a Julia set whose orbits to plot. When set, samples are drawn as initial
+        /// values of z instead of values of c.
+        #[arg(long, value_name = "C", value_parser = parse_complex::<f32>)]
+        julia: Option<Complex<f32>>,
+
+        /// The power z is raised to every iteration.
+        #[arg(long, value_name = "D", default_value = "2")]
+        power: f32,
+
+        /// The coefficient of the previous iterate of the phoenix fractal.
+        #[arg(long, value_name = "P", value_parser = parse_complex::<f32>, default_value = "-0.5,0")]
+        phoenix: Complex<f32>,
+
+        /// The seed used to generate samples.
+        #[arg(long, value_name = "SEED")]
+        seed: Option<u64>,
+
+        /// The minimum number of iterations an orbit must survive before escaping for it to be
+        /// plotted.
+        #[arg(long, value_name = "MIN_ITERATIONS", default_value = "0")]
+        min_iterations: u32,
+
+        /// Which orbits to plot.
+        #[arg(long, value_enum, value_name = "ORBITS", default_value_t = Orbits::default())]
+        orbits: Orbits,
+
+        /// Whether or not to plot escaping orbits while iterating them a second time, instead of
+        /// storing their trajectories, so memory use doesn't grow with the number of iterations.
+        #[arg(long)]
+        stream_orbits: bool,
+
+        /// The number of logical streams to split samples between, each accumulating its own
+        /// volume, so that the volume is bit for bit the same with any number of threads. Uses a
+        /// volume per stream instead of a volume per thread. As with images, several times more
+        /// streams than threads balance the work between threads.
+        #[arg(long, value_name = "STREAMS")]
+        streams: Option<u32>,
+
+        /// The fractal whose orbits to plot.
+        #[arg(long, value_enum, value_name = "FRACTAL", default_value_t = Fractal::default())]
+        fractal: Fractal,
+    },
+    /// Render escaping orbits into a histogram that counts the hits of every band of escape times
+    /// separately, which `recolor` colors any way afterwards without rendering it again.
+    Bands {
+        /// The number of mandelbrot iterations each complex number undegoes.
+        n_iterations: u32,
+
+        /// The number of times to sample each pixel. (num_samples = image_size * image_size *
+        /// samples).
+        samples: u32,
+
+        /// The width and height of the image in pixels.
+        image_size: u32,
+
+        /// The file to write the histogram to, excluding the extension.
+        #[arg(short, long, value_name = "FILENAME", default_value = "buddhabrot")]
+        file: PathBuf,
+
+        /// Whether or not to overwrite the file if it already exists.
+        #[arg(short, long)]
+        overwrite: bool,
+
+        /// The number of bands the escape times between the minimum number of iterations and
+        /// `n_iterations` are split into. Every band takes the memory of a whole image.
+        #[arg(long, value_name = "BANDS", default_value = "16")]
+        count: u32,
+
+        /// Compress the histogram with zstd at the given level between 1-22, which is detected when
+        /// it's read again.
+        #[arg(long, value_name = "LEVEL", num_args = 0..=1, default_missing_value = "3", value_parser = clap::value_parser!(u32).range(1..=22))]
+        compress: Option<u32>,
+
+        /// How the escape times are split into bands.
+        #[arg(long, value_enum, value_name = "SPACING", default_value_t = BandSpacing::default())]
+        spacing: BandSpacing,
+
+        /// The scale of the region sampled and viewed. Defaults to the recommended view of the fractal.
+        #[arg(short, long)]
+        scale: Option<f64>,
+
+        /// The center of the region sampled and viewed. Defaults to the recommended view of the
+        /// fractal.
+        #[arg(short, long, value_parser = parse_complex::<f64>)]
+        center: Option<Complex<f64>>,
+
+        /// The constant of a Julia set whose orbits to plot. When set, samples are drawn as initial
+        /// values of z instead of values of c.
+        #[arg(long, value_name = "C", value_parser = parse_complex::<f32>)]
+        julia: Option<Complex<f32>>,
+
+        /// The power z is raised to every iteration.
+        #[arg(long, value_name = "D", default_value = "2")]
+        power: f32,
+
+        /// The coefficient of the previous iterate of the phoenix fractal.
+        #[arg(long, value_name = "P", value_parser = parse_complex::<f32>, default_value = "-0.5,0")]
+        phoenix: Complex<f32>,
+
+        /// The seed used to generate samples.
+        #[arg(long, value_name = "SEED")]
+        seed: Option<u64>,
+
+        /// The minimum number of iterations an orbit must survive before escaping for it to be
+        /// plotted.
+        #[arg(long, value_name = "MIN_ITERATIONS", default_value = "0")]
+        min_iterations: u32,
+
+        /// The number of logical streams to split samples between, so that the histogram is bit for
+        /// bit the same with any number of threads.
+        #[arg(long, value_name = "STREAMS")]
+        streams: Option<u32>,
+
+        /// The fractal whose orbits to plot.
+        #[arg(long, value_enum, value_name = "FRACTAL", default_value_t = Fractal::default())]
+        fractal: Fractal,
+    },
+    /// Render small reference scenes and check their statistics against the expected ones.
+    Verify {
+        /// The name of the scene to render. When unspecified, every scene is rendered.
+        #[arg(long, value_name = "SCENE")]
+        scene: Option<String>,
+
+        /// Whether or not to list every scene instead of rendering them.
+        #[arg(long)]
+        list: bool,
+    },
+    /// Render fixed workloads and report how fast they render, for comparing kernels, thread counts
+    /// and machines.
+    Bench {
+        /// The name of the workload to render. When unspecified, every workload is rendered.
+        #[arg(long, value_name = "WORKLOAD")]
+        workload: Option<String>,
+
+        /// Whether or not to list every workload instead of rendering them.
+        #[arg(long)]
+        list: bool,
+
+        /// The numbers of threads to render every workload with, one after another. Defaults to the
+        /// number of rendering threads.
+        #[arg(long, value_name = "THREADS", value_delimiter = ',')]
+        thread_counts: Vec<usize>,
+
+        /// The floating point precision to iterate orbits in.
+        #[arg(long, value_enum, value_name = "PRECISION", default_value_t = Precision::default())]
+        precision: Precision,
+
+        /// Whether or not to time the kernels orbits are iterated with on the samples of every
+        /// workload instead of rendering them, comparing every SIMD level the CPU supports on a
+        /// single thread and, with `--features gpu`, the GPUs with the CPU.
+        #[arg(long)]
+        compare_kernels: bool,
+    },
+    Process {
+        /// The full input file path to process, including the extension.
+        input_file: PathBuf,
+
+        #[command(subcommand)]
+        colorize: Option<ColorizeCommand>,
+
+        /// The output file path, excluding the extension. When unspecified, overwrites the original file.
+        #[arg(short, long, value_name = "OUTFILE")]
+        file: Option<PathBuf>,
+
+        /// The exposure of the image.
+        ///
+        /// Recommended value: 2.5
         #[arg(short, long, value_name = "EXPOSURE")]
         exposure: Option<f32>,
 
-        /// The gamma of the image.
+        /// The gamma of the image, applied in linear light before formats of integer samples encode
+        /// it in sRGB.
         ///
         /// Recommended value: 0.45
         #[arg(short, long, value_name = "GAMMA")]
@@ -239,11 +1926,63 @@ enum Commands {
         #[arg(short, long, value_name = "BLACK_POINT")]
         black_point: Option<f32>,
 
+        /// The operators to tonemap the image with, one after another, in place of normalizing it,
+        /// the exposure, the gamma and the black point: `normalize`, `exposure:FACTOR`,
+        /// `gamma:GAMMA`, `power:P`, `log1p`, `sqrt`, `black-point:THRESHOLD` and `clamp`,
+        /// separated by commas. Formats that clamp values aren't normalized first, so the
+        /// operators should bring values between 0-1.
+        ///
+        /// Recommended value: log1p,normalize,gamma:2.2
+        #[arg(long, value_name = "OPERATORS", value_parser = Tonemap::parse, conflicts_with_all = ["exposure", "gamma", "black_point", "normalize"])]
+        tonemap: Option<Tonemap>,
+
         /// Whether to output the image in PNG format. If false, uses EXR. Note that this
         /// automatically normalizes and clamps the image.
         #[arg(long)]
         png: bool,
 
+        /// Whether to output the image in Radiance HDR format rather than EXR, leaving its values
+        /// unclamped like EXR does.
+        #[arg(long)]
+        hdr: bool,
+
+        /// Output the image in TIFF format with samples of the given bit depth rather than EXR,
+        /// writing BigTIFF for images over 4 GiB. 16-bit samples automatically normalize and clamp
+        /// the image like PNG does.
+        #[arg(long, value_name = "BITS", num_args = 0..=1, default_missing_value = "32")]
+        tiff: Option<TiffDepth>,
+
+        /// Whether to output the image as a portable float map rather than EXR, an uncompressed
+        /// format of raw floats that scientific tooling can read without any image library.
+        #[arg(long)]
+        pfm: bool,
+
+        /// Whether to output the image in lossless WebP format rather than EXR. Like PNG, values
+        /// are clamped between 0-1.
+        #[arg(long)]
+        webp: bool,
+
+        /// Output the image in lossy AVIF format with the given quality between 1-100 rather than
+        /// EXR, which makes much smaller files than PNG. Like PNG, values are clamped between 0-1.
+        #[arg(long, value_name = "QUALITY", num_args = 0..=1, default_missing_value = "80", value_parser = clap::value_parser!(u8).range(1..=100))]
+        avif: Option<u8>,
+
+        /// How values are rounded to the levels of PNG, WebP and AVIF images, breaking up the
+        /// bands smooth gradients of low density leave at 8 bits with fine grain.
+        #[arg(long, value_name = "DITHER", default_value = "none")]
+        dither: Dither,
+
+        /// Whether to write the PNG as an indexed image of a palette of 256 colors, which is many
+        /// times smaller than a full color one. Only works with `colorize-r`, whose colors make up
+        /// the palette.
+        #[arg(long, requires = "png")]
+        indexed: bool,
+
+        /// Whether to also write a JSON manifest next to the image, recording how it was rendered,
+        /// as far as the metadata of the original tells, and how it was processed.
+        #[arg(long)]
+        manifest: bool,
+
         /// Whether or not to clamp all pixels to a value between 0-1.
         #[arg(long)]
         clamp: bool,
@@ -272,48 +2011,453 @@ enum Commands {
         /// Whether or not to output the file in PNG format.
         #[arg(long)]
         png: bool,
-    },
-}
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
-enum ColorChannelMode {
-    /// Write to 1 color channel.
-    R,
-    /// Write to 2 color channels.
-    Rg,
-    /// Write to 3 color channels.
-    Rgb,
-}
+        /// Whether or not to output the file in Radiance HDR format.
+        #[arg(long)]
+        hdr: bool,
 
-#[derive(Subcommand)]
-enum ColorizeCommand {
-    /// Colorize the image with custom colors, only using values from the red color channel.
-    ///
-    /// Note: many EXR image viewers aren't very good at interpreting the colorized output, so
-    /// it is recommended to use the --png flag when doing so.
-    ColorizeR {
-        #[arg(long, value_name = "MIN_RED_COLOR", value_parser = parse_color)]
-        minr: (f32, f32, f32),
+        /// Output the file in TIFF format with samples of the given bit depth.
+        #[arg(long, value_name = "BITS", num_args = 0..=1, default_missing_value = "32")]
+        tiff: Option<TiffDepth>,
 
-        #[arg(long, value_name = "MAX_RED_COLOR", value_parser = parse_color)]
-        maxr: (f32, f32, f32),
+        /// Whether to output the image as a portable float map rather than EXR, an uncompressed
+        /// format of raw floats that scientific tooling can read without any image library.
+        #[arg(long)]
+        pfm: bool,
+
+        /// Whether to output the image in lossless WebP format rather than EXR. Like PNG, values
+        /// are clamped between 0-1.
+        #[arg(long)]
+        webp: bool,
+
+        /// Output the image in lossy AVIF format with the given quality between 1-100 rather than
+        /// EXR, which makes much smaller files than PNG. Like PNG, values are clamped between 0-1.
+        #[arg(long, value_name = "QUALITY", num_args = 0..=1, default_missing_value = "80", value_parser = clap::value_parser!(u8).range(1..=100))]
+        avif: Option<u8>,
+
+        /// How values are rounded to the levels of PNG, WebP and AVIF images, breaking up the
+        /// bands smooth gradients of low density leave at 8 bits with fine grain.
+        #[arg(long, value_name = "DITHER", default_value = "none")]
+        dither: Dither,
     },
-    /// Colorize the image with custom colors, using values from the red and green color channels.
-    ///
-    /// Note: many EXR image viewers aren't very good at interpreting the colorized output, so
-    /// it is recommended to use the --png flag when doing so.
-    ColorizeRg {
-        #[arg(long, value_name = "MIN_RED_COLOR", value_parser = parse_color)]
-        minr: (f32, f32, f32),
+    /// Add up histograms of the same image rendered separately, such as on several machines with
+    /// different seeds, into one histogram holding the samples of all of them.
+    Merge {
+        /// The full input file paths of the histograms, including the extension.
+        #[arg(required = true, num_args = 2..)]
+        input_files: Vec<PathBuf>,
 
-        #[arg(long, value_name = "MAX_RED_COLOR", value_parser = parse_color)]
-        maxr: (f32, f32, f32),
+        /// The output file path, excluding the extension.
+        #[arg(short, long, value_name = "OUTFILE")]
+        file: PathBuf,
 
-        #[arg(long, value_name = "MIN_GREEN_COLOR", value_parser = parse_color)]
-        ming: (f32, f32, f32),
+        /// Whether or not to overwrite the output file if it already exists.
+        #[arg(short, long)]
+        overwrite: bool,
 
-        #[arg(long, value_name = "MAX_GREEN_COLOR", value_parser = parse_color)]
-        maxg: (f32, f32, f32),
+        /// Whether to merge histograms rendered with different parameters or the same seed anyway,
+        /// keeping the parameters of the first.
+        #[arg(long)]
+        force: bool,
+
+        /// Compress the histogram with zstd at the given level between 1-22, which is detected when
+        /// it's read again.
+        #[arg(long, value_name = "LEVEL", num_args = 0..=1, default_missing_value = "3", value_parser = clap::value_parser!(u32).range(1..=22))]
+        compress: Option<u32>,
+    },
+    /// Compare an image or histogram to a reference, reporting how far apart they are and writing the
+    /// difference of every pixel, for checking that a faster kernel still renders what the
+    /// reference one does.
+    Diff {
+        /// The full file path of the reference, including the extension.
+        reference_file: PathBuf,
+
+        /// The full file path of the image or histogram to compare to the reference, including the
+        /// extension.
+        input_file: PathBuf,
+
+        /// The output file path of the image of the absolute difference of every channel, excluding
+        /// the extension. When unspecified, only the statistics are reported.
+        #[arg(short, long, value_name = "OUTFILE")]
+        file: Option<PathBuf>,
+
+        /// Whether or not to normalize both images between 0-1 before comparing them, so that renders
+        /// of different numbers of samples compare by the shape of their density alone.
+        #[arg(long)]
+        normalize: bool,
+
+        /// The largest root mean square difference to accept. When the images are further apart,
+        /// exits with an error.
+        #[arg(long, value_name = "RMSE")]
+        max_rmse: Option<f64>,
+
+        /// Whether or not to output the file in PNG format.
+        #[arg(long)]
+        png: bool,
+
+        /// Whether or not to output the file in Radiance HDR format.
+        #[arg(long)]
+        hdr: bool,
+
+        /// Output the file in TIFF format with samples of the given bit depth.
+        #[arg(long, value_name = "BITS", num_args = 0..=1, default_missing_value = "32")]
+        tiff: Option<TiffDepth>,
+
+        /// Whether to output the image as a portable float map rather than EXR, an uncompressed
+        /// format of raw floats that scientific tooling can read without any image library.
+        #[arg(long)]
+        pfm: bool,
+
+        /// Whether to output the image in lossless WebP format rather than EXR. Like PNG, values
+        /// are clamped between 0-1.
+        #[arg(long)]
+        webp: bool,
+
+        /// Output the image in lossy AVIF format with the given quality between 1-100 rather than
+        /// EXR, which makes much smaller files than PNG. Like PNG, values are clamped between 0-1.
+        #[arg(long, value_name = "QUALITY", num_args = 0..=1, default_missing_value = "80", value_parser = clap::value_parser!(u8).range(1..=100))]
+        avif: Option<u8>,
+
+        /// How values are rounded to the levels of PNG, WebP and AVIF images, breaking up the
+        /// bands smooth gradients of low density leave at 8 bits with fine grain.
+        #[arg(long, value_name = "DITHER", default_value = "none")]
+        dither: Dither,
+    },
+    /// Color a histogram rendered with `bands`, blending a palette across its bands of escape times
+    /// from the shortest orbits to the longest.
+    Recolor {
+        /// The full input file path of the histogram, including the extension.
+        input_file: PathBuf,
+
+        /// The output file path, excluding the extension. When unspecified, writes next to the
+        /// input file.
+        #[arg(short, long, value_name = "OUTFILE")]
+        file: Option<PathBuf>,
+
+        /// The colors of the palette, each a hex code or comma separated RGB values. Two colors
+        /// blend from the shortest orbits to the longest, and three such as `#ffffff #4060ff
+        /// #ff3000` color them like a nebulabrot.
+        #[arg(long, value_name = "COLOR", value_parser = parse_color, num_args = 2.., required = true)]
+        palette: Vec<(f32, f32, f32)>,
+
+        /// Whether or not to normalize all pixel values between 0-1 before writing the image.
+        /// Formats that clamp values are always normalized.
+        #[arg(long)]
+        normalize: bool,
+
+        /// Whether or not to overwrite the output file if it already exists.
+        #[arg(short, long)]
+        overwrite: bool,
+
+        /// Whether or not to output the file in PNG format.
+        #[arg(long)]
+        png: bool,
+
+        /// Whether or not to output the file in Radiance HDR format.
+        #[arg(long)]
+        hdr: bool,
+
+        /// Output the file in TIFF format with samples of the given bit depth.
+        #[arg(long, value_name = "BITS", num_args = 0..=1, default_missing_value = "32")]
+        tiff: Option<TiffDepth>,
+
+        /// Whether to output the image as a portable float map rather than EXR, an uncompressed
+        /// format of raw floats that scientific tooling can read without any image library.
+        #[arg(long)]
+        pfm: bool,
+
+        /// Whether to output the image in lossless WebP format rather than EXR. Like PNG, values
+        /// are clamped between 0-1.
+        #[arg(long)]
+        webp: bool,
+
+        /// Output the image in lossy AVIF format with the given quality between 1-100 rather than
+        /// EXR, which makes much smaller files than PNG. Like PNG, values are clamped between 0-1.
+        #[arg(long, value_name = "QUALITY", num_args = 0..=1, default_missing_value = "80", value_parser = clap::value_parser!(u8).range(1..=100))]
+        avif: Option<u8>,
+
+        /// How values are rounded to the levels of PNG, WebP and AVIF images, breaking up the
+        /// bands smooth gradients of low density leave at 8 bits with fine grain.
+        #[arg(long, value_name = "DITHER", default_value = "none")]
+        dither: Dither,
+    },
+    /// Trace the outlines of regions of an image at least as dense as each of a set of levels and
+    /// write them as SVG paths, for plotters and laser engravers.
+    Contour {
+        /// The full input file path to trace, including the extension.
+        input_file: PathBuf,
+
+        /// The output file path, excluding the extension. When unspecified, writes next to the
+        /// input file.
+        #[arg(short, long, value_name = "OUTFILE")]
+        file: Option<PathBuf>,
+
+        /// The densities of the normalized image to trace an outline at, separated by commas. Every
+        /// level must be larger than 0 and at most 1.
+        #[arg(
+            long,
+            value_name = "LEVELS",
+            value_delimiter = ',',
+            default_value = "0.05,0.1,0.2,0.4"
+        )]
+        levels: Vec<f32>,
+
+        /// The gamma the normalized image is corrected with before it's traced, spreading the
+        /// levels out over the faint orbits that make up most of the image.
+        #[arg(long, value_name = "GAMMA")]
+        gamma: Option<f32>,
+
+        /// The fewest corners an outline can have, below which it's left out so that specks of
+        /// noise aren't drawn.
+        #[arg(long, value_name = "POINTS", default_value_t = 8)]
+        min_points: usize,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum ColorChannelMode {
+    /// Write to 1 color channel.
+    R,
+    /// Write to 2 color channels.
+    Rg,
+    /// Write to 3 color channels.
+    Rgb,
+    /// Write to 3 color channels in a single pass, routing each orbit to the channels whose
+    /// iteration limit it escapes within.
+    Nebulabrot,
+    /// Write to 3 color channels in a single pass, plotting a different fractal into each channel.
+    Composite,
+    /// Write to 3 color channels in a single pass, routing each orbit of the newton or nova fractal
+    /// to a channel by the root it converges to.
+    Basins,
+    /// Write to 3 color channels in a single pass, routing each bounded orbit to a palette entry by
+    /// the period of the cycle it settles into.
+    Periods,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum LocatorOutput {
+    /// Paste the full set view into the bottom right corner of the image.
+    Inset,
+    /// Write the full set view to a separate file with a `-locator` suffix.
+    Separate,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum TiffDepth {
+    /// Write 16-bit integers in sRGB, clamping every value between 0-1.
+    #[value(name = "16")]
+    Integer,
+    /// Write 32-bit floats, keeping every value.
+    #[value(name = "32")]
+    Float,
+}
+
+/// The file format images are written in.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    /// OpenEXR, keeping every value as a 32-bit float.
+    Exr,
+    /// 8-bit PNG tagged as sRGB, clamping every value between 0-1 before encoding it.
+    Png,
+    /// Radiance HDR, keeping the range of values in a shared 8-bit exponent per pixel.
+    Hdr,
+    /// TIFF with samples of the given depth, which becomes BigTIFF for images too large for a
+    /// standard TIFF file.
+    Tiff(TiffDepth),
+    /// Portable float map, keeping every value as an uncompressed 32-bit float.
+    Pfm,
+    /// Lossless 8-bit WebP in sRGB, clamping every value between 0-1.
+    Webp,
+    /// Lossy 8-bit AVIF in sRGB of the given quality between 1-100, clamping every value between 0-1.
+    Avif(u8),
+}
+
+impl OutputFormat {
+    /// Gets the format selected by the `--png`, `--hdr`, `--tiff`, `--pfm`, `--webp` and `--avif`
+    /// flags, which defaults to EXR.
+    fn from_flags(
+        png: bool,
+        hdr: bool,
+        tiff: Option<TiffDepth>,
+        pfm: bool,
+        webp: bool,
+        avif: Option<u8>,
+    ) -> Result<OutputFormat, clap::Error> {
+        let mut selected = [
+            png.then_some(OutputFormat::Png),
+            hdr.then_some(OutputFormat::Hdr),
+            tiff.map(OutputFormat::Tiff),
+            pfm.then_some(OutputFormat::Pfm),
+            webp.then_some(OutputFormat::Webp),
+            avif.map(OutputFormat::Avif),
+        ]
+        .into_iter()
+        .flatten();
+
+        match (selected.next(), selected.next()) {
+            (_, Some(_)) => Err(Cli::command().error(
+                ErrorKind::ArgumentConflict,
+                "an image can only be written in one format, so only one of --png, --hdr, --tiff, --pfm, --webp and --avif \
+                 can be used",
+            )),
+            (format, None) => Ok(format.unwrap_or(OutputFormat::Exr)),
+        }
+    }
+
+    /// Gets the extension of files in this format.
+    fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Exr => "exr",
+            OutputFormat::Png => "png",
+            OutputFormat::Hdr => "hdr",
+            OutputFormat::Tiff(_) => "tiff",
+            OutputFormat::Pfm => "pfm",
+            OutputFormat::Webp => "webp",
+            OutputFormat::Avif(_) => "avif",
+        }
+    }
+
+    /// Gets the name of the flag selecting this format and the value it takes, or `None` for EXR,
+    /// which is written when no flag is given.
+    fn flag(self) -> Option<(&'static str, String)> {
+        match self {
+            OutputFormat::Exr => None,
+            OutputFormat::Tiff(depth) => Some(("tiff", histogram::value_name(&depth))),
+            OutputFormat::Avif(quality) => Some(("avif", quality.to_string())),
+            format => Some((format.extension(), true.to_string())),
+        }
+    }
+
+    /// Whether this format can only hold values between 0-1, so images are normalized and clamped
+    /// before being written in it.
+    fn clamps(self) -> bool {
+        matches!(
+            self,
+            OutputFormat::Png | OutputFormat::Tiff(TiffDepth::Integer) | OutputFormat::Webp | OutputFormat::Avif(_)
+        )
+    }
+}
+
+/// A file written alongside the image of a render with `--outputs`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Output {
+    /// The image in another format, normalized first when the format clamps values.
+    Image(OutputFormat),
+    /// The raw histogram of the image.
+    Raw(RawFormat),
+}
+
+/// Parses an output of `--outputs`, which is the name of a format followed by its option after a
+/// colon for the formats that take one.
+fn parse_output(s: &str) -> Result<Output, String> {
+    let (name, option) = match s.split_once(':') {
+        Some((name, option)) => (name, Some(option)),
+        None => (s, None),
+    };
+    let output = match (name, option) {
+        ("exr", None) => Output::Image(OutputFormat::Exr),
+        ("png", None) => Output::Image(OutputFormat::Png),
+        ("hdr", None) => Output::Image(OutputFormat::Hdr),
+        ("tiff", None | Some("32")) => Output::Image(OutputFormat::Tiff(TiffDepth::Float)),
+        ("tiff", Some("16")) => Output::Image(OutputFormat::Tiff(TiffDepth::Integer)),
+        ("pfm", None) => Output::Image(OutputFormat::Pfm),
+        ("webp", None) => Output::Image(OutputFormat::Webp),
+        ("avif", None) => Output::Image(OutputFormat::Avif(80)),
+        ("avif", Some(quality)) => match quality.parse::<u8>() {
+            Ok(quality @ 1..=100) => Output::Image(OutputFormat::Avif(quality)),
+            _ => {
+                return Err(format!(
+                    "{} is not a valid AVIF quality. expected a number between 1-100",
+                    quality
+                ))
+            },
+        },
+        (histogram::EXTENSION, None) => Output::Raw(RawFormat::Histogram(None)),
+        (histogram::EXTENSION, Some(level)) => match level.parse::<u32>() {
+            Ok(level @ 1..=22) => Output::Raw(RawFormat::Histogram(Some(level))),
+            _ => {
+                return Err(format!(
+                    "{} is not a valid compression level. expected a number between 1-22",
+                    level
+                ))
+            },
+        },
+        ("npy", None) => Output::Raw(RawFormat::Npy),
+        #[cfg(feature = "hdf5")]
+        ("h5", None) => Output::Raw(RawFormat::Hdf5),
+        ("zarr", None) => Output::Raw(RawFormat::Zarr(256)),
+        ("zarr", Some(chunk_size)) => match chunk_size.parse::<usize>() {
+            Ok(chunk_size) if chunk_size > 0 => Output::Raw(RawFormat::Zarr(chunk_size)),
+            _ => return Err(format!("{} is not a valid zarr chunk width", chunk_size)),
+        },
+        ("csv", None) => Output::Raw(RawFormat::Table {
+            format: TableFormat::Csv,
+            nonzero: false,
+        }),
+        ("tsv", None) => Output::Raw(RawFormat::Table {
+            format: TableFormat::Tsv,
+            nonzero: false,
+        }),
+        _ => {
+            return Err(format!(
+                "{} is not a valid output. expected one of exr, png, hdr, tiff[:16|32], pfm, webp, avif[:QUALITY], \
+                 bhist[:LEVEL], npy, zarr[:CHUNK], csv and tsv",
+                s
+            ))
+        },
+    };
+    Ok(output)
+}
+
+/// The animation format timelapses are encoded in.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum TimelapseFormat {
+    /// Write an animated PNG, which keeps every color of every snapshot.
+    Apng,
+    /// Write a GIF, which is read almost anywhere but reduces every snapshot to 256 colors.
+    Gif,
+}
+
+impl TimelapseFormat {
+    /// Gets the extension of files in this format.
+    fn extension(self) -> &'static str {
+        match self {
+            TimelapseFormat::Apng => "png",
+            TimelapseFormat::Gif => "gif",
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum ColorizeCommand {
+    /// Colorize the image with custom colors, only using values from the red color channel.
+    ///
+    /// Note: many EXR image viewers aren't very good at interpreting the colorized output, so
+    /// it is recommended to use the --png flag when doing so.
+    ColorizeR {
+        #[arg(long, value_name = "MIN_RED_COLOR", value_parser = parse_color)]
+        minr: (f32, f32, f32),
+
+        #[arg(long, value_name = "MAX_RED_COLOR", value_parser = parse_color)]
+        maxr: (f32, f32, f32),
+    },
+    /// Colorize the image with custom colors, using values from the red and green color channels.
+    ///
+    /// Note: many EXR image viewers aren't very good at interpreting the colorized output, so
+    /// it is recommended to use the --png flag when doing so.
+    ColorizeRg {
+        #[arg(long, value_name = "MIN_RED_COLOR", value_parser = parse_color)]
+        minr: (f32, f32, f32),
+
+        #[arg(long, value_name = "MAX_RED_COLOR", value_parser = parse_color)]
+        maxr: (f32, f32, f32),
+
+        #[arg(long, value_name = "MIN_GREEN_COLOR", value_parser = parse_color)]
+        ming: (f32, f32, f32),
+
+        #[arg(long, value_name = "MAX_GREEN_COLOR", value_parser = parse_color)]
+        maxg: (f32, f32, f32),
     },
     /// Colorize the image with custom colors, using values from the red, green, and blue color channels.
     ///
@@ -323,81 +2467,1856 @@ enum ColorizeCommand {
         #[arg(long, value_name = "MIN_RED_COLOR", value_parser = parse_color)]
         minr: (f32, f32, f32),
 
-        #[arg(long, value_name = "MAX_RED_COLOR", value_parser = parse_color)]
-        maxr: (f32, f32, f32),
+        #[arg(long, value_name = "MAX_RED_COLOR", value_parser = parse_color)]
+        maxr: (f32, f32, f32),
+
+        #[arg(long, value_name = "MIN_GREEN_COLOR", value_parser = parse_color)]
+        ming: (f32, f32, f32),
+
+        #[arg(long, value_name = "MAX_GREEN_COLOR", value_parser = parse_color)]
+        maxg: (f32, f32, f32),
+
+        #[arg(long, value_name = "MIN_BLUE_COLOR", value_parser = parse_color)]
+        minb: (f32, f32, f32),
+
+        #[arg(long, value_name = "MAX_BLUE_COLOR", value_parser = parse_color)]
+        maxb: (f32, f32, f32),
+    },
+}
+
+fn parse_bands(s: &str) -> Result<Nebulabrot, String> {
+    let limits = s
+        .split(',')
+        .map(|v| v.trim().parse::<u32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("{} is not a valid list of iteration limits", s))?;
+
+    match limits[..] {
+        [r, g, b] => Ok(Nebulabrot::new(r, g, b)),
+        _ => Err(format!("expected 3 iteration limits but got {}", limits.len())),
+    }
+}
+
+fn parse_fractals(s: &str) -> Result<ChannelFractals, String> {
+    let fractals = s
+        .split(',')
+        .map(|v| Fractal::from_str(v.trim(), true))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match fractals[..] {
+        [r, g, b] => Ok(ChannelFractals::new(r, g, b)),
+        _ => Err(format!("expected 3 fractals but got {}", fractals.len())),
+    }
+}
+
+fn parse_formula(s: &str) -> Result<Formula, String> {
+    Formula::parse(s).map_err(|e| e.pointing_into(s))
+}
+
+fn parse_trap(s: &str) -> Result<Trap, String> {
+    let (shape, params) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected a shape and its parameters like `point:0,0` but got `{}`", s))?;
+    let params = params
+        .split(',')
+        .map(|v| {
+            v.trim()
+                .parse::<f32>()
+                .map_err(|e| format!("invalid parameter `{}`: {}", v.trim(), e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match (shape.trim(), &params[..]) {
+        ("point", &[x, y]) => Ok(Trap::Point(Complex::new(x, y))),
+        ("line", &[x, y, degrees]) => Ok(Trap::line(Complex::new(x, y), degrees)),
+        ("circle", &[x, y, radius]) => Ok(Trap::Circle {
+            center: Complex::new(x, y),
+            radius,
+        }),
+        ("point", _) => Err("a point trap takes 2 parameters: X,Y".to_string()),
+        ("line", _) => Err("a line trap takes 3 parameters: X,Y,DEGREES".to_string()),
+        ("circle", _) => Err("a circle trap takes 3 parameters: X,Y,RADIUS".to_string()),
+        (shape, _) => Err(format!(
+            "unknown trap shape `{}`, expected point, line or circle",
+            shape
+        )),
+    }
+}
+
+fn parse_axes(s: &str) -> Result<[Axis; 3], String> {
+    let axes = s
+        .split(',')
+        .map(|v| Axis::from_str(v.trim(), true))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match axes[..] {
+        [x, y, z] if x != y && y != z && x != z => Ok([x, y, z]),
+        [_, _, _] => Err("expected 3 different axes".to_string()),
+        _ => Err(format!("expected 3 axes but got {}", axes.len())),
+    }
+}
+
+fn parse_point(s: &str) -> Result<[f32; 3], String> {
+    let coords = s
+        .split(',')
+        .map(|v| {
+            v.trim()
+                .parse::<f32>()
+                .map_err(|e| format!("invalid coordinate `{}`: {}", v.trim(), e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match coords[..] {
+        [x, y, z] => Ok([x, y, z]),
+        _ => Err(format!("expected 3 coordinates but got {}", coords.len())),
+    }
+}
+
+fn parse_region(s: &str) -> Result<Region, String> {
+    let coords = s
+        .split(',')
+        .map(|v| {
+            v.trim()
+                .parse::<f32>()
+                .map_err(|e| format!("invalid coordinate `{}`: {}", v.trim(), e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match coords[..] {
+        [x0, y0, x1, y1] if x0 != x1 && y0 != y1 => Ok(Region::new(Complex::new(x0, y0), Complex::new(x1, y1))),
+        [_, _, _, _] => Err("the corners of a region must differ in both coordinates".to_string()),
+        _ => Err(format!("expected 4 coordinates but got {}", coords.len())),
+    }
+}
+
+fn parse_mobius(s: &str) -> Result<Mobius, String> {
+    let coefficients = s
+        .split(',')
+        .map(|v| {
+            v.trim()
+                .parse::<f32>()
+                .map_err(|e| format!("invalid coefficient `{}`: {}", v.trim(), e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let [a, b, c, d] = match coefficients[..] {
+        [a, b, c, d] => [a, b, c, d].map(|x| Complex::new(x, 0.0)),
+        [ar, ai, br, bi, cr, ci, dr, di] => [
+            Complex::new(ar, ai),
+            Complex::new(br, bi),
+            Complex::new(cr, ci),
+            Complex::new(dr, di),
+        ],
+        _ => return Err(format!("expected 4 or 8 coefficients but got {}", coefficients.len())),
+    };
+
+    Mobius::new(a, b, c, d).ok_or_else(|| "ad - bc must not be zero".to_string())
+}
+
+/// A value parsed from a flag along with the text it was given as, for values that can't be written
+/// back out once they're parsed.
+#[derive(Clone)]
+struct Given<T> {
+    value: T,
+    text: String,
+}
+
+fn parse_rotation(s: &str) -> Result<Given<Rotation>, String> {
+    let value = s.split(',').try_fold(Rotation::IDENTITY, |rotation, plane| {
+        let (axes, degrees) = plane.split_once(':').ok_or_else(|| {
+            format!(
+                "expected a plane and an angle like `zr-cr:1` but got `{}`",
+                plane.trim()
+            )
+        })?;
+        let (a, b) = axes
+            .split_once('-')
+            .ok_or_else(|| format!("expected a plane like `zr-cr` but got `{}`", axes.trim()))?;
+        let (a, b) = (Axis::from_str(a.trim(), true)?, Axis::from_str(b.trim(), true)?);
+        if a == b {
+            return Err(format!("a plane needs two different axes but got `{}`", axes.trim()));
+        }
+
+        let degrees = degrees
+            .trim()
+            .parse::<f32>()
+            .map_err(|e| format!("invalid angle `{}`: {}", degrees.trim(), e))?;
+        Ok(rotation.then(Rotation::plane(a, b, degrees.to_radians())))
+    })?;
+
+    Ok(Given {
+        value,
+        text: s.trim().to_owned(),
+    })
+}
+
+fn parse_complex<T: FromStr>(s: &str) -> Result<Complex<T>, String> {
+    let seps = s.chars().filter(|&c| c == ',').count();
+
+    if seps != 1 {
+        return Err(format!(
+            "expected complex number to have exactly one separator but got {seps}."
+        ));
+    }
+
+    let mut parts = s.split(',');
+
+    let re: T = parts
+        .next()
+        .unwrap()
+        .parse()
+        .map_err(|_| "could not parse real component of complex number.".to_string())?;
+
+    let im: T = parts
+        .next()
+        .unwrap()
+        .parse()
+        .map_err(|_| "could not parse imaginary component of complex number.".to_string())?;
+
+    Ok(Complex::new(re, im))
+}
+
+fn main() -> clap::error::Result<(), clap::Error> {
+    let cli = Cli::parse();
+    let _profiler = Profiler::start();
+
+    if cli.threads == Some(0) {
+        let err = Cli::command().error(ErrorKind::InvalidValue, "--threads must be at least 1");
+        return Ok(err.print()?);
+    }
+
+    // The number of CPUs is limited by the affinity mask and cgroup quota of the process, so a
+    // renderer in a container doesn't start more threads than it gets CPU time for
+    let threads = cli.threads.unwrap_or_else(num_cpus::get);
+    if cli.numa {
+        let topology = Topology::detect().limit(threads);
+        println!(
+            "Pinning {} threads to {} NUMA nodes.",
+            topology.cpus(),
+            topology.nodes.len()
+        );
+        if let Err(e) = numa::install(topology) {
+            let err = Cli::command().error(ErrorKind::Io, format!("could not build the thread pool: {e}"));
+            return Ok(err.print()?);
+        }
+    } else if let Err(e) = rayon::ThreadPoolBuilder::new().num_threads(threads).build_global() {
+        let err = Cli::command().error(ErrorKind::Io, format!("could not build the thread pool: {e}"));
+        return Ok(err.print()?);
+    }
+
+    match cli.command {
+        Commands::Generate {
+            n_iterations,
+            samples,
+            image_size,
+            mode,
+            mut file,
+            overwrite,
+            scale,
+            center,
+            julia,
+            power,
+            phoenix,
+            png,
+            hdr,
+            tiff,
+            pfm,
+            webp,
+            avif,
+            dither,
+            normalize,
+            tonemap,
+            histogram,
+            compress,
+            npy,
+            #[cfg(feature = "hdf5")]
+            hdf5,
+            zarr,
+            table,
+            nonzero,
+            outputs,
+            dzi,
+            timelapse,
+            timelapse_format,
+            manifest,
+            checkpoint,
+            checkpoint_slices,
+            autosave,
+            keep_checkpoints,
+            recover,
+            resume,
+            rotate,
+            reflect,
+            seed,
+            frame,
+            frames: frame_count,
+            rotation,
+            transform,
+            mobius,
+            noise,
+            bands,
+            fractals,
+            min_iterations,
+            orbits,
+            fractal,
+            formula,
+            escape_radius,
+            quaternion,
+            quaternion_plane,
+            weight,
+            trap,
+            trap_falloff,
+            projection,
+            precision,
+            #[cfg(feature = "gpu")]
+            gpu,
+            #[cfg(feature = "gpu")]
+            hybrid,
+            arc_length,
+            metropolis,
+            chains,
+            burn_in,
+            chain_swap,
+            auto_tune,
+            tune_target,
+            locator,
+            locator_output,
+            target,
+            c_plane,
+            low_memory,
+            stream_orbits,
+            streams,
+            tiles,
+            backing,
+            sparse,
+            exact_counts,
+            tiled_layout,
+            duty_cycle,
+            ignore_memory_limit,
+            max_memory,
+        } => {
+            let format = match OutputFormat::from_flags(png, hdr, tiff, pfm, webp, avif) {
+                Ok(format) => format,
+                Err(err) => return Ok(err.print()?),
+            };
+            if zarr == Some(0) {
+                let err = Cli::command().error(ErrorKind::ValueValidation, "zarr chunks must be at least a pixel wide");
+                return Ok(err.print()?);
+            }
+            if dzi == Some(0) {
+                let err = Cli::command().error(ErrorKind::ValueValidation, "dzi tiles must be at least a pixel wide");
+                return Ok(err.print()?);
+            }
+            if timelapse == Some(0) {
+                let err = Cli::command().error(ErrorKind::ValueValidation, "a timelapse needs at least a snapshot");
+                return Ok(err.print()?);
+            }
+            if checkpoint_slices == Some(0) {
+                let err = Cli::command().error(ErrorKind::ValueValidation, "a checkpoint needs at least a slice");
+                return Ok(err.print()?);
+            }
+            if !(0.0..=1.0).contains(&tune_target) {
+                let err = Cli::command().error(
+                    ErrorKind::ValueValidation,
+                    format!("--tune-target must be between 0-1, but got {}", tune_target),
+                );
+                return Ok(err.print()?);
+            }
+            if keep_checkpoints == 0 {
+                let err = Cli::command().error(ErrorKind::ValueValidation, "at least one checkpoint must be kept");
+                return Ok(err.print()?);
+            }
+            let mut raw_formats = [
+                histogram.then_some(RawFormat::Histogram(compress)),
+                npy.then_some(RawFormat::Npy),
+                zarr.map(|chunk_size| RawFormat::Zarr(chunk_size as usize)),
+                table.map(|format| RawFormat::Table { format, nonzero }),
+            ]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+            #[cfg(feature = "hdf5")]
+            if hdf5 {
+                raw_formats.push(RawFormat::Hdf5);
+            }
+
+            // Every output shares the file name of the image, so only one file is written per extension
+            let mut previews = Vec::<OutputFormat>::new();
+            for output in outputs {
+                let output = match output {
+                    Output::Raw(RawFormat::Table { format, .. }) => Output::Raw(RawFormat::Table { format, nonzero }),
+                    Output::Raw(RawFormat::Histogram(None)) => Output::Raw(RawFormat::Histogram(compress)),
+                    output => output,
+                };
+                match output {
+                    Output::Image(preview) if preview == format || previews.contains(&preview) => {},
+                    Output::Raw(raw) if raw_formats.contains(&raw) => {},
+                    Output::Image(preview) => previews.push(preview),
+                    Output::Raw(raw) => raw_formats.push(raw),
+                }
+            }
+            let extensions = std::iter::once(format.extension())
+                .chain(previews.iter().map(|preview| preview.extension()))
+                .chain(raw_formats.iter().map(|raw| raw.extension()))
+                .collect::<Vec<_>>();
+            if (1..extensions.len()).any(|i| extensions[..i].contains(&extensions[i])) {
+                let err = Cli::command().error(
+                    ErrorKind::ArgumentConflict,
+                    "only one file can be written per format, so a format can't be listed with different options",
+                );
+                return Ok(err.print()?);
+            }
+            let locator = locator.map(|width| width as usize);
+            let dzi = dzi.map(|tile_size| tile_size as usize);
+            let im_width = image_size as usize;
+            let im_size = im_width * im_width;
+            file.set_extension(format.extension());
+
+            let Some(transform) = Transforms::new(&transform) else {
+                let err = Cli::command().error(
+                    ErrorKind::TooManyValues,
+                    format!("--transform takes at most {} transforms", Transforms::MAX_LEN),
+                );
+                return Ok(err.print()?);
+            };
+
+            let origins_weight = match (target, c_plane) {
+                (Some(_), true) => {
+                    let err = Cli::command().error(
+                        ErrorKind::ArgumentConflict,
+                        "--target and --c-plane can't be used together, since they weight sampled points differently",
+                    );
+                    return Ok(err.print()?);
+                },
+                (Some(target), false) => Some(OriginsWeight::Target(target)),
+                (None, true) => Some(OriginsWeight::Contribution),
+                (None, false) => None,
+            };
+            let origins_suffix = origins_weight.map(|weight| match weight {
+                OriginsWeight::Target(_) => "origins",
+                OriginsWeight::Contribution => "c-plane",
+            });
+
+            if mobius.is_some() && locator.is_some() {
+                let err = Cli::command().error(
+                    ErrorKind::ArgumentConflict,
+                    "--locator can't be used with --mobius, since the image's frame isn't a square of the full set view",
+                );
+                return Ok(err.print()?);
+            }
+
+            // A resumed render keeps writing checkpoints to the file it was resumed from
+            let keep_checkpoints = keep_checkpoints as usize;
+            let checkpoint = checkpoint
+                .or(resume.clone())
+                .or_else(|| autosave.map(|_| suffixed(&file, "checkpoint", histogram::EXTENSION)));
+            let mut resume = resume;
+
+            // An incomplete checkpoint where checkpoints are written was left by an interrupted render, which is
+            // continued rather than overwritten unless asked to start over
+            if let (None, Some(path)) = (&resume, &checkpoint) {
+                if let Ok((found, _)) = Checkpoint::load_newest(path, keep_checkpoints) {
+                    if !found.is_complete() {
+                        let question = format!(
+                            "Found an incomplete checkpoint {:?} at slice {} of {}. Resume it?",
+                            path,
+                            found.slice + 1,
+                            found.slices
+                        );
+                        let resumed = if recover {
+                            true
+                        } else if io::stdin().is_terminal() {
+                            confirm(&question, true)?
+                        } else if !overwrite {
+                            let err = Cli::command().error(
+                                ErrorKind::ValueValidation,
+                                format!(
+                                    "found an incomplete checkpoint {:?}. to continue it, use --recover, or to start \
+                                     over, use the -o flag",
+                                    path
+                                ),
+                            );
+                            return Ok(err.print()?);
+                        } else {
+                            false
+                        };
+
+                        if resumed {
+                            resume = Some(path.clone());
+                        }
+                    }
+                }
+            }
+
+            let resumed = match &resume {
+                Some(path) => match Checkpoint::load_newest(path, keep_checkpoints) {
+                    Ok((resumed, read)) => {
+                        if &read != path {
+                            println!("The newest checkpoint can't be read, so resuming from {:?}.", read);
+                        }
+                        Some(resumed)
+                    },
+                    Err(e) => {
+                        let err = Cli::command().error(
+                            ErrorKind::Io,
+                            format!("could not read the checkpoint {:?}: {}", path, e),
+                        );
+                        return Ok(err.print()?);
+                    },
+                },
+                None => None,
+            };
+
+            // A resumed render takes the seed of its checkpoint, since a random seed would never match it
+            let seed = seed
+                .or_else(|| resumed.as_ref()?.parameters.get("seed")?.parse().ok())
+                .unwrap_or_else(|| {
+                    let seed = rand::random();
+                    println!("Using seed {}.", seed);
+                    seed
+                });
+            let mut settings = SampleSettings {
+                n: n_iterations,
+                min_iterations,
+                m: samples,
+                scale: scale.unwrap_or(fractal.view().scale),
+                center: center.as_ref().map(deep::approx).unwrap_or(fractal.view().center),
+                julia,
+                power,
+                phoenix,
+                duty_cycle,
+                noise: FrameNoise::new(seed, frame, noise),
+                rotation: rotation.as_ref().map(|rotation| rotation.value.powi(frame)),
+                transform,
+                mobius,
+                orbits,
+                fractal,
+                weight,
+                trap: OrbitTrap::new(trap, trap_falloff),
+                projection,
+                precision: precision.unwrap_or_default(),
+                sampler: if metropolis {
+                    Sampler::Metropolis(MetropolisSettings {
+                        chains,
+                        burn_in,
+                        swap_interval: chain_swap,
+                    })
+                } else {
+                    Sampler::Uniform
+                },
+                streaming: stream_orbits,
+                streams: streams.map(|streams| streams as usize),
+                ..Default::default()
+            };
+
+            // The parameters of `generate` that the sample settings don't hold are recorded as they were given,
+            // along with the exact center of deep zooms, which the settings only hold approximately
+            let mut given_parameters = Parameters::new();
+            given_parameters.insert("width".to_owned(), im_width.to_string());
+            given_parameters.insert("mode".to_owned(), histogram::value_name(&mode));
+            if let Some(center) = &center {
+                given_parameters.insert("center".to_owned(), format!("{},{}", center.re, center.im));
+            }
+            if let Some(rotation) = &rotation {
+                given_parameters.insert("rotation".to_owned(), rotation.text.clone());
+            }
+            if let Some(formula) = &formula {
+                given_parameters.insert("formula".to_owned(), formula.to_string());
+                given_parameters.insert("escape_radius".to_owned(), escape_radius.to_string());
+            }
+            if let Some(quaternion) = quaternion {
+                given_parameters.insert("quaternion".to_owned(), format!("{},{}", quaternion.re, quaternion.im));
+                given_parameters.insert("quaternion_plane".to_owned(), histogram::value_name(&quaternion_plane));
+            }
+            let render_parameters = |settings: &SampleSettings| {
+                let mut parameters = histogram::parameters(settings);
+                parameters.extend(given_parameters.clone());
+                parameters
+            };
+
+            // The file name is a template filled in with the parameters of every frame, and each frame of
+            // an animation is written to its own numbered file unless the template already numbers them
+            let template = file.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+            let numbered = frame_count > 1 && !template.contains("{frame}");
+            let (date, time) = utc_now();
+            let mut files = Vec::new();
+            for frame in frame..frame + frame_count.max(1) {
+                let mut parameters = render_parameters(&SampleSettings {
+                    noise: FrameNoise::new(seed, frame, noise),
+                    ..settings
+                });
+                for (key, value) in [("date", &date), ("time", &time)] {
+                    parameters.insert(key.to_owned(), value.clone());
+                }
+
+                let name = match expand_template(&template, &parameters) {
+                    Ok(name) => name,
+                    Err(e) => {
+                        let err = Cli::command().error(ErrorKind::ValueValidation, e);
+                        return Ok(err.print()?);
+                    },
+                };
+                let name = if numbered {
+                    format!("{}-{:04}", name, frame)
+                } else {
+                    name
+                };
+                files.push((frame, file.with_file_name(format!("{}.{}", name, format.extension()))));
+            }
+
+            // The images plotted alongside the main one are written next to it with a suffix
+            let separate_locator = locator.is_some() && locator_output == LocatorOutput::Separate;
+            let suffixes = [
+                arc_length.then_some("arc-length"),
+                separate_locator.then_some("locator"),
+                origins_suffix,
+            ];
+            for (_, file) in files.iter() {
+                let suffixed_files = suffixes
+                    .into_iter()
+                    .flatten()
+                    .map(|suffix| suffixed(file, suffix, format.extension()));
+                let preview_files = previews.iter().map(|preview| file.with_extension(preview.extension()));
+                let raw_files = raw_formats.iter().map(|format| file.with_extension(format.extension()));
+                let manifest_file = manifest.then(|| file.with_extension("json"));
+                let dzi_files = dzi
+                    .into_iter()
+                    .flat_map(|_| [Pyramid::descriptor_path(file), Pyramid::tiles_dir(file)]);
+                let files = std::iter::once(file.clone())
+                    .chain(suffixed_files)
+                    .chain(preview_files)
+                    .chain(raw_files);
+                for file in files.chain(manifest_file).chain(dzi_files) {
+                    if file.exists() && !overwrite {
+                        let err = Cli::command().error(
+                            ErrorKind::ValueValidation,
+                            format!("file {:?} already exists. to overwrite it, use the -o flag", file),
+                        );
+                        return Ok(err.print()?);
+                    }
+                }
+            }
+
+            let quaternion = quaternion.map(|offset| {
+                CustomIteration::Quaternion(QuaternionSlice {
+                    j: offset.re,
+                    k: offset.im,
+                    plane: quaternion_plane,
+                })
+            });
+            let custom = match formula {
+                Some(_) if quaternion.is_some() => {
+                    let err = Cli::command().error(
+                        ErrorKind::ArgumentConflict,
+                        "--formula and --quaternion each replace the fractal, so only one of them can be used",
+                    );
+                    return Ok(err.print()?);
+                },
+                Some(formula) => Some(CustomIteration::Formula(formula.with_escape_radius(escape_radius))),
+                None => quaternion,
+            };
+            #[cfg(feature = "gpu")]
+            let custom = match custom {
+                Some(custom) if gpu => {
+                    let err = Cli::command().error(
+                        ErrorKind::ArgumentConflict,
+                        format!("--gpu can't be used with {}", custom.flag()),
+                    );
+                    return Ok(err.print()?);
+                },
+                None if gpu => match gpu::devices().and_then(|gpus| {
+                    gpus.into_iter()
+                        .map(|gpu| gpu.fits(im_width, im_width).map(|_| gpu))
+                        .collect::<Result<Vec<_>, _>>()
+                }) {
+                    Ok(mut gpus) => {
+                        if hybrid {
+                            gpus.push(Arc::new(CpuSampler::new()));
+                        }
+
+                        let names: Vec<_> = gpus.iter().map(|gpu| gpu.name()).collect();
+                        println!("Sampling orbits on {}.", names.join(", "));
+                        Some(CustomIteration::Gpu(gpus))
+                    },
+                    Err(e) => {
+                        let err = Cli::command().error(ErrorKind::InvalidValue, format!("--gpu: {}", e));
+                        return Ok(err.print()?);
+                    },
+                },
+                custom => custom,
+            };
+
+            // Only the built-in fractals can be iterated in more than single precision, and only the
+            // mandelbrot set relative to the center of the view in arbitrary precision
+            let pixel_size = settings.pixel_size(im_width);
+            let best = match Precision::for_pixel_size(pixel_size) {
+                _ if custom.is_some() || low_memory || auto_tune => Precision::Single,
+                Precision::Arbitrary
+                    if !settings.supports_arbitrary_precision() || locator.is_some() || target.is_some() || c_plane =>
+                {
+                    Precision::Double
+                },
+                precision => precision,
+            };
+            let automatic = precision.is_none();
+            let precision = precision.unwrap_or(best);
+            settings.precision = precision;
+
+            if pixel_size < precision.min_pixel_size() {
+                let advice = if best != precision {
+                    format!("render with --precision {} to avoid them", best.name())
+                } else {
+                    String::from("no precision these options can be rendered in resolves them")
+                };
+                println!(
+                    "Pixels {:.1e} across are too small for {} precision to resolve, so the image will break up into blocks; {}.",
+                    pixel_size,
+                    precision.name(),
+                    advice,
+                );
+            } else if automatic && precision != Precision::Single {
+                println!(
+                    "Iterating orbits in {} precision to resolve the view.",
+                    precision.name()
+                );
+            }
+
+            let custom = match custom {
+                None if precision == Precision::Arbitrary => Some(CustomIteration::Deep(match &center {
+                    Some(center) => DeepZoom::new(center, settings.scale),
+                    None => DeepZoom::from_settings(&settings),
+                })),
+                custom => custom,
+            };
+
+            if let Some(custom) = &custom {
+                let quaternion = matches!(custom, CustomIteration::Quaternion(_));
+                let deep = matches!(custom, CustomIteration::Deep(_));
+                #[cfg(feature = "gpu")]
+                let gpu = matches!(custom, CustomIteration::Gpu(_));
+                let conflict = if low_memory {
+                    Some("--low-memory")
+                } else if auto_tune {
+                    Some("--auto-tune")
+                } else {
+                    match mode {
+                        ColorChannelMode::Composite => Some("composite mode"),
+                        ColorChannelMode::Basins => Some("basins mode"),
+                        ColorChannelMode::Periods if quaternion => Some("periods mode"),
+                        #[cfg(feature = "gpu")]
+                        ColorChannelMode::Nebulabrot | ColorChannelMode::Periods if gpu => {
+                            Some("nebulabrot or periods mode")
+                        },
+                        _ if precision == Precision::Double => Some("--precision f64"),
+                        _ if !deep && precision == Precision::Arbitrary => Some("--precision arbitrary"),
+                        _ if deep && (locator.is_some() || target.is_some() || c_plane) => {
+                            Some("--locator, --target or --c-plane")
+                        },
+                        _ if quaternion && metropolis => Some("--metropolis"),
+                        _ if quaternion && (weight != OrbitWeight::Hits || arc_length) => {
+                            Some("--weight or --arc-length")
+                        },
+                        _ if quaternion
+                            && (projection != Projection::ZrZi || rotation.is_some() || !transform.is_identity()) =>
+                        {
+                            Some("--projection, --rotation or --transform")
+                        },
+                        _ if quaternion && (power != 2.0 || fractal != Fractal::default()) => {
+                            Some("--power or --fractal")
+                        },
+                        _ if quaternion && (target.is_some() || c_plane) => Some("--target or --c-plane"),
+                        #[cfg(feature = "gpu")]
+                        _ if gpu && (arc_length || locator.is_some() || target.is_some() || c_plane) => {
+                            Some("--arc-length, --locator, --target or --c-plane")
+                        },
+                        #[cfg(feature = "gpu")]
+                        _ if gpu => gpu::unsupported_feature(&settings),
+                        _ => None,
+                    }
+                };
+
+                if let Some(conflict) = conflict {
+                    let err = Cli::command().error(
+                        ErrorKind::ArgumentConflict,
+                        format!("{} can't be used with {}", custom.flag(), conflict),
+                    );
+                    return Ok(err.print()?);
+                }
+            }
+            let custom = custom.as_ref();
+
+            if auto_tune {
+                let tune = TuneSettings {
+                    target: tune_target,
+                    ..Default::default()
+                };
+                let report = tune_iterations(&settings, &tune.up_to(n_iterations));
+                print_tune_report(&report);
+                settings.n = report.recommended;
+            }
+            let n_iterations = settings.n;
+
+            if mode == ColorChannelMode::Nebulabrot {
+                let bands =
+                    bands.unwrap_or_else(|| Nebulabrot::new(n_iterations, n_iterations / 10, n_iterations / 100));
+                settings.n = bands.max();
+                settings.nebulabrot = Some(bands);
+
+                if arc_length {
+                    let err = Cli::command().error(
+                        ErrorKind::ArgumentConflict,
+                        "--arc-length can't be used in nebulabrot mode, since every channel is already in use",
+                    );
+                    return Ok(err.print()?);
+                }
+            }
+
+            if mode == ColorChannelMode::Composite {
+                settings.channel_fractals = Some(fractals.unwrap_or(ChannelFractals::new(
+                    Fractal::Mandelbrot,
+                    Fractal::BurningShip,
+                    Fractal::Tricorn,
+                )));
+
+                if arc_length {
+                    let err = Cli::command().error(
+                        ErrorKind::ArgumentConflict,
+                        "--arc-length can't be used in composite mode, since every channel is already in use",
+                    );
+                    return Ok(err.print()?);
+                }
+
+                if metropolis {
+                    let err = Cli::command().error(
+                        ErrorKind::ArgumentConflict,
+                        "--metropolis can't be used in composite mode, since each channel follows a different orbit",
+                    );
+                    return Ok(err.print()?);
+                }
+            }
+
+            if mode == ColorChannelMode::Basins {
+                settings.basins = true;
+
+                if arc_length {
+                    let err = Cli::command().error(
+                        ErrorKind::ArgumentConflict,
+                        "--arc-length can't be used in basins mode, since every channel is already in use",
+                    );
+                    return Ok(err.print()?);
+                }
+            }
+
+            if mode == ColorChannelMode::Periods {
+                settings.periods = true;
+
+                if arc_length {
+                    let err = Cli::command().error(
+                        ErrorKind::ArgumentConflict,
+                        "--arc-length can't be used in periods mode, since every channel is already in use",
+                    );
+                    return Ok(err.print()?);
+                }
+            }
+
+            if exact_counts {
+                let conflict = if arc_length {
+                    Some("--arc-length")
+                } else if weight != OrbitWeight::Hits {
+                    Some("--weight")
+                } else if metropolis {
+                    Some("--metropolis")
+                } else if !matches!(mode, ColorChannelMode::R | ColorChannelMode::Rg | ColorChannelMode::Rgb) {
+                    Some("nebulabrot, composite, basins or periods mode")
+                } else {
+                    None
+                };
+
+                if let Some(conflict) = conflict {
+                    let err = Cli::command().error(
+                        ErrorKind::ArgumentConflict,
+                        format!(
+                            "--exact-counts only counts whole hits, so it can't be used with {}",
+                            conflict
+                        ),
+                    );
+                    return Ok(err.print()?);
+                }
+            }
+
+            if streams == Some(0) {
+                let err = Cli::command().error(ErrorKind::ValueValidation, "streams must be at least 1");
+                return Ok(err.print()?);
+            }
+
+            let accumulation = Accumulation {
+                sparse,
+                exact: exact_counts,
+                tiled: tiled_layout,
+            };
+
+            if low_memory {
+                if arc_length || locator.is_some() || target.is_some() || c_plane {
+                    let err = Cli::command().error(
+                        ErrorKind::ArgumentConflict,
+                        "--low-memory only renders the image itself, so it can't be used with --arc-length, --locator, \
+                         --target or --c-plane",
+                    );
+                    return Ok(err.print()?);
+                }
+
+                if let Err(e) = countable(&settings) {
+                    let err = Cli::command().error(ErrorKind::ArgumentConflict, format!("--low-memory: {}", e));
+                    return Ok(err.print()?);
+                }
+
+                if tiles.is_some() && (reflect || rotate) {
+                    let err = Cli::command().error(
+                        ErrorKind::ArgumentConflict,
+                        "--tiles never holds the whole image in memory, so it can't be reflected or rotated",
+                    );
+                    return Ok(err.print()?);
+                }
+
+                if tiles.is_some() && backing.is_some() {
+                    let err = Cli::command().error(
+                        ErrorKind::ArgumentConflict,
+                        "--tiles already keeps all but a single tile on disk, so it can't be used with --backing",
+                    );
+                    return Ok(err.print()?);
+                }
+
+                if tiles == Some(0) {
+                    let err = Cli::command().error(ErrorKind::ValueValidation, "tiles must be at least a pixel wide");
+                    return Ok(err.print()?);
+                }
+            }
+
+            // Fail fast instead of rendering an empty image, checking every channel that will be rendered
+            let channels: &[&str] = match mode {
+                ColorChannelMode::R => &["red channel"],
+                ColorChannelMode::Rg => &["red channel", "green channel"],
+                ColorChannelMode::Rgb => &["red channel", "green channel", "blue channel"],
+                ColorChannelMode::Nebulabrot
+                | ColorChannelMode::Composite
+                | ColorChannelMode::Basins
+                | ColorChannelMode::Periods => &["image"],
+            };
+            for (channel, name) in channels.iter().enumerate() {
+                let settings = SampleSettings {
+                    n: settings.n / 10u32.pow(channel as u32),
+                    ..settings
+                };
+
+                if let Err(e) = settings.validate(im_width, im_width) {
+                    return Ok(invalid_settings(name, e).print()?);
+                }
+            }
+
+            // Check that the render fits in memory now rather than running out partway through it
+            let threads = rayon::current_num_threads();
+            let point = match settings.precision {
+                Precision::Single => mem::size_of::<Complex<f32>>(),
+                _ => mem::size_of::<Complex<f64>>(),
+            };
+            let trajectory = if settings.streaming { 0 } else { settings.n };
+            let encoded = match format {
+                OutputFormat::Png | OutputFormat::Webp | OutputFormat::Avif(_) => 3,
+                OutputFormat::Tiff(TiffDepth::Integer) => 6,
+                OutputFormat::Exr | OutputFormat::Hdr | OutputFormat::Tiff(TiffDepth::Float) | OutputFormat::Pfm => {
+                    mem::size_of::<Rgb>()
+                },
+            };
+            let estimate_for = |shared: bool, tiles: Option<u32>| {
+                if shared {
+                    // Threads only keep their counters, and share every image
+                    let counted = shared::channels(&settings);
+                    let (images, output) = match tiles {
+                        Some(size) => {
+                            // Stitching keeps a row of tiles in memory
+                            let side = (size as usize).min(im_width) * counted * channels.len() * mem::size_of::<f32>();
+                            (side * (size as usize).min(im_width), side * im_width)
+                        },
+                        None if backing.is_some() => (0, im_size * counted * channels.len() * mem::size_of::<f32>()),
+                        None => (
+                            im_size * counted * mem::size_of::<AtomicU32>(),
+                            im_size * counted * channels.len() * mem::size_of::<f32>(),
+                        ),
+                    };
+                    MemoryEstimate {
+                        images: images + threads * shared::thread_memory(counted),
+                        trajectories: threads * trajectory as usize * point,
+                        output,
+                    }
+                } else {
+                    let pixel = match mode {
+                        ColorChannelMode::R | ColorChannelMode::Rg | ColorChannelMode::Rgb => {
+                            if arc_length {
+                                mem::size_of::<Rg>()
+                            } else if exact_counts {
+                                mem::size_of::<Count>()
+                            } else {
+                                mem::size_of::<f32>()
+                            }
+                        },
+                        _ => mem::size_of::<Rgb>(),
+                    };
+                    let locator_size = locator.map_or(0, |width| width * width);
+                    let origins_size = origins_weight.map_or(0, |_| im_size);
+                    let canvas = (im_size + locator_size + origins_size) * pixel;
+
+                    // Every channel is kept once rendered, then fused into the image written along with
+                    // every additional image
+                    let finished = im_size * channels.len() * mem::size_of::<f32>();
+                    let written =
+                        (im_size * (1 + arc_length as usize) + locator_size + origins_size) * mem::size_of::<Rgb>();
+                    let accumulators = streams.map_or(threads, |streams| streams as usize);
+                    MemoryEstimate::new(
+                        canvas,
+                        accumulators,
+                        threads,
+                        trajectory,
+                        point,
+                        finished + written + im_size * encoded,
+                    )
+                }
+            };
+
+            // With a memory cap, fall back from an image per thread to one image of counters shared by
+            // every thread, and then to ever smaller tiles, until the render fits. Only --low-memory
+            // itself caps the samples per pixel, so the cap never changes what is rendered
+            let (shared, tiles) = match max_memory {
+                Some(max) => {
+                    let shareable = custom.is_none()
+                        && precision == Precision::Single
+                        && !(arc_length || locator.is_some() || target.is_some() || c_plane)
+                        && timelapse.is_none()
+                        && checkpoint.is_none()
+                        && countable(&settings).is_ok();
+                    let tileable = backing.is_none() && !reflect && !rotate;
+
+                    let mut candidates = Vec::new();
+                    if !low_memory {
+                        candidates.push((false, None));
+                    }
+                    if tiles.is_some() {
+                        candidates.push((true, tiles));
+                    } else if low_memory || shareable {
+                        candidates.push((true, None));
+                        if tileable {
+                            let sizes = std::iter::successors(Some(image_size / 2), |size| Some(size / 2));
+                            candidates.extend(
+                                sizes
+                                    .take_while(|&size| size >= MIN_TILE_SIZE)
+                                    .map(|size| (true, Some(size))),
+                            );
+                        }
+                    }
+
+                    let Some(choice) = candidates
+                        .iter()
+                        .copied()
+                        .find(|&(shared, tiles)| estimate_for(shared, tiles).total() <= max)
+                    else {
+                        let (shared, tiles) = *candidates.last().unwrap();
+                        let err = Cli::command().error(
+                            ErrorKind::ValueValidation,
+                            format!(
+                                "the render is expected to need at least {}, more than the {} allowed by --max-memory",
+                                format_bytes(estimate_for(shared, tiles).total()),
+                                format_bytes(max)
+                            ),
+                        );
+                        return Ok(err.print()?);
+                    };
+
+                    match choice {
+                        (true, Some(size)) if tiles.is_none() => {
+                            println!(
+                                "Rendering in tiles of {} pixels to stay within {}.",
+                                size,
+                                format_bytes(max)
+                            )
+                        },
+                        (true, None) if !low_memory => println!(
+                            "Rendering into counters shared by every thread to stay within {}.",
+                            format_bytes(max)
+                        ),
+                        _ => (),
+                    }
+                    choice
+                },
+                None => (low_memory, tiles),
+            };
+            if shared && timelapse.is_some() {
+                let err = Cli::command().error(
+                    ErrorKind::ArgumentConflict,
+                    "the low-memory profile writes each channel as soon as it's sampled, so it can't take the snapshots of a \
+                     timelapse",
+                );
+                return Ok(err.print()?);
+            }
+
+            if checkpoint.is_some() {
+                let conflict = if shared {
+                    Some("the low-memory profile")
+                } else if timelapse.is_some() {
+                    Some("--timelapse")
+                } else if frame_count > 1 {
+                    Some("--frames")
+                } else if arc_length || locator.is_some() || target.is_some() || c_plane {
+                    Some("--arc-length, --locator, --target or --c-plane")
+                } else {
+                    None
+                };
+
+                if let Some(conflict) = conflict {
+                    let err = Cli::command().error(
+                        ErrorKind::ArgumentConflict,
+                        format!(
+                            "checkpoints only keep the image itself, sampled a slice at a time, so they can't be used with {}",
+                            conflict
+                        ),
+                    );
+                    return Ok(err.print()?);
+                }
+            }
+            let estimate = estimate_for(shared, tiles);
+            if low_memory && settings.m > LOW_MEMORY_SAMPLES {
+                println!(
+                    "Reducing samples per pixel from {} to {} for the low-memory profile.",
+                    settings.m, LOW_MEMORY_SAMPLES
+                );
+                settings.m = LOW_MEMORY_SAMPLES;
+            }
+
+            println!(
+                "Expecting the render to need up to {} of memory for {} of images, {} of trajectories and {} of output{}.",
+                format_bytes(estimate.total()),
+                format_bytes(estimate.images),
+                format_bytes(estimate.trajectories),
+                format_bytes(estimate.output),
+                if sparse {
+                    ", though sparse images may need far less"
+                } else {
+                    ""
+                }
+            );
+            if let Some(available) = available_memory() {
+                if estimate.total() > available && !sparse && !ignore_memory_limit {
+                    let err = Cli::command().error(
+                        ErrorKind::ValueValidation,
+                        format!(
+                            "the render is expected to need {} of memory, but only {} is available. to use less, \
+                             try --low-memory, --sparse or --tiles, or to render anyway, use --ignore-memory-limit",
+                            format_bytes(estimate.total()),
+                            format_bytes(available)
+                        ),
+                    );
+                    return Ok(err.print()?);
+                }
+
+                if estimate.total() > available / 4 * 3 {
+                    println!(
+                        "Warning: the render is expected to use most of the {} of memory available.",
+                        format_bytes(available)
+                    );
+                }
+            }
+
+            // A checkpoint can only be resumed with the parameters it was made with, including the size of the
+            // image and the channels it's rendered in
+            let mut checkpoint = match checkpoint {
+                Some(path) => {
+                    if Some(&path) != resume.as_ref() && path.exists() && !overwrite {
+                        let err = Cli::command().error(
+                            ErrorKind::ValueValidation,
+                            format!("file {:?} already exists. to overwrite it, use the -o flag", path),
+                        );
+                        return Ok(err.print()?);
+                    }
+
+                    let parameters = render_parameters(&settings);
+                    let state = match resumed {
+                        Some(resumed) => {
+                            let mismatches = resumed.mismatches(&parameters);
+                            if !mismatches.is_empty() {
+                                let err = Cli::command().error(
+                                    ErrorKind::ValueValidation,
+                                    format!(
+                                        "the checkpoint was made with a different {}, so it can't be resumed with these \
+                                         options",
+                                        mismatches.join(", ")
+                                    ),
+                                );
+                                return Ok(err.print()?);
+                            }
+
+                            match resumed.next_slice() {
+                                Some(slice) => println!("Resuming from slice {} of {}.", slice.index + 1, slice.count),
+                                None => println!("Every slice of the checkpoint is already sampled."),
+                            }
+                            resumed
+                        },
+                        None => {
+                            let slices = checkpoint_slices.unwrap_or(if autosave.is_some() {
+                                AUTOSAVE_SLICES
+                            } else {
+                                CHECKPOINT_SLICES
+                            });
+                            Checkpoint::new(im_width, im_width, parameters, slices as usize)
+                        },
+                    };
+                    Some((path, state))
+                },
+                None => None,
+            };
+
+            // Interrupting the render stops sampling and writes what was drawn so far instead of losing it
+            interrupt::install();
+
+            // Frames are encoded on another thread while the next one is sampled
+            let mut output = OutputQueue::new();
+            for (frame, file) in files {
+                if interrupt::interrupted() {
+                    break;
+                }
+
+                // Every frame of an animation is a frame of the profile, ending once the next starts
+                profiling::finish_frame!();
+                settings.noise = FrameNoise::new(seed, frame, noise);
+                settings.rotation = rotation.as_ref().map(|rotation| rotation.value.powi(frame));
+                if frame_count > 1 {
+                    println!("Rendering frame {}.", frame);
+                }
+
+                // Images store the parameters they were rendered with along with how they were tonemapped
+                let mut image_parameters = render_parameters(&settings);
+                for (key, enabled) in [("normalize", normalize), ("reflect", reflect), ("rotate", rotate)] {
+                    image_parameters.insert(key.to_owned(), enabled.to_string());
+                }
+                if let Some(tonemap) = &tonemap {
+                    image_parameters.insert("tonemap".to_owned(), tonemap.to_string());
+                }
+                if let Some((flag, value)) = format.flag() {
+                    image_parameters.insert(flag.to_owned(), value);
+                }
+                image_parameters.insert("dither".to_owned(), histogram::value_name(&dither));
+                if manifest {
+                    let manifest = Manifest::new(image_parameters.clone(), (im_width, im_width), settings.streams);
+                    manifest.write(file.with_extension("json"))?;
+                }
+
+                if shared {
+                    let passes = (0..channels.len() as u32)
+                        .map(|channel| SampleSettings {
+                            n: settings.n / 10u32.pow(channel),
+                            ..settings
+                        })
+                        .collect::<Vec<_>>();
+
+                    if let Some(tile_size) = tiles {
+                        let grid = TileGrid::new(
+                            file.with_extension("tiles"),
+                            im_width,
+                            im_width,
+                            tile_size as usize,
+                            passes.iter().map(shared::channels).sum(),
+                        );
+
+                        let start_time = std::time::Instant::now();
+                        grid.render(&passes).unwrap();
+                        let elapsed = start_time.elapsed();
+                        println!(
+                            "Finished rendering buddhabrot in {}.",
+                            humantime::format_duration(std::time::Duration::new(elapsed.as_secs(), 0))
+                        );
+
+                        if file.exists() && overwrite {
+                            std::fs::remove_file(file.clone()).unwrap();
+                        }
+
+                        // Every frame has its own tiles, so they're stitched while the next frame renders
+                        let parameters = render_parameters(&settings);
+                        let (raw_formats, previews) = (raw_formats.clone(), previews.clone());
+                        output.write(move || {
+                            for format in raw_formats {
+                                let raw = grid.stitch(false).unwrap();
+                                let raw_file = file.with_extension(format.extension());
+                                let pixel = |x, y| Rgb::from(raw.get((x, y)));
+                                format.write(raw_file, im_width, im_width, &parameters, pixel).unwrap();
+                            }
+
+                            for preview in previews {
+                                let normalized = normalize || preview.clamps();
+                                let mut parameters = image_parameters.clone();
+                                parameters.insert("normalize".to_owned(), normalized.to_string());
+
+                                let stitched = grid.stitch(normalized).unwrap();
+                                let preview_file = file.with_extension(preview.extension());
+                                let pixel = |x, y| stitched.get((x, y));
+                                write_pixels(im_width, im_width, pixel, preview_file, preview, dither, &parameters);
+                            }
+
+                            if let Some(tile_size) = dzi {
+                                let stitched = grid.stitch(true).unwrap();
+                                let pixel = |x, y| stitched.get((x, y));
+                                write_pyramid(im_width, im_width, pixel, &file, tile_size, dither);
+                            }
+
+                            let stitched = grid.stitch(normalize).unwrap();
+                            let pixel = |x, y| stitched.get((x, y));
+                            write_pixels(im_width, im_width, pixel, file, format, dither, &image_parameters);
+                            drop(stitched);
+                            grid.remove().unwrap();
+                        });
+                        continue;
+                    }
+
+                    let start_time = std::time::Instant::now();
+                    let mut ims = passes
+                        .into_iter()
+                        .flat_map(|settings| {
+                            let channels = shared::channels(&settings);
+                            let im = match &backing {
+                                Some(path) => SharedImage::mapped(path, im_size, im_width, channels).unwrap(),
+                                None => SharedImage::new(im_size, im_width, channels),
+                            };
+                            sample_shared(&im, settings);
+                            im.into_images()
+                        })
+                        .collect::<Vec<_>>();
+                    let elapsed = start_time.elapsed();
+                    println!(
+                        "Finished rendering buddhabrot in {}.",
+                        humantime::format_duration(std::time::Duration::new(elapsed.as_secs(), 0))
+                    );
+
+                    for &format in raw_formats.iter() {
+                        let (raw, raw_file) = (ims.clone(), file.with_extension(format.extension()));
+                        let parameters = render_parameters(&settings);
+                        output.write(move || write_raw_channels(&raw, raw_file, format, &parameters).unwrap());
+                    }
+
+                    for im in ims.iter_mut() {
+                        if let Some(tonemap) = &tonemap {
+                            tonemap.apply(im);
+                        } else if normalize {
+                            normalize_im(im);
+                        }
+
+                        if reflect {
+                            reflect_im(im);
+                        }
+
+                        if rotate {
+                            rotate_im(im);
+                        }
+                    }
+
+                    if file.exists() && overwrite {
+                        std::fs::remove_file(file.clone()).unwrap();
+                    }
+
+                    if let Some(path) = &backing {
+                        std::fs::remove_file(path).unwrap();
+                    }
+
+                    for &preview in previews.iter() {
+                        let normalized = normalize || (tonemap.is_none() && preview.clamps());
+                        let mut parameters = image_parameters.clone();
+                        parameters.insert("normalize".to_owned(), normalized.to_string());
+
+                        let mut ims = ims.clone();
+                        if normalized && !normalize {
+                            ims.iter_mut().for_each(normalize_im);
+                        }
+                        let preview_file = file.with_extension(preview.extension());
+                        output.write(move || write_channels(&ims, preview_file, preview, dither, &parameters));
+                    }
+
+                    if let Some(tile_size) = dzi {
+                        let mut ims = ims.clone();
+                        if !normalize && tonemap.is_none() {
+                            ims.iter_mut().for_each(normalize_im);
+                        }
+                        let file = file.clone();
+                        output.write(move || {
+                            write_pyramid(im_width, im_width, channel_colors(&ims), &file, tile_size, dither)
+                        });
+                    }
+                    output.write(move || write_channels(&ims, file, format, dither, &image_parameters));
+                    continue;
+                }
+
+                let start_time = std::time::Instant::now();
+                let render = |settings: SampleSettings| {
+                    let mut arc = None;
+                    let mut locator_im = None;
+                    let mut origins = None;
+                    let mut render_primary = |settings| {
+                        let primary = render_primary(
+                            settings,
+                            custom,
+                            im_width,
+                            accumulation,
+                            arc_length,
+                            locator,
+                            origins_weight,
+                        );
+                        arc = primary.arc;
+                        locator_im = primary.locator;
+                        origins = primary.origins;
+                        primary.image
+                    };
+
+                    let im = match mode {
+                        ColorChannelMode::R => {
+                            let im1 = render_primary(settings);
+                            fuse(im1.clone(), im1.clone(), im1)
+                        },
+                        ColorChannelMode::Rg => {
+                            let im1 = render_primary(settings);
+                            let im2 = render_channel(
+                                SampleSettings {
+                                    n: n_iterations / 10,
+                                    ..settings
+                                },
+                                custom,
+                                im_width,
+                                accumulation,
+                            );
+                            fuse(im1, im2, Image::<f32>::new(im_size, im_width))
+                        },
+                        ColorChannelMode::Rgb => {
+                            let im1 = render_primary(settings);
+                            let im2 = render_channel(
+                                SampleSettings {
+                                    n: n_iterations / 10,
+                                    ..settings
+                                },
+                                custom,
+                                im_width,
+                                accumulation,
+                            );
+                            let im3 = render_channel(
+                                SampleSettings {
+                                    n: n_iterations / 100,
+                                    ..settings
+                                },
+                                custom,
+                                im_width,
+                                accumulation,
+                            );
+                            fuse(im1, im2, im3)
+                        },
+                        ColorChannelMode::Nebulabrot
+                        | ColorChannelMode::Composite
+                        | ColorChannelMode::Basins
+                        | ColorChannelMode::Periods => {
+                            let canvas = blank_canvas::<Rgb>(im_width, accumulation, locator, origins_weight);
+                            let mut canvas = render_canvas(canvas, settings, custom);
+                            locator_im = canvas.layers.pop().map(|layer| layer.image);
+                            origins = canvas.origins.map(|origins| origins.image);
+                            canvas.main
+                        },
+                    };
+                    (im, arc, locator_im, origins)
+                };
+
+                let (mut im, arc, locator_im, mut origins) = match timelapse {
+                    None => match checkpoint.as_mut() {
+                        None => render(settings),
+                        Some((path, state)) => {
+                            // The checkpoint is written after every slice, or once the interval to save it at has
+                            // passed, so an interruption loses at most that much
+                            let mut saved = std::time::Instant::now();
+                            let (mut unsaved, mut partial) = (!path.exists(), None);
+                            while let Some(slice) = state.next_slice() {
+                                println!("Rendering slice {} of {}.", slice.index + 1, slice.count);
+                                let (im, ..) = render(SampleSettings {
+                                    slice: Some(slice),
+                                    ..settings
+                                });
+                                if interrupt::interrupted() {
+                                    partial = Some(im);
+                                    break;
+                                }
+
+                                state.add_slice(im);
+                                unsaved = true;
+                                if state.is_complete() || autosave.is_none_or(|interval| saved.elapsed() >= interval) {
+                                    state.save_rotated(path, keep_checkpoints, compress)?;
+                                    (saved, unsaved) = (std::time::Instant::now(), false);
+                                }
+                            }
+
+                            // An interrupted render saves every slice it finished, while the hits of the slice it
+                            // cut short are only written with the image
+                            if unsaved {
+                                state.save_rotated(path, keep_checkpoints, compress)?;
+                            }
+                            let mut im = mem::take(&mut state.image);
+                            if let Some(partial) = partial {
+                                im.merge(partial);
+                            }
+                            (im, None, None, None)
+                        },
+                    },
+                    Some(snapshots) => {
+                        // Every snapshot adds the next slice of the samples to those drawn before
+                        let count = snapshots as usize;
+                        let mut frames = Vec::with_capacity(count);
+                        println!("Rendering snapshot 1 of {}.", count);
+                        let (mut total, mut total_arc, mut locator_total, mut total_origins) = render(SampleSettings {
+                            slice: Some(SampleSlice { index: 0, count }),
+                            ..settings
+                        });
+                        for index in 0..count {
+                            if index > 0 && interrupt::interrupted() {
+                                break;
+                            }
+                            if index > 0 {
+                                println!("Rendering snapshot {} of {}.", index + 1, count);
+                                let slice = Some(SampleSlice { index, count });
+                                let (im, arc, locator_im, origins) = render(SampleSettings { slice, ..settings });
+                                total.merge(im);
+                                if let (Some(total), Some(arc)) = (total_arc.as_mut(), arc) {
+                                    total.merge(arc);
+                                }
+                                if let (Some(total), Some(origins)) = (total_origins.as_mut(), origins) {
+                                    total.merge(origins);
+                                }
+                                // The locator shows the full set, which is drawn anew every time
+                                locator_total = locator_im;
+                            }
+
+                            let mut snapshot = total.clone();
+                            match &tonemap {
+                                Some(tonemap) => tonemap.apply(&mut snapshot),
+                                None => normalize_im(&mut snapshot),
+                            }
+                            if reflect {
+                                reflect_im(&mut snapshot);
+                            }
+                            if rotate {
+                                rotate_im(&mut snapshot);
+                            }
+                            frames.push(quantize(
+                                im_width,
+                                im_width,
+                                |x, y| snapshot.get((x, y)).to_tuple_rgb(),
+                                dither,
+                            ));
+                        }
+
+                        let timelapse_file = suffixed(&file, "timelapse", timelapse_format.extension());
+
+                        if timelapse_file.exists() && overwrite {
+                            std::fs::remove_file(timelapse_file.clone()).unwrap();
+                        }
+
+                        let parameters = image_parameters.clone();
+                        output.write(move || write_timelapse(frames, timelapse_file, timelapse_format, &parameters));
+                        (total, total_arc, locator_total, total_origins)
+                    },
+                };
+                let mut arc = arc.map(|arc| fuse(arc.clone(), arc.clone(), arc));
+                let elapsed = start_time.elapsed();
+                println!(
+                    "Finished rendering buddhabrot in {}.",
+                    humantime::format_duration(std::time::Duration::new(elapsed.as_secs(), 0))
+                );
+                if interrupt::interrupted() {
+                    image_parameters.insert("interrupted".to_owned(), true.to_string());
+                    match &checkpoint {
+                        Some((path, state)) => println!(
+                            "Interrupted after {} of {} slices, which are saved to {:?} to continue with --resume.",
+                            state.slice, state.slices, path
+                        ),
+                        None => {
+                            println!("Interrupted. To be able to continue a render, use --checkpoint or --autosave.")
+                        },
+                    }
+                }
+
+                for &format in raw_formats.iter() {
+                    let (raw, raw_file) = (im.clone(), file.with_extension(format.extension()));
+                    let parameters = render_parameters(&settings);
+                    output.write(move || {
+                        let pixel = |x, y| raw.get((x, y));
+                        format.write(raw_file, im_width, im_width, &parameters, pixel).unwrap()
+                    });
+                }
+
+                for im in std::iter::once(&mut im).chain(arc.as_mut()).chain(origins.as_mut()) {
+                    if let Some(tonemap) = &tonemap {
+                        tonemap.apply(im);
+                    } else if normalize {
+                        normalize_im(im);
+                    }
+
+                    if reflect {
+                        reflect_im(im);
+                    }
+
+                    if rotate {
+                        rotate_im(im);
+                    }
+                }
+
+                if let Some(mut locator_im) = locator_im {
+                    normalize_im(&mut locator_im);
+                    mark_frame(
+                        &mut locator_im,
+                        Viewport::default(),
+                        settings.viewport(),
+                        Rgb::new(1.0, 1.0, 1.0),
+                    );
+
+                    if rotate {
+                        rotate_im(&mut locator_im);
+                    }
 
-        #[arg(long, value_name = "MIN_GREEN_COLOR", value_parser = parse_color)]
-        ming: (f32, f32, f32),
+                    match locator_output {
+                        LocatorOutput::Inset => paste_inset(&mut im, locator_im),
+                        LocatorOutput::Separate => {
+                            let locator_file = suffixed(&file, "locator", format.extension());
 
-        #[arg(long, value_name = "MAX_GREEN_COLOR", value_parser = parse_color)]
-        maxg: (f32, f32, f32),
+                            if locator_file.exists() && overwrite {
+                                std::fs::remove_file(locator_file.clone()).unwrap();
+                            }
 
-        #[arg(long, value_name = "MIN_BLUE_COLOR", value_parser = parse_color)]
-        minb: (f32, f32, f32),
+                            let parameters = image_parameters.clone();
+                            output.write(move || write_rgb(locator_im, locator_file, format, dither, &parameters));
+                        },
+                    }
+                }
 
-        #[arg(long, value_name = "MAX_BLUE_COLOR", value_parser = parse_color)]
-        maxb: (f32, f32, f32),
-    },
-}
+                if let Some(arc) = arc {
+                    let arc_file = suffixed(&file, "arc-length", format.extension());
 
-fn parse_complex<T>(s: &str) -> Result<Complex<T>, String>
-where
-    T: FromStr + Copy,
-{
-    let seps = s.chars().filter(|&c| c == ',').count();
+                    if arc_file.exists() && overwrite {
+                        std::fs::remove_file(arc_file.clone()).unwrap();
+                    }
 
-    if seps != 1 {
-        return Err(format!(
-            "expected complex number to have exactly one separator but got {seps}."
-        ));
-    }
+                    let parameters = image_parameters.clone();
+                    output.write(move || write_rgb(arc, arc_file, format, dither, &parameters));
+                }
 
-    let mut parts = s.split(',');
+                if let (Some(origins), Some(suffix)) = (origins, origins_suffix) {
+                    let origins_file = suffixed(&file, suffix, format.extension());
 
-    let re: T = parts
-        .next()
-        .unwrap()
-        .parse()
-        .map_err(|_| format!("could not parse real component of complex number."))?;
+                    if origins_file.exists() && overwrite {
+                        std::fs::remove_file(origins_file.clone()).unwrap();
+                    }
 
-    let im: T = parts
-        .next()
-        .unwrap()
-        .parse()
-        .map_err(|_| format!("could not parse imaginary component of complex number."))?;
+                    let parameters = image_parameters.clone();
+                    output.write(move || write_rgb(origins, origins_file, format, dither, &parameters));
+                }
 
-    Ok(Complex::new(re, im))
-}
+                if file.exists() && overwrite {
+                    std::fs::remove_file(file.clone()).unwrap();
+                }
 
-fn main() -> clap::error::Result<(), clap::Error> {
-    let cli = Cli::parse();
+                for &preview in previews.iter() {
+                    let normalized = normalize || (tonemap.is_none() && preview.clamps());
+                    let mut parameters = image_parameters.clone();
+                    parameters.insert("normalize".to_owned(), normalized.to_string());
 
-    match cli.command {
-        Commands::Generate {
+                    let mut im = im.clone();
+                    if normalized && !normalize {
+                        normalize_im(&mut im);
+                    }
+                    let preview_file = file.with_extension(preview.extension());
+                    output.write(move || write_rgb(im, preview_file, preview, dither, &parameters));
+                }
+
+                if let Some(tile_size) = dzi {
+                    let mut im = im.clone();
+                    if !normalize && tonemap.is_none() {
+                        normalize_im(&mut im);
+                    }
+                    let file = file.clone();
+                    output.write(move || {
+                        let pixel = |x, y| im.get((x, y)).to_tuple_rgb();
+                        write_pyramid(im_width, im_width, pixel, &file, tile_size, dither)
+                    });
+                }
+
+                let parameters = image_parameters.clone();
+                output.write(move || write_rgb(im, file, format, dither, &parameters));
+            }
+            drop(output);
+
+            if let Some(peak) = peak_memory() {
+                println!("Used at most {} of memory.", format_bytes(peak));
+            }
+
+            if interrupt::interrupted() {
+                println!("Wrote the samples drawn before the render was interrupted.");
+                std::process::exit(interrupt::EXIT_CODE);
+            }
+        },
+        Commands::Tune {
+            max_iterations,
+            batch,
+            min_escape,
+            target,
+            scale,
+            center,
+            seed,
+            fractal,
+            power,
+            phoenix,
+        } => {
+            let settings = SampleSettings {
+                n: max_iterations,
+                m: 0,
+                scale: scale.unwrap_or(fractal.view().scale),
+                center: center.unwrap_or(fractal.view().center),
+                fractal,
+                power,
+                phoenix,
+                noise: FrameNoise::new(seed.unwrap_or_else(rand::random), 0, NoiseCorrelation::Independent),
+                ..Default::default()
+            };
+
+            let tune = TuneSettings {
+                batch,
+                min_escape,
+                target,
+                ..Default::default()
+            };
+            let report = tune_iterations(&settings, &tune.up_to(max_iterations));
+            print_tune_report(&report);
+        },
+        Commands::Volume {
             n_iterations,
             samples,
-            image_size,
-            mode,
-            progress_update,
+            resolution,
             mut file,
             overwrite,
+            format,
+            axes,
             scale,
             center,
-            png,
             normalize,
-            rotate,
-            reflect,
+            points,
+            point_orbits,
+            point_every,
+            point_iterations,
+            julia,
+            power,
+            phoenix,
+            seed,
+            min_iterations,
+            orbits,
+            stream_orbits,
+            streams,
+            fractal,
         } => {
-            let im_width = image_size as usize;
-            let im_size = im_width * im_width;
-            let progress_update = if let Some(up) = progress_update {
-                up as usize
-            } else {
-                im_size * 2
+            if streams == Some(0) {
+                let err = Cli::command().error(ErrorKind::ValueValidation, "streams must be at least 1");
+                return Ok(err.print()?);
+            }
+            if point_every == 0 {
+                let err = Cli::command().error(ErrorKind::ValueValidation, "point-every must be at least 1");
+                return Ok(err.print()?);
+            }
+
+            file.set_extension(format.extension());
+            let points_file = points.map(|points| suffixed(&file, "points", points.extension()));
+
+            for file in std::iter::once(&file).chain(points_file.as_ref()) {
+                if file.exists() && !overwrite {
+                    let err = Cli::command().error(
+                        ErrorKind::ValueValidation,
+                        format!("file {:?} already exists. to overwrite it, use the -o flag", file),
+                    );
+                    return Ok(err.print()?);
+                }
+            }
+
+            let seed = seed.unwrap_or_else(|| {
+                let seed = rand::random();
+                println!("Using seed {}.", seed);
+                seed
+            });
+            let resolution = resolution as usize;
+            // Samples are drawn from the whole fractal, since the volume's region spans other axes
+            let settings = SampleSettings {
+                n: n_iterations,
+                min_iterations,
+                m: samples,
+                scale: fractal.view().scale,
+                center: fractal.view().center,
+                julia,
+                power,
+                phoenix,
+                noise: FrameNoise::new(seed, 0, NoiseCorrelation::Independent),
+                orbits,
+                fractal,
+                streaming: stream_orbits,
+                streams: streams.map(|streams| streams as usize),
+                ..Default::default()
             };
 
-            file.set_extension(if png { "png" } else { "exr" });
+            if let Err(e) = settings.validate(resolution, resolution) {
+                return Ok(invalid_settings("volume", e).print()?);
+            }
+
+            let start_time = std::time::Instant::now();
+            let view = VolumeView { axes, center, scale };
+            let mut volume = Volume::new(resolution, resolution, resolution);
+            sample_volume(&mut volume, settings, view);
+            let elapsed = start_time.elapsed();
+            println!(
+                "Finished rendering volume in {}.",
+                humantime::format_duration(std::time::Duration::new(elapsed.as_secs(), 0))
+            );
+
+            if normalize {
+                volume.normalize();
+            }
+
+            volume.write(file, format)?;
+
+            if let (Some(points), Some(points_file)) = (points, points_file) {
+                let cloud = sample_points(settings, view, point_orbits as usize, point_every as usize);
+                println!("Collected {} points.", cloud.points.len());
+                cloud.write(points_file, points, point_iterations)?;
+            }
+        },
+        Commands::Bands {
+            n_iterations,
+            samples,
+            image_size,
+            mut file,
+            overwrite,
+            count,
+            compress,
+            spacing,
+            scale,
+            center,
+            julia,
+            power,
+            phoenix,
+            seed,
+            min_iterations,
+            streams,
+            fractal,
+        } => {
+            if count == 0 {
+                let err = Cli::command().error(ErrorKind::ValueValidation, "there must be at least one band");
+                return Ok(err.print()?);
+            }
+            if streams == Some(0) {
+                let err = Cli::command().error(ErrorKind::ValueValidation, "streams must be at least 1");
+                return Ok(err.print()?);
+            }
 
+            file.set_extension(histogram::EXTENSION);
             if file.exists() && !overwrite {
                 let err = Cli::command().error(
                     ErrorKind::ValueValidation,
@@ -406,65 +4325,222 @@ fn main() -> clap::error::Result<(), clap::Error> {
                 return Ok(err.print()?);
             }
 
-            let start_time = std::time::Instant::now();
-            let mut im = match mode {
-                ColorChannelMode::R => {
-                    let im1 = Arc::new(Mutex::new(Image::<Float>::new(im_size, im_width)));
-                    sample(im1.clone(), n_iterations, samples, progress_update, scale, center);
+            let seed = seed.unwrap_or_else(|| {
+                let seed = rand::random();
+                println!("Using seed {}.", seed);
+                seed
+            });
+            let im_width = image_size as usize;
+            let settings = SampleSettings {
+                n: n_iterations,
+                min_iterations,
+                m: samples,
+                scale: scale.unwrap_or(fractal.view().scale),
+                center: center.unwrap_or(fractal.view().center),
+                julia,
+                power,
+                phoenix,
+                noise: FrameNoise::new(seed, 0, NoiseCorrelation::Independent),
+                fractal,
+                streams: streams.map(|streams| streams as usize),
+                ..Default::default()
+            };
 
-                    let im = Arc::try_unwrap(im1).unwrap().into_inner().unwrap();
-                    fuse(im.clone(), im.clone(), im)
-                },
-                ColorChannelMode::Rg => {
-                    let im1 = Arc::new(Mutex::new(Image::<Float>::new(im_size, im_width)));
-                    sample(im1.clone(), n_iterations, samples, progress_update, scale, center);
+            if let Err(e) = settings.validate(im_width, im_width) {
+                return Ok(invalid_settings("image", e).print()?);
+            }
+
+            let start_time = std::time::Instant::now();
+            let bands = EscapeBands::new(count as usize, min_iterations, n_iterations, spacing);
+            let mut im = BandedImage::new(im_width, im_width, bands);
+            sample_banded(&mut im, settings);
+            let elapsed = start_time.elapsed();
+            println!(
+                "Finished rendering bands in {}.",
+                humantime::format_duration(std::time::Duration::new(elapsed.as_secs(), 0))
+            );
 
-                    let im2 = Arc::new(Mutex::new(Image::<Float>::new(im_size, im_width)));
-                    sample(im2.clone(), n_iterations / 10, samples, progress_update, scale, center);
+            im.save(file, &histogram::parameters(&settings), compress)?;
+        },
+        Commands::Verify { scene, list } => {
+            if list {
+                for scene in SCENES {
+                    println!("{:<20} {}", scene.name, scene.description);
+                }
+                return Ok(());
+            }
 
-                    let im1 = Arc::try_unwrap(im1).unwrap().into_inner().unwrap();
-                    let im2 = Arc::try_unwrap(im2).unwrap().into_inner().unwrap();
-                    fuse(im1, im2, Image::<f32>::new(im_size, im_width))
+            let scenes = match scene {
+                Some(name) => match Scene::find(&name) {
+                    Some(scene) => std::slice::from_ref(scene),
+                    None => {
+                        let names = SCENES.iter().map(|scene| scene.name).collect::<Vec<_>>();
+                        let err = Cli::command().error(
+                            ErrorKind::InvalidValue,
+                            format!("unknown scene {:?}. available scenes are: {}", name, names.join(", ")),
+                        );
+                        return Ok(err.print()?);
+                    },
                 },
-                ColorChannelMode::Rgb => {
-                    let im1 = Arc::new(Mutex::new(Image::<Float>::new(im_size, im_width)));
-                    sample(im1.clone(), n_iterations, samples, progress_update, scale, center);
+                None => SCENES,
+            };
 
-                    let im2 = Arc::new(Mutex::new(Image::<Float>::new(im_size, im_width)));
-                    sample(im2.clone(), n_iterations / 10, samples, progress_update, scale, center);
+            let mut failed = 0;
+            for scene in scenes {
+                let stats = scene.render();
+                let deviations = scene.check(&stats);
 
-                    let im3 = Arc::new(Mutex::new(Image::<Float>::new(im_size, im_width)));
-                    sample(im3.clone(), n_iterations / 100, samples, progress_update, scale, center);
+                if deviations.is_empty() {
+                    println!("{:<20} ok", scene.name);
+                } else {
+                    failed += 1;
+                    println!("{:<20} FAILED", scene.name);
+                    for deviation in deviations {
+                        println!("    {}", deviation);
+                    }
+                }
+            }
+
+            if failed > 0 {
+                Cli::command()
+                    .error(
+                        ErrorKind::ValueValidation,
+                        format!(
+                            "{} of {} scenes deviated from their expected statistics",
+                            failed,
+                            scenes.len()
+                        ),
+                    )
+                    .exit();
+            }
+        },
+        Commands::Bench {
+            workload,
+            list,
+            thread_counts,
+            precision,
+            compare_kernels,
+        } => {
+            if list {
+                for workload in WORKLOADS {
+                    println!("{:<20} {}", workload.name, workload.description);
+                }
+                return Ok(());
+            }
 
-                    let im1 = Arc::try_unwrap(im1).unwrap().into_inner().unwrap();
-                    let im2 = Arc::try_unwrap(im2).unwrap().into_inner().unwrap();
-                    let im3 = Arc::try_unwrap(im3).unwrap().into_inner().unwrap();
-                    fuse(im1, im2, im3)
+            let workloads = match workload {
+                Some(name) => match Workload::find(&name) {
+                    Some(workload) => std::slice::from_ref(workload),
+                    None => {
+                        let names = WORKLOADS.iter().map(|workload| workload.name).collect::<Vec<_>>();
+                        let err = Cli::command().error(
+                            ErrorKind::InvalidValue,
+                            format!(
+                                "unknown workload {:?}. available workloads are: {}",
+                                name,
+                                names.join(", ")
+                            ),
+                        );
+                        return Ok(err.print()?);
+                    },
                 },
+                None => WORKLOADS,
             };
-            let elapsed = start_time.elapsed();
-            println!(
-                "Finished rendering buddhabrot in {}.",
-                humantime::format_duration(std::time::Duration::new(elapsed.as_secs(), 0))
-            );
 
-            if normalize {
-                normalize_im(&mut im);
+            let threads = if thread_counts.is_empty() {
+                vec![rayon::current_num_threads()]
+            } else {
+                thread_counts
+            };
+            if threads.contains(&0) {
+                let err = Cli::command().error(ErrorKind::InvalidValue, "thread counts must be at least 1");
+                return Ok(err.print()?);
             }
 
-            if reflect {
-                reflect_im(&mut im);
-            }
+            if compare_kernels {
+                if precision == Precision::Arbitrary {
+                    let err = Cli::command().error(
+                        ErrorKind::ArgumentConflict,
+                        "--compare-kernels only compares kernels iterating in f32 and f64",
+                    );
+                    return Ok(err.print()?);
+                }
 
-            if rotate {
-                rotate_im(&mut im);
-            }
+                #[cfg(feature = "gpu")]
+                let devices = {
+                    let mut devices: Vec<Arc<dyn Accelerator>> = vec![Arc::new(CpuSampler::new())];
+                    match gpu::devices() {
+                        Ok(gpus) => devices.extend(gpus),
+                        Err(e) => println!("Comparing the CPU alone: {}.", e),
+                    }
+                    devices
+                };
+
+                println!(
+                    "{:<20} {:<34} {:>10} {:>14} {:>8}",
+                    "workload", "kernel", "seconds", "samples/s", "agrees"
+                );
+                for workload in workloads {
+                    let settings = workload.settings(precision);
+                    let count = workload.width * workload.width * workload.m as usize;
+
+                    // Only the plain mandelbrot iteration runs in SIMD lanes
+                    let results = match (workload.fractal, precision) {
+                        (Fractal::Mandelbrot, Precision::Single) => {
+                            compare_simd_levels(&KernelSamples::<f32>::new(&settings, count))
+                        },
+                        (Fractal::Mandelbrot, _) => compare_simd_levels(&KernelSamples::<f64>::new(&settings, count)),
+                        _ => Vec::new(),
+                    };
+
+                    #[cfg(feature = "gpu")]
+                    let results = if precision == Precision::Single {
+                        match compare_accelerators(workload, count as u64, &devices) {
+                            Ok(accelerated) => results.into_iter().chain(accelerated).collect(),
+                            Err(e) => {
+                                let err = Cli::command().error(ErrorKind::InvalidValue, format!("--gpu: {}", e));
+                                return Ok(err.print()?);
+                            },
+                        }
+                    } else {
+                        results
+                    };
+
+                    for result in results.iter() {
+                        println!(
+                            "{:<20} {:<34} {:>10.3} {:>14.0} {:>8}",
+                            workload.name,
+                            result.kernel,
+                            result.seconds,
+                            result.samples_per_second(),
+                            if result.agrees { "yes" } else { "no" }
+                        );
+                    }
+                }
 
-            if file.exists() && overwrite {
-                std::fs::remove_file(file.clone()).unwrap();
+                return Ok(());
             }
 
-            write_rgb(im, file, png);
+            println!(
+                "{:<20} {:>8} {:>10} {:>14} {:>14} {:>12}",
+                "workload", "threads", "seconds", "samples/s", "points/s", "peak memory"
+            );
+            for &threads in threads.iter() {
+                let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+                for workload in workloads {
+                    let result = pool.install(|| workload.run(precision));
+                    let memory = result.peak_memory.map_or("unknown".to_string(), format_bytes);
+                    println!(
+                        "{:<20} {:>8} {:>10.3} {:>14.0} {:>14.0} {:>12}",
+                        workload.name,
+                        threads,
+                        result.seconds,
+                        result.samples_per_second(),
+                        result.points_per_second(),
+                        memory
+                    );
+                }
+            }
         },
         Commands::Process {
             mut input_file,
@@ -473,88 +4549,159 @@ fn main() -> clap::error::Result<(), clap::Error> {
             exposure,
             gamma,
             black_point,
+            tonemap,
             png,
+            hdr,
+            tiff,
+            pfm,
+            webp,
+            avif,
+            dither,
+            indexed,
+            manifest,
             clamp,
             normalize,
         } => {
+            let format = match OutputFormat::from_flags(png, hdr, tiff, pfm, webp, avif) {
+                Ok(format) => format,
+                Err(err) => return Ok(err.print()?),
+            };
+            let palette = match colorize {
+                Some(ColorizeCommand::ColorizeR { minr, maxr }) if indexed => Some((minr, maxr)),
+                _ if indexed => {
+                    let err = Cli::command().error(
+                        ErrorKind::MissingRequiredArgument,
+                        "an indexed image has a single channel, so --indexed needs the palette of colorize-r",
+                    );
+                    return Ok(err.print()?);
+                },
+                _ => None,
+            };
             let mut im = load_image(&input_file)?;
 
-            if png || normalize {
-                normalize_im(&mut im);
+            // The processed image keeps the parameters of the original, adding how it was tonemapped
+            let mut parameters = read_parameters(&input_file);
+            let mut set = |key: &str, value: String| {
+                parameters.insert(key.to_owned(), value);
+            };
+            set(
+                "normalize",
+                ((format.clamps() && tonemap.is_none()) || normalize).to_string(),
+            );
+            set("clamp", (format.clamps() || clamp).to_string());
+            if let Some(tonemap) = &tonemap {
+                set("tonemap", tonemap.to_string());
             }
-
-            if let Some(exp) = exposure {
-                for px in im.pixels_mut() {
-                    px.r *= exp;
-                    px.g *= exp;
-                    px.b *= exp;
-                }
+            if let Some(exposure) = exposure {
+                set("exposure", exposure.to_string());
             }
-
-            if let Some(gam) = gamma {
-                for px in im.pixels_mut() {
-                    px.r = px.r.powf(1.0 / gam);
-                    px.g = px.g.powf(1.0 / gam);
-                    px.b = px.b.powf(1.0 / gam);
-                }
+            if let Some(gamma) = gamma {
+                set("gamma", gamma.to_string());
             }
-
-            if let Some(thres) = black_point {
-                for px in im.pixels_mut() {
-                    px.r = if px.r < thres { 0.0 } else { px.r };
-                    px.g = if px.g < thres { 0.0 } else { px.g };
-                    px.b = if px.b < thres { 0.0 } else { px.b };
-                }
+            if let Some(black_point) = black_point {
+                set("black_point", black_point.to_string());
+            }
+            let color = |(r, g, b): (f32, f32, f32)| format!("{},{},{}", r, g, b);
+            match colorize {
+                Some(ColorizeCommand::ColorizeR { minr, maxr }) => {
+                    set("colorize_minr", color(minr));
+                    set("colorize_maxr", color(maxr));
+                },
+                Some(ColorizeCommand::ColorizeRg { minr, maxr, ming, maxg }) => {
+                    set("colorize_minr", color(minr));
+                    set("colorize_maxr", color(maxr));
+                    set("colorize_ming", color(ming));
+                    set("colorize_maxg", color(maxg));
+                },
+                Some(ColorizeCommand::ColorizeRgb {
+                    minr,
+                    maxr,
+                    ming,
+                    maxg,
+                    minb,
+                    maxb,
+                }) => {
+                    set("colorize_minr", color(minr));
+                    set("colorize_maxr", color(maxr));
+                    set("colorize_ming", color(ming));
+                    set("colorize_maxg", color(maxg));
+                    set("colorize_minb", color(minb));
+                    set("colorize_maxb", color(maxb));
+                },
+                None => {},
             }
 
-            if png || clamp {
-                for px in im.pixels_mut() {
-                    px.r = px.r.clamp(0.0, 1.0);
-                    px.g = px.g.clamp(0.0, 1.0);
-                    px.b = px.b.clamp(0.0, 1.0);
+            {
+                profiling::scope!("tonemap");
+
+                let tonemap =
+                    tonemap.unwrap_or_else(|| Tonemap::new(format.clamps() || normalize, exposure, gamma, black_point));
+                tonemap.apply(&mut im);
+
+                if format.clamps() || clamp {
+                    for px in im.pixels_mut() {
+                        px.r = px.r.clamp(0.0, 1.0);
+                        px.g = px.g.clamp(0.0, 1.0);
+                        px.b = px.b.clamp(0.0, 1.0);
+                    }
                 }
-            }
 
-            if let Some(color) = colorize {
-                let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+                // The colors of an indexed image are looked up in its palette instead
+                if let Some(color) = colorize.filter(|_| palette.is_none()) {
+                    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
 
-                let f = |(r, g, b): (f32, f32, f32)| match color {
-                    ColorizeCommand::ColorizeR { minr, maxr } => (
-                        lerp(minr.0, maxr.0, r),
-                        lerp(minr.1, maxr.1, r),
-                        lerp(minr.2, maxr.2, r),
-                    ),
-                    ColorizeCommand::ColorizeRg { minr, maxr, ming, maxg } => (
-                        lerp(minr.0, maxr.0, r) * 0.5 + lerp(ming.0, maxg.0, g) * 0.5,
-                        lerp(minr.1, maxr.1, r) * 0.5 + lerp(ming.1, maxg.1, g) * 0.5,
-                        lerp(minr.2, maxr.2, r) * 0.5 + lerp(ming.2, maxg.2, g) * 0.5,
-                    ),
-                    ColorizeCommand::ColorizeRgb {
-                        minr,
-                        maxr,
-                        ming,
-                        maxg,
-                        minb,
-                        maxb,
-                    } => (
-                        lerp(minr.0, maxr.0, r) / 3.0 + lerp(ming.0, maxg.0, g) / 3.0 + lerp(minb.0, maxb.0, b) / 3.0,
-                        lerp(minr.1, maxr.1, r) / 3.0 + lerp(ming.1, maxg.1, g) / 3.0 + lerp(minb.1, maxb.1, b) / 3.0,
-                        lerp(minr.2, maxr.2, r) / 3.0 + lerp(ming.2, maxg.2, g) / 3.0 + lerp(minb.2, maxb.2, b) / 3.0,
-                    ),
-                };
+                    let f = |(r, g, b): (f32, f32, f32)| match color {
+                        ColorizeCommand::ColorizeR { minr, maxr } => (
+                            lerp(minr.0, maxr.0, r),
+                            lerp(minr.1, maxr.1, r),
+                            lerp(minr.2, maxr.2, r),
+                        ),
+                        ColorizeCommand::ColorizeRg { minr, maxr, ming, maxg } => (
+                            lerp(minr.0, maxr.0, r) * 0.5 + lerp(ming.0, maxg.0, g) * 0.5,
+                            lerp(minr.1, maxr.1, r) * 0.5 + lerp(ming.1, maxg.1, g) * 0.5,
+                            lerp(minr.2, maxr.2, r) * 0.5 + lerp(ming.2, maxg.2, g) * 0.5,
+                        ),
+                        ColorizeCommand::ColorizeRgb {
+                            minr,
+                            maxr,
+                            ming,
+                            maxg,
+                            minb,
+                            maxb,
+                        } => (
+                            lerp(minr.0, maxr.0, r) / 3.0
+                                + lerp(ming.0, maxg.0, g) / 3.0
+                                + lerp(minb.0, maxb.0, b) / 3.0,
+                            lerp(minr.1, maxr.1, r) / 3.0
+                                + lerp(ming.1, maxg.1, g) / 3.0
+                                + lerp(minb.1, maxb.1, b) / 3.0,
+                            lerp(minr.2, maxr.2, r) / 3.0
+                                + lerp(ming.2, maxg.2, g) / 3.0
+                                + lerp(minb.2, maxb.2, b) / 3.0,
+                        ),
+                    };
 
-                for px in im.pixels_mut() {
-                    *px = f((*px).into()).into();
+                    for px in im.pixels_mut() {
+                        *px = f((*px).into()).into();
+                    }
                 }
             }
 
-            input_file.set_extension(if png { "png" } else { "exr" });
+            input_file.set_extension(format.extension());
             let out_file = if let Some(f) = &file { f } else { &input_file };
             if out_file.exists() {
                 std::fs::remove_file(out_file).unwrap();
             }
 
-            write_rgb(im, out_file.to_path_buf(), png);
+            if manifest {
+                let manifest = Manifest::new(parameters.clone(), (im.width, im.size / im.width), None);
+                manifest.write(out_file.with_extension("json"))?;
+            }
+
+            match palette {
+                Some((min, max)) => write_indexed(im, min, max, out_file.to_path_buf(), dither, &parameters),
+                None => write_rgb(im, out_file.to_path_buf(), format, dither, &parameters),
+            }
         },
         Commands::Fuse {
             red_file,
@@ -562,7 +4709,17 @@ fn main() -> clap::error::Result<(), clap::Error> {
             blue_file,
             file,
             png,
+            hdr,
+            tiff,
+            pfm,
+            webp,
+            avif,
+            dither,
         } => {
+            let format = match OutputFormat::from_flags(png, hdr, tiff, pfm, webp, avif) {
+                Ok(format) => format,
+                Err(err) => return Ok(err.print()?),
+            };
             let red_im = load_image(&red_file)?;
             let mut im = Image::<Rgb>::new(red_im.size, red_im.width);
 
@@ -604,7 +4761,195 @@ fn main() -> clap::error::Result<(), clap::Error> {
                 }
             }
 
-            write_rgb(im, file, png);
+            write_rgb(im, file, format, dither, &read_parameters(&red_file));
+        },
+        Commands::Merge {
+            input_files,
+            mut file,
+            overwrite,
+            force,
+            compress,
+        } => {
+            file.set_extension(histogram::EXTENSION);
+            if file.exists() && !overwrite {
+                let err = Cli::command().error(
+                    ErrorKind::ValueValidation,
+                    format!("file {:?} already exists. to overwrite it, use the -o flag", file),
+                );
+                return Ok(err.print()?);
+            }
+
+            let header = match histogram::merge_histograms(&input_files, &file, force, compress) {
+                Ok(header) => header,
+                Err(e) => {
+                    let err = Cli::command().error(ErrorKind::Io, format!("couldn't merge histograms: {}", e));
+                    return Ok(err.print()?);
+                },
+            };
+            println!(
+                "Merged {} histograms of {}x{} pixels into {:?}.",
+                input_files.len(),
+                header.width,
+                header.height,
+                file
+            );
+        },
+        Commands::Diff {
+            reference_file,
+            input_file,
+            file,
+            normalize,
+            max_rmse,
+            png,
+            hdr,
+            tiff,
+            pfm,
+            webp,
+            avif,
+            dither,
+        } => {
+            let format = match OutputFormat::from_flags(png, hdr, tiff, pfm, webp, avif) {
+                Ok(format) => format,
+                Err(err) => return Ok(err.print()?),
+            };
+            let mut reference = load_image(&reference_file)?;
+            let mut im = load_image(&input_file)?;
+            if im.width != reference.width || im.size != reference.size {
+                let err = Cli::command().error(
+                    ErrorKind::Io,
+                    format!(
+                        "file {:?} has different dimensions than {:?}",
+                        input_file, reference_file
+                    ),
+                );
+                return Ok(err.print()?);
+            }
+
+            if normalize {
+                normalize_im(&mut reference);
+                normalize_im(&mut im);
+            }
+
+            let (difference, image) = Difference::compute(&reference, &im);
+            if difference.pixels == 0 {
+                println!("The images are identical.");
+            } else {
+                println!("{} of {} pixels differ.", difference.pixels, reference.size);
+                println!("  max    {:.6}", difference.max);
+                println!("  mean   {:.6}", difference.mean);
+                println!("  rmse   {:.6}", difference.rmse);
+                println!("  psnr   {:.2} dB", difference.psnr);
+            }
+
+            if let Some(file) = file {
+                let mut parameters = Parameters::new();
+                parameters.insert("reference".to_owned(), reference_file.display().to_string());
+                parameters.insert("input".to_owned(), input_file.display().to_string());
+                parameters.insert("normalize".to_owned(), normalize.to_string());
+                write_rgb(image, file, format, dither, &parameters);
+            }
+
+            if let Some(max_rmse) = max_rmse.filter(|&max_rmse| difference.rmse > max_rmse) {
+                Cli::command()
+                    .error(
+                        ErrorKind::ValueValidation,
+                        format!(
+                            "the images differ by a root mean square of {}, more than {}",
+                            difference.rmse, max_rmse
+                        ),
+                    )
+                    .exit();
+            }
+        },
+        Commands::Recolor {
+            input_file,
+            file,
+            palette,
+            normalize,
+            overwrite,
+            png,
+            hdr,
+            tiff,
+            pfm,
+            webp,
+            avif,
+            dither,
+        } => {
+            let format = match OutputFormat::from_flags(png, hdr, tiff, pfm, webp, avif) {
+                Ok(format) => format,
+                Err(err) => return Ok(err.print()?),
+            };
+
+            let mut file = file.unwrap_or_else(|| input_file.clone());
+            file.set_extension(format.extension());
+            if file.exists() && !overwrite {
+                let err = Cli::command().error(
+                    ErrorKind::ValueValidation,
+                    format!("file {:?} already exists. to overwrite it, use the -o flag", file),
+                );
+                return Ok(err.print()?);
+            }
+
+            let (bands, mut parameters) = match BandedImage::load(&input_file) {
+                Ok(loaded) => loaded,
+                Err(e) => {
+                    let err = Cli::command().error(ErrorKind::Io, format!("couldn't read {:?}: {}", input_file, e));
+                    return Ok(err.print()?);
+                },
+            };
+
+            let palette = palette.into_iter().map(Rgb::from).collect::<Vec<_>>();
+            let mut im = bands.colorize(&palette);
+            let normalize = normalize || format.clamps();
+            if normalize {
+                normalize_im(&mut im);
+            }
+
+            parameters.insert("normalize".to_owned(), normalize.to_string());
+            write_rgb(im, file, format, dither, &parameters);
+        },
+        Commands::Contour {
+            input_file,
+            file,
+            levels,
+            gamma,
+            min_points,
+        } => {
+            // The border around the image is empty, so a region at least as dense as 0 has no outline
+            if let Some(&level) = levels.iter().find(|&&level| !(level > 0.0 && level <= 1.0)) {
+                let err = Cli::command().error(
+                    ErrorKind::ValueValidation,
+                    format!("contour levels must be larger than 0 and at most 1, but got {}", level),
+                );
+                return Ok(err.print()?);
+            }
+
+            let mut im = load_image(&input_file)?;
+            normalize_im(&mut im);
+
+            // Every channel counts towards the density of a pixel equally
+            let density = im
+                .pixels()
+                .map(|px| {
+                    let density = (px.r + px.g + px.b) / 3.0;
+                    gamma.map_or(density, |gamma| density.powf(1.0 / gamma))
+                })
+                .collect::<Vec<_>>();
+            let height = im.size / im.width;
+
+            let contours = levels
+                .iter()
+                .map(|&level| contour::trace(&density, im.width, height, level, min_points))
+                .collect::<Vec<_>>();
+            for contour in contours.iter() {
+                println!("Traced {} outlines at {}.", contour.outlines.len(), contour.level);
+            }
+
+            let mut file = file.unwrap_or(input_file);
+            file.set_extension("svg");
+            let mut out = AtomicFile::create(file)?;
+            contour::write_svg(&mut out, im.width, height, &contours)?;
+            out.commit()?;
         },
     }
 
@@ -0,0 +1,69 @@
+use std::{
+    env,
+    io::{self, Write},
+    path::Path,
+};
+
+use serde_json::json;
+
+use crate::{atomic::AtomicFile, histogram::Parameters};
+
+/// The version of the renderer.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The commit the renderer was built from, ending in `-dirty` if the checkout had uncommitted
+/// changes, or `None` if it wasn't built from a git checkout.
+pub const GIT_HASH: Option<&str> = option_env!("BUDDHABROT_GIT_HASH");
+
+/// A record of how an image was made, written as a JSON file next to it, holding everything needed
+/// to render it again exactly.
+///
+/// Besides the arguments the renderer was run with, it holds the effective value of every
+/// parameter, including those left to their defaults or picked at random such as the seed.
+#[derive(Clone, Debug)]
+pub struct Manifest {
+    /// The arguments the renderer was run with, starting with the path of the executable.
+    pub command: Vec<String>,
+    /// The parameters the image was rendered and tonemapped with.
+    pub parameters: Parameters,
+    /// The width and height of the image in pixels.
+    pub size: (usize, usize),
+    /// The number of threads samples were drawn on.
+    pub threads: usize,
+    /// The number of logical streams samples were split between, which make the image the same on
+    /// any number of threads.
+    pub streams: Option<usize>,
+}
+
+impl Manifest {
+    /// Creates the manifest of an image made by the running process, drawing samples on the global
+    /// thread pool.
+    pub fn new(parameters: Parameters, size: (usize, usize), streams: Option<usize>) -> Manifest {
+        Self {
+            command: env::args().collect(),
+            parameters,
+            size,
+            threads: rayon::current_num_threads(),
+            streams,
+        }
+    }
+
+    /// Writes this manifest as pretty-printed JSON.
+    pub fn write(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let manifest = json!({
+            "version": VERSION,
+            "git": GIT_HASH,
+            "command": self.command,
+            "parameters": self.parameters,
+            "width": self.size.0,
+            "height": self.size.1,
+            "threads": self.threads,
+            "streams": self.streams,
+        });
+
+        let mut out = AtomicFile::create(path)?;
+        serde_json::to_writer_pretty(&mut out, &manifest)?;
+        writeln!(out)?;
+        out.commit()
+    }
+}
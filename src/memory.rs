@@ -0,0 +1,98 @@
+use std::fs;
+
+/// The memory a render is expected to need at its peak, in bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryEstimate {
+    /// The images every thread accumulates into, along with the image they're merged into.
+    pub images: usize,
+    /// The buffers every thread stores the trajectory of the orbit it plots in.
+    pub trajectories: usize,
+    /// The finished images held while they're tonemapped and encoded.
+    pub output: usize,
+}
+
+impl MemoryEstimate {
+    /// Estimates the memory of `accumulators` copies of `accumulator` bytes of images sampled into
+    /// by `threads` threads, each storing trajectories of up to `n` points of `point` bytes, after
+    /// which `output` bytes of finished images are written.
+    pub fn new(
+        accumulator: usize,
+        accumulators: usize,
+        threads: usize,
+        n: u32,
+        point: usize,
+        output: usize,
+    ) -> MemoryEstimate {
+        Self {
+            images: accumulator * (accumulators + 1),
+            trajectories: threads * n as usize * point,
+            output,
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.images + self.trajectories + self.output
+    }
+}
+
+/// Gets the memory available to start new processes with, in bytes, where the platform reports it.
+pub fn available_memory() -> Option<usize> {
+    // Only Linux reports the memory that can be reclaimed from caches along with the free memory
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|line| line.starts_with("MemAvailable:"))?;
+    let kilobytes = line.split_whitespace().nth(1)?.parse::<usize>().ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// Gets the largest amount of memory the process has had resident, in bytes.
+#[cfg(unix)]
+pub fn peak_memory() -> Option<usize> {
+    let mut usage = unsafe { std::mem::zeroed::<libc::rusage>() };
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return None;
+    }
+
+    // macOS reports bytes, while every other unix reports kilobytes
+    let unit = if cfg!(target_os = "macos") { 1 } else { 1024 };
+    Some(usage.ru_maxrss as usize * unit)
+}
+
+#[cfg(not(unix))]
+pub fn peak_memory() -> Option<usize> {
+    None
+}
+
+/// Formats a number of bytes in the largest binary unit it has at least one of.
+pub fn format_bytes(bytes: usize) -> String {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", value, units[unit])
+}
+
+/// Parses an amount of memory such as `512M`, `8G` or `1.5GiB`, in bytes. Suffixes count in powers
+/// of 1024, with or without a trailing `B` or `iB`.
+pub fn parse_bytes(s: &str) -> Result<usize, String> {
+    let trimmed = s.trim();
+    let digits = trimmed.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let suffix = trimmed[digits.len()..].to_ascii_lowercase();
+    let unit = match suffix.trim_end_matches('b').trim_end_matches('i') {
+        "" => 1u64,
+        "k" => 1 << 10,
+        "m" => 1 << 20,
+        "g" => 1 << 30,
+        "t" => 1 << 40,
+        _ => return Err(format!("{} is not a valid amount of memory", s)),
+    };
+
+    let value = digits
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| format!("{} is not a valid amount of memory", s))?;
+    Ok((value * unit as f64) as usize)
+}
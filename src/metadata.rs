@@ -0,0 +1,73 @@
+use std::{collections::HashMap, fs::File, io, path::Path};
+
+use exr::meta::{
+    attribute::{AttributeValue, Text},
+    MetaData,
+};
+
+use crate::histogram::Parameters;
+
+/// The prefix of the names parameters are stored under in the metadata of an image, keeping them
+/// apart from what other tools store there.
+pub const PREFIX: &str = "buddhabrot:";
+
+/// The name and version of the renderer, stored as the software that made every image.
+pub const SOFTWARE: &str = concat!("buddhabrot ", env!("CARGO_PKG_VERSION"));
+
+/// Gets the name every parameter is stored under in the metadata of an image, along with its
+/// value.
+pub fn entries(parameters: &Parameters) -> impl Iterator<Item = (String, &str)> {
+    parameters
+        .iter()
+        .map(|(key, value)| (format!("{}{}", PREFIX, key), value.as_str()))
+}
+
+/// Collects the parameters stored in the metadata entries of an image, skipping those stored by
+/// other tools.
+pub fn from_entries<'a>(entries: impl IntoIterator<Item = (&'a str, &'a str)>) -> Parameters {
+    entries
+        .into_iter()
+        .filter_map(|(name, value)| Some((name.strip_prefix(PREFIX)?.to_owned(), value.to_owned())))
+        .collect()
+}
+
+/// Gets the parameters as text attributes of an EXR layer.
+pub fn exr_attributes(parameters: &Parameters) -> HashMap<Text, AttributeValue> {
+    entries(parameters)
+        .filter_map(|(name, value)| {
+            Some((
+                Text::new_or_none(name)?,
+                AttributeValue::Text(Text::new_or_none(value)?),
+            ))
+        })
+        .collect()
+}
+
+/// Reads the parameters stored in the text chunks of a PNG file.
+pub fn read_png(path: impl AsRef<Path>) -> io::Result<Parameters> {
+    let reader = png::Decoder::new(File::open(path)?)
+        .read_info()
+        .map_err(io::Error::other)?;
+    let chunks = reader.info().uncompressed_latin1_text.iter();
+    Ok(from_entries(
+        chunks.map(|chunk| (chunk.keyword.as_str(), chunk.text.as_str())),
+    ))
+}
+
+/// Reads the parameters stored in the attributes of the first layer of an EXR file.
+pub fn read_exr(path: impl AsRef<Path>) -> exr::error::Result<Parameters> {
+    let meta = MetaData::read_from_file(path, false)?;
+    let attributes = meta.headers.first().map(|header| &header.own_attributes.other);
+    let texts = attributes
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, value)| match value {
+            AttributeValue::Text(text) => Some((name.to_string(), text.to_string())),
+            _ => None,
+        });
+
+    let texts = texts.collect::<Vec<_>>();
+    Ok(from_entries(
+        texts.iter().map(|(name, value)| (name.as_str(), value.as_str())),
+    ))
+}
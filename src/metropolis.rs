@@ -0,0 +1,288 @@
+use num_traits::Float;
+use rand::Rng;
+
+use crate::{
+    canvas::Canvas,
+    color::Color,
+    complex::Complex,
+    dynamics::Dynamics,
+    noise::NoiseStream,
+    sample::{contribution, plot_trajectory, sample_point, trace, Orbits, SampleSettings},
+    throttle::Throttle,
+};
+
+/// The number of uniform samples used to estimate the average contribution of a sample, which
+/// normalizes the brightness of Metropolis renders to match uniform ones.
+const NORMALIZATION_SAMPLES: usize = 1000;
+
+/// The maximum number of uniform samples drawn when looking for a chain's starting point.
+const MAX_SEED_ATTEMPTS: usize = 10000;
+
+/// The probability of proposing a completely new sample rather than perturbing the current one.
+const LARGE_STEP_PROBABILITY: f32 = 0.2;
+
+/// The parameters of Metropolis-Hastings importance sampling.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MetropolisSettings {
+    /// The number of independent chains advanced in turn by each thread.
+    pub chains: u32,
+    /// The number of mutations each chain undergoes before it starts plotting.
+    pub burn_in: u32,
+    /// The number of plotted mutations after which a chain is retired and replaced by a freshly
+    /// seeded one, or 0 to never replace chains.
+    pub swap_interval: u32,
+}
+
+impl Default for MetropolisSettings {
+    fn default() -> Self {
+        Self {
+            chains: 4,
+            burn_in: 1000,
+            swap_interval: 100000,
+        }
+    }
+}
+
+/// Acceptance statistics of Metropolis-Hastings sampling.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MetropolisStats {
+    /// The number of mutations proposed after burn-in.
+    pub proposed: u64,
+    /// The number of mutations accepted after burn-in.
+    pub accepted: u64,
+    /// The number of chains run, including those that replaced retired chains.
+    pub chains: u64,
+    /// The lowest acceptance rate of any single chain.
+    pub min_acceptance: f32,
+    /// The highest acceptance rate of any single chain.
+    pub max_acceptance: f32,
+}
+
+impl MetropolisStats {
+    /// Gets the fraction of proposed mutations that were accepted.
+    #[inline]
+    pub fn acceptance_rate(&self) -> f32 {
+        self.accepted as f32 / self.proposed.max(1) as f32
+    }
+
+    /// Combines the statistics of two sets of chains.
+    pub fn merge(self, rhs: MetropolisStats) -> MetropolisStats {
+        if self.chains == 0 {
+            return rhs;
+        } else if rhs.chains == 0 {
+            return self;
+        }
+
+        Self {
+            proposed: self.proposed + rhs.proposed,
+            accepted: self.accepted + rhs.accepted,
+            chains: self.chains + rhs.chains,
+            min_acceptance: self.min_acceptance.min(rhs.min_acceptance),
+            max_acceptance: self.max_acceptance.max(rhs.max_acceptance),
+        }
+    }
+
+    fn record<F>(&mut self, chain: &Chain<F>) {
+        let rate = chain.accepted as f32 / chain.proposed.max(1) as f32;
+        *self = self.merge(MetropolisStats {
+            proposed: chain.proposed,
+            accepted: chain.accepted,
+            chains: 1,
+            min_acceptance: rate,
+            max_acceptance: rate,
+        });
+    }
+}
+
+/// A single Markov chain over the sampled region.
+struct Chain<F> {
+    c: Complex<F>,
+    trajectory: Vec<Complex<F>>,
+    contribution: u32,
+    /// The number of plotted mutations since this chain was seeded.
+    age: u32,
+    proposed: u64,
+    accepted: u64,
+}
+
+/// A Metropolis-Hastings sampler advancing several independent chains in turn.
+///
+/// Samples are drawn proportionally to the number of their trajectory's points that land inside
+/// the image, so much less time is spent on samples that don't contribute to it. Each deposit is
+/// weighted by the inverse of its sample's contribution, which keeps the result unbiased. Samples
+/// and their trajectories are held in the scalar `F`.
+pub struct MetropolisSampler<F = f32> {
+    metropolis: MetropolisSettings,
+    /// The average contribution of a uniformly drawn sample.
+    mean: f64,
+    chains: Vec<Chain<F>>,
+    next: usize,
+    /// The statistics of retired chains.
+    retired: MetropolisStats,
+}
+
+impl<F: Float> MetropolisSampler<F> {
+    /// Creates a new sampler for a `width` by `height` image, seeding and burning in its chains.
+    pub fn new<D: Dynamics<F>>(
+        settings: &SampleSettings,
+        dynamics: &D,
+        metropolis: MetropolisSettings,
+        stream: &mut NoiseStream,
+        width: usize,
+        height: usize,
+    ) -> MetropolisSampler<F> {
+        let mean = (0..NORMALIZATION_SAMPLES)
+            .map(|_| {
+                let c = sample_point::<F>(settings, stream.next_point());
+                contribution(settings, c, &trace(settings, dynamics, c), width, height) as f64
+            })
+            .sum::<f64>()
+            / NORMALIZATION_SAMPLES as f64;
+
+        // If no uniform sample contributed, the image is almost certainly empty
+        let chains = if mean > 0.0 {
+            (0..metropolis.chains.max(1))
+                .filter_map(|_| seed_chain(settings, dynamics, &metropolis, stream, width, height))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        Self {
+            metropolis,
+            mean,
+            chains,
+            next: 0,
+            retired: MetropolisStats::default(),
+        }
+    }
+
+    /// Plots `count` mutations into `canvas`.
+    pub fn run<T: Color + Clone + Copy, D: Dynamics<F>>(
+        &mut self,
+        canvas: &mut Canvas<T>,
+        settings: &SampleSettings,
+        dynamics: &D,
+        stream: &mut NoiseStream,
+        throttle: &mut Throttle,
+        count: usize,
+    ) {
+        if self.chains.is_empty() {
+            return;
+        }
+
+        let width = canvas.main.width;
+        let height = canvas.main.size / canvas.main.width;
+
+        for _ in 0..count {
+            let index = self.next;
+            self.next = (self.next + 1) % self.chains.len();
+
+            let chain = &mut self.chains[index];
+            let escaped = mutate(chain, settings, dynamics, stream, width, height);
+            canvas.counts.samples += 1;
+            canvas.counts.escaped += escaped as u64;
+            chain.age += 1;
+            throttle.tick(1);
+            plot_trajectory(
+                canvas,
+                settings,
+                settings.fractal,
+                dynamics,
+                chain.c,
+                chain.trajectory.iter().copied(),
+                (self.mean / chain.contribution as f64) as f32,
+            );
+
+            if self.metropolis.swap_interval != 0 && chain.age >= self.metropolis.swap_interval {
+                if let Some(fresh) = seed_chain(settings, dynamics, &self.metropolis, stream, width, height) {
+                    self.retired.record(&std::mem::replace(chain, fresh));
+                }
+            }
+        }
+    }
+
+    /// Gets the acceptance statistics of every chain this sampler has run.
+    pub fn stats(&self) -> MetropolisStats {
+        let mut stats = self.retired;
+        for chain in self.chains.iter() {
+            stats.record(chain);
+        }
+        stats
+    }
+}
+
+/// Starts a new chain at a uniformly drawn sample which contributes to the image, then burns it in.
+fn seed_chain<D: Dynamics<F>, F: Float>(
+    settings: &SampleSettings,
+    dynamics: &D,
+    metropolis: &MetropolisSettings,
+    stream: &mut NoiseStream,
+    width: usize,
+    height: usize,
+) -> Option<Chain<F>> {
+    let mut chain = (0..MAX_SEED_ATTEMPTS).find_map(|_| {
+        let c = sample_point(settings, stream.next_point());
+        let trajectory = trace(settings, dynamics, c);
+        let contribution = contribution(settings, c, &trajectory, width, height);
+
+        (contribution > 0).then_some(Chain {
+            c,
+            trajectory,
+            contribution,
+            age: 0,
+            proposed: 0,
+            accepted: 0,
+        })
+    })?;
+
+    for _ in 0..metropolis.burn_in {
+        mutate(&mut chain, settings, dynamics, stream, width, height);
+    }
+    chain.proposed = 0;
+    chain.accepted = 0;
+
+    Some(chain)
+}
+
+/// Proposes a mutation of a chain's sample, accepting it with the Metropolis-Hastings acceptance
+/// probability, and returns whether the orbit of the proposed sample escaped.
+fn mutate<D: Dynamics<F>, F: Float>(
+    chain: &mut Chain<F>,
+    settings: &SampleSettings,
+    dynamics: &D,
+    stream: &mut NoiseStream,
+    width: usize,
+    height: usize,
+) -> bool {
+    let c = if stream.rng().gen::<f32>() < LARGE_STEP_PROBABILITY {
+        sample_point(settings, stream.next_point())
+    } else {
+        // Perturb the sample by an exponentially distributed distance between a 10000th and a 10th
+        // of the size of the sampled region, in a uniformly random direction
+        let size = 4.0 * settings.scale as f32;
+        let (r1, r2) = (size * 1e-4, size * 1e-1);
+        let rng = stream.rng();
+        let r = r2 * (-(r2 / r1).ln() * rng.gen::<f32>()).exp();
+        let theta = rng.gen::<f32>() * std::f32::consts::TAU;
+        chain.c + Complex::new(r * theta.cos(), r * theta.sin()).map(|x| F::from(x).unwrap())
+    };
+
+    let trajectory = trace(settings, dynamics, c);
+    let contribution = contribution(settings, c, &trajectory, width, height);
+    // Only escaping orbits have trajectories when escaping orbits are plotted, and only bounded ones
+    // when bounded orbits are
+    let escaped = (settings.orbits == Orbits::Escaping) != trajectory.is_empty();
+
+    // Both mutations are symmetric, so the acceptance probability is the ratio of contributions
+    chain.proposed += 1;
+    let acceptance = contribution as f32 / chain.contribution as f32;
+    if contribution > 0 && stream.rng().gen::<f32>() < acceptance {
+        chain.accepted += 1;
+        chain.c = c;
+        chain.trajectory = trajectory;
+        chain.contribution = contribution;
+    }
+
+    escaped
+}
@@ -0,0 +1,119 @@
+use clap::ValueEnum;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Additive recurrence constants of the R2 low-discrepancy sequence (`1/φ₂` and `1/φ₂²`, where `φ₂` is
+/// the plastic number). Offsetting a sample pattern by successive multiples of these spreads the
+/// pattern evenly over the unit square in time.
+const R2_ALPHA: (f32, f32) = (0.754_877_7, 0.569_840_3);
+
+/// Determines how sample noise is correlated across the frames of an animation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum NoiseCorrelation {
+    /// Every frame draws a completely new set of samples. Noise is uncorrelated in time, which
+    /// flickers when played back.
+    Independent,
+    /// Every frame reuses the exact same set of samples. Noise does not flicker, but the pattern
+    /// "sticks" to the screen during zooms and pans.
+    Fixed,
+    /// Every frame reuses the same set of samples, toroidally shifted by a low-discrepancy offset
+    /// per frame. Error is spread evenly over time, trading flicker for fine temporal grain.
+    #[default]
+    BlueNoise,
+}
+
+/// The noise configuration of a single frame.
+#[derive(Clone, Copy, Debug)]
+pub struct FrameNoise {
+    pub seed: u64,
+    pub frame: u32,
+    pub correlation: NoiseCorrelation,
+}
+
+impl FrameNoise {
+    /// Creates a new noise configuration for the given frame of an animation.
+    #[inline]
+    pub fn new(seed: u64, frame: u32, correlation: NoiseCorrelation) -> FrameNoise {
+        Self {
+            seed,
+            frame,
+            correlation,
+        }
+    }
+
+    /// Creates a noise configuration for a still image with a random seed.
+    pub fn random() -> FrameNoise {
+        Self::new(rand::random(), 0, NoiseCorrelation::default())
+    }
+
+    /// Creates the random stream with index `stream` of this frame. Distinct streams are
+    /// statistically independent of one another.
+    pub fn stream(&self, stream: u64) -> NoiseStream {
+        let (seed, offset) = match self.correlation {
+            NoiseCorrelation::Independent => (mix(mix(self.seed, stream), self.frame as u64), (0.0, 0.0)),
+            NoiseCorrelation::Fixed => (mix(self.seed, stream), (0.0, 0.0)),
+            NoiseCorrelation::BlueNoise => {
+                let offset = (
+                    (self.frame as f64 * R2_ALPHA.0 as f64).fract() as f32,
+                    (self.frame as f64 * R2_ALPHA.1 as f64).fract() as f32,
+                );
+                (mix(self.seed, stream), offset)
+            },
+        };
+
+        NoiseStream {
+            rng: StdRng::seed_from_u64(seed),
+            offset,
+        }
+    }
+}
+
+impl Default for FrameNoise {
+    fn default() -> Self {
+        Self::random()
+    }
+}
+
+/// A seeded stream of uniformly distributed sample points.
+pub struct NoiseStream {
+    rng: StdRng,
+    offset: (f32, f32),
+}
+
+impl NoiseStream {
+    /// Gets the next point in the unit square `[0, 1)²`.
+    #[inline]
+    pub fn next_point(&mut self) -> (f32, f32) {
+        let u = self.rng.gen::<f32>() + self.offset.0;
+        let v = self.rng.gen::<f32>() + self.offset.1;
+        (wrap(u), wrap(v))
+    }
+
+    /// Gets the underlying random number generator, for draws that don't need temporal
+    /// decorrelation.
+    #[inline]
+    pub fn rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+}
+
+/// Wraps a value in `[0, 2)` back into `[0, 1)`.
+#[inline]
+fn wrap(x: f32) -> f32 {
+    if x >= 1.0 {
+        x - 1.0
+    } else {
+        x
+    }
+}
+
+/// Combines a seed with a value using the SplitMix64 finalizer.
+#[inline]
+fn mix(seed: u64, value: u64) -> u64 {
+    let mut z = seed
+        ^ value
+            .wrapping_add(0x9e37_79b9_7f4a_7c15)
+            .wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,211 @@
+use std::{fs, sync::OnceLock, thread};
+
+/// The CPUs of every NUMA node of the machine, each of which has its own memory that its CPUs reach
+/// faster than that of other nodes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Topology {
+    pub nodes: Vec<Vec<usize>>,
+}
+
+/// The topology the global thread pool was pinned to by [`install`], along with the node of every
+/// thread of the pool.
+static PLACEMENT: OnceLock<(Topology, Vec<usize>)> = OnceLock::new();
+
+impl Topology {
+    /// Detects the nodes of the machine, which are only known on Linux. Anywhere else, and on
+    /// machines without NUMA support, every CPU is on a single node.
+    ///
+    /// Only the CPUs the process is allowed to run on are included, so that a container restricted
+    /// to a set of CPUs only pins threads to those.
+    pub fn detect() -> Topology {
+        let topology = Self::read().unwrap_or_else(|| Self {
+            nodes: vec![(0..num_cpus::get()).collect()],
+        });
+
+        let Some(allowed) = allowed_cpus() else {
+            return topology;
+        };
+        let nodes = topology
+            .nodes
+            .into_iter()
+            .map(|cpus| cpus.into_iter().filter(|cpu| allowed.contains(cpu)).collect::<Vec<_>>())
+            .filter(|cpus| !cpus.is_empty())
+            .collect::<Vec<_>>();
+
+        if nodes.is_empty() {
+            return Self { nodes: vec![allowed] };
+        }
+        Self { nodes }
+    }
+
+    fn read() -> Option<Topology> {
+        let mut nodes = Vec::new();
+        for entry in fs::read_dir("/sys/devices/system/node").ok()? {
+            let entry = entry.ok()?;
+            let name = entry.file_name();
+            let Some(index) = name
+                .to_str()
+                .and_then(|name| name.strip_prefix("node")?.parse::<usize>().ok())
+            else {
+                continue;
+            };
+
+            let cpus = parse_cpu_list(&fs::read_to_string(entry.path().join("cpulist")).ok()?)?;
+            if !cpus.is_empty() {
+                nodes.push((index, cpus));
+            }
+        }
+
+        nodes.sort();
+        (!nodes.is_empty()).then(|| Self {
+            nodes: nodes.into_iter().map(|(_, cpus)| cpus).collect(),
+        })
+    }
+
+    /// Gets the number of CPUs across every node.
+    pub fn cpus(&self) -> usize {
+        self.nodes.iter().map(Vec::len).sum()
+    }
+
+    /// Keeps only `cpus` of the CPUs, taking them from every node in turn so that the threads
+    /// pinned to them stay spread evenly over the nodes.
+    pub fn limit(self, cpus: usize) -> Topology {
+        let longest = self.nodes.iter().map(Vec::len).max().unwrap_or(0);
+        let order = (0..longest).flat_map(|index| {
+            let nodes = self.nodes.iter().enumerate();
+            nodes.filter_map(move |(node, all)| Some((node, *all.get(index)?)))
+        });
+
+        let mut nodes = self.nodes.iter().map(|_| Vec::new()).collect::<Vec<_>>();
+        for (node, cpu) in order.take(cpus) {
+            nodes[node].push(cpu);
+        }
+
+        Self {
+            nodes: nodes.into_iter().filter(|cpus| !cpus.is_empty()).collect(),
+        }
+    }
+}
+
+/// Parses a list of CPUs such as `0-3,8,10-11`.
+fn parse_cpu_list(list: &str) -> Option<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        cpus.extend(start.parse::<usize>().ok()?..=end.parse::<usize>().ok()?);
+    }
+
+    Some(cpus)
+}
+
+/// Builds the global thread pool with one thread per CPU of `topology`, pinning every thread to its
+/// CPU. The threads of a node are numbered one after another.
+///
+/// Memory is placed on the node of the thread that first writes to it, so the image every thread
+/// accumulates into stays on its node. Must be called before anything else uses the pool.
+pub fn install(topology: Topology) -> Result<(), rayon::ThreadPoolBuildError> {
+    let cpus = topology
+        .nodes
+        .iter()
+        .enumerate()
+        .flat_map(|(node, cpus)| cpus.iter().map(move |&cpu| (node, cpu)))
+        .collect::<Vec<_>>();
+
+    if PLACEMENT
+        .set((topology, cpus.iter().map(|&(node, _)| node).collect()))
+        .is_err()
+    {
+        return Ok(());
+    }
+
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(cpus.len())
+        .start_handler(move |index| pin(&[cpus[index].1]))
+        .build_global()
+}
+
+/// Gets the node the thread of the global pool with the given index runs on, which is always 0
+/// unless the pool was [`install`]ed.
+pub fn node_of_thread(index: usize) -> usize {
+    PLACEMENT
+        .get()
+        .and_then(|(_, nodes)| nodes.get(index).copied())
+        .unwrap_or(0)
+}
+
+/// Merges the accumulators of the threads of every node into one on a thread pinned to that node,
+/// returning one accumulator per node to be merged across nodes afterwards. Every accumulator is
+/// paired with the node it was filled on.
+///
+/// Without an [`install`]ed pool, the accumulators are returned as they are.
+pub fn merge_by_node<A: Send>(accumulators: Vec<(usize, A)>, merge: impl Fn(&mut A, A) + Sync) -> Vec<A> {
+    let Some((topology, _)) = PLACEMENT.get() else {
+        return accumulators.into_iter().map(|(_, accumulator)| accumulator).collect();
+    };
+
+    let mut groups = topology.nodes.iter().map(|_| Vec::new()).collect::<Vec<_>>();
+    for (node, accumulator) in accumulators {
+        groups[node].push(accumulator);
+    }
+
+    let merge = &merge;
+    thread::scope(|scope| {
+        let handles = groups
+            .into_iter()
+            .zip(&topology.nodes)
+            .filter(|(group, _)| !group.is_empty())
+            .map(|(group, cpus)| {
+                scope.spawn(move || {
+                    profiling::scope!("merge node");
+                    pin(cpus);
+                    let mut group = group.into_iter();
+                    let mut merged = group.next().unwrap();
+                    for accumulator in group {
+                        merge(&mut merged, accumulator);
+                    }
+                    merged
+                })
+            })
+            .collect::<Vec<_>>();
+
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+    })
+}
+
+/// Gets the CPUs the calling thread is allowed to run on, which are only known on Linux.
+#[cfg(target_os = "linux")]
+fn allowed_cpus() -> Option<Vec<usize>> {
+    unsafe {
+        let mut set = std::mem::zeroed::<libc::cpu_set_t>();
+        if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+            return None;
+        }
+
+        let cpus = (0..libc::CPU_SETSIZE as usize)
+            .filter(|&cpu| libc::CPU_ISSET(cpu, &set))
+            .collect::<Vec<_>>();
+        (!cpus.is_empty()).then_some(cpus)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn allowed_cpus() -> Option<Vec<usize>> {
+    None
+}
+
+/// Restricts the calling thread to running on the given CPUs.
+#[cfg(target_os = "linux")]
+fn pin(cpus: &[usize]) {
+    unsafe {
+        let mut set = std::mem::zeroed::<libc::cpu_set_t>();
+        for &cpu in cpus {
+            libc::CPU_SET(cpu, &mut set);
+        }
+
+        // Failing to pin only costs speed, so the thread keeps running wherever it is
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin(_cpus: &[usize]) {}
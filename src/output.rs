@@ -0,0 +1,72 @@
+use std::{
+    panic,
+    sync::mpsc::{self, SyncSender},
+    thread::{self, JoinHandle},
+};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Writes finished images on a background thread, so that the next frame of an animation is sampled
+/// while the last one is encoded.
+///
+/// Only one image is encoded at a time, and handing over another waits for it to finish, so at most
+/// one frame's images are held in memory besides the one being rendered. Dropping the queue waits
+/// for every image handed over to be written.
+pub struct OutputQueue {
+    sender: Option<SyncSender<Job>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl OutputQueue {
+    /// Starts the thread images are written on.
+    pub fn new() -> OutputQueue {
+        let (sender, receiver) = mpsc::sync_channel::<Job>(0);
+        let thread = thread::Builder::new()
+            .name("output".to_owned())
+            .spawn(move || {
+                for job in receiver {
+                    job();
+                }
+            })
+            .unwrap();
+
+        Self {
+            sender: Some(sender),
+            thread: Some(thread),
+        }
+    }
+
+    /// Runs `write` on the output thread once the images handed over before are written.
+    pub fn write(&mut self, write: impl FnOnce() + Send + 'static) {
+        let sent = self.sender.as_ref().unwrap().send(Box::new(write));
+        if sent.is_err() {
+            // The output thread only stops early if writing an image panicked, which is passed on
+            self.join();
+        }
+    }
+
+    /// Waits for every image handed over to be written, passing on the panic of a write that
+    /// failed.
+    fn join(&mut self) {
+        self.sender = None;
+        if let Some(thread) = self.thread.take() {
+            if let Err(panic) = thread.join() {
+                if !thread::panicking() {
+                    panic::resume_unwind(panic);
+                }
+            }
+        }
+    }
+}
+
+impl Default for OutputQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for OutputQueue {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
@@ -0,0 +1,145 @@
+use clap::ValueEnum;
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::{
+    atomic::AtomicFile,
+    dynamics::Dynamics,
+    numa,
+    sample::{run_chunks, sample_point, Orbits, SampleSettings},
+    volume::VolumeView,
+};
+
+/// The points of a subset of the orbits landing in a [`VolumeView`], for exploring the orbits
+/// themselves in 3D tools rather than the density they add up to.
+#[derive(Clone, Debug, Default)]
+pub struct PointCloud {
+    /// The coordinates of every point along the axes of the view.
+    pub points: Vec<[f32; 3]>,
+    /// The iteration of its orbit every point was reached at, counting from 1.
+    pub iterations: Vec<u32>,
+}
+
+impl PointCloud {
+    /// Adds every point of `other` after the points of this cloud.
+    pub fn merge(&mut self, other: PointCloud) {
+        self.points.extend(other.points);
+        self.iterations.extend(other.iterations);
+    }
+
+    /// Writes this cloud to a file in the given format, along with the iteration of every point
+    /// when `iterations` is set.
+    pub fn write(&self, path: impl AsRef<Path>, format: PointFormat, iterations: bool) -> io::Result<()> {
+        let mut out = AtomicFile::create(path)?;
+
+        match format {
+            PointFormat::Ply => {
+                write!(
+                    out,
+                    "ply\nformat binary_little_endian 1.0\ncomment buddhabrot orbits\nelement vertex {}\n\
+                     property float x\nproperty float y\nproperty float z\n",
+                    self.points.len()
+                )?;
+                if iterations {
+                    writeln!(out, "property uint iteration")?;
+                }
+                writeln!(out, "end_header")?;
+
+                for (point, iteration) in self.points.iter().zip(self.iterations.iter()) {
+                    for coordinate in point {
+                        out.write_all(&coordinate.to_le_bytes())?;
+                    }
+                    if iterations {
+                        out.write_all(&iteration.to_le_bytes())?;
+                    }
+                }
+            },
+            PointFormat::Xyz => {
+                for (&[x, y, z], iteration) in self.points.iter().zip(self.iterations.iter()) {
+                    if iterations {
+                        writeln!(out, "{} {} {} {}", x, y, z, iteration)?;
+                    } else {
+                        writeln!(out, "{} {} {}", x, y, z)?;
+                    }
+                }
+            },
+        }
+
+        out.commit()
+    }
+}
+
+/// The file format a point cloud is written in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum PointFormat {
+    /// A binary PLY file, which MeshLab, CloudCompare and Blender open directly.
+    #[default]
+    Ply,
+    /// A text file of a line of coordinates per point.
+    Xyz,
+}
+
+impl PointFormat {
+    /// Gets the file extension of this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            PointFormat::Ply => "ply",
+            PointFormat::Xyz => "xyz",
+        }
+    }
+}
+
+/// Collects the points landing in the region of `view` of the orbits of `orbits` samples, keeping
+/// every `every`th point of each orbit.
+pub fn sample_points(settings: SampleSettings, view: VolumeView, orbits: usize, every: usize) -> PointCloud {
+    let n = settings.n;
+    let kernel = settings.kernel(settings.fractal);
+
+    let clouds = run_chunks(
+        &settings,
+        orbits,
+        PointCloud::default,
+        |cloud, stream, throttle, count| {
+            let mut trajectory = Vec::new();
+            for _ in 0..count {
+                let sample = sample_point::<f32>(&settings, stream.next_point());
+                let (z, c) = settings.orbit_start(&kernel, sample);
+                let mut deposit = |(iteration, z)| {
+                    if let Some(point) = view.to_point(z, sample) {
+                        cloud.points.push(point);
+                        cloud.iterations.push(iteration as u32 + 1);
+                    }
+                };
+
+                match settings.orbits {
+                    Orbits::Escaping => {
+                        kernel.trajectory(z, c, n, &mut trajectory);
+                        if trajectory.len() as u32 >= settings.min_iterations {
+                            trajectory
+                                .iter()
+                                .copied()
+                                .enumerate()
+                                .step_by(every)
+                                .for_each(&mut deposit);
+                        }
+                    },
+                    Orbits::Bounded => {
+                        if kernel.escape_time(z, c, n).is_none() {
+                            kernel.orbit(z, c, n).enumerate().step_by(every).for_each(&mut deposit);
+                        }
+                    },
+                }
+
+                throttle.tick(1);
+            }
+        },
+    );
+
+    let mut cloud = PointCloud::default();
+    for other in numa::merge_by_node(clouds, PointCloud::merge) {
+        cloud.merge(other);
+    }
+    cloud
+}
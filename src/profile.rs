@@ -0,0 +1,52 @@
+/// The file the spans recorded with the `profile-with-puffin` feature are written to, which can be
+/// opened with `puffin_viewer`.
+pub const PUFFIN_FILE: &str = "buddhabrot.puffin";
+
+/// Keeps the profiler enabled by the `profile-with-tracy` or `profile-with-puffin` feature recording
+/// spans for as long as it lives. Without either feature, spans compile to nothing and this does
+/// nothing.
+///
+/// Tracy streams spans to a Tracy server connecting while the render runs, while puffin collects them
+/// until this is dropped and then writes them to [`PUFFIN_FILE`].
+pub struct Profiler {
+    #[cfg(feature = "profile-with-tracy")]
+    _client: tracy_client::Client,
+    #[cfg(feature = "profile-with-puffin")]
+    view: puffin::GlobalFrameView,
+}
+
+impl Profiler {
+    /// Starts recording spans.
+    pub fn start() -> Profiler {
+        #[cfg(feature = "profile-with-puffin")]
+        puffin::set_scopes_on(true);
+
+        Self {
+            #[cfg(feature = "profile-with-tracy")]
+            _client: tracy_client::Client::start(),
+            #[cfg(feature = "profile-with-puffin")]
+            view: puffin::GlobalFrameView::default(),
+        }
+    }
+}
+
+impl Drop for Profiler {
+    fn drop(&mut self) {
+        // Spans are only handed over to the profiler once the frame they're in is finished
+        profiling::finish_frame!();
+
+        #[cfg(feature = "profile-with-puffin")]
+        {
+            let written = std::fs::File::create(PUFFIN_FILE)
+                .map_err(|e| e.to_string())
+                .and_then(|file| {
+                    let mut file = std::io::BufWriter::new(file);
+                    self.view.lock().write(&mut file).map_err(|e| e.to_string())
+                });
+            match written {
+                Ok(()) => println!("Wrote profile to {}.", PUFFIN_FILE),
+                Err(e) => eprintln!("could not write profile to {}: {}", PUFFIN_FILE, e),
+            }
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    atomic::AtomicFile,
+    color::{Color, Rgb},
+    images::Image,
+};
+
+/// The layout of the tiles of a Deep Zoom image, the pyramid of downsampled versions of an image
+/// that deep-zoom viewers like OpenSeadragon load one tile at a time.
+///
+/// Level `0` of the pyramid is a single pixel, and every level doubles the width and height of the
+/// one before it up to the full image, which is split into square tiles of the same size on every
+/// level.
+#[derive(Clone, Copy, Debug)]
+pub struct Pyramid {
+    /// The width of every tile in pixels, not counting the overlap.
+    pub tile_size: usize,
+    /// The number of pixels every tile shares with each of its neighbours, which keeps viewers from
+    /// showing seams between them.
+    pub overlap: usize,
+}
+
+impl Pyramid {
+    /// Gets the path of the descriptor of a Deep Zoom image whose tiles are stored next to it.
+    pub fn descriptor_path(path: &Path) -> PathBuf {
+        path.with_extension("dzi")
+    }
+
+    /// Gets the directory the tiles of a Deep Zoom image are stored in, which is named after its
+    /// descriptor.
+    pub fn tiles_dir(path: &Path) -> PathBuf {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        path.with_file_name(format!("{}_files", stem))
+    }
+
+    /// Writes the pyramid of `im` as a Deep Zoom image at `path`, handing every tile to
+    /// `write_tile` along with the path without an extension it's written to.
+    pub fn write(
+        self,
+        path: &Path,
+        im: Image<Rgb>,
+        extension: &str,
+        mut write_tile: impl FnMut(Image<Rgb>, PathBuf),
+    ) -> io::Result<()> {
+        let (width, height) = (im.width, im.size / im.width);
+        let mut descriptor = AtomicFile::create(Self::descriptor_path(path))?;
+        writeln!(descriptor, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            descriptor,
+            r#"<Image xmlns="http://schemas.microsoft.com/deepzoom/2008" Format="{}" Overlap="{}" TileSize="{}">"#,
+            extension, self.overlap, self.tile_size
+        )?;
+        writeln!(descriptor, r#"  <Size Width="{}" Height="{}"/>"#, width, height)?;
+        writeln!(descriptor, "</Image>")?;
+        descriptor.commit()?;
+
+        let tiles_dir = Self::tiles_dir(path);
+        let levels = width.max(height).next_power_of_two().trailing_zeros() as usize;
+        let mut level = im;
+        for index in (0..=levels).rev() {
+            let level_dir = tiles_dir.join(index.to_string());
+            fs::create_dir_all(&level_dir)?;
+
+            let (width, height) = (level.width, level.size / level.width);
+            for row in 0..height.div_ceil(self.tile_size) {
+                for column in 0..width.div_ceil(self.tile_size) {
+                    let x = (column * self.tile_size).saturating_sub(self.overlap);
+                    let y = (row * self.tile_size).saturating_sub(self.overlap);
+                    let tile_width = (((column + 1) * self.tile_size + self.overlap).min(width)) - x;
+                    let tile_height = (((row + 1) * self.tile_size + self.overlap).min(height)) - y;
+
+                    let mut tile = Image::new(tile_width * tile_height, tile_width);
+                    for (tx, ty, px) in tile.enumerate_pixels_mut() {
+                        *px = level.get((x + tx, y + ty));
+                    }
+                    write_tile(tile, level_dir.join(format!("{}_{}", column, row)));
+                }
+            }
+
+            if index > 0 {
+                level = downsample(&level);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Halves the width and height of an image, rounding up, by averaging every block of up to two by
+/// two pixels.
+pub fn downsample(im: &Image<Rgb>) -> Image<Rgb> {
+    let (width, height) = (im.width, im.size / im.width);
+    let (half_width, half_height) = (width.div_ceil(2), height.div_ceil(2));
+
+    let mut half = Image::<Rgb>::new(half_width * half_height, half_width);
+    for (x, y, px) in half.enumerate_pixels_mut() {
+        let mut sum = Rgb::empty();
+        let mut count = 0.0;
+        for sy in 2 * y..(2 * y + 2).min(height) {
+            for sx in 2 * x..(2 * x + 2).min(width) {
+                sum.add(im.get((sx, sy)));
+                count += 1.0;
+            }
+        }
+        *px = sum.map(|v| v / count);
+    }
+
+    half
+}
@@ -0,0 +1,141 @@
+use std::ops::{Add, Mul, Sub};
+
+use crate::complex::Complex;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quaternion<T> {
+    pub re: T,
+    pub i: T,
+    pub j: T,
+    pub k: T,
+}
+
+impl<T> Quaternion<T> {
+    /// Creates a new instance of `Quaternion` with the given real part and `i`, `j` and `k` parts.
+    #[inline]
+    pub fn new(re: T, i: T, j: T, k: T) -> Quaternion<T> {
+        Self { re, i, j, k }
+    }
+
+    /// Maps a function over every part of a quaternion.
+    #[inline]
+    pub fn map<F: Fn(T) -> U, U>(self, f: F) -> Quaternion<U> {
+        Quaternion::<U> {
+            re: f(self.re),
+            i: f(self.i),
+            j: f(self.j),
+            k: f(self.k),
+        }
+    }
+}
+
+impl<T: Default> Quaternion<T> {
+    /// Embeds a complex number into the quaternions, with its imaginary part along `i`.
+    #[inline]
+    pub fn from_complex(z: Complex<T>) -> Quaternion<T> {
+        Self {
+            re: z.re,
+            i: z.im,
+            j: Default::default(),
+            k: Default::default(),
+        }
+    }
+}
+
+impl<T: Default> Default for Quaternion<T> {
+    fn default() -> Self {
+        Self {
+            re: Default::default(),
+            i: Default::default(),
+            j: Default::default(),
+            k: Default::default(),
+        }
+    }
+}
+
+impl<T: Add<T, Output = T>> Add for Quaternion<T> {
+    type Output = Self;
+
+    /// Adds two quaternions together.
+    #[inline]
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            re: self.re + rhs.re,
+            i: self.i + rhs.i,
+            j: self.j + rhs.j,
+            k: self.k + rhs.k,
+        }
+    }
+}
+
+impl<T: Sub<T, Output = T>> Sub for Quaternion<T> {
+    type Output = Self;
+
+    /// Subtracts one quaternion from another.
+    #[inline]
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self {
+            re: self.re - rhs.re,
+            i: self.i - rhs.i,
+            j: self.j - rhs.j,
+            k: self.k - rhs.k,
+        }
+    }
+}
+
+impl<T: Clone + Copy + Mul<T, Output = T> + Add<T, Output = T> + Sub<T, Output = T>> Mul for Quaternion<T> {
+    type Output = Self;
+
+    /// Computes the Hamilton product of two quaternions, which isn't commutative.
+    #[inline]
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self {
+            re: self.re * rhs.re - self.i * rhs.i - self.j * rhs.j - self.k * rhs.k,
+            i: self.re * rhs.i + self.i * rhs.re + self.j * rhs.k - self.k * rhs.j,
+            j: self.re * rhs.j - self.i * rhs.k + self.j * rhs.re + self.k * rhs.i,
+            k: self.re * rhs.k + self.i * rhs.j - self.j * rhs.i + self.k * rhs.re,
+        }
+    }
+}
+
+impl<T: Clone + Copy + Mul<T, Output = T> + Add<T, Output = T> + Sub<T, Output = T>> Quaternion<T> {
+    /// Computes the square of a quaternion, which is cheaper than multiplying it by itself.
+    #[inline]
+    pub fn square(self) -> Quaternion<T> {
+        let two_re = self.re + self.re;
+        Self {
+            re: self.re * self.re - self.i * self.i - self.j * self.j - self.k * self.k,
+            i: two_re * self.i,
+            j: two_re * self.j,
+            k: two_re * self.k,
+        }
+    }
+
+    /// Computes the squared absolute value (norm) of a quaternion.
+    #[inline]
+    pub fn norm_sqr(self) -> T {
+        self.re * self.re + self.i * self.i + self.j * self.j + self.k * self.k
+    }
+}
+
+macro_rules! impl_op_real {
+    ($($op:tt, $fn:ident, $trait:ident);*) => {
+        $(
+            impl<T: Clone + Copy + $trait<T, Output = T>> $trait<T> for Quaternion<T> {
+                type Output = Self;
+
+                #[inline]
+                fn $fn(self, rhs: T) -> Self::Output {
+                    Self {
+                        re: self.re $op rhs,
+                        i: self.i $op rhs,
+                        j: self.j $op rhs,
+                        k: self.k $op rhs,
+                    }
+                }
+            }
+        )*
+    }
+}
+
+impl_op_real!(+, add, Add; -, sub, Sub; *, mul, Mul);
@@ -0,0 +1,100 @@
+use clap::ValueEnum;
+use num_traits::Float;
+
+use crate::complex::Complex;
+
+/// An axis of the 4D space `(cr, ci, zr, zi)` the points of every orbit live in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Axis {
+    Cr,
+    Ci,
+    Zr,
+    Zi,
+}
+
+impl Axis {
+    #[inline]
+    pub(crate) fn index(self) -> usize {
+        match self {
+            Axis::Cr => 0,
+            Axis::Ci => 1,
+            Axis::Zr => 2,
+            Axis::Zi => 3,
+        }
+    }
+}
+
+/// A rotation of the 4D space `(cr, ci, zr, zi)`, applied to every point of an orbit before it is
+/// projected onto the image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rotation {
+    /// The rows of the rotation matrix, acting on column vectors `(cr, ci, zr, zi)`.
+    matrix: [[f32; 4]; 4],
+}
+
+impl Rotation {
+    /// The rotation that leaves every point in place.
+    pub const IDENTITY: Rotation = Rotation {
+        matrix: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    };
+
+    /// Creates a rotation by `angle` radians within the plane spanned by axes `a` and `b`, turning
+    /// `a` towards `b`.
+    pub fn plane(a: Axis, b: Axis, angle: f32) -> Rotation {
+        let (a, b) = (a.index(), b.index());
+        let (sin, cos) = angle.sin_cos();
+
+        let mut matrix = Self::IDENTITY.matrix;
+        matrix[a][a] = cos;
+        matrix[a][b] = -sin;
+        matrix[b][a] = sin;
+        matrix[b][b] = cos;
+        Self { matrix }
+    }
+
+    /// Creates the rotation applying `self` followed by `next`.
+    pub fn then(self, next: Rotation) -> Rotation {
+        let mut matrix = [[0.0; 4]; 4];
+        for (i, row) in matrix.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = (0..4).map(|k| next.matrix[i][k] * self.matrix[k][j]).sum();
+            }
+        }
+        Self { matrix }
+    }
+
+    /// Creates the rotation applying `self` `n` times.
+    pub fn powi(mut self, mut n: u32) -> Rotation {
+        let mut result = Self::IDENTITY;
+        while n > 0 {
+            if n & 1 == 1 {
+                result = result.then(self);
+            }
+            self = self.then(self);
+            n >>= 1;
+        }
+        result
+    }
+
+    /// Rotates the point `(c, z)`.
+    #[inline]
+    pub fn apply<F: Float>(&self, c: Complex<F>, z: Complex<F>) -> (Complex<F>, Complex<F>) {
+        let v = [c.re, c.im, z.re, z.im];
+        let [cr, ci, zr, zi] = self.matrix.map(|row| {
+            let row = row.map(|x| F::from(x).unwrap());
+            row[0] * v[0] + row[1] * v[1] + row[2] * v[2] + row[3] * v[3]
+        });
+        (Complex::new(cr, ci), Complex::new(zr, zi))
+    }
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
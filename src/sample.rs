@@ -1,108 +1,1423 @@
+use clap::ValueEnum;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-use rand::{thread_rng, Rng};
+use num_traits::{Float, NumCast};
+use rayon::prelude::*;
 use std::{
-    sync::{Arc, Mutex},
-    thread,
+    fmt,
+    ops::Range,
+    sync::{Arc, Mutex, OnceLock},
 };
 
 use crate::{
+    canvas::{Canvas, Mobius, OriginsWeight, PlaneMap, Viewport},
     color::{Color, ColorChannel},
     complex::Complex,
+    deep::{plot_deep_sample, sample_deep, DeepStats, DeepZoom, ReferenceOrbit},
+    dynamics::{Dynamics, OrbitState},
+    fractal::{ChannelFractals, Fractal, Kernel},
     images::Image,
+    interrupt,
+    metropolis::{MetropolisSampler, MetropolisSettings, MetropolisStats},
+    noise::{FrameNoise, NoiseStream},
+    numa,
+    rotation::Rotation,
+    simd::SimdFloat,
+    throttle::Throttle,
+    transform::Transforms,
+    trap::OrbitTrap,
 };
 
+/// The parameters of a buddhabrot render.
+#[derive(Clone, Copy, Debug)]
+pub struct SampleSettings {
+    /// The number of mandelbrot iterations each complex number undergoes.
+    pub n: u32,
+    /// The minimum number of iterations an orbit must survive before escaping for it to be plotted.
+    pub min_iterations: u32,
+    /// The number of samples taken per pixel.
+    pub m: u32,
+    pub scale: f64,
+    pub center: Complex<f64>,
+    /// The floating point precision orbits are iterated in.
+    pub precision: Precision,
+    /// The seed and frame used to generate samples.
+    pub noise: FrameNoise,
+    /// The amount each point of an orbit deposits into the image.
+    pub weight: OrbitWeight,
+    /// The channel to accumulate each orbit's arc length into, in addition to the regular deposit.
+    pub arc_length_channel: Option<ColorChannel>,
+    /// The strategy used to choose samples.
+    pub sampler: Sampler,
+    /// The iteration limits used to route orbits to color channels, rendering a nebulabrot in a
+    /// single pass. When set, `n` should be the largest of the limits.
+    pub nebulabrot: Option<Nebulabrot>,
+    /// Which orbits are plotted.
+    pub orbits: Orbits,
+    /// The iteration whose orbits are plotted.
+    pub fractal: Fractal,
+    /// The power `z` is raised to every iteration.
+    pub power: f32,
+    /// The fraction of time each sampling thread spends working, between 0-1. Threads sleep for the
+    /// rest of the time, keeping the machine responsive.
+    pub duty_cycle: f32,
+    /// The fractal plotted into each color channel, overriding `fractal`.
+    pub channel_fractals: Option<ChannelFractals>,
+    /// The constant `c` of a Julia set. When set, samples are initial values of `z` rather than
+    /// values of `c`, rendering the density of the Julia set's orbits.
+    pub julia: Option<Complex<f32>>,
+    /// The coefficient of the previous iterate of the phoenix fractal.
+    pub phoenix: Complex<f32>,
+    /// Whether orbits are routed to a color channel by the root of `zᵈ - 1` they converge to, for
+    /// fractals with roots.
+    pub basins: bool,
+    /// The pair of coordinates of the 4D space `(cr, ci, zr, zi)` plotted onto the image.
+    pub projection: Projection,
+    /// The rotation of the 4D space applied to every point before it is projected, if any.
+    pub rotation: Option<Rotation>,
+    /// The transforms applied to every point of an orbit before it is rotated and projected.
+    pub transform: Transforms,
+    /// The Möbius transformation applied to the plotted plane before it is mapped onto the image, if
+    /// any.
+    pub mobius: Option<Mobius>,
+    /// The orbit trap points are weighted by when weighting by trap.
+    pub trap: OrbitTrap,
+    /// Whether bounded orbits are routed to color channels by the period of the cycle they settle
+    /// into.
+    pub periods: bool,
+    /// Whether escaping orbits are plotted while they are iterated a second time, after a first
+    /// pass storing nothing finds out that they escape, rather than storing their trajectories. No
+    /// memory is needed however long orbits are, though orbits whose escape time isn't already
+    /// known are iterated twice.
+    pub streaming: bool,
+    /// The number of logical streams samples are split between, each accumulating a fixed range of
+    /// the samples in order into its own image. When set, the image is bit for bit the same however
+    /// many threads render it, at the cost of an image per stream rather than per thread.
+    pub streams: Option<usize>,
+    /// The slice of the samples of the image that is drawn, or every sample when unset.
+    pub slice: Option<SampleSlice>,
+}
+
+impl Default for SampleSettings {
+    fn default() -> Self {
+        Self {
+            n: 1000,
+            min_iterations: 0,
+            m: 1,
+            scale: 1.0,
+            center: Complex::new(0.0, 0.0),
+            precision: Precision::default(),
+            noise: FrameNoise::default(),
+            weight: OrbitWeight::default(),
+            arc_length_channel: None,
+            sampler: Sampler::default(),
+            nebulabrot: None,
+            orbits: Orbits::default(),
+            fractal: Fractal::default(),
+            power: 2.0,
+            duty_cycle: 1.0,
+            channel_fractals: None,
+            julia: None,
+            phoenix: Complex::new(-0.5, 0.0),
+            basins: false,
+            projection: Projection::default(),
+            rotation: None,
+            transform: Transforms::IDENTITY,
+            mobius: None,
+            trap: OrbitTrap::default(),
+            periods: false,
+            streaming: false,
+            streams: None,
+            slice: None,
+        }
+    }
+}
+
+/// One of `count` equal slices of the samples of an image, so that an image can be sampled a slice
+/// at a time and looked at in between. Sampling every slice draws exactly the samples of the whole
+/// image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SampleSlice {
+    pub index: usize,
+    pub count: usize,
+}
+
+impl SampleSlice {
+    /// Gets the work items of [`CHUNK_SIZE`] samples in this slice, out of the `chunks` work items
+    /// of the image.
+    pub fn chunks(self, chunks: usize) -> Range<usize> {
+        self.index * chunks / self.count..(self.index + 1) * chunks / self.count
+    }
+
+    /// Gets the indices of the samples in this slice, out of the `samples` of the image.
+    pub fn samples(self, samples: u64) -> Range<u64> {
+        let chunks = self.chunks(samples.div_ceil(CHUNK_SIZE as u64) as usize);
+        (chunks.start * CHUNK_SIZE) as u64..((chunks.end * CHUNK_SIZE) as u64).min(samples)
+    }
+}
+
+/// Determines how samples are chosen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Sampler {
+    /// Samples are drawn uniformly from the sampled region.
+    #[default]
+    Uniform,
+    /// Samples are drawn with Metropolis-Hastings importance sampling, favoring samples whose
+    /// trajectories land inside the image.
+    Metropolis(MetropolisSettings),
+}
+
+/// Counts of how productive the samples drawn into a canvas were.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SampleCounts {
+    /// The number of samples drawn, counting a sample once for every fractal it's iterated under.
+    pub samples: u64,
+    /// The number of samples whose orbits escaped within `n` iterations.
+    pub escaped: u64,
+    /// The number of points of plotted orbits that landed inside the main image.
+    pub plotted: u64,
+    /// The number of points of plotted orbits that landed outside the main image.
+    pub culled: u64,
+}
+
+impl SampleCounts {
+    /// Adds up the counts of two sampling runs.
+    pub fn merge(self, rhs: SampleCounts) -> SampleCounts {
+        Self {
+            samples: self.samples + rhs.samples,
+            escaped: self.escaped + rhs.escaped,
+            plotted: self.plotted + rhs.plotted,
+            culled: self.culled + rhs.culled,
+        }
+    }
+}
+
+/// Statistics collected while sampling.
+#[derive(Clone, Debug, Default)]
+pub struct SampleStats {
+    /// The acceptance statistics of Metropolis-Hastings sampling, if it was used.
+    pub metropolis: Option<MetropolisStats>,
+    /// The glitch statistics of a deep zoom, if one was rendered.
+    pub deep: Option<DeepStats>,
+    /// The counts of every accumulator the samples were drawn into, which is one per thread unless
+    /// [`streams`](SampleSettings::streams) were set, or one per device for accelerated renders.
+    /// Empty if the sampler doesn't count them.
+    pub threads: Vec<SampleCounts>,
+}
+
+impl SampleStats {
+    /// Gets the counts of every thread added up.
+    pub fn counts(&self) -> SampleCounts {
+        self.threads
+            .iter()
+            .fold(SampleCounts::default(), |total, &counts| total.merge(counts))
+    }
+
+    /// Combines the statistics of two sampling runs.
+    pub fn merge(self, rhs: SampleStats) -> SampleStats {
+        Self {
+            threads: self.threads.into_iter().chain(rhs.threads).collect(),
+            metropolis: match (self.metropolis, rhs.metropolis) {
+                (Some(a), Some(b)) => Some(a.merge(b)),
+                (a, b) => a.or(b),
+            },
+            deep: match (self.deep, rhs.deep) {
+                (Some(a), Some(b)) => Some(a.merge(b)),
+                (a, b) => a.or(b),
+            },
+        }
+    }
+}
+
+impl SampleSettings {
+    /// Gets the viewport of the main image.
+    #[inline]
+    pub fn viewport(&self) -> Viewport {
+        Viewport::new(self.center, self.scale)
+    }
+
+    /// Gets the map from the plotted plane to the pixels of the main image.
+    #[inline]
+    pub fn plane_map(&self) -> PlaneMap {
+        PlaneMap {
+            viewport: self.viewport(),
+            mobius: self.mobius,
+        }
+    }
+
+    /// Transforms, rotates and projects the point `z` of the orbit of `sample` onto the image plane.
+    #[inline]
+    pub fn project<F: Float>(&self, z: Complex<F>, sample: Complex<F>) -> Complex<F> {
+        let z = self.transform.apply(z);
+        match self.rotation {
+            Some(rotation) => {
+                let (c, z) = rotation.apply(sample, z);
+                self.projection.project(z, c)
+            },
+            None => self.projection.project(z, sample),
+        }
+    }
+
+    /// Gets the kernel iterating `fractal` with these settings' power.
+    #[inline]
+    pub fn kernel(&self, fractal: Fractal) -> Kernel {
+        Kernel::new(fractal, self.power).with_phoenix(self.phoenix)
+    }
+
+    /// Gets every fractal plotted with these settings.
+    pub fn fractals(&self) -> Vec<Fractal> {
+        match self.channel_fractals {
+            Some(channel_fractals) => channel_fractals.fractals(),
+            None => vec![self.fractal],
+        }
+    }
+
+    /// Gets the largest escape radius of any fractal plotted with these settings.
+    pub fn escape_radius(&self) -> f32 {
+        self.fractals()
+            .into_iter()
+            .map(|fractal| self.kernel(fractal).escape_radius())
+            .fold(0.0, f32::max)
+    }
+
+    /// Gets the initial value of `z` and the constant `c` of the orbit of `sample` under `dynamics`.
+    #[inline]
+    pub fn orbit_start<D: Dynamics<F>, F: Float>(&self, dynamics: &D, sample: Complex<F>) -> (Complex<F>, Complex<F>) {
+        match self.julia {
+            Some(c) => (sample, c.map(|x| F::from(x).unwrap())),
+            None => (dynamics.start(sample), sample),
+        }
+    }
+
+    /// Gets the width of a pixel of a square image of the view `width` pixels across.
+    #[inline]
+    pub fn pixel_size(&self, width: usize) -> f64 {
+        4.0 * self.scale / width as f64
+    }
+
+    /// Whether or not every feature enabled by these settings can be rendered in arbitrary
+    /// precision.
+    #[inline]
+    pub fn supports_arbitrary_precision(&self) -> bool {
+        self.fixed_precision_feature().is_none()
+    }
+
+    /// Gets the first feature enabled by these settings that can't be rendered in arbitrary
+    /// precision, if any.
+    fn fixed_precision_feature(&self) -> Option<&'static str> {
+        if self.fractal != Fractal::Mandelbrot || self.power != 2.0 || self.channel_fractals.is_some() {
+            Some("fractals other than the mandelbrot set")
+        } else if matches!(self.sampler, Sampler::Metropolis(_)) {
+            Some("Metropolis sampling")
+        } else if self.weight == OrbitWeight::Trap {
+            Some("orbit traps")
+        } else if self.basins || self.periods {
+            Some("root and period channels")
+        } else if self.projection != Projection::ZrZi
+            || self.rotation.is_some()
+            || !self.transform.is_identity()
+            || self.mobius.is_some()
+        {
+            Some("projections, rotations, transforms and Möbius transformations")
+        } else {
+            None
+        }
+    }
+
+    /// Gets the kernel of every fractal plotted with these settings, paired with its fractal.
+    pub fn kernels(&self) -> Vec<(Fractal, Kernel)> {
+        self.fractals()
+            .into_iter()
+            .map(|fractal| (fractal, self.kernel(fractal)))
+            .collect()
+    }
+
+    /// Checks that rendering a `width` by `height` image with these settings can produce anything
+    /// other than an empty or trivial image.
+    pub fn validate(&self, width: usize, height: usize) -> Result<(), ConfigError> {
+        if width == 0 || height == 0 {
+            return Err(ConfigError::EmptyImage);
+        }
+
+        if self.m == 0 {
+            return Err(ConfigError::NoSamples);
+        }
+
+        if self.n == 0 {
+            return Err(ConfigError::NoIterations);
+        }
+
+        if self.min_iterations > self.n {
+            return Err(ConfigError::MinIterationsAboveMax {
+                min_iterations: self.min_iterations,
+                n: self.n,
+            });
+        }
+
+        if !(self.scale.is_finite() && self.scale > 0.0) {
+            return Err(ConfigError::InvalidScale(self.scale));
+        }
+
+        if !(self.duty_cycle > 0.0 && self.duty_cycle <= 1.0) {
+            return Err(ConfigError::InvalidDutyCycle(self.duty_cycle));
+        }
+
+        if !(self.power.is_finite() && self.power > 1.0) {
+            return Err(ConfigError::InvalidPower(self.power));
+        }
+
+        if self.power != 2.0 {
+            if let Some(fractal) = self.fractals().into_iter().find(|fractal| !fractal.has_power()) {
+                return Err(ConfigError::FixedPower(fractal));
+            }
+        }
+
+        if let Some(nebulabrot) = self.nebulabrot {
+            for (channel, limit) in nebulabrot.limits() {
+                if limit == 0 || limit < self.min_iterations || limit > self.n {
+                    return Err(ConfigError::EmptyNebulabrotChannel { channel, limit });
+                }
+            }
+        }
+
+        if self.orbits == Orbits::Escaping {
+            if let Some(fractal) = self.fractals().into_iter().find(|fractal| !fractal.escapes()) {
+                return Err(ConfigError::NeverEscapes(fractal));
+            }
+        }
+
+        if self.basins {
+            if self.nebulabrot.is_some() || self.channel_fractals.is_some() {
+                return Err(ConfigError::ChannelConflict);
+            }
+
+            if !self.fractal.has_roots() {
+                return Err(ConfigError::NoRoots(self.fractal));
+            }
+
+            if self.power.fract() != 0.0 {
+                return Err(ConfigError::FractionalRoots(self.power));
+            }
+        }
+
+        if self.periods {
+            if self.nebulabrot.is_some() || self.channel_fractals.is_some() || self.basins {
+                return Err(ConfigError::ChannelConflict);
+            }
+
+            if self.orbits != Orbits::Bounded {
+                return Err(ConfigError::PeriodsEscaping);
+            }
+        }
+
+        if self.channel_fractals.is_some() {
+            if self.nebulabrot.is_some() {
+                return Err(ConfigError::ChannelConflict);
+            }
+
+            if matches!(self.sampler, Sampler::Metropolis(_)) {
+                return Err(ConfigError::ChannelFractalsMetropolis);
+            }
+        }
+
+        if self.precision == Precision::Arbitrary {
+            if let Some(feature) = self.fixed_precision_feature() {
+                return Err(ConfigError::FixedPrecisionOnly(feature));
+            }
+        }
+
+        if self.orbits == Orbits::Bounded
+            && matches!(self.sampler, Sampler::Metropolis(_))
+            && self.n > MAX_RETAINED_ORBIT
+        {
+            return Err(ConfigError::OrbitTooLong { n: self.n });
+        }
+
+        // Projections onto `c`, or rotations mixing it in, plot samples, which fill the whole
+        // viewport, and transforms can carry points anywhere
+        if self.projection != Projection::ZrZi
+            || self.rotation.is_some()
+            || !self.transform.is_identity()
+            || self.mobius.is_some()
+        {
+            return Ok(());
+        }
+
+        // Every plotted point other than a sample itself lies within the escape radius, so if the
+        // closest point of the viewport to the origin is outside it, only the samples are plotted
+        let radius = self.escape_radius();
+        let half = 2.0 * self.scale;
+        let nearest = Complex::new(
+            0.0f64.clamp(self.center.re - half, self.center.re + half),
+            0.0f64.clamp(self.center.im - half, self.center.im + half),
+        );
+        let distance = nearest.abs() as f32;
+        if distance > radius {
+            return Err(ConfigError::ViewportOutsideOrbits { distance, radius });
+        }
+
+        Ok(())
+    }
+}
+
+/// A configuration that would produce an empty or trivial image, or that can't be rendered at all.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConfigError {
+    /// The image has no pixels.
+    EmptyImage,
+    /// No samples would be taken.
+    NoSamples,
+    /// Orbits would not be iterated at all.
+    NoIterations,
+    /// The minimum number of iterations is larger than the maximum, so every orbit is filtered out.
+    MinIterationsAboveMax { min_iterations: u32, n: u32 },
+    /// The scale is zero, negative, or not finite.
+    InvalidScale(f64),
+    /// The duty cycle is not between 0-1, or is 0.
+    InvalidDutyCycle(f32),
+    /// The power is at most 1 or not finite, so orbits never escape.
+    InvalidPower(f32),
+    /// The power isn't 2, but the fractal doesn't raise `z` to a power.
+    FixedPower(Fractal),
+    /// Escaping orbits would be plotted, but orbits of the fractal converge instead of escaping.
+    NeverEscapes(Fractal),
+    /// Orbits would be routed to color channels by root, but the fractal has no roots.
+    NoRoots(Fractal),
+    /// Orbits would be routed to color channels by root, but the power isn't a whole number, so the
+    /// roots can't be counted.
+    FractionalRoots(f32),
+    /// The viewport lies entirely outside the escape radius, where no orbit ever lands.
+    ViewportOutsideOrbits { distance: f32, radius: f32 },
+    /// A nebulabrot channel's iteration limit is 0, below the minimum number of iterations, or above
+    /// the number of iterations, so the channel would receive no orbits.
+    EmptyNebulabrotChannel { channel: ColorChannel, limit: u32 },
+    /// Bounded orbits would have to be stored, but are longer than [`MAX_RETAINED_ORBIT`].
+    OrbitTooLong { n: u32 },
+    /// Orbits would be routed to color channels by more than one of nebulabrot limits, fractal, root
+    /// and period.
+    ChannelConflict,
+    /// A feature that needs orbits in a fixed precision would be rendered in arbitrary precision.
+    FixedPrecisionOnly(&'static str),
+    /// Orbits would be routed to color channels by period, but only escaping orbits are plotted,
+    /// which never settle into a cycle.
+    PeriodsEscaping,
+    /// Metropolis sampling follows a single trajectory per sample, so it can't plot a different
+    /// fractal into each channel.
+    ChannelFractalsMetropolis,
+    /// Shared accumulation only counts hits, but the named feature deposits something else.
+    NotCountable(&'static str),
+}
+
+impl ConfigError {
+    /// Whether or not this error means nothing would be plotted, rather than that the settings are
+    /// invalid or can't be rendered together.
+    pub fn empties_image(&self) -> bool {
+        matches!(
+            self,
+            ConfigError::EmptyImage
+                | ConfigError::NoSamples
+                | ConfigError::NoIterations
+                | ConfigError::MinIterationsAboveMax { .. }
+                | ConfigError::NeverEscapes(_)
+                | ConfigError::ViewportOutsideOrbits { .. }
+                | ConfigError::EmptyNebulabrotChannel { .. }
+        )
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::EmptyImage => write!(f, "the image has no pixels"),
+            ConfigError::NoSamples => write!(f, "the number of samples per pixel is 0, so nothing would be plotted"),
+            ConfigError::NoIterations => write!(f, "the number of iterations is 0, so no orbit would be plotted"),
+            ConfigError::MinIterationsAboveMax { min_iterations, n } => write!(
+                f,
+                "the minimum number of iterations ({}) is larger than the number of iterations ({}), so every \
+                 orbit would be filtered out",
+                min_iterations, n
+            ),
+            ConfigError::InvalidScale(scale) => write!(f, "the scale must be a positive number, but got {}", scale),
+            ConfigError::InvalidDutyCycle(duty_cycle) => {
+                write!(
+                    f,
+                    "the duty cycle must be larger than 0 and at most 1, but got {}",
+                    duty_cycle
+                )
+            },
+            ConfigError::InvalidPower(power) => write!(f, "the power must be larger than 1, but got {}", power),
+            ConfigError::FixedPower(fractal) => write!(
+                f,
+                "the {} fractal only supports a power of 2",
+                fractal.to_possible_value().unwrap().get_name()
+            ),
+            ConfigError::NeverEscapes(fractal) => write!(
+                f,
+                "orbits of the {} fractal converge instead of escaping, so only bounded orbits can be plotted",
+                fractal.to_possible_value().unwrap().get_name()
+            ),
+            ConfigError::NoRoots(fractal) => write!(
+                f,
+                "orbits of the {} fractal don't converge to roots, so they can't be told apart by root",
+                fractal.to_possible_value().unwrap().get_name()
+            ),
+            ConfigError::FractionalRoots(power) => write!(
+                f,
+                "orbits can only be told apart by root for whole number powers, but got {}",
+                power
+            ),
+            ConfigError::ViewportOutsideOrbits { distance, radius } => write!(
+                f,
+                "the viewport is {:.3} away from the origin, entirely outside the escape radius of {:.3}, so no \
+                 orbit would land inside it",
+                distance, radius
+            ),
+            ConfigError::EmptyNebulabrotChannel { channel, limit } => write!(
+                f,
+                "the {:?} nebulabrot channel's iteration limit ({}) must be positive, at least the minimum number of \
+                 iterations, and at most the number of iterations",
+                channel, limit
+            ),
+            ConfigError::OrbitTooLong { n } => write!(
+                f,
+                "Metropolis sampling of bounded orbits stores every orbit, but {} iterations exceeds the limit of {}",
+                n, MAX_RETAINED_ORBIT
+            ),
+            ConfigError::ChannelConflict => write!(
+                f,
+                "color channels can be assigned nebulabrot iteration limits, fractals, roots or periods, but only one of \
+                 them"
+            ),
+            ConfigError::FixedPrecisionOnly(feature) => {
+                write!(f, "{} can't be rendered in arbitrary precision", feature)
+            },
+            ConfigError::PeriodsEscaping => write!(
+                f,
+                "escaping orbits never settle into a cycle, so only bounded orbits can be told apart by period"
+            ),
+            ConfigError::ChannelFractalsMetropolis => {
+                write!(
+                    f,
+                    "Metropolis sampling can't plot a different fractal into each channel"
+                )
+            },
+            ConfigError::NotCountable(feature) => write!(
+                f,
+                "shared accumulation only counts hits, so it can't be used with {}",
+                feature
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// The number of samples drawn before their orbits are iterated together, enough to fill the SIMD
+/// lanes of every precision many times over.
+const BATCH_SIZE: usize = 256;
+
+/// The maximum number of points of a bounded orbit that may be stored at once. Bounded orbits are
+/// as long as the number of iterations, so they are streamed rather than stored where possible.
+pub const MAX_RETAINED_ORBIT: u32 = 1 << 22;
+
+/// The floating point precision orbits are iterated and plotted in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Precision {
+    /// 32-bit floats, which are fastest but break down visibly at scales below about `1e-5`.
+    #[default]
+    #[value(name = "f32")]
+    Single,
+    /// 64-bit floats, for deep zooms down to scales of about `1e-13`.
+    #[value(name = "f64")]
+    Double,
+    /// Floats with as many bits as the scale needs, for zooms of any depth. Every orbit is iterated
+    /// in software, which is orders of magnitude slower, and only plain mandelbrot and Julia orbits
+    /// can be iterated this way.
+    #[value(name = "arbitrary")]
+    Arbitrary,
+}
+
+impl Precision {
+    /// Gets the size of the smallest pixel this precision can resolve, which is twice the spacing of
+    /// its floats around the escape radius, where orbits have the least precision to spare.
+    pub fn min_pixel_size(self) -> f64 {
+        match self {
+            Precision::Single => 4.0 * f32::EPSILON as f64,
+            Precision::Double => 4.0 * f64::EPSILON,
+            Precision::Arbitrary => 0.0,
+        }
+    }
+
+    /// Chooses the fastest precision that resolves pixels of the given size.
+    pub fn for_pixel_size(pixel_size: f64) -> Precision {
+        [Precision::Single, Precision::Double]
+            .into_iter()
+            .find(|precision| pixel_size >= precision.min_pixel_size())
+            .unwrap_or(Precision::Arbitrary)
+    }
+
+    /// Gets the name this precision is chosen by on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Precision::Single => "f32",
+            Precision::Double => "f64",
+            Precision::Arbitrary => "arbitrary",
+        }
+    }
+}
+
+/// Determines which orbits are plotted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Orbits {
+    /// Plot the orbits of samples that escape within `n` iterations, rendering the buddhabrot.
+    #[default]
+    Escaping,
+    /// Plot the orbits of samples that don't escape within `n` iterations, rendering the
+    /// anti-buddhabrot.
+    Bounded,
+}
+
+/// The iteration limits of the three channels of a nebulabrot. Each channel plots the orbits that
+/// escape within its limit, so one pass renders what would otherwise take three.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Nebulabrot {
+    pub red: u32,
+    pub green: u32,
+    pub blue: u32,
+}
+
+impl Nebulabrot {
+    /// Creates new nebulabrot channel limits.
+    #[inline]
+    pub fn new(red: u32, green: u32, blue: u32) -> Nebulabrot {
+        Self { red, green, blue }
+    }
+
+    /// Gets the largest limit of any channel, which is the number of iterations a sample must
+    /// undergo.
+    #[inline]
+    pub fn max(&self) -> u32 {
+        self.red.max(self.green).max(self.blue)
+    }
+
+    /// Gets each channel paired with its limit.
+    #[inline]
+    pub fn limits(&self) -> [(ColorChannel, u32); 3] {
+        [
+            (ColorChannel::Red, self.red),
+            (ColorChannel::Green, self.green),
+            (ColorChannel::Blue, self.blue),
+        ]
+    }
+
+    /// Gets the deposit of an orbit that escaped after `escape` iterations, with a one in every
+    /// channel whose limit it escaped within.
+    #[inline]
+    pub fn route<T: Color>(&self, escape: u32) -> T {
+        let mut deposit = T::empty();
+        for (channel, limit) in self.limits() {
+            if escape <= limit {
+                deposit.add(T::one(channel));
+            }
+        }
+        deposit
+    }
+}
+
+impl fmt::Display for Nebulabrot {
+    /// Formats the limits of the red, green and blue channels, separated by commas.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{},{}", self.red, self.green, self.blue)
+    }
+}
+
+/// Determines the amount each point of an orbit deposits into the image.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OrbitWeight {
+    /// Every point deposits exactly one hit.
+    #[default]
+    Hits,
+    /// Every point deposits the total Euclidean arc length of its orbit, emphasizing orbits that
+    /// travel far over ones that stay close together.
+    ArcLength,
+    /// Every point deposits its weight under the orbit trap, highlighting orbits that pass close to
+    /// the trap.
+    Trap,
+}
+
+/// Determines which two coordinates of the 4D space `(cr, ci, zr, zi)` each point of an orbit is
+/// plotted at, accumulating over the other two.
+///
+/// Here `c` is the sample an orbit started from, which is the initial value of `z` in Julia mode.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Projection {
+    /// Plot `(zr, zi)`, rendering the buddhabrot.
+    #[default]
+    #[value(name = "zr-zi")]
+    ZrZi,
+    /// Plot `(cr, ci)`, shading each sample by the length of its orbit.
+    #[value(name = "cr-ci")]
+    CrCi,
+    /// Plot `(zr, cr)`.
+    #[value(name = "zr-cr")]
+    ZrCr,
+    /// Plot `(zr, ci)`.
+    #[value(name = "zr-ci")]
+    ZrCi,
+    /// Plot `(zi, cr)`.
+    #[value(name = "zi-cr")]
+    ZiCr,
+    /// Plot `(zi, ci)`.
+    #[value(name = "zi-ci")]
+    ZiCi,
+}
+
+impl Projection {
+    /// Projects the point `z` of the orbit of the sample `c` onto the image plane.
+    #[inline]
+    pub fn project<F>(self, z: Complex<F>, c: Complex<F>) -> Complex<F> {
+        match self {
+            Projection::ZrZi => z,
+            Projection::CrCi => c,
+            Projection::ZrCr => Complex::new(z.re, c.re),
+            Projection::ZrCi => Complex::new(z.re, c.im),
+            Projection::ZiCr => Complex::new(z.im, c.re),
+            Projection::ZiCi => Complex::new(z.im, c.im),
+        }
+    }
+}
+
 pub fn sample<T: Color + Clone + Copy + Send + Sync + 'static>(
     im: Arc<Mutex<Image<T>>>,
-    n: u32,
-    m: u32,
-    progress_update: usize,
-    scale: f32,
-    center: Complex<f32>,
-) {
-    let cpus = num_cpus::get();
-    let size = im.lock().unwrap().size;
-    let width = im.lock().unwrap().width;
-    let height = size / width;
-    let iters = size * m as usize;
-    let thread_progress_up = progress_update / cpus;
+    settings: SampleSettings,
+) -> SampleStats {
+    let canvas = Arc::new(Mutex::new(Canvas::new(std::mem::take(&mut *im.lock().unwrap()))));
+    let stats = sample_canvas(canvas.clone(), settings);
+    *im.lock().unwrap() = Arc::try_unwrap(canvas).ok().unwrap().into_inner().unwrap().main;
+    stats
+}
+
+/// Samples orbits into every image of a canvas.
+pub fn sample_canvas<T: Color + Clone + Copy + Send + Sync + 'static>(
+    canvas: Arc<Mutex<Canvas<T>>>,
+    settings: SampleSettings,
+) -> SampleStats {
+    match settings.precision {
+        Precision::Single => sample_dynamics::<_, _, f32>(canvas, settings, settings.kernels()),
+        Precision::Double => sample_dynamics::<_, _, f64>(canvas, settings, settings.kernels()),
+        Precision::Arbitrary => sample_deep(canvas, settings, &DeepZoom::from_settings(&settings)),
+    }
+}
+
+/// Samples orbits of custom `dynamics` into every image of a canvas, in place of the fractal of
+/// `settings`.
+///
+/// Orbits are iterated in the scalar `F` the dynamics are implemented for, regardless of the
+/// precision of `settings`.
+pub fn sample_canvas_with<T: Color + Clone + Copy + Send + Sync + 'static, D: Dynamics<F>, F: Float + Send>(
+    canvas: Arc<Mutex<Canvas<T>>>,
+    settings: SampleSettings,
+    dynamics: D,
+) -> SampleStats {
+    sample_dynamics(canvas, settings, vec![(settings.fractal, dynamics)])
+}
+
+/// Samples orbits of each of `dynamics` into every image of a canvas, routing them to color
+/// channels by the fractal they're paired with.
+fn sample_dynamics<T: Color + Clone + Copy + Send + Sync + 'static, D: Dynamics<F>, F: Float + Send>(
+    canvas: Arc<Mutex<Canvas<T>>>,
+    settings: SampleSettings,
+    dynamics: Vec<(Fractal, D)>,
+) -> SampleStats {
+    let blank = canvas.lock().unwrap().blank();
+    let (width, height) = (blank.main.width, blank.main.size / blank.main.width);
+    let iters = blank.main.size * settings.m as usize;
+
+    // Metropolis chains carry over from one work item to the next, so every thread keeps its own
+    let subcanvases = run_chunks(
+        &settings,
+        iters,
+        || (blank.clone(), None),
+        |(subcanvas, sampler): &mut (Canvas<T>, Option<MetropolisSampler<F>>), stream, throttle, count| {
+            match settings.sampler {
+                Sampler::Uniform => plot_uniform::<_, _, F>(subcanvas, &settings, &dynamics, stream, throttle, count),
+                Sampler::Metropolis(metropolis) => {
+                    // Metropolis sampling can't plot several fractals, so there is only one
+                    let dynamics = &dynamics[0].1;
+                    let sampler = sampler.get_or_insert_with(|| {
+                        MetropolisSampler::new(&settings, dynamics, metropolis, stream, width, height)
+                    });
+                    sampler.run(subcanvas, &settings, dynamics, stream, throttle, count);
+                },
+            }
+        },
+    );
+
+    let subcanvases = subcanvases
+        .into_iter()
+        .map(|(node, (subcanvas, sampler))| {
+            let stats = SampleStats {
+                metropolis: sampler.map(|sampler| sampler.stats()),
+                threads: vec![subcanvas.counts],
+                ..Default::default()
+            };
+            (node, (subcanvas, stats))
+        })
+        .collect();
+
+    let mut stats = SampleStats::default();
+    let merge = |(canvas, stats): &mut (Canvas<T>, SampleStats), (other, other_stats)| {
+        canvas.merge(other);
+        *stats = std::mem::take(stats).merge(other_stats);
+    };
+    for (subcanvas, subcanvas_stats) in numa::merge_by_node(subcanvases, merge) {
+        canvas.lock().unwrap().merge(subcanvas);
+        stats = stats.merge(subcanvas_stats);
+    }
+
+    stats
+}
+
+/// The number of samples in a work item of [`run_chunks`].
+///
+/// Every work item draws its samples from its own noise stream, so an image only depends on the
+/// noise configuration and its number of samples, however the work items are spread over threads.
+pub(crate) const CHUNK_SIZE: usize = 1 << 14;
+
+/// Draws `iters` samples on the rayon thread pool in work items of at most [`CHUNK_SIZE`] samples,
+/// which idle threads steal from busy ones, showing the progress on a progress bar.
+///
+/// Every thread of the pool folds the work items it runs into its own accumulator created by
+/// `init`, passing each of them to `work` along with the stream, throttle and number of samples of
+/// the item, and the accumulators are returned once every item is done, each paired with the
+/// [`numa`] node of its thread.
+///
+/// With [`streams`](SampleSettings::streams) set, every logical stream instead folds a contiguous
+/// range of the work items into its own accumulator in order, and the accumulators are returned in
+/// the order of their streams, all on the first node, so that merging them in order gives the same
+/// result on any number of threads. Streams are only balanced between threads as a whole, so a thread
+/// can run out of streams while others are still drawing theirs.
+///
+/// With a [`slice`](SampleSettings::slice) set, only the work items of that slice are drawn. Once the
+/// process is [interrupted](interrupt::interrupted), the work items left are skipped, and the
+/// accumulators are returned with whatever was drawn until then.
+pub(crate) fn run_chunks<A: Send>(
+    settings: &SampleSettings,
+    iters: usize,
+    init: impl Fn() -> A + Sync + Send,
+    work: impl Fn(&mut A, &mut NoiseStream, &mut Throttle, usize) + Sync + Send,
+) -> Vec<(usize, A)> {
+    profiling::scope!("sample");
+    let chunks = iters.div_ceil(CHUNK_SIZE);
+    let chunks = settings.slice.map_or(0..chunks, |slice| slice.chunks(chunks));
+    let (multiprogress, bar) = progress_bar((chunks.end * CHUNK_SIZE).min(iters) - chunks.start * CHUNK_SIZE);
+    let throttle = || Throttle::new(settings.duty_cycle).with_progress(bar.clone());
+
+    let run = |accumulator: &mut A, throttle: &mut Throttle, chunk: usize| {
+        if interrupt::interrupted() {
+            return;
+        }
+        profiling::scope!("sample chunk");
+        let count = CHUNK_SIZE.min(iters - chunk * CHUNK_SIZE);
+        work(accumulator, &mut settings.noise.stream(chunk as u64), throttle, count);
+    };
+
+    if let Some(streams) = settings.streams {
+        let accumulators = (0..streams)
+            .into_par_iter()
+            .map(|stream| {
+                let len = chunks.len();
+                chunks.start + stream * len / streams..chunks.start + (stream + 1) * len / streams
+            })
+            .filter(|range| !range.is_empty())
+            .map(|range| {
+                let (mut accumulator, mut throttle) = (init(), throttle());
+                for chunk in range {
+                    run(&mut accumulator, &mut throttle, chunk);
+                }
+                (0, accumulator)
+            })
+            .collect();
+
+        multiprogress.clear().unwrap();
+        return accumulators;
+    }
+
+    // Only the thread a slot belongs to ever locks it, so there's never any contention
+    let slots = (0..rayon::current_num_threads())
+        .map(|_| Mutex::new(None))
+        .collect::<Vec<_>>();
+
+    chunks.into_par_iter().for_each(|chunk| {
+        let mut slot = slots[rayon::current_thread_index().unwrap_or(0)].lock().unwrap();
+        let (accumulator, throttle) = slot.get_or_insert_with(|| (init(), throttle()));
+        run(accumulator, throttle, chunk);
+    });
+
+    multiprogress.clear().unwrap();
 
+    slots
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, slot)| Some((numa::node_of_thread(index), slot.into_inner().unwrap()?.0)))
+        .collect()
+}
+
+/// Creates the progress bar shown while sampling `iters` samples.
+pub(crate) fn progress_bar(iters: usize) -> (MultiProgress, ProgressBar) {
     let multiprogress = MultiProgress::new();
-    let style = ProgressStyle::with_template("{spinner:.green} [{elapsed}] [{bar:50.white/blue}] {pos}/{len} ({eta})")
-        .unwrap()
-        .progress_chars("=> ")
-        .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏");
+    let style = ProgressStyle::with_template(
+        "{spinner:.green} [{elapsed}] [{bar:50.white/blue}] {human_pos}/{human_len} samples ({per_sec}, {eta})",
+    )
+    .unwrap()
+    .progress_chars("=> ")
+    .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏");
     let bar = multiprogress.add(ProgressBar::new(iters as u64).with_style(style));
     bar.inc(0);
 
-    let mut threads = Vec::new();
+    (multiprogress, bar)
+}
+
+/// What [`sample_chunk`] keeps from one call to the next while sampling a render, so that the
+/// costly setup of a sampler is done once rather than for every chunk.
+#[derive(Default)]
+pub struct ChunkState {
+    /// The Metropolis sampler of each precision, whose chains carry over from one chunk to the
+    /// next.
+    single: Option<MetropolisSampler<f32>>,
+    double: Option<MetropolisSampler<f64>>,
+    /// The view and reference orbit of an arbitrary precision render, calculated by the first
+    /// chunk to need them and shared with every state [forked](Self::fork) from this one.
+    deep: Arc<OnceLock<(DeepZoom, ReferenceOrbit)>>,
+}
+
+impl ChunkState {
+    /// Creates a state of its own for another thread sampling the same render, which shares the
+    /// reference orbit of this one but draws from Metropolis chains of its own.
+    pub fn fork(&self) -> ChunkState {
+        ChunkState {
+            single: None,
+            double: None,
+            deep: self.deep.clone(),
+        }
+    }
+}
 
-    for id in 0..cpus {
-        // Increment the Arc's reference count and move into each thread
-        let bar = bar.clone();
-        let im = im.clone();
+/// Plots the trajectories of `count` samples drawn from `stream` into `canvas`, carrying the state
+/// of its samplers over from the last chunk sampled with the same `state`.
+pub fn sample_chunk<T: Color + Clone + Copy>(
+    canvas: &mut Canvas<T>,
+    settings: &SampleSettings,
+    state: &mut ChunkState,
+    stream: &mut NoiseStream,
+    throttle: &mut Throttle,
+    count: usize,
+) -> SampleStats {
+    match settings.precision {
+        Precision::Single => sample_chunk_in(canvas, settings, &mut state.single, stream, throttle, count),
+        Precision::Double => sample_chunk_in(canvas, settings, &mut state.double, stream, throttle, count),
+        Precision::Arbitrary => {
+            let (zoom, reference) = state.deep.get_or_init(|| {
+                let zoom = DeepZoom::from_settings(settings);
+                let reference = ReferenceOrbit::new(&zoom, settings);
+                (zoom, reference)
+            });
+            let mut stats = DeepStats {
+                skipped: reference.skip(),
+                ..Default::default()
+            };
+            for _ in 0..count {
+                plot_deep_sample(canvas, settings, zoom, reference, stream, &mut stats);
+                throttle.tick(1);
+            }
+            SampleStats {
+                deep: Some(stats),
+                ..Default::default()
+            }
+        },
+    }
+}
 
-        threads.push(thread::spawn(move || {
-            let mut rng = thread_rng();
-            let thread_progress_offset = id * thread_progress_up;
-            // Create a new thread-local image to prevent blocking
-            let mut subim = Image::<T>::new(size, width);
+/// Plots the trajectories of `count` samples drawn from `stream` into `canvas`, iterating them in
+/// the scalar `F`. A Metropolis `sampler` is created the first time it's needed.
+fn sample_chunk_in<T: Color + Clone + Copy, F: SimdFloat>(
+    canvas: &mut Canvas<T>,
+    settings: &SampleSettings,
+    sampler: &mut Option<MetropolisSampler<F>>,
+    stream: &mut NoiseStream,
+    throttle: &mut Throttle,
+    count: usize,
+) -> SampleStats {
+    let kernels = settings.kernels();
+    match settings.sampler {
+        Sampler::Uniform => {
+            plot_uniform::<_, _, F>(canvas, settings, &kernels, stream, throttle, count);
+            SampleStats::default()
+        },
+        Sampler::Metropolis(metropolis) => {
+            let (width, height) = (canvas.main.width, canvas.main.size / canvas.main.width);
+            let (_, kernel) = kernels[0];
+            let sampler = sampler
+                .get_or_insert_with(|| MetropolisSampler::new(settings, &kernel, metropolis, stream, width, height));
+            sampler.run(canvas, settings, &kernel, stream, throttle, count);
+            SampleStats {
+                metropolis: Some(sampler.stats()),
+                ..Default::default()
+            }
+        },
+    }
+}
 
-            for i in 0..iters.div_ceil(cpus) {
-                // Generate a random complex number
-                let r1 = rng.gen::<f32>() * 4.0 - 2.0;
-                let r2 = rng.gen::<f32>() * 4.0 - 2.0;
+/// Plots the trajectories of `count` samples drawn uniformly from `stream` under each of `dynamics`
+/// into `canvas`, in batches of [`BATCH_SIZE`].
+fn plot_uniform<T: Color + Clone + Copy, D: Dynamics<F>, F: Float>(
+    canvas: &mut Canvas<T>,
+    settings: &SampleSettings,
+    dynamics: &[(Fractal, D)],
+    stream: &mut NoiseStream,
+    throttle: &mut Throttle,
+    count: usize,
+) {
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    let mut trajectory = Vec::new();
+    for _ in 0..count {
+        batch.push(stream.next_point());
+        if batch.len() == BATCH_SIZE {
+            plot_samples::<_, _, F>(canvas, settings, dynamics, &batch, &mut trajectory);
+            batch.clear();
+        }
+        throttle.tick(1);
+    }
+    plot_samples::<_, _, F>(canvas, settings, dynamics, &batch, &mut trajectory);
+}
 
-                // Transform random complex number into the specified frame
-                let c = Complex::new(r1, r2) * scale + center;
+/// Plots the trajectories of the samples at each of `points` in the unit square under each of
+/// `dynamics` into `canvas`, finding out which orbits are plotted for the whole batch at once.
+/// Trajectories are calculated into `trajectory` one after another.
+fn plot_samples<T: Color + Clone + Copy, D: Dynamics<F>, F: Float>(
+    canvas: &mut Canvas<T>,
+    settings: &SampleSettings,
+    dynamics: &[(Fractal, D)],
+    points: &[(f32, f32)],
+    trajectory: &mut Vec<Complex<F>>,
+) {
+    let samples = points
+        .iter()
+        .map(|&point| sample_point::<F>(settings, point))
+        .collect::<Vec<_>>();
+    let mut times = vec![None; samples.len()];
 
-                // Calculate the path of this complex number over n iterations
-                let trajectory = mandelbrot(c, n);
+    let batches = dynamics
+        .iter()
+        .map(|(fractal, dynamics)| {
+            let (z, c): (Vec<_>, Vec<_>) = samples
+                .iter()
+                .map(|&sample| settings.orbit_start(dynamics, sample))
+                .unzip();
+            dynamics.escape_times(&z, &c, settings.n, &mut times);
+            (*fractal, dynamics, z, c, times.clone())
+        })
+        .collect::<Vec<_>>();
 
-                // Iterate through each point in the complex number's journey
-                for z in trajectory {
-                    // Convert the complex number to pixel coordinates
-                    let p = (z - center) / scale * 0.25 + 0.5;
-                    let px = (p.re * width as f32) as i32;
-                    let py = (p.im * height as f32) as i32;
+    // Plot sample by sample rather than fractal by fractal, in the order they were drawn
+    for (i, &sample) in samples.iter().enumerate() {
+        for (fractal, dynamics, z, c, times) in &batches {
+            plot_orbit(
+                canvas,
+                settings,
+                (*fractal, *dynamics),
+                sample,
+                (z[i], c[i]),
+                times[i],
+                trajectory,
+            );
+        }
+    }
+}
 
-                    // Ensure the complex number is inside the image
-                    if px < 0 || py < 0 || px >= width as i32 || py >= height as i32 {
-                        continue;
-                    }
+/// Plots the orbit of `sample` under `dynamics`, starting from `start` and escaping after
+/// `escape_time` iterations, into `canvas` if it is one of the orbits plotted by `settings`. Its
+/// trajectory is calculated into `trajectory`.
+#[inline]
+fn plot_orbit<T: Color + Clone + Copy, D: Dynamics<F>, F: Float>(
+    canvas: &mut Canvas<T>,
+    settings: &SampleSettings,
+    (fractal, dynamics): (Fractal, &D),
+    sample: Complex<F>,
+    (z, c): (Complex<F>, Complex<F>),
+    escape_time: Option<u32>,
+    trajectory: &mut Vec<Complex<F>>,
+) {
+    canvas.counts.samples += 1;
+    canvas.counts.escaped += escape_time.is_some() as u64;
 
-                    // Plot the pixel
-                    subim.add((px as usize, py as usize), T::one(ColorChannel::Red));
-                }
+    match settings.orbits {
+        Orbits::Escaping => {
+            // An orbit's trajectory is as long as its escape time, so only escaping orbits that
+            // are long enough are iterated again to calculate it
+            let Some(time) = escape_time.filter(|&time| time >= settings.min_iterations) else {
+                return;
+            };
 
-                // Update the progress bar if needed
-                if i != 0 && (i + thread_progress_offset) % progress_update == 0 {
-                    bar.inc(progress_update as u64)
-                }
+            if settings.streaming {
+                let orbit = dynamics.orbit(z, c, time);
+                plot_trajectory(canvas, settings, fractal, dynamics, sample, orbit, 1.0);
+                return;
             }
 
-            // Get a mutable reference to the main image, adding the thread-local image to it
-            let mut global_im = im.lock().unwrap();
-            for (x, y, px) in subim.into_enumerate_pixels() {
-                global_im.add((x, y), px);
+            dynamics.trajectory(z, c, settings.n, trajectory);
+            plot_trajectory(
+                canvas,
+                settings,
+                fractal,
+                dynamics,
+                sample,
+                trajectory.iter().copied(),
+                1.0,
+            );
+        },
+        Orbits::Bounded => {
+            // Bounded orbits are as long as they can be, so rather than storing them, iterate them
+            // again while plotting
+            if escape_time.is_none() {
+                plot_trajectory(
+                    canvas,
+                    settings,
+                    fractal,
+                    dynamics,
+                    sample,
+                    dynamics.orbit(z, c, settings.n),
+                    1.0,
+                );
             }
-        }))
+        },
     }
+}
 
-    for thread in threads {
-        let _ = thread.join();
+/// Calculates the trajectory of `sample` under `dynamics` if it is one of the orbits plotted by
+/// `settings`, or an empty trajectory if it isn't.
+pub(crate) fn trace<D: Dynamics<F>, F: Float>(
+    settings: &SampleSettings,
+    dynamics: &D,
+    sample: Complex<F>,
+) -> Vec<Complex<F>> {
+    let (z, c) = settings.orbit_start(dynamics, sample);
+    match settings.orbits {
+        Orbits::Escaping => {
+            let mut trajectory = Vec::new();
+            dynamics.trajectory(z, c, settings.n, &mut trajectory);
+            trajectory
+        },
+        Orbits::Bounded if dynamics.escape_time(z, c, settings.n).is_none() => {
+            dynamics.orbit(z, c, settings.n).collect()
+        },
+        Orbits::Bounded => Vec::new(),
     }
+}
 
-    multiprogress.clear().unwrap();
+/// Maps a point in the unit square to a complex number in the sampled region.
+#[inline]
+pub(crate) fn sample_point<F: Float>(settings: &SampleSettings, (u, v): (f32, f32)) -> Complex<F> {
+    let cast = |x: f64| F::from(x).unwrap();
+    let (two, four) = (cast(2.0), cast(4.0));
+
+    // Generate a random complex number
+    let r1 = F::from(u).unwrap() * four - two;
+    let r2 = F::from(v).unwrap() * four - two;
+
+    // Transform random complex number into the specified frame
+    Complex::new(r1, r2) * cast(settings.scale) + settings.center.map(cast)
+}
+
+/// The color channels orbits converging to each root are plotted into, repeating after the third root.
+const ROOT_CHANNELS: [ColorChannel; 3] = [ColorChannel::Red, ColorChannel::Green, ColorChannel::Blue];
+
+/// The color channels bounded orbits settling into cycles of each period are plotted into, starting
+/// with period 1.
+const PERIOD_CHANNELS: [&[ColorChannel]; 6] = [
+    &[ColorChannel::Red],
+    &[ColorChannel::Green],
+    &[ColorChannel::Blue],
+    &[ColorChannel::Red, ColorChannel::Green],
+    &[ColorChannel::Green, ColorChannel::Blue],
+    &[ColorChannel::Blue, ColorChannel::Red],
+];
+
+/// Gets a one in every channel the orbit of `sample` under the dynamics of `fractal` is plotted
+/// into, given its `trajectory`.
+pub(crate) fn route<T: Color, D: Dynamics<F>, F: Float, I: Iterator<Item = Complex<F>>>(
+    settings: &SampleSettings,
+    fractal: Fractal,
+    dynamics: &D,
+    sample: Complex<F>,
+    trajectory: I,
+) -> T {
+    match (settings.nebulabrot, settings.channel_fractals) {
+        (Some(nebulabrot), _) => nebulabrot.route(trajectory.count() as u32),
+        (None, Some(channel_fractals)) => channel_fractals.route(fractal),
+        (None, None) if settings.basins => {
+            let last = trajectory.fold(None, |last: Option<OrbitState<F>>, z| match last {
+                Some(last) => Some(OrbitState { z, prev: last.z }),
+                None => Some(OrbitState::new(z)),
+            });
+            // Orbits that haven't converged yet may still be far from the root they end up at
+            match last.filter(|&state| dynamics.converged(state)) {
+                Some(state) => T::one(ROOT_CHANNELS[dynamics.root(state.z) % ROOT_CHANNELS.len()]),
+                None => T::empty(),
+            }
+        },
+        (None, None) if settings.periods => match trajectory.last() {
+            Some(z) => {
+                let (_, c) = settings.orbit_start(dynamics, sample);
+                let period = dynamics.period(z, c, PERIOD_CHANNELS.len() as u32);
+                // Longer cycles and orbits that haven't settled yet are plotted into every channel
+                let channels = period.map_or(&ROOT_CHANNELS[..], |period| PERIOD_CHANNELS[period as usize - 1]);
+                channels.iter().fold(T::empty(), |mut deposit, &channel| {
+                    deposit.add(T::one(channel));
+                    deposit
+                })
+            },
+            None => T::empty(),
+        },
+        (None, None) => T::one(ColorChannel::Red),
+    }
+}
+
+/// Plots a trajectory of `dynamics`, an iteration of `fractal`, starting from `sample` into every
+/// image of `canvas`, scaling every deposit by `factor`. The trajectory is iterated once more if
+/// its arc length is needed.
+pub(crate) fn plot_trajectory<
+    T: Color + Clone + Copy,
+    D: Dynamics<F>,
+    F: Float,
+    I: Iterator<Item = Complex<F>> + Clone,
+>(
+    canvas: &mut Canvas<T>,
+    settings: &SampleSettings,
+    fractal: Fractal,
+    dynamics: &D,
+    sample: Complex<F>,
+    trajectory: I,
+    factor: f32,
+) {
+    let SampleSettings {
+        weight,
+        arc_length_channel,
+        ..
+    } = *settings;
+
+    // Scale factors are converted to the scalar the image's channels are stored in
+    let channel = |x: f32| <T::Channel as NumCast>::from(x).unwrap();
+
+    // Compute the values to deposit at each point, only measuring the orbit when required
+    let arc_length = if weight == OrbitWeight::ArcLength || arc_length_channel.is_some() {
+        channel(arc_length(trajectory.clone()))
+    } else {
+        channel(0.0)
+    };
+    let one: T = route(settings, fractal, dynamics, sample, trajectory.clone());
+    // Trap weights vary along the orbit, so the trapped part of the deposit is weighted at each point
+    let (mut deposit, mut trapped) = match weight {
+        OrbitWeight::Hits => (one, T::empty()),
+        OrbitWeight::ArcLength => (one.map(|x| x * arc_length), T::empty()),
+        OrbitWeight::Trap => (T::empty(), one),
+    };
+    if let Some(channel) = arc_length_channel {
+        deposit.add(T::one(channel).map(|x| x * arc_length));
+    }
+    if factor != 1.0 {
+        let factor = channel(factor);
+        deposit = deposit.map(|x| x * factor);
+        trapped = trapped.map(|x| x * factor);
+    }
+
+    let plane_map = settings.plane_map();
+    let width = canvas.main.width;
+    let height = canvas.main.size / canvas.main.width;
+
+    // Sum the deposits of every visit counted by the origins image, plotted at the sample afterwards
+    let mut visits = T::empty();
+    let mut visited = false;
+
+    // Iterate through each point in the complex number's journey
+    for z in trajectory {
+        // Trap weights are measured from the iterate itself, before it is projected
+        let deposit = if weight == OrbitWeight::Trap {
+            let mut deposit = deposit;
+            deposit.add(trapped.map(|x| x * channel(settings.trap.weight(single(z)))));
+            deposit
+        } else {
+            deposit
+        };
+        let z = settings.project(z, sample);
+
+        // Convert the complex number to pixel coordinates, ensuring it is inside the image
+        let plotted = match plane_map.to_pixel(z, width, height) {
+            Some(px) => {
+                canvas.main.add(px, deposit);
+                canvas.counts.plotted += 1;
+                true
+            },
+            None => {
+                canvas.counts.culled += 1;
+                false
+            },
+        };
+
+        for layer in canvas.layers.iter_mut() {
+            let width = layer.image.width;
+            if let Some(px) = layer.viewport.to_pixel(z, width, layer.image.size / width) {
+                layer.image.add(px, deposit);
+            }
+        }
+
+        if let Some(origins) = &canvas.origins {
+            let visit = match origins.weight {
+                OriginsWeight::Target(target) => target.contains(single(z)),
+                OriginsWeight::Contribution => plotted,
+            };
+            if visit {
+                visits.add(deposit);
+                visited = true;
+            }
+        }
+    }
+
+    if let (Some(origins), true) = (canvas.origins.as_mut(), visited) {
+        let width = origins.image.width;
+        if let Some(px) = origins.viewport.to_pixel(sample, width, origins.image.size / width) {
+            origins.image.add(px, visits);
+        }
+    }
+}
+
+/// Counts the points of the trajectory of `sample` that land inside a `width` by `height` image.
+pub(crate) fn contribution<F: Float>(
+    settings: &SampleSettings,
+    sample: Complex<F>,
+    trajectory: &[Complex<F>],
+    width: usize,
+    height: usize,
+) -> u32 {
+    if (trajectory.len() as u32) < settings.min_iterations {
+        return 0;
+    }
+
+    let plane_map = settings.plane_map();
+    trajectory
+        .iter()
+        .filter(|&&z| plane_map.to_pixel(settings.project(z, sample), width, height).is_some())
+        .count() as u32
 }
 
-fn mandelbrot(c: Complex<f32>, n: u32) -> Vec<Complex<f32>> {
-    let mut z_re = c.re;
-    let mut z_im = c.im;
+pub(crate) fn mandelbrot<F: Float>(z: Complex<F>, c: Complex<F>, n: u32, sequence: &mut Vec<Complex<F>>) {
+    let (two, four) = (F::from(2.0).unwrap(), F::from(4.0).unwrap());
+    let mut z_re = z.re;
+    let mut z_im = z.im;
 
     let mut z_re_2 = z_re * z_re;
     let mut z_im_2 = z_im * z_im;
 
-    let mut sequence = Vec::new();
+    sequence.clear();
 
     for _ in 0..n {
         sequence.push(Complex::new(z_re, z_im));
@@ -125,7 +1440,7 @@ fn mandelbrot(c: Complex<f32>, n: u32) -> Vec<Complex<f32>> {
         // z = x + iy
         // z² = (x² + iy²) = x² - y² + 2ixy
         // c = x₀ + y₀
-        z_im = 2.0 * z_re * z_im + c.im;
+        z_im = two * z_re * z_im + c.im;
         z_re = z_re_2 - z_im_2 + c.re;
 
         // Update cached squares of z_re and z_im.
@@ -145,11 +1460,121 @@ fn mandelbrot(c: Complex<f32>, n: u32) -> Vec<Complex<f32>> {
         // side of the inequality (2² = 4).
         // z_mag > 2
         // z_mag² > 2²
-        if z_mag_2 > 4.0 {
-            return sequence;
+        if z_mag_2 > four {
+            return;
+        }
+    }
+
+    // If the loop completes without escaping, leave the sequence empty
+    sequence.clear();
+}
+
+/// Computes the total Euclidean length of the path traced by a trajectory.
+fn arc_length<F: Float>(mut trajectory: impl Iterator<Item = Complex<F>>) -> f32 {
+    let Some(mut prev) = trajectory.next() else {
+        return 0.0;
+    };
+
+    let length = trajectory.fold(F::zero(), |length, z| {
+        let d = z - prev;
+        prev = z;
+        length + d.re.hypot(d.im)
+    });
+    length.to_f32().unwrap()
+}
+
+/// Rounds a point to single precision.
+#[inline]
+fn single<F: Float>(z: Complex<F>) -> Complex<f32> {
+    z.map(|x| x.to_f32().unwrap())
+}
+
+/// An iterator over the first `n` points of the orbit of `c`, stopping early if it escapes.
+///
+/// Unlike [`mandelbrot`], the points of escaping orbits are yielded as well, and nothing is stored.
+#[derive(Clone, Copy, Debug)]
+pub struct Orbit<D = Kernel, F = f32> {
+    dynamics: D,
+    c: Complex<F>,
+    state: OrbitState<F>,
+    remaining: u32,
+}
+
+impl Orbit {
+    /// Creates an iterator over the mandelbrot orbit of `c`.
+    #[inline]
+    pub fn new(c: Complex<f32>, n: u32) -> Orbit {
+        Self::with_dynamics(Kernel::default(), c, c, n)
+    }
+}
+
+impl<D: Dynamics<F>, F: Float> Orbit<D, F> {
+    /// Creates an iterator over the orbit of `z` under `dynamics` with constant `c`.
+    #[inline]
+    pub fn with_dynamics(dynamics: D, z: Complex<F>, c: Complex<F>, n: u32) -> Orbit<D, F> {
+        Self {
+            dynamics,
+            c,
+            state: OrbitState::new(z),
+            remaining: n,
+        }
+    }
+}
+
+impl<D: Dynamics<F>, F: Float> Iterator for Orbit<D, F> {
+    type Item = Complex<F>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let z = self.state.z;
+        self.state = self.dynamics.advance(self.state, self.c);
+        self.remaining -= 1;
+
+        // Stop after this point if the next one escapes, or after the next one if it converges
+        if self.dynamics.escaped(self.state.z) {
+            self.remaining = 0;
+        } else if self.dynamics.converged(self.state) {
+            self.remaining = self.remaining.min(1);
+        }
+
+        Some(z)
+    }
+}
+
+/// Computes the number of iterations it takes for `c` to escape the mandelbrot set, or `None` if it
+/// doesn't escape within `n` iterations.
+///
+/// This is equivalent to the length of the trajectory returned by [`mandelbrot`], but doesn't store
+/// the trajectory.
+pub fn escape_time(c: Complex<f32>, n: u32) -> Option<u32> {
+    mandelbrot_escape_time(c, c, n)
+}
+
+/// Computes the number of iterations it takes for the mandelbrot orbit of `z` with constant `c` to
+/// escape, or `None` if it doesn't escape within `n` iterations.
+pub(crate) fn mandelbrot_escape_time<F: Float>(z: Complex<F>, c: Complex<F>, n: u32) -> Option<u32> {
+    let (two, four) = (F::from(2.0).unwrap(), F::from(4.0).unwrap());
+    let mut z_re = z.re;
+    let mut z_im = z.im;
+
+    let mut z_re_2 = z_re * z_re;
+    let mut z_im_2 = z_im * z_im;
+
+    for i in 0..n {
+        z_im = two * z_re * z_im + c.im;
+        z_re = z_re_2 - z_im_2 + c.re;
+
+        z_re_2 = z_re * z_re;
+        z_im_2 = z_im * z_im;
+
+        if z_re_2 + z_im_2 > four {
+            return Some(i + 1);
         }
     }
 
-    // If the loop completes without escaping, return an empty vector
-    Vec::new()
+    None
 }
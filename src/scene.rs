@@ -0,0 +1,394 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    canvas::Canvas,
+    complex::Complex,
+    fractal::Fractal,
+    images::Image,
+    metropolis::MetropolisSettings,
+    noise::{FrameNoise, NoiseCorrelation},
+    sample::{sample_canvas, Orbits, SampleSettings, Sampler},
+};
+
+/// Aggregate statistics of a rendered image, which are independent of its exact noise pattern.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImageStats {
+    /// The average number of hits deposited per sample.
+    pub hits_per_sample: f32,
+    /// The fraction of pixels with at least one hit.
+    pub coverage: f32,
+    /// The hit-weighted mean position of the image in the complex plane.
+    pub centroid: Complex<f32>,
+}
+
+impl ImageStats {
+    /// Measures the statistics of an image rendered with the given settings.
+    pub fn measure(im: &Image<f32>, settings: &SampleSettings) -> ImageStats {
+        let viewport = settings.viewport();
+        let height = im.size / im.width;
+
+        let mut total = 0.0f64;
+        let mut covered = 0;
+        let mut centroid = Complex::new(0.0f64, 0.0f64);
+        for (x, y, &hits) in im.enumerate_pixels() {
+            if hits > 0.0 {
+                // Measure from the center of the pixel rather than its corner
+                let z = viewport.to_complex((x, y), im.width, height)
+                    + Complex::new(2.0 / im.width as f64, 2.0 / height as f64) * viewport.scale;
+                total += hits as f64;
+                covered += 1;
+                centroid = centroid + z * hits as f64;
+            }
+        }
+
+        let samples = (im.size as f64 * settings.m as f64).max(1.0);
+        let centroid = centroid / total.max(1.0);
+
+        Self {
+            hits_per_sample: (total / samples) as f32,
+            coverage: covered as f32 / im.size.max(1) as f32,
+            centroid: Complex::new(centroid.re as f32, centroid.im as f32),
+        }
+    }
+}
+
+/// How far each statistic of a scene may deviate from its expected value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tolerance {
+    /// The largest deviation of the hits per sample, relative to the expected value.
+    pub hits_per_sample: f32,
+    /// The largest absolute deviation of the coverage.
+    pub coverage: f32,
+    /// The largest distance of either component of the centroid, relative to the width of the
+    /// viewport.
+    pub centroid: f32,
+}
+
+const TOLERANCE: Tolerance = Tolerance {
+    hits_per_sample: 0.05,
+    coverage: 0.02,
+    centroid: 0.01,
+};
+
+/// The tolerance of scenes dominated by a few very long orbits.
+const NOISY_TOLERANCE: Tolerance = Tolerance {
+    hits_per_sample: 0.1,
+    ..TOLERANCE
+};
+
+/// The tolerance of Metropolis-Hastings scenes, whose brightness depends on a small normalization
+/// batch.
+const METROPOLIS_TOLERANCE: Tolerance = Tolerance {
+    hits_per_sample: 0.35,
+    coverage: 0.02,
+    centroid: 0.025,
+};
+
+/// A statistic of a scene which deviated from its expected value by more than its tolerance.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Deviation {
+    pub statistic: &'static str,
+    pub expected: f32,
+    pub actual: f32,
+    pub tolerance: f32,
+}
+
+impl fmt::Display for Deviation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} was {:.5}, but expected {:.5} (±{:.5})",
+            self.statistic, self.actual, self.expected, self.tolerance
+        )
+    }
+}
+
+/// A small, fully specified render with known statistics, used to check that an installation
+/// renders correctly and that changes to the renderer don't alter its output.
+#[derive(Clone, Copy, Debug)]
+pub struct Scene {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// The width and height of the image in pixels.
+    pub width: usize,
+    pub n: u32,
+    pub m: u32,
+    pub scale: f32,
+    pub center: Complex<f32>,
+    pub fractal: Fractal,
+    pub power: f32,
+    pub orbits: Orbits,
+    pub julia: Option<Complex<f32>>,
+    pub metropolis: bool,
+    pub seed: u64,
+    pub expected: ImageStats,
+    pub tolerance: Tolerance,
+}
+
+/// Every built-in scene.
+pub const SCENES: &[Scene] = &[
+    Scene {
+        name: "buddhabrot",
+        description: "The full buddhabrot at a low iteration count",
+        width: 128,
+        n: 200,
+        m: 8,
+        scale: 1.0,
+        center: Complex { re: 0.0, im: 0.0 },
+        fractal: Fractal::Mandelbrot,
+        power: 2.0,
+        orbits: Orbits::Escaping,
+        julia: None,
+        metropolis: false,
+        seed: 1,
+        expected: ImageStats {
+            hits_per_sample: 2.326,
+            coverage: 0.9999,
+            centroid: Complex {
+                re: -0.142,
+                im: -0.0025,
+            },
+        },
+        tolerance: TOLERANCE,
+    },
+    Scene {
+        name: "anti-buddhabrot",
+        description: "The orbits of points inside the mandelbrot set",
+        width: 96,
+        n: 100,
+        m: 4,
+        scale: 1.0,
+        center: Complex { re: 0.0, im: 0.0 },
+        fractal: Fractal::Mandelbrot,
+        power: 2.0,
+        orbits: Orbits::Bounded,
+        julia: None,
+        metropolis: false,
+        seed: 2,
+        expected: ImageStats {
+            hits_per_sample: 9.62,
+            coverage: 0.179,
+            centroid: Complex { re: -0.223, im: 0.0 },
+        },
+        tolerance: NOISY_TOLERANCE,
+    },
+    Scene {
+        name: "burning-ship",
+        description: "The full burning ship",
+        width: 128,
+        n: 200,
+        m: 8,
+        scale: 1.0,
+        center: Complex { re: 0.0, im: 0.0 },
+        fractal: Fractal::BurningShip,
+        power: 2.0,
+        orbits: Orbits::Escaping,
+        julia: None,
+        metropolis: false,
+        seed: 3,
+        expected: ImageStats {
+            hits_per_sample: 2.861,
+            coverage: 0.9999,
+            centroid: Complex { re: -0.098, im: 0.027 },
+        },
+        tolerance: TOLERANCE,
+    },
+    Scene {
+        name: "tricorn",
+        description: "The full tricorn",
+        width: 128,
+        n: 200,
+        m: 8,
+        scale: 1.0,
+        center: Complex { re: 0.0, im: 0.0 },
+        fractal: Fractal::Tricorn,
+        power: 2.0,
+        orbits: Orbits::Escaping,
+        julia: None,
+        metropolis: false,
+        seed: 4,
+        expected: ImageStats {
+            hits_per_sample: 2.403,
+            coverage: 0.9999,
+            centroid: Complex { re: 0.001, im: -0.001 },
+        },
+        tolerance: TOLERANCE,
+    },
+    Scene {
+        name: "celtic",
+        description: "The full celtic mandelbrot in its recommended view",
+        width: 128,
+        n: 200,
+        m: 8,
+        scale: 0.9,
+        center: Complex { re: -0.2, im: 0.0 },
+        fractal: Fractal::Celtic,
+        power: 2.0,
+        orbits: Orbits::Escaping,
+        julia: None,
+        metropolis: false,
+        seed: 8,
+        expected: ImageStats {
+            hits_per_sample: 3.49,
+            coverage: 0.9999,
+            centroid: Complex { re: -0.178, im: 0.0 },
+        },
+        tolerance: TOLERANCE,
+    },
+    Scene {
+        name: "multibrot",
+        description: "The full cubic multibrot",
+        width: 128,
+        n: 200,
+        m: 8,
+        scale: 1.0,
+        center: Complex { re: 0.0, im: 0.0 },
+        fractal: Fractal::Mandelbrot,
+        power: 3.0,
+        orbits: Orbits::Escaping,
+        julia: None,
+        metropolis: false,
+        seed: 5,
+        expected: ImageStats {
+            hits_per_sample: 1.683,
+            coverage: 0.9999,
+            centroid: Complex { re: 0.001, im: -0.002 },
+        },
+        tolerance: TOLERANCE,
+    },
+    Scene {
+        name: "juliabrot",
+        description: "The orbits of the julia set of -0.8 + 0.156i",
+        width: 128,
+        n: 200,
+        m: 8,
+        scale: 0.5,
+        center: Complex { re: 0.0, im: 0.0 },
+        fractal: Fractal::Mandelbrot,
+        power: 2.0,
+        orbits: Orbits::Escaping,
+        julia: Some(Complex { re: -0.8, im: 0.156 }),
+        metropolis: false,
+        seed: 6,
+        expected: ImageStats {
+            hits_per_sample: 21.82,
+            coverage: 0.9935,
+            centroid: Complex {
+                re: -0.4626,
+                im: 0.0061,
+            },
+        },
+        tolerance: TOLERANCE,
+    },
+    Scene {
+        name: "metropolis-zoom",
+        description: "A zoom into the buddhabrot sampled with Metropolis-Hastings",
+        width: 96,
+        n: 500,
+        m: 8,
+        scale: 0.1,
+        center: Complex { re: -0.2, im: 0.6 },
+        fractal: Fractal::Mandelbrot,
+        power: 2.0,
+        orbits: Orbits::Escaping,
+        julia: None,
+        metropolis: true,
+        seed: 7,
+        expected: ImageStats {
+            hits_per_sample: 2.07,
+            coverage: 1.0,
+            centroid: Complex {
+                re: -0.2185,
+                im: 0.6556,
+            },
+        },
+        tolerance: METROPOLIS_TOLERANCE,
+    },
+];
+
+impl Scene {
+    /// Finds the built-in scene with the given name.
+    pub fn find(name: &str) -> Option<&'static Scene> {
+        SCENES.iter().find(|scene| scene.name == name)
+    }
+
+    /// Gets the settings this scene is rendered with.
+    pub fn settings(&self) -> SampleSettings {
+        SampleSettings {
+            n: self.n,
+            m: self.m,
+            scale: self.scale.into(),
+            center: self.center.map(f64::from),
+            fractal: self.fractal,
+            power: self.power,
+            orbits: self.orbits,
+            julia: self.julia,
+            noise: FrameNoise::new(self.seed, 0, NoiseCorrelation::Fixed),
+            sampler: if self.metropolis {
+                Sampler::Metropolis(MetropolisSettings::default())
+            } else {
+                Sampler::Uniform
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Renders this scene and measures its statistics.
+    pub fn render(&self) -> ImageStats {
+        let settings = self.settings();
+        let canvas = Arc::new(Mutex::new(Canvas::new(Image::<f32>::new(
+            self.width * self.width,
+            self.width,
+        ))));
+        sample_canvas(canvas.clone(), settings);
+
+        let canvas = Arc::try_unwrap(canvas).ok().unwrap().into_inner().unwrap();
+        ImageStats::measure(&canvas.main, &settings)
+    }
+
+    /// Compares measured statistics against the expected ones, returning every statistic that
+    /// deviates by more than its tolerance.
+    pub fn check(&self, stats: &ImageStats) -> Vec<Deviation> {
+        let centroid_tolerance = self.tolerance.centroid * 4.0 * self.scale;
+        let checks = [
+            (
+                "hits per sample",
+                self.expected.hits_per_sample,
+                stats.hits_per_sample,
+                self.tolerance.hits_per_sample * self.expected.hits_per_sample,
+            ),
+            (
+                "coverage",
+                self.expected.coverage,
+                stats.coverage,
+                self.tolerance.coverage,
+            ),
+            (
+                "centroid real part",
+                self.expected.centroid.re,
+                stats.centroid.re,
+                centroid_tolerance,
+            ),
+            (
+                "centroid imaginary part",
+                self.expected.centroid.im,
+                stats.centroid.im,
+                centroid_tolerance,
+            ),
+        ];
+
+        checks
+            .into_iter()
+            .filter(|&(_, expected, actual, tolerance)| actual.is_nan() || (actual - expected).abs() > tolerance)
+            .map(|(statistic, expected, actual, tolerance)| Deviation {
+                statistic,
+                expected,
+                actual,
+                tolerance,
+            })
+            .collect()
+    }
+}
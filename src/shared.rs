@@ -0,0 +1,314 @@
+use std::{
+    io, mem,
+    path::Path,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use crate::{
+    canvas::PlaneMap,
+    color::{Color, Rgb},
+    complex::Complex,
+    dynamics::Dynamics,
+    images::{Buffer, Image, MappedBuffer},
+    sample::{route, run_chunks, sample_point, ConfigError, OrbitWeight, Orbits, Precision, SampleSettings, Sampler},
+};
+
+/// The width and height of a tile of thread-local counters.
+const TILE_SIZE: usize = 64;
+
+/// The maximum number of tiles a thread keeps allocated before flushing them all.
+const MAX_RESIDENT_TILES: usize = 256;
+
+/// The number of samples between flushes of a thread's counters.
+const FLUSH_INTERVAL: usize = 1 << 16;
+
+/// An image of hit counts in one or more channels shared between every sampling thread.
+///
+/// Unlike [`sample_canvas`](crate::sample::sample_canvas), which gives every thread its own
+/// full-size copy of the image, memory usage doesn't grow with the number of threads.
+pub struct SharedImage {
+    /// The counts of every channel of a pixel, one pixel after another.
+    data: Buffer<AtomicU32>,
+    pub size: usize,
+    pub width: usize,
+    pub channels: usize,
+    /// The pixel position of the top left corner of this image within the canvas it is a tile of.
+    pub origin: (usize, usize),
+    /// The width and height of the canvas orbits are plotted onto.
+    pub canvas: (usize, usize),
+}
+
+impl SharedImage {
+    /// Creates a new, blank image with `channels` channels.
+    pub fn new(size: usize, width: usize, channels: usize) -> SharedImage {
+        let data = (0..size * channels).map(|_| AtomicU32::new(0)).collect();
+        Self::from_buffer(Buffer::Heap(data), width, channels)
+    }
+
+    fn from_buffer(data: Buffer<AtomicU32>, width: usize, channels: usize) -> SharedImage {
+        let size = data.len() / channels;
+        Self {
+            data,
+            size,
+            width,
+            channels,
+            origin: (0, 0),
+            canvas: (width, size / width),
+        }
+    }
+
+    /// Creates a new, blank image with `channels` channels whose counters are stored in the file at
+    /// `path` and mapped into memory, replacing the file if it exists.
+    ///
+    /// Counts spill to disk when the image doesn't fit in memory, and those flushed by the sampling
+    /// threads so far are left in the file if the render dies.
+    pub fn mapped(path: impl AsRef<Path>, size: usize, width: usize, channels: usize) -> io::Result<SharedImage> {
+        // SAFETY: the file is created for this image alone, and nothing else touches it while the
+        // image is alive
+        let data = unsafe { MappedBuffer::create(path, size * channels)? };
+        Ok(Self::from_buffer(Buffer::Mapped(data), width, channels))
+    }
+
+    /// Maps the counts left in the file at `path` by an image created with
+    /// [`mapped`](Self::mapped) back into an image `width` pixels wide with `channels` channels.
+    pub fn open(path: impl AsRef<Path>, width: usize, channels: usize) -> io::Result<SharedImage> {
+        // SAFETY: the file was left by an image that is gone, and nothing else touches it while this
+        // image is alive
+        let data = unsafe { MappedBuffer::open(path)? };
+        Ok(Self::from_buffer(Buffer::Mapped(data), width, channels))
+    }
+
+    /// Makes this image the tile at `origin` of a larger `width` by `height` canvas, counting only
+    /// the hits that land inside the tile while sampling as many orbits as the whole canvas would.
+    pub fn within(mut self, origin: (usize, usize), (width, height): (usize, usize)) -> SharedImage {
+        self.origin = origin;
+        self.canvas = (width, height);
+        self
+    }
+
+    /// Converts a pixel position of the canvas into the position within this image, if it is inside.
+    #[inline]
+    fn locate(&self, (x, y): (usize, usize)) -> Option<(usize, usize)> {
+        let (x, y) = (x.checked_sub(self.origin.0)?, y.checked_sub(self.origin.1)?);
+        (x < self.width && y < self.size / self.width).then_some((x, y))
+    }
+
+    /// Gets the number of hits of a channel of a pixel at a given `(x, y)` pixel position.
+    #[inline]
+    pub fn get(&self, px: (usize, usize), channel: usize) -> u32 {
+        self.data[(px.1 * self.width + px.0) * self.channels + channel].load(Ordering::Relaxed)
+    }
+
+    /// Adds `count` hits to a channel of a pixel at a given `(x, y)` pixel position.
+    #[inline]
+    pub fn add(&self, px: (usize, usize), channel: usize, count: u32) {
+        self.data[(px.1 * self.width + px.0) * self.channels + channel].fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Converts the hit counts of every channel into a regular image.
+    pub fn into_images(self) -> Vec<Image<f32>> {
+        let mut ims = vec![Image::new(self.size, self.width); self.channels];
+        for (i, count) in self.data.iter().enumerate() {
+            let pixel = i / self.channels;
+            ims[i % self.channels].set(
+                (pixel % self.width, pixel / self.width),
+                count.load(Ordering::Relaxed) as f32,
+            );
+        }
+        ims
+    }
+}
+
+/// Gets the number of channels a render with these settings counts hits into, which is every
+/// color channel when orbits are routed to channels and a single one otherwise.
+pub fn channels(settings: &SampleSettings) -> usize {
+    if settings.nebulabrot.is_some() || settings.channel_fractals.is_some() || settings.basins || settings.periods {
+        3
+    } else {
+        1
+    }
+}
+
+/// Gets the most memory, in bytes, the counters of a single thread hold while sampling an image with
+/// the given number of channels.
+pub fn thread_memory(channels: usize) -> usize {
+    MAX_RESIDENT_TILES * TILE_SIZE * TILE_SIZE * channels * mem::size_of::<u16>()
+}
+
+/// Thread-local 16-bit hit counters covering every channel of an image in lazily allocated tiles.
+///
+/// Hits are counted locally so threads rarely touch the shared image, and only the tiles a thread
+/// has recently hit are kept in memory.
+struct TileCounters {
+    tiles: Vec<Option<Box<[u16]>>>,
+    /// The indices of every allocated tile.
+    resident: Vec<usize>,
+    tiles_x: usize,
+    channels: usize,
+}
+
+impl TileCounters {
+    fn new(width: usize, height: usize, channels: usize) -> TileCounters {
+        let tiles_x = width.div_ceil(TILE_SIZE);
+        Self {
+            tiles: vec![None; tiles_x * height.div_ceil(TILE_SIZE)],
+            resident: Vec::new(),
+            tiles_x,
+            channels,
+        }
+    }
+
+    /// Counts a hit in a channel at a given `(x, y)` pixel position, flushing into `im` when a
+    /// counter would overflow or too many tiles are allocated.
+    #[inline]
+    fn add(&mut self, (x, y): (usize, usize), channel: usize, im: &SharedImage) {
+        let tile = (y / TILE_SIZE) * self.tiles_x + x / TILE_SIZE;
+        if self.tiles[tile].is_none() {
+            if self.resident.len() >= MAX_RESIDENT_TILES {
+                self.flush(im);
+            }
+
+            self.tiles[tile] = Some(vec![0; TILE_SIZE * TILE_SIZE * self.channels].into_boxed_slice());
+            self.resident.push(tile);
+        }
+
+        let counters = self.tiles[tile].as_mut().unwrap();
+        let counter = &mut counters[((y % TILE_SIZE) * TILE_SIZE + x % TILE_SIZE) * self.channels + channel];
+        *counter += 1;
+
+        if *counter == u16::MAX {
+            im.add((x, y), channel, u16::MAX as u32);
+            *counter = 0;
+        }
+    }
+
+    /// Counts a hit in every channel of `deposit` holding a one at every point of a trajectory that
+    /// lands inside `im`.
+    fn add_trajectory(
+        &mut self,
+        trajectory: impl Iterator<Item = Complex<f32>>,
+        deposit: Rgb,
+        plane_map: &PlaneMap,
+        im: &SharedImage,
+    ) {
+        let (r, g, b) = deposit.to_tuple_rgb();
+        let channels = [r, g, b].map(|x| x > 0.0);
+        let (width, height) = im.canvas;
+        for z in trajectory {
+            if let Some(px) = plane_map.to_pixel(z, width, height).and_then(|px| im.locate(px)) {
+                for channel in (0..im.channels).filter(|&channel| channels[channel]) {
+                    self.add(px, channel, im);
+                }
+            }
+        }
+    }
+
+    /// Adds every counter into `im` and frees every tile.
+    #[profiling::function]
+    fn flush(&mut self, im: &SharedImage) {
+        let height = im.size / im.width;
+
+        for tile in self.resident.drain(..) {
+            let counters = self.tiles[tile].take().unwrap();
+            let (x0, y0) = ((tile % self.tiles_x) * TILE_SIZE, (tile / self.tiles_x) * TILE_SIZE);
+
+            for (i, &count) in counters.iter().enumerate() {
+                let (pixel, channel) = (i / self.channels, i % self.channels);
+                let (x, y) = (x0 + pixel % TILE_SIZE, y0 + pixel / TILE_SIZE);
+                if count != 0 && x < im.width && y < height {
+                    im.add((x, y), channel, count as u32);
+                }
+            }
+        }
+    }
+}
+
+/// Checks that every deposit of a render with these settings is a single hit, which is all a
+/// [`SharedImage`] can count.
+pub fn countable(settings: &SampleSettings) -> Result<(), ConfigError> {
+    if settings.weight != OrbitWeight::Hits {
+        return Err(ConfigError::NotCountable("arc length weighting"));
+    }
+
+    if settings.arc_length_channel.is_some() {
+        return Err(ConfigError::NotCountable("an arc length channel"));
+    }
+
+    match settings.precision {
+        Precision::Single => (),
+        Precision::Double => return Err(ConfigError::NotCountable("double precision")),
+        Precision::Arbitrary => return Err(ConfigError::NotCountable("arbitrary precision")),
+    }
+
+    if matches!(settings.sampler, Sampler::Metropolis(_)) {
+        return Err(ConfigError::NotCountable("Metropolis sampling"));
+    }
+
+    Ok(())
+}
+
+/// Samples orbits into a shared image of hit counts, with as many [`channels`] as the settings
+/// route orbits into. The settings must be [`countable`].
+pub fn sample_shared(im: &SharedImage, settings: SampleSettings) {
+    let n = settings.n;
+    let width = im.width;
+    let height = im.size / im.width;
+    let iters = im.canvas.0 * im.canvas.1 * settings.m as usize;
+    let plane_map = settings.plane_map();
+    let kernels = settings.kernels();
+
+    let counters = run_chunks(
+        &settings,
+        iters,
+        || (TileCounters::new(width, height, im.channels), 0),
+        |(counters, unflushed), stream, throttle, count| {
+            let mut trajectory = Vec::new();
+            for _ in 0..count {
+                let sample = sample_point::<f32>(&settings, stream.next_point());
+                let project = |z| settings.project(z, sample);
+
+                for &(fractal, ref kernel) in kernels.iter() {
+                    let (z, c) = settings.orbit_start(kernel, sample);
+                    match settings.orbits {
+                        Orbits::Escaping if settings.streaming => {
+                            if let Some(time) = kernel.escape_time(z, c, n).filter(|&t| t >= settings.min_iterations) {
+                                let deposit = route(&settings, fractal, kernel, sample, kernel.orbit(z, c, time));
+                                counters.add_trajectory(kernel.orbit(z, c, time).map(project), deposit, &plane_map, im);
+                            }
+                        },
+                        Orbits::Escaping => {
+                            kernel.trajectory(z, c, n, &mut trajectory);
+                            if trajectory.len() as u32 >= settings.min_iterations {
+                                let deposit = route(&settings, fractal, kernel, sample, trajectory.iter().copied());
+                                counters.add_trajectory(
+                                    trajectory.iter().copied().map(project),
+                                    deposit,
+                                    &plane_map,
+                                    im,
+                                );
+                            }
+                        },
+                        Orbits::Bounded => {
+                            if kernel.escape_time(z, c, n).is_none() {
+                                let deposit = route(&settings, fractal, kernel, sample, kernel.orbit(z, c, n));
+                                counters.add_trajectory(kernel.orbit(z, c, n).map(project), deposit, &plane_map, im);
+                            }
+                        },
+                    }
+                }
+
+                throttle.tick(1);
+            }
+
+            *unflushed += count;
+            if *unflushed >= FLUSH_INTERVAL {
+                counters.flush(im);
+                *unflushed = 0;
+            }
+        },
+    );
+
+    for (_, (mut counters, _)) in counters {
+        counters.flush(im);
+    }
+}
@@ -0,0 +1,324 @@
+use num_traits::Float;
+use std::sync::OnceLock;
+use wide::{f32x8, f64x4, CmpGt};
+
+use crate::{complex::Complex, sample::mandelbrot_escape_time};
+
+/// The instructions orbits are iterated with, from the slowest to the fastest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SimdLevel {
+    /// One orbit at a time, on CPUs without any vector instructions this crate uses.
+    Scalar,
+    /// 128-bit vector instructions, which are SSE2 on x86 and NEON on ARM. Orbits are iterated in
+    /// 256-bit vectors, each of which takes a pair of 128-bit registers.
+    Vector128,
+    /// 256-bit AVX2 vectors.
+    Avx2,
+    /// 512-bit AVX-512 vectors.
+    Avx512,
+}
+
+impl SimdLevel {
+    /// Gets the fastest level the CPU running this supports, detecting it once the first time.
+    pub fn detect() -> SimdLevel {
+        static LEVEL: OnceLock<SimdLevel> = OnceLock::new();
+        *LEVEL.get_or_init(|| {
+            #[cfg(target_arch = "x86_64")]
+            {
+                if is_x86_feature_detected!("avx512f") {
+                    return SimdLevel::Avx512;
+                }
+                if is_x86_feature_detected!("avx2") {
+                    return SimdLevel::Avx2;
+                }
+            }
+
+            // SSE2 and NEON are part of the baseline of their architectures
+            if cfg!(any(target_arch = "x86_64", target_arch = "aarch64")) {
+                SimdLevel::Vector128
+            } else {
+                SimdLevel::Scalar
+            }
+        })
+    }
+
+    /// Gets every level the CPU running this supports, from the slowest to the fastest.
+    pub fn supported() -> impl Iterator<Item = SimdLevel> {
+        [
+            SimdLevel::Scalar,
+            SimdLevel::Vector128,
+            SimdLevel::Avx2,
+            SimdLevel::Avx512,
+        ]
+        .into_iter()
+        .filter(|&level| level <= SimdLevel::detect())
+    }
+}
+
+/// A float whose mandelbrot orbits can be iterated several at once, one in each lane of a SIMD
+/// vector.
+pub trait SimdFloat: Float {
+    /// The number of orbits iterated side by side with 128-bit instructions, which fill a 256-bit
+    /// vector split over two registers.
+    const LANES: usize;
+
+    /// Computes the escape time of the mandelbrot orbit of every point of `z` with the matching
+    /// constant of `c` into `times`, rounding exactly like iterating them one at a time would. The
+    /// orbits are iterated with the fastest [`SimdLevel`] the CPU supports.
+    fn mandelbrot_escape_times(z: &[Complex<Self>], c: &[Complex<Self>], n: u32, times: &mut [Option<u32>]) {
+        Self::mandelbrot_escape_times_with(SimdLevel::detect(), z, c, n, times)
+    }
+
+    /// Computes escape times like [`mandelbrot_escape_times`](SimdFloat::mandelbrot_escape_times),
+    /// with the given level rather than the fastest one, for comparing levels with one another.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the CPU doesn't support `level`.
+    fn mandelbrot_escape_times_with(
+        level: SimdLevel,
+        z: &[Complex<Self>],
+        c: &[Complex<Self>],
+        n: u32,
+        times: &mut [Option<u32>],
+    );
+}
+
+macro_rules! impl_simd_float {
+    ($($float:ty, $lanes:expr, $vector:ty, $avx2:ident, $avx2_lanes:expr, $avx512:ident, $avx512_lanes:expr);*) => {
+        $(
+            impl SimdFloat for $float {
+                const LANES: usize = $lanes;
+
+                fn mandelbrot_escape_times_with(
+                    level: SimdLevel,
+                    z: &[Complex<Self>],
+                    c: &[Complex<Self>],
+                    n: u32,
+                    times: &mut [Option<u32>],
+                ) {
+                    assert!(level <= SimdLevel::detect(), "the CPU doesn't support {:?}", level);
+                    match level {
+                        SimdLevel::Scalar => {
+                            for ((&z, &c), time) in z.iter().zip(c).zip(times) {
+                                *time = mandelbrot_escape_time(z, c, n);
+                            }
+                        },
+                        SimdLevel::Vector128 => escape_times::<$float, $vector, $lanes>(z, c, n, times),
+                        // The assertion only lets either through where the CPU supports it, which
+                        // makes calling kernels enabling their instructions sound
+                        #[cfg(target_arch = "x86_64")]
+                        SimdLevel::Avx2 => unsafe {
+                            x86::escape_times_avx2::<$float, x86::$avx2, $avx2_lanes>(z, c, n, times)
+                        },
+                        #[cfg(target_arch = "x86_64")]
+                        SimdLevel::Avx512 => unsafe {
+                            x86::escape_times_avx512::<$float, x86::$avx512, $avx512_lanes>(z, c, n, times)
+                        },
+                        #[cfg(not(target_arch = "x86_64"))]
+                        SimdLevel::Avx2 | SimdLevel::Avx512 => unreachable!("AVX is only detected on x86"),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_simd_float!(
+    f32, 8, f32x8, Avx2F32, 8, Avx512F32, 16;
+    f64, 4, f64x4, Avx2F64, 4, Avx512F64, 8
+);
+
+/// A vector of `L` floats, each lane of which iterates its own orbit.
+trait Vector<F, const L: usize>: Copy {
+    /// Creates the vector holding `lanes`.
+    fn load(lanes: [F; L]) -> Self;
+
+    /// Squares every lane.
+    fn square(self) -> Self;
+
+    /// Applies one mandelbrot iteration to every lane, returning the mask of the lanes that are
+    /// outside the escape radius afterwards.
+    ///
+    /// The operations are the same and in the same order as those of the scalar iteration, so
+    /// every lane rounds exactly like it.
+    fn step(lanes: &mut Lanes<Self>) -> u32;
+}
+
+/// The vectors of the orbits iterated side by side.
+struct Lanes<V> {
+    z_re: V,
+    z_im: V,
+    z_re_2: V,
+    z_im_2: V,
+    c_re: V,
+    c_im: V,
+}
+
+macro_rules! impl_wide_vector {
+    ($($float:ty, $vector:ty, $lanes:expr);*) => {
+        $(
+            impl Vector<$float, $lanes> for $vector {
+                #[inline(always)]
+                fn load(lanes: [$float; $lanes]) -> Self {
+                    <$vector>::from(lanes)
+                }
+
+                #[inline(always)]
+                fn square(self) -> Self {
+                    self * self
+                }
+
+                #[inline(always)]
+                fn step(lanes: &mut Lanes<Self>) -> u32 {
+                    let (two, four) = (<$vector>::splat(2.0), <$vector>::splat(4.0));
+                    lanes.z_im = two * lanes.z_re * lanes.z_im + lanes.c_im;
+                    lanes.z_re = lanes.z_re_2 - lanes.z_im_2 + lanes.c_re;
+
+                    lanes.z_re_2 = lanes.z_re * lanes.z_re;
+                    lanes.z_im_2 = lanes.z_im * lanes.z_im;
+
+                    (lanes.z_re_2 + lanes.z_im_2).cmp_gt(four).move_mask() as u32
+                }
+            }
+        )*
+    };
+}
+
+impl_wide_vector!(f32, f32x8, 8; f64, f64x4, 4);
+
+/// Computes escape times `L` orbits at a time in the vectors `V`.
+#[inline(always)]
+fn escape_times<F: Float, V: Vector<F, L>, const L: usize>(
+    z: &[Complex<F>],
+    c: &[Complex<F>],
+    n: u32,
+    times: &mut [Option<u32>],
+) {
+    let all = u32::MAX >> (32 - L);
+
+    for ((z, c), times) in z.chunks(L).zip(c.chunks(L)).zip(times.chunks_mut(L)) {
+        // Lanes past the end of the last chunk start out finished and are never read
+        let mut done = all ^ (u32::MAX >> (32 - z.len()));
+
+        let gather = |points: &[Complex<F>], part: fn(&Complex<F>) -> F| {
+            let mut lanes = [F::zero(); L];
+            lanes
+                .iter_mut()
+                .zip(points)
+                .for_each(|(lane, point)| *lane = part(point));
+            V::load(lanes)
+        };
+        let (z_re, z_im) = (gather(z, |z| z.re), gather(z, |z| z.im));
+        let mut lanes = Lanes {
+            z_re,
+            z_im,
+            z_re_2: z_re.square(),
+            z_im_2: z_im.square(),
+            c_re: gather(c, |c| c.re),
+            c_im: gather(c, |c| c.im),
+        };
+
+        times.fill(None);
+        for i in 0..n {
+            // Escaped lanes keep iterating until every lane is done, but their escape time is only
+            // recorded the first time
+            let escaped = V::step(&mut lanes) & !done;
+            if escaped != 0 {
+                for (lane, time) in times.iter_mut().enumerate() {
+                    if escaped & (1 << lane) != 0 {
+                        *time = Some(i + 1);
+                    }
+                }
+
+                done |= escaped;
+                if done == all {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Kernels using the AVX2 and AVX-512 extensions of x86, which are compiled in regardless of the
+/// target and only called once the CPU is known to support them.
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    use super::{escape_times, Lanes, Vector};
+    use crate::complex::Complex;
+
+    /// Computes escape times with AVX2 enabled.
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn escape_times_avx2<F: num_traits::Float, V: Vector<F, L>, const L: usize>(
+        z: &[Complex<F>],
+        c: &[Complex<F>],
+        n: u32,
+        times: &mut [Option<u32>],
+    ) {
+        escape_times::<F, V, L>(z, c, n, times)
+    }
+
+    /// Computes escape times with AVX-512 enabled.
+    #[target_feature(enable = "avx512f")]
+    pub unsafe fn escape_times_avx512<F: num_traits::Float, V: Vector<F, L>, const L: usize>(
+        z: &[Complex<F>],
+        c: &[Complex<F>],
+        n: u32,
+        times: &mut [Option<u32>],
+    ) {
+        escape_times::<F, V, L>(z, c, n, times)
+    }
+
+    macro_rules! impl_x86_vector {
+        (
+            $($name:ident($vector:ty, $float:ty, $lanes:expr):
+                $loadu:ident, $set1:ident, $add:ident, $sub:ident, $mul:ident, |$norm:ident, $four:ident| $mask:expr);*
+        ) => {
+            $(
+                /// A vector only created inside kernels enabling the instructions it is built from.
+                #[derive(Clone, Copy)]
+                pub struct $name($vector);
+
+                impl Vector<$float, $lanes> for $name {
+                    #[inline(always)]
+                    fn load(lanes: [$float; $lanes]) -> Self {
+                        unsafe { Self($loadu(lanes.as_ptr())) }
+                    }
+
+                    #[inline(always)]
+                    fn square(self) -> Self {
+                        unsafe { Self($mul(self.0, self.0)) }
+                    }
+
+                    #[inline(always)]
+                    fn step(lanes: &mut Lanes<Self>) -> u32 {
+                        unsafe {
+                            let ($four, two) = ($set1(4.0), $set1(2.0));
+                            lanes.z_im = Self($add($mul($mul(two, lanes.z_re.0), lanes.z_im.0), lanes.c_im.0));
+                            lanes.z_re = Self($add($sub(lanes.z_re_2.0, lanes.z_im_2.0), lanes.c_re.0));
+
+                            lanes.z_re_2 = lanes.z_re.square();
+                            lanes.z_im_2 = lanes.z_im.square();
+
+                            let $norm = $add(lanes.z_re_2.0, lanes.z_im_2.0);
+                            $mask as u32
+                        }
+                    }
+                }
+            )*
+        };
+    }
+
+    impl_x86_vector!(
+        Avx2F32(__m256, f32, 8): _mm256_loadu_ps, _mm256_set1_ps, _mm256_add_ps, _mm256_sub_ps, _mm256_mul_ps,
+            |norm, four| _mm256_movemask_ps(_mm256_cmp_ps::<_CMP_GT_OQ>(norm, four));
+        Avx2F64(__m256d, f64, 4): _mm256_loadu_pd, _mm256_set1_pd, _mm256_add_pd, _mm256_sub_pd, _mm256_mul_pd,
+            |norm, four| _mm256_movemask_pd(_mm256_cmp_pd::<_CMP_GT_OQ>(norm, four));
+        Avx512F32(__m512, f32, 16): _mm512_loadu_ps, _mm512_set1_ps, _mm512_add_ps, _mm512_sub_ps, _mm512_mul_ps,
+            |norm, four| _mm512_cmp_ps_mask::<_CMP_GT_OQ>(norm, four);
+        Avx512F64(__m512d, f64, 8): _mm512_loadu_pd, _mm512_set1_pd, _mm512_add_pd, _mm512_sub_pd, _mm512_mul_pd,
+            |norm, four| _mm512_cmp_pd_mask::<_CMP_GT_OQ>(norm, four)
+    );
+}
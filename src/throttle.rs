@@ -0,0 +1,113 @@
+use std::{
+    mem, thread,
+    time::{Duration, Instant},
+};
+
+use indicatif::ProgressBar;
+
+/// The amount of work a thread does before pausing.
+const SLICE: Duration = Duration::from_millis(50);
+
+/// The number of samples between checks of the time spent working.
+const CHECK_INTERVAL: usize = 256;
+
+/// How often a thread adds the samples it has plotted to its progress bar.
+const REPORT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Limits the fraction of time a worker thread spends working by having it periodically sleep.
+///
+/// Unlike lowering thread priority, this needs no special permissions and keeps a machine
+/// responsive even when the scheduler would otherwise give every spare cycle to the render.
+///
+/// Every sample a thread plots passes through its throttle, so it also counts them for the
+/// progress bar, only touching the bar every [`REPORT_INTERVAL`] so that threads never wait on one
+/// another to report their progress.
+pub struct Throttle {
+    duty_cycle: f32,
+    slice_start: Instant,
+    pending: usize,
+    progress: Option<Progress>,
+}
+
+/// The samples a thread has plotted since it last moved its progress bar.
+struct Progress {
+    bar: ProgressBar,
+    unreported: u64,
+    reported_at: Instant,
+}
+
+impl Throttle {
+    /// Creates a new throttle keeping its thread working for `duty_cycle` of the time, between 0-1.
+    pub fn new(duty_cycle: f32) -> Throttle {
+        Self {
+            duty_cycle,
+            slice_start: Instant::now(),
+            pending: 0,
+            progress: None,
+        }
+    }
+
+    /// Has this throttle move `bar` by the samples it's told about.
+    pub fn with_progress(mut self, bar: ProgressBar) -> Throttle {
+        self.progress = Some(Progress {
+            bar,
+            unreported: 0,
+            reported_at: Instant::now(),
+        });
+        self
+    }
+
+    /// Changes the fraction of time the thread spends working, starting a new slice of work if it
+    /// differs from the current one.
+    #[inline]
+    pub fn set_duty_cycle(&mut self, duty_cycle: f32) {
+        if duty_cycle != self.duty_cycle {
+            self.duty_cycle = duty_cycle;
+            self.restart();
+        }
+    }
+
+    /// Starts a new slice of work, such as after the thread has been idle.
+    #[inline]
+    pub fn restart(&mut self) {
+        self.slice_start = Instant::now();
+        self.pending = 0;
+    }
+
+    /// Records that `samples` samples have been plotted, sleeping if the current slice of work is
+    /// over.
+    #[inline]
+    pub fn tick(&mut self, samples: usize) {
+        self.pending += samples;
+        if self.pending < CHECK_INTERVAL {
+            return;
+        }
+        let pending = mem::take(&mut self.pending);
+
+        if let Some(progress) = &mut self.progress {
+            progress.unreported += pending as u64;
+            if progress.reported_at.elapsed() >= REPORT_INTERVAL {
+                progress.bar.inc(mem::take(&mut progress.unreported));
+                progress.reported_at = Instant::now();
+            }
+        }
+
+        if self.duty_cycle >= 1.0 {
+            return;
+        }
+
+        let elapsed = self.slice_start.elapsed();
+        if elapsed >= SLICE {
+            thread::sleep(elapsed.mul_f32((1.0 - self.duty_cycle) / self.duty_cycle));
+            self.slice_start = Instant::now();
+        }
+    }
+}
+
+impl Drop for Throttle {
+    fn drop(&mut self) {
+        if let Some(progress) = &self.progress {
+            progress.bar.inc(progress.unreported + self.pending as u64);
+        }
+    }
+}
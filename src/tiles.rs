@@ -0,0 +1,186 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use crate::{
+    atomic::AtomicFile,
+    sample::SampleSettings,
+    shared::{self, sample_shared, SharedImage},
+};
+
+/// A rectangle of pixels of an image rendered on its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tile {
+    /// The pixel position of the top left corner of the tile.
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// A grid of square tiles covering an image too large to keep in memory.
+///
+/// Every tile is rendered into its own accumulation buffer, which is written to a file in `dir`
+/// before the next tile is started, so only a single tile is ever held in memory. The files hold
+/// one plane of 32-bit little endian floats per channel.
+pub struct TileGrid {
+    dir: PathBuf,
+    pub width: usize,
+    pub height: usize,
+    pub tile_size: usize,
+    /// The number of channels every tile holds, which is the sum of the channels of every pass.
+    pub channels: usize,
+}
+
+impl TileGrid {
+    /// Creates the grid of tiles of at most `tile_size` pixels wide and tall covering a `width` by
+    /// `height` image, whose tiles are stored in `dir`.
+    pub fn new(dir: PathBuf, width: usize, height: usize, tile_size: usize, channels: usize) -> TileGrid {
+        Self {
+            dir,
+            width,
+            height,
+            tile_size,
+            channels,
+        }
+    }
+
+    /// Gets every tile of the grid, one row after another.
+    pub fn tiles(&self) -> impl Iterator<Item = Tile> + '_ {
+        let columns = self.width.div_ceil(self.tile_size);
+        (0..self.height.div_ceil(self.tile_size))
+            .flat_map(move |row| (0..columns).map(move |column| self.tile(column, row)))
+    }
+
+    fn tile(&self, column: usize, row: usize) -> Tile {
+        let (x, y) = (column * self.tile_size, row * self.tile_size);
+        Tile {
+            x,
+            y,
+            width: self.tile_size.min(self.width - x),
+            height: self.tile_size.min(self.height - y),
+        }
+    }
+
+    fn path(&self, tile: Tile) -> PathBuf {
+        self.dir.join(format!("{}-{}.tile", tile.x, tile.y))
+    }
+
+    /// Renders every tile, sampling each pass in turn into a [`SharedImage`] covering the tile and
+    /// writing its channels to the tile's file. The settings must be
+    /// [`countable`](shared::countable).
+    pub fn render(&self, passes: &[SampleSettings]) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+
+        let count = self.tiles().count();
+        for (i, tile) in self.tiles().enumerate() {
+            println!("Rendering tile {} of {}.", i + 1, count);
+            profiling::scope!("render tile");
+
+            let mut file = AtomicFile::create(self.path(tile))?;
+            for &settings in passes {
+                let im = SharedImage::new(tile.width * tile.height, tile.width, shared::channels(&settings))
+                    .within((tile.x, tile.y), (self.width, self.height));
+                sample_shared(&im, settings);
+
+                for im in im.into_images() {
+                    for pixel in im.pixels() {
+                        file.write_all(&pixel.to_le_bytes())?;
+                    }
+                }
+            }
+            file.commit()?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads every channel of a rendered tile.
+    #[profiling::function]
+    fn read(&self, tile: Tile) -> io::Result<Vec<Vec<f32>>> {
+        let bytes = fs::read(self.path(tile))?;
+        let plane = tile.width * tile.height;
+        if bytes.len() != plane * self.channels * 4 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("tile {:?} is truncated", self.path(tile)),
+            ));
+        }
+
+        Ok(bytes
+            .chunks_exact(plane * 4)
+            .map(|plane| {
+                plane
+                    .chunks_exact(4)
+                    .map(|x| f32::from_le_bytes(x.try_into().unwrap()))
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// Assembles the rendered tiles into a single image, read one row of tiles at a time. When
+    /// `normalize` is set, every channel is divided by its largest value across every tile.
+    pub fn stitch(&self, normalize: bool) -> io::Result<Stitched<'_>> {
+        let mut divisors = vec![1.0; self.channels];
+        if normalize {
+            divisors.fill(0.0);
+            for tile in self.tiles() {
+                for (max, plane) in divisors.iter_mut().zip(self.read(tile)?) {
+                    *max = plane.into_iter().fold(*max, f32::max);
+                }
+            }
+        }
+
+        let first = self.read_row(0)?;
+        Ok(Stitched {
+            grid: self,
+            divisors,
+            row: Mutex::new((0, first)),
+        })
+    }
+
+    fn read_row(&self, row: usize) -> io::Result<Vec<Vec<Vec<f32>>>> {
+        let columns = self.width.div_ceil(self.tile_size);
+        (0..columns).map(|column| self.read(self.tile(column, row))).collect()
+    }
+
+    /// Deletes the directory holding the tiles.
+    pub fn remove(self) -> io::Result<()> {
+        fs::remove_dir_all(self.dir)
+    }
+}
+
+/// The image assembled from the tiles of a [`TileGrid`], keeping only the row of tiles last read
+/// from in memory.
+pub struct Stitched<'a> {
+    grid: &'a TileGrid,
+    divisors: Vec<f32>,
+    /// The index of the row of tiles in memory and the channels of each of its tiles.
+    row: Mutex<(usize, Vec<Vec<Vec<f32>>>)>,
+}
+
+impl Stitched<'_> {
+    /// Gets the color of the pixel at a given `(x, y)` pixel position. A single channel is gray,
+    /// and a missing third channel is black.
+    ///
+    /// Reading pixels one row after another reads every tile exactly once.
+    pub fn get(&self, (x, y): (usize, usize)) -> (f32, f32, f32) {
+        let size = self.grid.tile_size;
+        let mut row = self.row.lock().unwrap();
+        if row.0 != y / size {
+            *row = (y / size, self.grid.read_row(y / size).unwrap());
+        }
+
+        let tile = &row.1[x / size];
+        let width = self.grid.tile(x / size, y / size).width;
+        let channel = |i: usize| tile[i][(y % size) * width + x % size] / self.divisors[i];
+        match self.grid.channels {
+            1 => (channel(0), channel(0), channel(0)),
+            2 => (channel(0), channel(1), 0.0),
+            _ => (channel(0), channel(1), channel(2)),
+        }
+    }
+}
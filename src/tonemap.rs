@@ -0,0 +1,168 @@
+use num_traits::{Float, NumCast};
+
+use crate::{color::Color, images::Image};
+
+/// A step of a [`Tonemap`], mapping every channel of every pixel on its own.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+    /// Divides every channel by its brightest value in the image, bringing values between 0-1.
+    Normalize,
+    /// Multiplies values by a factor.
+    Exposure(f32),
+    /// Raises values to the power of one over the gamma, brightening dim values for gammas above 1.
+    Gamma(f32),
+    /// Raises values to a power.
+    Power(f32),
+    /// Takes the natural logarithm of one more than every value, which compresses the orders of
+    /// magnitude between the few densest pixels and the faint ones most into a range that can be
+    /// seen, while keeping empty pixels black.
+    Log1p,
+    /// Takes the square root of values, a gentler compression than a logarithm.
+    Sqrt,
+    /// Sets values below a threshold to 0.
+    BlackPoint(f32),
+    /// Clamps values between 0-1.
+    Clamp,
+}
+
+impl Operator {
+    /// Maps a single value. Normalizing depends on the whole image, so it leaves a value alone.
+    #[inline]
+    pub fn map<F: Float>(self, v: F) -> F {
+        let cast = |value: f32| <F as NumCast>::from(value).unwrap();
+        match self {
+            Operator::Normalize => v,
+            Operator::Exposure(factor) => v * cast(factor),
+            Operator::Gamma(gamma) => v.powf(F::one() / cast(gamma)),
+            Operator::Power(power) => v.powf(cast(power)),
+            Operator::Log1p => v.ln_1p(),
+            Operator::Sqrt => v.sqrt(),
+            Operator::BlackPoint(threshold) => {
+                if v < cast(threshold) {
+                    F::zero()
+                } else {
+                    v
+                }
+            },
+            Operator::Clamp => v.max(F::zero()).min(F::one()),
+        }
+    }
+}
+
+impl std::fmt::Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operator::Normalize => write!(f, "normalize"),
+            Operator::Exposure(factor) => write!(f, "exposure:{}", factor),
+            Operator::Gamma(gamma) => write!(f, "gamma:{}", gamma),
+            Operator::Power(power) => write!(f, "power:{}", power),
+            Operator::Log1p => write!(f, "log1p"),
+            Operator::Sqrt => write!(f, "sqrt"),
+            Operator::BlackPoint(threshold) => write!(f, "black-point:{}", threshold),
+            Operator::Clamp => write!(f, "clamp"),
+        }
+    }
+}
+
+/// The operators that map the raw hits of a render to the values of its image, applied one after
+/// another.
+///
+/// The densest pixels of a render are often hit millions of times more than the faint wisps
+/// around them, so how that range is compressed decides most of how an image looks. A tonemap such
+/// as `log1p,normalize,gamma:2.2` is written as its operators separated by commas, each with its
+/// parameter after a colon if it takes one.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Tonemap {
+    pub operators: Vec<Operator>,
+}
+
+impl Tonemap {
+    /// Creates the tonemap applying the given fixed steps in the order `process` has always
+    /// applied them: normalizing, exposure, gamma and the black point.
+    pub fn new(normalize: bool, exposure: Option<f32>, gamma: Option<f32>, black_point: Option<f32>) -> Tonemap {
+        let operators = [
+            normalize.then_some(Operator::Normalize),
+            exposure.map(Operator::Exposure),
+            gamma.map(Operator::Gamma),
+            black_point.map(Operator::BlackPoint),
+        ];
+        Tonemap {
+            operators: operators.into_iter().flatten().collect(),
+        }
+    }
+
+    /// Parses a tonemap written as its operators separated by commas, such as
+    /// `log1p,normalize,gamma:2.2`.
+    pub fn parse(s: &str) -> Result<Tonemap, String> {
+        let operators = s
+            .split(',')
+            .map(|operator| {
+                let (name, value) = match operator.trim().split_once(':') {
+                    Some((name, value)) => {
+                        let value = value
+                            .trim()
+                            .parse::<f32>()
+                            .map_err(|e| format!("invalid parameter `{}`: {}", value.trim(), e))?;
+                        (name.trim(), Some(value))
+                    },
+                    None => (operator.trim(), None),
+                };
+
+                match (name, value) {
+                    ("normalize", None) => Ok(Operator::Normalize),
+                    ("log1p", None) => Ok(Operator::Log1p),
+                    ("sqrt", None) => Ok(Operator::Sqrt),
+                    ("clamp", None) => Ok(Operator::Clamp),
+                    ("exposure", Some(factor)) => Ok(Operator::Exposure(factor)),
+                    ("gamma", Some(gamma)) => Ok(Operator::Gamma(gamma)),
+                    ("power", Some(power)) => Ok(Operator::Power(power)),
+                    ("black-point", Some(threshold)) => Ok(Operator::BlackPoint(threshold)),
+                    ("normalize" | "log1p" | "sqrt" | "clamp", Some(_)) => {
+                        Err(format!("the {} operator takes no parameter", name))
+                    },
+                    ("exposure" | "gamma" | "power" | "black-point", None) => {
+                        Err(format!("the {} operator takes a parameter like `{}:2`", name, name))
+                    },
+                    (name, _) => Err(format!(
+                        "unknown operator `{}`, expected normalize, exposure, gamma, power, log1p, sqrt, black-point \
+                         or clamp",
+                        name
+                    )),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Tonemap { operators })
+    }
+
+    /// Whether this tonemap leaves values as they are.
+    pub fn is_empty(&self) -> bool {
+        self.operators.is_empty()
+    }
+
+    /// Applies every operator of this tonemap to `im`, one after another.
+    pub fn apply<T: Color + Clone + Copy>(&self, im: &mut Image<T>) {
+        for &operator in self.operators.iter() {
+            if operator == Operator::Normalize {
+                let mut max = T::empty();
+                for px in im.pixels() {
+                    max = max.max(*px);
+                }
+                for px in im.pixels_mut() {
+                    px.cdiv_assign(max);
+                }
+            } else {
+                for px in im.pixels_mut() {
+                    *px = px.map(|v| operator.map(v));
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Tonemap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let operators = self.operators.iter().map(Operator::to_string).collect::<Vec<_>>();
+        write!(f, "{}", operators.join(","))
+    }
+}
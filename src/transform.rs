@@ -0,0 +1,107 @@
+use clap::ValueEnum;
+use num_traits::Float;
+
+use crate::complex::Complex;
+
+/// A map of the complex plane applied to every point of an orbit before it is projected, warping
+/// the plotted image without changing the iteration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Transform {
+    /// `z²`
+    Square,
+    /// The principal square root of `z`.
+    Sqrt,
+    /// `1/z`, turning the plane inside out around the unit circle.
+    Reciprocal,
+    /// The principal natural logarithm of `z`, unrolling circles around the origin into lines.
+    Log,
+    /// `eᶻ`
+    Exp,
+    /// The complex conjugate of `z`.
+    Conj,
+}
+
+impl Transform {
+    /// Applies this transform to `z`.
+    #[inline]
+    pub fn apply<F: Float>(self, z: Complex<F>) -> Complex<F> {
+        match self {
+            Transform::Square => z * z,
+            Transform::Sqrt => {
+                let r = z.re.hypot(z.im).sqrt();
+                let theta = z.im.atan2(z.re) * F::from(0.5).unwrap();
+                Complex::new(r * theta.cos(), r * theta.sin())
+            },
+            Transform::Reciprocal => {
+                let norm = z.re * z.re + z.im * z.im;
+                Complex::new(z.re / norm, -z.im / norm)
+            },
+            Transform::Log => Complex::new(z.re.hypot(z.im).ln(), z.im.atan2(z.re)),
+            Transform::Exp => {
+                let r = z.re.exp();
+                Complex::new(r * z.im.cos(), r * z.im.sin())
+            },
+            Transform::Conj => Complex::new(z.re, -z.im),
+        }
+    }
+}
+
+/// A sequence of transforms applied one after another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Transforms {
+    steps: [Transform; Transforms::MAX_LEN],
+    len: usize,
+}
+
+impl Transforms {
+    /// The largest number of transforms a sequence can hold.
+    pub const MAX_LEN: usize = 8;
+
+    /// The empty sequence, leaving every point in place.
+    pub const IDENTITY: Transforms = Transforms {
+        steps: [Transform::Conj; Transforms::MAX_LEN],
+        len: 0,
+    };
+
+    /// Creates the sequence applying `steps` in order, or `None` if there are more than
+    /// [`MAX_LEN`](Self::MAX_LEN) of them.
+    pub fn new(steps: &[Transform]) -> Option<Transforms> {
+        if steps.len() > Self::MAX_LEN {
+            return None;
+        }
+
+        let mut transforms = Self::IDENTITY;
+        transforms.steps[..steps.len()].copy_from_slice(steps);
+        transforms.len = steps.len();
+        Some(transforms)
+    }
+
+    /// Checks whether this sequence is empty.
+    #[inline]
+    pub fn is_identity(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Applies every transform of this sequence to `z` in order.
+    #[inline]
+    pub fn apply<F: Float>(&self, z: Complex<F>) -> Complex<F> {
+        self.steps[..self.len].iter().fold(z, |z, transform| transform.apply(z))
+    }
+}
+
+impl std::fmt::Display for Transforms {
+    /// Formats the name of every transform of this sequence in order, separated by commas.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names = self.steps[..self.len]
+            .iter()
+            .map(|transform| transform.to_possible_value().unwrap().get_name().to_owned())
+            .collect::<Vec<_>>();
+        write!(f, "{}", names.join(","))
+    }
+}
+
+impl Default for Transforms {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
@@ -0,0 +1,82 @@
+use crate::complex::Complex;
+
+/// A shape the iterates of an orbit are measured against.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Trap {
+    /// A single point.
+    Point(Complex<f32>),
+    /// The infinite line through `point` along the unit vector `direction`, which is `degrees` from
+    /// the real axis.
+    Line {
+        point: Complex<f32>,
+        direction: Complex<f32>,
+        degrees: f32,
+    },
+    /// The circle around `center`.
+    Circle { center: Complex<f32>, radius: f32 },
+}
+
+impl Trap {
+    /// Creates the line through `point` at `degrees` from the real axis.
+    pub fn line(point: Complex<f32>, degrees: f32) -> Trap {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Trap::Line {
+            point,
+            direction: Complex::new(cos, sin),
+            degrees,
+        }
+    }
+
+    /// Computes the distance from `z` to this trap.
+    #[inline]
+    pub fn distance(&self, z: Complex<f32>) -> f32 {
+        match *self {
+            Trap::Point(point) => (z - point).abs(),
+            Trap::Line { point, direction, .. } => {
+                let d = z - point;
+                (d.re * direction.im - d.im * direction.re).abs()
+            },
+            Trap::Circle { center, radius } => ((z - center).abs() - radius).abs(),
+        }
+    }
+}
+
+impl std::fmt::Display for Trap {
+    /// Formats the trap like the `--trap` flag takes it, as its shape and parameters.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Trap::Point(point) => write!(f, "point:{},{}", point.re, point.im),
+            Trap::Line { point, degrees, .. } => write!(f, "line:{},{},{}", point.re, point.im, degrees),
+            Trap::Circle { center, radius } => write!(f, "circle:{},{},{}", center.re, center.im, radius),
+        }
+    }
+}
+
+/// An orbit trap, weighting every iterate of an orbit by its closeness to a trap.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrbitTrap {
+    pub trap: Trap,
+    /// The distance from the trap at which an iterate's weight falls to `1/e`.
+    pub falloff: f32,
+}
+
+impl OrbitTrap {
+    /// Creates a new orbit trap.
+    #[inline]
+    pub fn new(trap: Trap, falloff: f32) -> OrbitTrap {
+        Self { trap, falloff }
+    }
+
+    /// Gets the weight of the iterate `z`, which is one on the trap and decays exponentially away
+    /// from it.
+    #[inline]
+    pub fn weight(&self, z: Complex<f32>) -> f32 {
+        (-self.trap.distance(z) / self.falloff).exp()
+    }
+}
+
+impl Default for OrbitTrap {
+    fn default() -> Self {
+        Self::new(Trap::Point(Complex::new(0.0, 0.0)), 0.1)
+    }
+}
@@ -0,0 +1,124 @@
+use rayon::prelude::*;
+
+use crate::{
+    dynamics::Dynamics,
+    sample::{sample_point, SampleSettings, CHUNK_SIZE},
+};
+
+/// The parameters of an automatic max-iteration tuning pass.
+#[derive(Clone, Debug)]
+pub struct TuneSettings {
+    /// The candidate values of `n` to measure.
+    pub candidates: Vec<u32>,
+    /// The number of samples to draw.
+    pub batch: usize,
+    /// The minimum number of iterations an orbit must survive before escaping for it to count as
+    /// detail. Orbits escaping sooner only contribute to the blurry outer haze.
+    pub min_escape: u32,
+    /// The fraction of the best measured productivity that the recommended `n` must retain. Lower
+    /// values favor detail, higher values favor render speed.
+    pub target: f32,
+}
+
+impl Default for TuneSettings {
+    fn default() -> Self {
+        Self {
+            candidates: vec![50, 100, 200, 500, 1000, 2000, 5000, 10000, 20000, 50000],
+            batch: 20000,
+            min_escape: 20,
+            target: 0.5,
+        }
+    }
+}
+
+impl TuneSettings {
+    /// Limits the candidates to those below `max`, adding `max` itself as the final candidate.
+    pub fn up_to(mut self, max: u32) -> TuneSettings {
+        self.candidates.retain(|&n| n < max);
+        self.candidates.push(max);
+        self
+    }
+}
+
+/// The statistics measured for a single candidate `n`.
+#[derive(Clone, Copy, Debug)]
+pub struct TuneSample {
+    pub n: u32,
+    /// The fraction of samples that escape within `n` iterations.
+    pub escaped: f32,
+    /// The fraction of samples that escape after at least `min_escape` but at most `n` iterations.
+    pub detail: f32,
+    /// The number of points plotted by detailed orbits per iteration computed.
+    pub productivity: f32,
+}
+
+/// The result of an automatic max-iteration tuning pass.
+#[derive(Clone, Debug)]
+pub struct TuneReport {
+    pub samples: Vec<TuneSample>,
+    /// The recommended value of `n`.
+    pub recommended: u32,
+}
+
+/// Measures the escape statistics of a small batch of samples at several values of `n` and
+/// recommends the largest `n` whose productivity stays within `tune.target` of the best candidate.
+///
+/// The viewport and seed are taken from `settings`; its `n` and `m` are ignored.
+pub fn tune_iterations(settings: &SampleSettings, tune: &TuneSettings) -> TuneReport {
+    let max_n = tune.candidates.iter().copied().max().unwrap_or(0);
+    let kernel = settings.kernel(settings.fractal);
+
+    // Escape times are measured once at the largest candidate, since an orbit escaping within `max_n`
+    // iterations escapes at the same iteration for every candidate larger than its escape time.
+    let escape_times = (0..tune.batch.div_ceil(CHUNK_SIZE))
+        .into_par_iter()
+        .flat_map_iter(|chunk| {
+            let mut stream = settings.noise.stream(chunk as u64);
+            (0..CHUNK_SIZE.min(tune.batch - chunk * CHUNK_SIZE)).map(move |_| {
+                let sample = sample_point::<f32>(settings, stream.next_point());
+                let (z, c) = settings.orbit_start(&kernel, sample);
+                kernel.escape_time(z, c, max_n)
+            })
+        })
+        .collect::<Vec<_>>();
+    let total = escape_times.len().max(1) as f32;
+
+    let mut samples = Vec::new();
+    for &n in tune.candidates.iter() {
+        let mut escaped = 0;
+        let mut detail = 0;
+        let mut plotted = 0u64;
+        let mut cost = 0u64;
+
+        for &t in escape_times.iter() {
+            match t {
+                Some(t) if t <= n => {
+                    escaped += 1;
+                    cost += t as u64;
+                    if t >= tune.min_escape {
+                        detail += 1;
+                        plotted += t as u64;
+                    }
+                },
+                _ => cost += n as u64,
+            }
+        }
+
+        samples.push(TuneSample {
+            n,
+            escaped: escaped as f32 / total,
+            detail: detail as f32 / total,
+            productivity: plotted as f32 / cost.max(1) as f32,
+        });
+    }
+
+    let best = samples.iter().map(|s| s.productivity).fold(0.0, f32::max);
+    let recommended = samples
+        .iter()
+        .filter(|s| s.productivity >= best * tune.target)
+        .map(|s| s.n)
+        .max()
+        .unwrap_or(max_n);
+
+    TuneReport { samples, recommended }
+}
@@ -0,0 +1,248 @@
+use clap::ValueEnum;
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::{
+    atomic::AtomicFile,
+    complex::Complex,
+    dynamics::Dynamics,
+    numa,
+    rotation::Axis,
+    sample::{run_chunks, sample_point, Orbits, SampleSettings},
+};
+
+/// A grid of voxels accumulating hits, the 3D analog of an [`Image`](crate::images::Image).
+#[derive(Debug, Clone)]
+pub struct Volume {
+    data: Vec<f32>,
+    pub width: usize,
+    pub height: usize,
+    pub depth: usize,
+}
+
+impl Volume {
+    /// Creates a new, empty volume.
+    #[inline]
+    pub fn new(width: usize, height: usize, depth: usize) -> Volume {
+        Self {
+            data: vec![0.0; width * height * depth],
+            width,
+            height,
+            depth,
+        }
+    }
+
+    #[inline]
+    fn index(&self, (x, y, z): (usize, usize, usize)) -> usize {
+        (z * self.height + y) * self.width + x
+    }
+
+    /// Gets the value of a voxel at a given `(x, y, z)` position.
+    #[inline]
+    pub fn get(&self, voxel: (usize, usize, usize)) -> f32 {
+        self.data[self.index(voxel)]
+    }
+
+    /// Adds to the value of a voxel at a given `(x, y, z)` position.
+    #[inline]
+    pub fn add(&mut self, voxel: (usize, usize, usize), value: f32) {
+        let index = self.index(voxel);
+        self.data[index] += value;
+    }
+
+    /// Adds every voxel of `other`, which must have the same dimensions, into this volume.
+    #[profiling::function]
+    pub fn merge(&mut self, other: &Volume) {
+        for (voxel, value) in self.data.iter_mut().zip(other.data.iter()) {
+            *voxel += value;
+        }
+    }
+
+    /// Scales every voxel so that the largest is one.
+    pub fn normalize(&mut self) {
+        let max = self.data.iter().copied().fold(0.0, f32::max);
+        if max > 0.0 {
+            for voxel in self.data.iter_mut() {
+                *voxel /= max;
+            }
+        }
+    }
+
+    /// Get an iterator over every voxel, in order of increasing `x`, then `y`, then `z`.
+    #[inline]
+    pub fn voxels(&self) -> impl Iterator<Item = f32> + '_ {
+        self.data.iter().copied()
+    }
+
+    /// Writes this volume to a file in the given format.
+    pub fn write(&self, path: impl AsRef<Path>, format: VolumeFormat) -> io::Result<()> {
+        let mut out = AtomicFile::create(path)?;
+
+        match format {
+            VolumeFormat::Nrrd => {
+                let endian = if cfg!(target_endian = "little") {
+                    "little"
+                } else {
+                    "big"
+                };
+                write!(
+                    out,
+                    "NRRD0004\ntype: float\ndimension: 3\nsizes: {} {} {}\nendian: {}\nencoding: raw\n\n",
+                    self.width, self.height, self.depth, endian
+                )?;
+                self.write_data(&mut out, f32::to_ne_bytes)?;
+            },
+            VolumeFormat::Vtk => {
+                // Legacy VTK files are always big endian
+                write!(
+                    out,
+                    "# vtk DataFile Version 3.0\nbuddhabrot\nBINARY\nDATASET STRUCTURED_POINTS\n\
+                     DIMENSIONS {} {} {}\nORIGIN 0 0 0\nSPACING 1 1 1\nPOINT_DATA {}\n\
+                     SCALARS density float 1\nLOOKUP_TABLE default\n",
+                    self.width,
+                    self.height,
+                    self.depth,
+                    self.data.len()
+                )?;
+                self.write_data(&mut out, f32::to_be_bytes)?;
+            },
+            VolumeFormat::Raw => self.write_data(&mut out, f32::to_le_bytes)?,
+        }
+
+        out.commit()
+    }
+
+    fn write_data(&self, out: &mut impl Write, bytes: fn(f32) -> [u8; 4]) -> io::Result<()> {
+        for &voxel in self.data.iter() {
+            out.write_all(&bytes(voxel))?;
+        }
+        Ok(())
+    }
+}
+
+/// The file format a volume is written in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum VolumeFormat {
+    /// A NRRD file with the voxels attached to its header.
+    #[default]
+    Nrrd,
+    /// A legacy VTK structured points file.
+    Vtk,
+    /// Bare little endian 32-bit floats without a header.
+    Raw,
+}
+
+impl VolumeFormat {
+    /// Gets the file extension of this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            VolumeFormat::Nrrd => "nrrd",
+            VolumeFormat::Vtk => "vtk",
+            VolumeFormat::Raw => "raw",
+        }
+    }
+}
+
+/// The region of a 3-subspace of `(cr, ci, zr, zi)` covered by a volume.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VolumeView {
+    /// The coordinates along the `x`, `y` and `z` axes of the volume.
+    pub axes: [Axis; 3],
+    pub center: [f32; 3],
+    pub scale: f32,
+}
+
+impl VolumeView {
+    /// Converts the point `z` of the orbit of `sample` to the position of the voxel it lands in, or
+    /// `None` if it lies outside the volume.
+    #[inline]
+    pub fn to_voxel(&self, z: Complex<f32>, sample: Complex<f32>, volume: &Volume) -> Option<(usize, usize, usize)> {
+        let point = [sample.re, sample.im, z.re, z.im];
+        let dims = [volume.width, volume.height, volume.depth];
+
+        let mut voxel = [0; 3];
+        for i in 0..3 {
+            let u = (point[self.axes[i].index()] - self.center[i]) / (4.0 * self.scale) + 0.5;
+            if !(0.0..1.0).contains(&u) {
+                return None;
+            }
+            voxel[i] = (u * dims[i] as f32) as usize;
+        }
+
+        Some((voxel[0], voxel[1], voxel[2]))
+    }
+
+    /// Gets the coordinates of the point `z` of the orbit of `sample` along the axes of the volume,
+    /// or `None` if it lies outside the region the volume covers.
+    #[inline]
+    pub fn to_point(&self, z: Complex<f32>, sample: Complex<f32>) -> Option<[f32; 3]> {
+        let point = [sample.re, sample.im, z.re, z.im];
+        let coordinates = self.axes.map(|axis| point[axis.index()]);
+        let inside =
+            (0..3).all(|i| (0.0..1.0).contains(&((coordinates[i] - self.center[i]) / (4.0 * self.scale) + 0.5)));
+        inside.then_some(coordinates)
+    }
+}
+
+impl Default for VolumeView {
+    fn default() -> Self {
+        Self {
+            axes: [Axis::Zr, Axis::Zi, Axis::Cr],
+            center: [0.0; 3],
+            scale: 1.0,
+        }
+    }
+}
+
+/// Samples orbits into a volume, taking `settings.m` samples per column of voxels.
+pub fn sample_volume(volume: &mut Volume, settings: SampleSettings, view: VolumeView) {
+    let n = settings.n;
+    let iters = volume.width * volume.height * settings.m as usize;
+    let kernel = settings.kernel(settings.fractal);
+    let blank = Volume::new(volume.width, volume.height, volume.depth);
+
+    let subvolumes = run_chunks(
+        &settings,
+        iters,
+        || blank.clone(),
+        |subvolume, stream, throttle, count| {
+            let mut trajectory = Vec::new();
+            for _ in 0..count {
+                let sample = sample_point::<f32>(&settings, stream.next_point());
+                let (z, c) = settings.orbit_start(&kernel, sample);
+                let mut deposit = |z| {
+                    if let Some(voxel) = view.to_voxel(z, sample, subvolume) {
+                        subvolume.add(voxel, 1.0);
+                    }
+                };
+
+                match settings.orbits {
+                    Orbits::Escaping if settings.streaming => {
+                        if let Some(time) = kernel.escape_time(z, c, n).filter(|&t| t >= settings.min_iterations) {
+                            kernel.orbit(z, c, time).for_each(&mut deposit);
+                        }
+                    },
+                    Orbits::Escaping => {
+                        kernel.trajectory(z, c, n, &mut trajectory);
+                        if trajectory.len() as u32 >= settings.min_iterations {
+                            trajectory.iter().copied().for_each(&mut deposit);
+                        }
+                    },
+                    Orbits::Bounded => {
+                        if kernel.escape_time(z, c, n).is_none() {
+                            kernel.orbit(z, c, n).for_each(&mut deposit);
+                        }
+                    },
+                }
+
+                throttle.tick(1);
+            }
+        },
+    );
+
+    for subvolume in numa::merge_by_node(subvolumes, |volume, other| volume.merge(&other)) {
+        volume.merge(&subvolume);
+    }
+}
@@ -0,0 +1,191 @@
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+/// Gets a path in the temporary directory, excluding the extension, that no other test writes to.
+fn temp_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("buddhabrot-cli-{}-{}", name, std::process::id()))
+}
+
+/// Runs the renderer with `args`, returning what it printed.
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_buddhabrot"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Reads the value of `key` from the manifest written next to `file`.
+fn manifest_value(file: &Path, key: &str) -> serde_json::Value {
+    let manifest = std::fs::read_to_string(file.with_extension("json")).unwrap();
+    let manifest: serde_json::Value = serde_json::from_str(&manifest).unwrap();
+    manifest["parameters"][key].clone()
+}
+
+#[test]
+fn max_memory_keeps_samples_per_pixel() {
+    let file = temp_file("max-memory");
+    let path = file.to_str().unwrap();
+
+    // An image per thread doesn't fit, so the counters shared by every thread are chosen instead
+    let output = run(&[
+        "generate",
+        "5",
+        "17",
+        "768",
+        "r",
+        "--threads",
+        "8",
+        "--max-memory",
+        "30M",
+        "--manifest",
+        "--png",
+        "-o",
+        "--seed",
+        "1",
+        "-f",
+        path,
+    ]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.contains("shared by every thread"), "{}", stdout);
+    assert!(!stdout.contains("Reducing samples per pixel"), "{}", stdout);
+    assert_eq!(manifest_value(&file, "m"), "17");
+
+    std::fs::remove_file(file.with_extension("png")).unwrap();
+    std::fs::remove_file(file.with_extension("json")).unwrap();
+}
+
+#[test]
+fn manifest_records_flag_values() {
+    let file = temp_file("manifest");
+    let path = file.to_str().unwrap();
+
+    let output = run(&[
+        "generate",
+        "20",
+        "1",
+        "64",
+        "r",
+        "--formula",
+        "z^2 + sin(c)",
+        "--transform",
+        "sqrt,conj",
+        "--manifest",
+        "--png",
+        "-o",
+        "--seed",
+        "1",
+        "-f",
+        path,
+    ]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(manifest_value(&file, "formula"), "z^2 + sin(c)");
+    assert_eq!(manifest_value(&file, "transform"), "sqrt,conj");
+    assert_eq!(manifest_value(&file, "noise"), "blue-noise");
+
+    std::fs::remove_file(file.with_extension("png")).unwrap();
+    std::fs::remove_file(file.with_extension("json")).unwrap();
+}
+
+#[test]
+fn resume_refuses_different_formula() {
+    let file = temp_file("resume");
+    let path = file.to_str().unwrap();
+    let checkpoint = file.with_extension("bhist");
+    let checkpoint_path = checkpoint.to_str().unwrap();
+
+    let args = [
+        "generate", "20", "1", "64", "r", "--png", "-o", "--seed", "1", "-f", path,
+    ];
+    let output = run(&[&args[..], &["--formula", "z^2 + c", "--checkpoint", checkpoint_path]].concat());
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    std::fs::remove_file(file.with_extension("png")).unwrap();
+
+    let output = run(&[&args[..], &["--formula", "z^3 + c", "--resume", checkpoint_path]].concat());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("made with a different formula"), "{}", stderr);
+    assert!(!file.with_extension("png").exists());
+
+    std::fs::remove_file(checkpoint).unwrap();
+}
+
+#[test]
+fn merge_refuses_different_formula() {
+    let files = ["merge-a", "merge-b", "merge-c"].map(temp_file);
+    let renders = [
+        (&files[0], "1", "z^2 + c"),
+        (&files[1], "2", "z^2 + c"),
+        (&files[2], "3", "z^3 + c"),
+    ];
+    for (file, seed, formula) in renders {
+        let path = file.to_str().unwrap();
+        let args = [
+            "generate",
+            "20",
+            "1",
+            "64",
+            "r",
+            "--histogram",
+            "--png",
+            "-o",
+            "--seed",
+            seed,
+            "-f",
+            path,
+        ];
+        let output = run(&[&args[..], &["--formula", formula]].concat());
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        std::fs::remove_file(file.with_extension("png")).unwrap();
+    }
+
+    let histograms = files.each_ref().map(|file| file.with_extension("bhist"));
+    let histogram = |i: usize| histograms[i].to_str().unwrap();
+    let merged = temp_file("merged");
+    let merged_path = merged.to_str().unwrap();
+
+    // Histograms differing only in their seeds are merged
+    let output = run(&["merge", histogram(0), histogram(1), "-o", "-f", merged_path]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Merged 2 histograms"),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = run(&["merge", histogram(0), histogram(2), "-o", "-f", merged_path]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("rendered with different formula"), "{}", stderr);
+
+    std::fs::remove_file(merged.with_extension("bhist")).unwrap();
+    for histogram in histograms {
+        std::fs::remove_file(histogram).unwrap();
+    }
+}
+
+#[test]
+fn template_replaces_unsafe_characters() {
+    let file = temp_file("template");
+    let template = format!("{}-{{formula}}-{{projection}}", file.to_str().unwrap());
+
+    let output = run(&[
+        "generate",
+        "20",
+        "1",
+        "64",
+        "r",
+        "--formula",
+        "z^2 + c/2",
+        "--png",
+        "-o",
+        "--seed",
+        "1",
+        "-f",
+        &template,
+    ]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let expanded = format!("{}-z^2_+_c_2-zr-zi.png", file.to_str().unwrap());
+    std::fs::remove_file(expanded).unwrap();
+}